@@ -11,6 +11,8 @@ use core::str;
 #[cfg(feature = "std")]
 use std::error;
 
+pub mod structured;
+
 /// Format a byte array as a colon-delimited hex string.
 ///
 /// - Source: <https://github.com/tendermint/signatory>
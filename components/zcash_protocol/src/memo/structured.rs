@@ -0,0 +1,538 @@
+//! Structured memos that may span the memo fields of multiple outputs.
+//!
+//! A single memo field holds at most 512 bytes. This module defines a payload format
+//! that carries a sequence of typed fields, and a chunking format that splits an
+//! encoded payload across the [`Memo::Arbitrary`] memos of several outputs of the
+//! same transaction so that the recipient can reassemble it.
+//!
+//! # Chunk encoding
+//!
+//! Each chunk occupies the 511 bytes of a [`Memo::Arbitrary`] memo:
+//!
+//! | Offset | Length | Contents                                               |
+//! |--------|--------|--------------------------------------------------------|
+//! | 0      | 4      | The magic bytes `"ZSM"` followed by the version `0x01` |
+//! | 4      | 8      | The [`MessageId`] shared by all chunks of a message    |
+//! | 12     | 1      | The index of this chunk                                |
+//! | 13     | 1      | The total number of chunks in the message              |
+//! | 14     | 2      | The big-endian length of the payload in this chunk     |
+//! | 16     | ≤ 495  | The payload, followed by zero padding                  |
+//!
+//! # Payload encoding
+//!
+//! The concatenated chunk payloads are a sequence of fields, each of which is encoded
+//! as a one-byte tag, a big-endian two-byte length, and the field value. Fields with
+//! tags that are not understood by this implementation are preserved, so that they
+//! can be inspected by the caller and re-encoded without loss.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str;
+
+#[cfg(feature = "std")]
+use std::error;
+
+use super::Memo;
+
+/// The magic bytes and version that prefix every structured memo chunk.
+pub const CHUNK_MAGIC: [u8; 4] = *b"ZSM\x01";
+
+/// The length of the header that precedes the payload of each chunk.
+const CHUNK_HEADER_LEN: usize = 16;
+
+/// The maximum number of payload bytes that may be carried by a single chunk.
+pub const MAX_CHUNK_PAYLOAD: usize = 511 - CHUNK_HEADER_LEN;
+
+/// The maximum number of chunks that a single message may be split across.
+pub const MAX_CHUNKS: usize = u8::MAX as usize;
+
+const TAG_TEXT: u8 = 0x01;
+const TAG_REPLY_TO: u8 = 0x02;
+const TAG_INVOICE_ID: u8 = 0x03;
+
+/// Errors that may occur when encoding, decoding, or reassembling a structured memo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The value of the field with the given tag exceeds the maximum field length of
+    /// 65535 bytes.
+    FieldTooLong { tag: u8, len: usize },
+    /// The encoded payload of the given length cannot be carried by [`MAX_CHUNKS`] chunks.
+    PayloadTooLong(usize),
+    /// The payload ended in the middle of a field.
+    Truncated,
+    /// The field with the given tag is required to be UTF-8 but is not.
+    InvalidUtf8 { tag: u8, error: str::Utf8Error },
+    /// The field with the given tag appeared more than once in the payload.
+    DuplicateField(u8),
+    /// No chunks were provided for reassembly.
+    NoChunks,
+    /// The provided chunks do not all belong to the same message, or disagree about
+    /// the number of chunks in the message.
+    InconsistentChunks,
+    /// Two chunks with the given index but differing payloads were provided.
+    ConflictingChunk(u8),
+    /// The chunk with the given index was not provided.
+    MissingChunk(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::FieldTooLong { tag, len } => write!(
+                f,
+                "Structured memo field {tag:#04x} length {len} is larger than the maximum of {}",
+                u16::MAX
+            ),
+            Error::PayloadTooLong(n) => write!(
+                f,
+                "Structured memo payload length {n} is larger than the maximum of {}",
+                MAX_CHUNKS * MAX_CHUNK_PAYLOAD
+            ),
+            Error::Truncated => write!(f, "Structured memo payload is truncated"),
+            Error::InvalidUtf8 { tag, error } => {
+                write!(
+                    f,
+                    "Structured memo field {tag:#04x} is not valid UTF-8: {error}"
+                )
+            }
+            Error::DuplicateField(tag) => {
+                write!(f, "Structured memo field {tag:#04x} appears more than once")
+            }
+            Error::NoChunks => write!(f, "No structured memo chunks were provided"),
+            Error::InconsistentChunks => write!(
+                f,
+                "Structured memo chunks do not belong to a single message"
+            ),
+            Error::ConflictingChunk(i) => {
+                write!(
+                    f,
+                    "Structured memo chunk {i} was provided with conflicting contents"
+                )
+            }
+            Error::MissingChunk(i) => write!(f, "Structured memo chunk {i} is missing"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {}
+
+/// An identifier shared by all of the chunks of a single structured memo.
+///
+/// Message identifiers only need to be unique among the structured memos included in a
+/// single transaction; generating them randomly is sufficient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MessageId([u8; 8]);
+
+impl MessageId {
+    /// Constructs a message identifier from its byte representation.
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        MessageId(bytes)
+    }
+
+    /// Returns the byte representation of this message identifier.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+
+/// A typed payload that may be transmitted using one or more memos.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructuredMemo {
+    text: Option<String>,
+    reply_to: Option<String>,
+    invoice_id: Option<Vec<u8>>,
+    unknown_fields: Vec<(u8, Vec<u8>)>,
+}
+
+impl StructuredMemo {
+    /// Constructs a new structured memo with no fields set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the free-form text of the message.
+    pub fn with_text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    /// Sets the address to which replies to this message should be sent.
+    ///
+    /// The address is carried as its string encoding and is not validated here.
+    pub fn with_reply_to(mut self, address: String) -> Self {
+        self.reply_to = Some(address);
+        self
+    }
+
+    /// Sets the identifier of the invoice that the enclosing transaction pays.
+    pub fn with_invoice_id(mut self, invoice_id: Vec<u8>) -> Self {
+        self.invoice_id = Some(invoice_id);
+        self
+    }
+
+    /// Adds a field with a tag that is not interpreted by this implementation.
+    ///
+    /// Unknown fields are encoded after all known fields, in the order in which they
+    /// were added. Tags `0x01` through `0x03` are reserved for the fields exposed by
+    /// this type and must not be used here.
+    pub fn with_unknown_field(mut self, tag: u8, value: Vec<u8>) -> Self {
+        self.unknown_fields.push((tag, value));
+        self
+    }
+
+    /// Returns the free-form text of the message, if any.
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    /// Returns the string encoding of the address to which replies should be sent, if any.
+    pub fn reply_to(&self) -> Option<&str> {
+        self.reply_to.as_deref()
+    }
+
+    /// Returns the identifier of the invoice that the enclosing transaction pays, if any.
+    pub fn invoice_id(&self) -> Option<&[u8]> {
+        self.invoice_id.as_deref()
+    }
+
+    /// Returns the fields of this memo whose tags are not interpreted by this
+    /// implementation, in encoding order.
+    pub fn unknown_fields(&self) -> &[(u8, Vec<u8>)] {
+        &self.unknown_fields
+    }
+
+    /// Serializes the fields of this memo into a single payload.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        let known = [
+            (TAG_TEXT, self.text.as_ref().map(|s| s.as_bytes())),
+            (TAG_REPLY_TO, self.reply_to.as_ref().map(|s| s.as_bytes())),
+            (TAG_INVOICE_ID, self.invoice_id.as_deref()),
+        ];
+
+        let mut payload = Vec::new();
+        for (tag, value) in known
+            .into_iter()
+            .filter_map(|(tag, value)| value.map(|v| (tag, v)))
+            .chain(
+                self.unknown_fields
+                    .iter()
+                    .map(|(tag, value)| (*tag, &value[..])),
+            )
+        {
+            let len = u16::try_from(value.len()).map_err(|_| Error::FieldTooLong {
+                tag,
+                len: value.len(),
+            })?;
+            payload.push(tag);
+            payload.extend_from_slice(&len.to_be_bytes());
+            payload.extend_from_slice(value);
+        }
+
+        Ok(payload)
+    }
+
+    /// Parses a structured memo from a payload produced by [`StructuredMemo::encode`].
+    pub fn decode(mut payload: &[u8]) -> Result<Self, Error> {
+        let utf8 = |tag: u8, value: &[u8]| {
+            str::from_utf8(value)
+                .map(String::from)
+                .map_err(|error| Error::InvalidUtf8 { tag, error })
+        };
+
+        let mut memo = StructuredMemo::new();
+        while !payload.is_empty() {
+            if payload.len() < 3 {
+                return Err(Error::Truncated);
+            }
+            let tag = payload[0];
+            let len = usize::from(u16::from_be_bytes([payload[1], payload[2]]));
+            let value = payload.get(3..3 + len).ok_or(Error::Truncated)?;
+            payload = &payload[3 + len..];
+
+            let slot_filled = match tag {
+                TAG_TEXT => memo.text.replace(utf8(tag, value)?).is_some(),
+                TAG_REPLY_TO => memo.reply_to.replace(utf8(tag, value)?).is_some(),
+                TAG_INVOICE_ID => memo.invoice_id.replace(value.to_vec()).is_some(),
+                _ => {
+                    memo.unknown_fields.push((tag, value.to_vec()));
+                    false
+                }
+            };
+            if slot_filled {
+                return Err(Error::DuplicateField(tag));
+            }
+        }
+
+        Ok(memo)
+    }
+
+    /// Encodes this memo and splits it into as many chunks as are required to carry it.
+    ///
+    /// Each of the returned memos should be attached to a separate output of the same
+    /// transaction, all of which are sent to the same recipient.
+    pub fn to_memos(&self, message_id: MessageId) -> Result<Vec<Memo>, Error> {
+        let payload = self.encode()?;
+        if payload.len() > MAX_CHUNKS * MAX_CHUNK_PAYLOAD {
+            return Err(Error::PayloadTooLong(payload.len()));
+        }
+
+        // An empty payload is still sent as a single chunk.
+        let count =
+            u8::try_from(((payload.len() + MAX_CHUNK_PAYLOAD - 1) / MAX_CHUNK_PAYLOAD).max(1))
+                .expect("payload length was checked above");
+        Ok((0..count)
+            .map(|index| {
+                let start = usize::from(index) * MAX_CHUNK_PAYLOAD;
+                let end = core::cmp::min(start + MAX_CHUNK_PAYLOAD, payload.len());
+                Chunk {
+                    message_id,
+                    index,
+                    count,
+                    payload: payload[start..end].to_vec(),
+                }
+                .to_memo()
+            })
+            .collect())
+    }
+}
+
+/// A single piece of a structured memo, as carried by one [`Memo::Arbitrary`] memo.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    message_id: MessageId,
+    index: u8,
+    count: u8,
+    payload: Vec<u8>,
+}
+
+impl Chunk {
+    /// Parses a chunk from the given memo.
+    ///
+    /// Returns `None` if the memo is not a well-formed structured memo chunk.
+    pub fn from_memo(memo: &Memo) -> Option<Self> {
+        let bytes = match memo {
+            Memo::Arbitrary(bytes) => bytes,
+            _ => return None,
+        };
+
+        if bytes[..4] != CHUNK_MAGIC {
+            return None;
+        }
+        let message_id = MessageId(bytes[4..12].try_into().unwrap());
+        let index = bytes[12];
+        let count = bytes[13];
+        let len = usize::from(u16::from_be_bytes([bytes[14], bytes[15]]));
+        if index >= count || len > MAX_CHUNK_PAYLOAD {
+            return None;
+        }
+        let payload = &bytes[CHUNK_HEADER_LEN..];
+        // Padding must be zeroed, so that each chunk has a unique encoding.
+        if payload[len..].iter().any(|&b| b != 0) {
+            return None;
+        }
+
+        Some(Chunk {
+            message_id,
+            index,
+            count,
+            payload: payload[..len].to_vec(),
+        })
+    }
+
+    /// Serializes this chunk as a [`Memo::Arbitrary`] memo.
+    pub fn to_memo(&self) -> Memo {
+        let mut bytes = [0u8; 511];
+        bytes[..4].copy_from_slice(&CHUNK_MAGIC);
+        bytes[4..12].copy_from_slice(&self.message_id.0);
+        bytes[12] = self.index;
+        bytes[13] = self.count;
+        bytes[14..16].copy_from_slice(&(self.payload.len() as u16).to_be_bytes());
+        bytes[CHUNK_HEADER_LEN..CHUNK_HEADER_LEN + self.payload.len()]
+            .copy_from_slice(&self.payload);
+        Memo::Arbitrary(Box::new(bytes))
+    }
+
+    /// Returns the identifier of the message to which this chunk belongs.
+    pub fn message_id(&self) -> MessageId {
+        self.message_id
+    }
+
+    /// Returns the index of this chunk within its message.
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Returns the total number of chunks in the message to which this chunk belongs.
+    pub fn count(&self) -> u8 {
+        self.count
+    }
+
+    /// Returns the portion of the message payload carried by this chunk.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Reassembles a structured memo from all of its chunks.
+///
+/// The chunks may be provided in any order. A chunk may be provided more than once (for
+/// example, when the same output has been decrypted by more than one key) so long as
+/// each copy is identical.
+pub fn reassemble<I: IntoIterator<Item = Chunk>>(chunks: I) -> Result<StructuredMemo, Error> {
+    let mut chunks = chunks.into_iter();
+    let first = chunks.next().ok_or(Error::NoChunks)?;
+    let (message_id, count) = (first.message_id, first.count);
+
+    let mut by_index = BTreeMap::new();
+    for chunk in core::iter::once(first).chain(chunks) {
+        if chunk.message_id != message_id || chunk.count != count {
+            return Err(Error::InconsistentChunks);
+        }
+        match by_index.get(&chunk.index) {
+            Some(existing) if existing != &chunk.payload => {
+                return Err(Error::ConflictingChunk(chunk.index));
+            }
+            Some(_) => {}
+            None => {
+                by_index.insert(chunk.index, chunk.payload);
+            }
+        }
+    }
+
+    let mut payload = Vec::new();
+    for index in 0..count {
+        payload.extend_from_slice(by_index.get(&index).ok_or(Error::MissingChunk(index))?);
+    }
+
+    StructuredMemo::decode(&payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use super::{reassemble, Chunk, Error, MessageId, StructuredMemo, MAX_CHUNK_PAYLOAD};
+    use crate::memo::Memo;
+
+    const ID: MessageId = MessageId([7; 8]);
+
+    #[test]
+    fn payload_round_trip() {
+        let memo = StructuredMemo::new()
+            .with_text(String::from("Thanks for lunch!"))
+            .with_reply_to(String::from("u1example"))
+            .with_invoice_id(vec![1, 2, 3, 4])
+            .with_unknown_field(0x80, vec![0xAA; 3]);
+
+        let payload = memo.encode().unwrap();
+        assert_eq!(&payload[..3], &[0x01, 0x00, 17]);
+        assert_eq!(StructuredMemo::decode(&payload), Ok(memo));
+    }
+
+    #[test]
+    fn decode_rejects_malformed_payloads() {
+        assert_eq!(
+            StructuredMemo::decode(&[0x01, 0x00, 0x02, b'a']),
+            Err(Error::Truncated)
+        );
+        assert_eq!(
+            StructuredMemo::decode(&[0x03, 0x00, 0x00, 0x03, 0x00, 0x00]),
+            Err(Error::DuplicateField(0x03))
+        );
+        assert!(matches!(
+            StructuredMemo::decode(&[0x02, 0x00, 0x01, 0xFF]),
+            Err(Error::InvalidUtf8 { tag: 0x02, .. })
+        ));
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let text = "z".repeat(3 * MAX_CHUNK_PAYLOAD);
+        let memo = StructuredMemo::new().with_text(text);
+
+        let memos = memo.to_memos(ID).unwrap();
+        assert_eq!(memos.len(), 4);
+
+        let mut chunks: Vec<Chunk> = memos.iter().map(|m| Chunk::from_memo(m).unwrap()).collect();
+        assert!(chunks
+            .iter()
+            .all(|c| c.message_id() == ID && c.count() == 4));
+
+        // Order and duplication of chunks do not matter.
+        chunks.reverse();
+        chunks.push(chunks[0].clone());
+        assert_eq!(reassemble(chunks.clone()), Ok(memo));
+
+        chunks.retain(|c| c.index() != 2);
+        assert_eq!(reassemble(chunks), Err(Error::MissingChunk(2)));
+    }
+
+    #[test]
+    fn empty_memo_is_a_single_chunk() {
+        let memos = StructuredMemo::new().to_memos(ID).unwrap();
+        assert_eq!(memos.len(), 1);
+        assert_eq!(
+            reassemble(Chunk::from_memo(&memos[0])),
+            Ok(StructuredMemo::new())
+        );
+    }
+
+    #[test]
+    fn inconsistent_chunks() {
+        let a = StructuredMemo::new().with_text(String::from("a"));
+        let b = StructuredMemo::new().with_text(String::from("b"));
+        let chunk =
+            |memo: &StructuredMemo, id| Chunk::from_memo(&memo.to_memos(id).unwrap()[0]).unwrap();
+
+        assert_eq!(
+            reassemble([chunk(&a, ID), chunk(&b, ID)]),
+            Err(Error::ConflictingChunk(0))
+        );
+        assert_eq!(
+            reassemble([chunk(&a, ID), chunk(&a, MessageId([8; 8]))]),
+            Err(Error::InconsistentChunks)
+        );
+        assert_eq!(reassemble([]), Err(Error::NoChunks));
+    }
+
+    #[test]
+    fn non_chunk_memos() {
+        assert_eq!(Chunk::from_memo(&Memo::Empty), None);
+        assert_eq!(Chunk::from_memo(&"hello".parse().unwrap()), None);
+
+        let mut memo = match StructuredMemo::new().to_memos(ID).unwrap().remove(0) {
+            Memo::Arbitrary(bytes) => bytes,
+            _ => unreachable!(),
+        };
+        // Nonzero padding is rejected.
+        memo[510] = 1;
+        assert_eq!(Chunk::from_memo(&Memo::Arbitrary(memo.clone())), None);
+        // An index beyond the chunk count is rejected.
+        memo[510] = 0;
+        memo[12] = 1;
+        assert_eq!(Chunk::from_memo(&Memo::Arbitrary(memo)), None);
+    }
+
+    #[test]
+    fn payload_too_long() {
+        let memo = StructuredMemo::new()
+            .with_unknown_field(0x10, vec![0; u16::MAX as usize])
+            .with_unknown_field(0x11, vec![0; u16::MAX as usize]);
+        assert!(matches!(memo.to_memos(ID), Err(Error::PayloadTooLong(_))));
+        assert!(matches!(
+            StructuredMemo::new()
+                .with_invoice_id(vec![0; 70000])
+                .encode(),
+            Err(Error::FieldTooLong {
+                tag: 0x03,
+                len: 70000
+            })
+        ));
+    }
+}
@@ -18,7 +18,11 @@ use nom::{
 
 use zcash_address::{ConversionError, ZcashAddress};
 use zcash_protocol::{
-    memo::{self, MemoBytes},
+    memo::{
+        self,
+        structured::{self, MessageId, StructuredMemo},
+        MemoBytes,
+    },
     value::BalanceError,
     value::Zatoshis,
 };
@@ -43,6 +47,11 @@ pub enum Zip321Error {
     RecipientMissing(usize),
     /// The ZIP 321 URI was malformed and failed to parse.
     ParseError(String),
+    /// A structured memo could not be encoded.
+    StructuredMemo(structured::Error),
+    /// A structured memo cannot be sent to a transparent recipient address, as memos are
+    /// not supported by the protocol for transparent outputs.
+    StructuredMemoToTransparent,
 }
 
 impl<E: Display> From<ConversionError<E>> for Zip321Error {
@@ -79,6 +88,13 @@ impl Display for Zip321Error {
                 write!(f, "Payment {idx} is missing its recipient address")
             }
             Zip321Error::ParseError(s) => write!(f, "Parse failure: {s}"),
+            Zip321Error::StructuredMemo(err) => {
+                write!(f, "Structured memo could not be encoded: {err}")
+            }
+            Zip321Error::StructuredMemoToTransparent => write!(
+                f,
+                "Cannot send a structured memo to a transparent recipient address"
+            ),
         }
     }
 }
@@ -88,6 +104,7 @@ impl std::error::Error for Zip321Error {
         match self {
             Zip321Error::InvalidBase64(err) => Some(err),
             Zip321Error::MemoBytesError(err) => Some(err),
+            Zip321Error::StructuredMemo(err) => Some(err),
             _ => None,
         }
     }
@@ -172,6 +189,43 @@ impl Payment {
         }
     }
 
+    /// Constructs the payments required to send a [`StructuredMemo`] to the given address
+    /// alongside the specified amount.
+    ///
+    /// The memo is split into as many chunks as are required to carry it. The first
+    /// returned payment carries the full amount and the first chunk; each subsequent
+    /// payment is a zero-valued payment to the same address that carries a further chunk.
+    /// All of the returned payments must be included in the same transaction for the
+    /// recipient to be able to reassemble the memo.
+    ///
+    /// Returns [`Zip321Error::StructuredMemoToTransparent`] if the recipient cannot receive
+    /// memos.
+    pub fn with_structured_memo(
+        recipient_address: ZcashAddress,
+        amount: Zatoshis,
+        memo: &StructuredMemo,
+        message_id: MessageId,
+    ) -> Result<Vec<Self>, Zip321Error> {
+        if !recipient_address.can_receive_memo() {
+            return Err(Zip321Error::StructuredMemoToTransparent);
+        }
+
+        Ok(memo
+            .to_memos(message_id)
+            .map_err(Zip321Error::StructuredMemo)?
+            .into_iter()
+            .enumerate()
+            .map(|(i, chunk)| Payment {
+                recipient_address: recipient_address.clone(),
+                amount: if i == 0 { amount } else { Zatoshis::ZERO },
+                memo: Some(chunk.into()),
+                label: None,
+                message: None,
+                other_params: vec![],
+            })
+            .collect())
+    }
+
     /// Returns the payment address to which the payment should be sent.
     pub fn recipient_address(&self) -> &ZcashAddress {
         &self.recipient_address
@@ -864,7 +918,10 @@ mod tests {
     use zcash_address::{testing::arb_address, ZcashAddress};
     use zcash_protocol::{
         consensus::NetworkType,
-        memo::{Memo, MemoBytes},
+        memo::{
+            structured::{self, Chunk, MessageId, StructuredMemo},
+            Memo, MemoBytes,
+        },
         value::{testing::arb_zatoshis, Zatoshis},
     };
 
@@ -873,7 +930,7 @@ mod tests {
         parse::{parse_amount, zcashparam, Param},
         render::{amount_str, memo_param, str_param},
         testing::{arb_addr_str, arb_valid_memo, arb_zip321_request, arb_zip321_uri},
-        Payment, TransactionRequest,
+        Payment, TransactionRequest, Zip321Error,
     };
 
     fn check_roundtrip(req: TransactionRequest) {
@@ -980,6 +1037,44 @@ mod tests {
         assert_eq!(memo_from_base64(&m_unicode_64).unwrap(), m_unicode);
     }
 
+    #[test]
+    fn test_zip321_structured_memo_payments() {
+        let recipient = ZcashAddress::try_from_encoded("ztestsapling1n65uaftvs2g7075q2x2a04shfk066u3lldzxsrprfrqtzxnhc9ps73v4lhx4l9yfxj46sl0q90k").unwrap();
+        let amount = Zatoshis::const_from_u64(10000);
+        let memo = StructuredMemo::new()
+            .with_text("x".repeat(600))
+            .with_invoice_id(vec![0x42; 16]);
+
+        let payments =
+            Payment::with_structured_memo(recipient, amount, &memo, MessageId::from_bytes([1; 8]))
+                .unwrap();
+        assert_eq!(payments.len(), 2);
+        assert_eq!(payments[0].amount(), amount);
+        assert_eq!(payments[1].amount(), Zatoshis::ZERO);
+
+        let req = TransactionRequest::new(payments).unwrap();
+        assert_eq!(req.total(), Ok(amount));
+        check_roundtrip(req.clone());
+
+        let chunks = req
+            .payments()
+            .values()
+            .map(|p| Chunk::from_memo(&Memo::try_from(p.memo().unwrap()).unwrap()).unwrap());
+        assert_eq!(structured::reassemble(chunks), Ok(memo));
+
+        let transparent =
+            ZcashAddress::try_from_encoded("tmEZhbWHTpdKMw5it8YDspUXSMGQyFwovpU").unwrap();
+        assert_eq!(
+            Payment::with_structured_memo(
+                transparent,
+                amount,
+                &StructuredMemo::new(),
+                MessageId::from_bytes([1; 8])
+            ),
+            Err(Zip321Error::StructuredMemoToTransparent)
+        );
+    }
+
     #[test]
     fn test_zip321_spec_valid_examples() {
         let valid_0 = "zcash:";
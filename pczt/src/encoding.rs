@@ -0,0 +1,694 @@
+//! Text encodings of PCZTs, for transfer over QR codes and other air-gapped channels.
+//!
+//! Two forms are provided:
+//!
+//! - A single-string form, produced by [`encode`] and parsed by [`decode`]. This is a
+//!   Bech32m string with the human-readable part `pczt`, using the same extended code
+//!   length as [ZIP 316] Unified Addresses so that a PCZT of any size can be encoded.
+//! - A multi-part form for animated QR codes, produced by [`Encoder`] and reassembled by
+//!   [`Decoder`]. The serialized PCZT is split into equal-length fragments, and parts are
+//!   generated with a fountain code in the style of [BCR-2020-005 (Uniform Resources)]:
+//!   the first parts each carry a single fragment, and every subsequent part carries the
+//!   XOR of a pseudo-randomly chosen set of fragments. A receiver can therefore start
+//!   scanning at any point, in any order, and recover the PCZT from any sufficiently
+//!   large set of distinct parts. Each part is a Bech32m string with the human-readable
+//!   part `pcztpart`.
+//!
+//! Both forms consist only of characters from the Bech32 character set, and may be
+//! converted to upper case in order to use the more compact QR alphanumeric mode.
+//!
+//! # Compatibility
+//!
+//! The multi-part form borrows the fountain-code construction of Uniform Resources, but
+//! it is a Zcash-specific format and is **not** UR-compatible: parts are Bech32m strings
+//! rather than `ur:` bytewords, the part header is a fixed binary layout rather than CBOR,
+//! and fragments are chosen using a BLAKE2b-based generator rather than Xoshiro256**.
+//! Generic UR decoders, including those built into hardware signers that consume
+//! `ur:crypto-psbt` and similar types, cannot read it. Both ends of an air-gapped transfer
+//! must use this module (or another implementation of the same format).
+//!
+//! Every multi-part fragment carries a BLAKE2b digest of the complete serialized PCZT;
+//! the [`Decoder`] checks the reassembled bytes against it before calling
+//! [`Pczt::parse`]. A PCZT may be split into at most [`MAX_FRAGMENT_COUNT`] fragments;
+//! parts claiming a larger fragment count are rejected.
+//!
+//! [ZIP 316]: https://zips.z.cash/zip-0316
+//! [BCR-2020-005 (Uniform Resources)]: https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md
+
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::num::NonZeroUsize;
+
+use bech32::{primitives::decode::CheckedHrpstring, Bech32m, Checksum, Hrp};
+
+use crate::{ParseError, Pczt};
+
+/// The human-readable part of a single-string PCZT encoding.
+pub const PCZT_HRP: &str = "pczt";
+
+/// The human-readable part of each part of a multi-part PCZT encoding.
+pub const PCZT_PART_HRP: &str = "pcztpart";
+
+/// The maximum number of fragments into which a PCZT may be split by a multi-part
+/// encoding.
+///
+/// This bounds the work and memory that a [`Decoder`] commits to on receiving a part.
+pub const MAX_FRAGMENT_COUNT: usize = 10_000;
+
+const MESSAGE_DIGEST_PERSONALIZATION: &[u8; 16] = b"Zcash_PCZT_Text_";
+const FOUNTAIN_PRNG_PERSONALIZATION: &[u8; 16] = b"Zcash_PCZT_LTPRG";
+
+/// The length of the header prepended to each fragment in a multi-part encoding:
+/// `seq_num || fragment_count || message_len || digest`.
+const PART_HEADER_LEN: usize = 4 + 4 + 4 + 32;
+
+/// The Bech32m checksum with the code length extended to that used by ZIP 316, so that
+/// arbitrarily large PCZTs can be encoded.
+enum Bech32mLong {}
+impl Checksum for Bech32mLong {
+    type MidstateRepr = <Bech32m as Checksum>::MidstateRepr;
+    // l^MAX from ZIP 316.
+    const CODE_LENGTH: usize = 4194368;
+    const CHECKSUM_LENGTH: usize = Bech32m::CHECKSUM_LENGTH;
+    const GENERATOR_SH: [u32; 5] = Bech32m::GENERATOR_SH;
+    const TARGET_RESIDUE: u32 = Bech32m::TARGET_RESIDUE;
+}
+
+/// Encodes the given PCZT as a single Bech32m string.
+///
+/// # Panics
+///
+/// Panics if the serialized PCZT is longer than the extended Bech32m code length
+/// (around 2.6 MB).
+pub fn encode(pczt: &Pczt) -> String {
+    bech32::encode::<Bech32mLong>(Hrp::parse_unchecked(PCZT_HRP), &pczt.serialize())
+        .expect("PCZT is short enough to encode")
+}
+
+/// Parses a PCZT from its single-string encoding.
+///
+/// Both lower-case and upper-case encodings are accepted.
+pub fn decode(s: &str) -> Result<Pczt, DecodeError> {
+    let bytes = decode_bech32m(s, PCZT_HRP)?;
+    Pczt::parse(&bytes).map_err(DecodeError::Pczt)
+}
+
+fn decode_bech32m(s: &str, expected_hrp: &str) -> Result<Vec<u8>, DecodeError> {
+    let parsed = CheckedHrpstring::new::<Bech32mLong>(s).map_err(|_| DecodeError::NotBech32m)?;
+    if parsed.hrp().lowercase_char_iter().ne(expected_hrp.chars()) {
+        return Err(DecodeError::UnexpectedPrefix(parsed.hrp().to_lowercase()));
+    }
+    Ok(parsed.byte_iter().collect())
+}
+
+fn message_digest(message: &[u8]) -> [u8; 32] {
+    blake2b_simd::Params::new()
+        .hash_length(32)
+        .personal(MESSAGE_DIGEST_PERSONALIZATION)
+        .hash(message)
+        .as_bytes()
+        .try_into()
+        .expect("correct length")
+}
+
+/// A generator of the parts of a multi-part PCZT encoding.
+///
+/// The first [`Encoder::fragment_count`] parts returned by [`Encoder::next_part`] each
+/// carry one fragment of the PCZT; displaying these in a loop is sufficient for a
+/// receiver that scans every part. Continuing past them yields an unbounded sequence of
+/// mixed parts, which allow a receiver that misses some parts to recover without waiting
+/// for those exact parts to be shown again.
+#[derive(Clone, Debug)]
+pub struct Encoder {
+    message_len: u32,
+    digest: [u8; 32],
+    fragments: Vec<Vec<u8>>,
+    seq_num: u32,
+}
+
+impl Encoder {
+    /// Prepares to encode the given PCZT in fragments of at most `max_fragment_len`
+    /// bytes.
+    ///
+    /// Fragment lengths are balanced, so every fragment may be somewhat shorter than
+    /// `max_fragment_len`. Each encoded part additionally carries a 44-byte header and a
+    /// Bech32m checksum.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the serialized PCZT is longer than `u32::MAX` bytes, or if it would need
+    /// to be split into more than [`MAX_FRAGMENT_COUNT`] fragments of `max_fragment_len`
+    /// bytes.
+    pub fn new(pczt: &Pczt, max_fragment_len: NonZeroUsize) -> Self {
+        Self::from_bytes(pczt.serialize(), max_fragment_len)
+    }
+
+    fn from_bytes(message: Vec<u8>, max_fragment_len: NonZeroUsize) -> Self {
+        let message_len = u32::try_from(message.len()).expect("PCZT is shorter than 4 GiB");
+        let digest = message_digest(&message);
+
+        // Balance the fragment lengths so that the final fragment needs little padding.
+        let max_fragment_len = max_fragment_len.get();
+        let fragment_count = message.len().div_ceil(max_fragment_len).max(1);
+        assert!(
+            fragment_count <= MAX_FRAGMENT_COUNT,
+            "PCZT requires too many fragments; increase max_fragment_len"
+        );
+        let fragment_len = message.len().div_ceil(fragment_count).max(1);
+
+        let fragments = (0..fragment_count)
+            .map(|i| {
+                let start = (i * fragment_len).min(message.len());
+                let end = ((i + 1) * fragment_len).min(message.len());
+                let mut fragment = message[start..end].to_vec();
+                fragment.resize(fragment_len, 0);
+                fragment
+            })
+            .collect();
+
+        Self {
+            message_len,
+            digest,
+            fragments,
+            seq_num: 0,
+        }
+    }
+
+    /// Returns the number of fragments the PCZT has been split into.
+    ///
+    /// This is the minimum number of parts a receiver needs to scan.
+    pub fn fragment_count(&self) -> usize {
+        self.fragments.len()
+    }
+
+    /// Returns `true` if the PCZT fits within a single part.
+    ///
+    /// In this case every part returned by [`Encoder::next_part`] is identical apart
+    /// from its sequence number, and a static QR code suffices.
+    pub fn is_single_part(&self) -> bool {
+        self.fragments.len() == 1
+    }
+
+    /// Returns the next part of the encoding.
+    pub fn next_part(&mut self) -> String {
+        self.seq_num = self.seq_num.wrapping_add(1).max(1);
+
+        let indices = choose_fragments(self.seq_num, self.fragments.len(), &self.digest);
+        let mut data = vec![0; self.fragments[0].len()];
+        for i in indices {
+            xor_into(&mut data, &self.fragments[i]);
+        }
+
+        let part = Part {
+            seq_num: self.seq_num,
+            fragment_count: self.fragments.len() as u32,
+            message_len: self.message_len,
+            digest: self.digest,
+            data,
+        };
+
+        bech32::encode::<Bech32mLong>(Hrp::parse_unchecked(PCZT_PART_HRP), &part.to_bytes())
+            .expect("part is short enough to encode")
+    }
+}
+
+/// A reassembler for the parts of a multi-part PCZT encoding.
+///
+/// Parts may be provided in any order, and duplicates are ignored.
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    state: Option<DecoderState>,
+}
+
+#[derive(Clone, Debug)]
+struct DecoderState {
+    fragment_count: u32,
+    message_len: u32,
+    digest: [u8; 32],
+    fragment_len: usize,
+    received: BTreeSet<u32>,
+    solved: Vec<Option<Vec<u8>>>,
+    solved_count: usize,
+    mixed: Vec<(BTreeSet<usize>, Vec<u8>)>,
+}
+
+impl Decoder {
+    /// Constructs a decoder that has not yet received any parts.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes a scanned part.
+    ///
+    /// Returns an error if the part is malformed, or if it belongs to a different PCZT
+    /// than the parts received so far. Once the final missing fragment has been
+    /// recovered, the reassembled PCZT is checked against the digest carried in every
+    /// part, and an error is returned if they do not match.
+    pub fn receive(&mut self, part: &str) -> Result<(), DecodeError> {
+        let part = Part::from_bytes(&decode_bech32m(part, PCZT_PART_HRP)?)?;
+
+        let state = self.state.get_or_insert_with(|| DecoderState {
+            fragment_count: part.fragment_count,
+            message_len: part.message_len,
+            digest: part.digest,
+            fragment_len: part.data.len(),
+            received: BTreeSet::new(),
+            solved: vec![None; part.fragment_count as usize],
+            solved_count: 0,
+            mixed: vec![],
+        });
+
+        if part.fragment_count != state.fragment_count
+            || part.message_len != state.message_len
+            || part.digest != state.digest
+            || part.data.len() != state.fragment_len
+        {
+            return Err(DecodeError::MismatchedPart);
+        }
+
+        if state.is_complete() || !state.received.insert(part.seq_num) {
+            return Ok(());
+        }
+
+        let indices = choose_fragments(part.seq_num, state.fragment_count as usize, &state.digest)
+            .into_iter()
+            .collect();
+        state.add(indices, part.data);
+
+        if state.is_complete() && message_digest(&state.message()) != state.digest {
+            return Err(DecodeError::DigestMismatch);
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` once enough parts have been received to recover the PCZT.
+    pub fn is_complete(&self) -> bool {
+        self.state.as_ref().is_some_and(|state| state.is_complete())
+    }
+
+    /// Returns the number of fragments recovered so far, and the total number of
+    /// fragments, for use in progress indicators.
+    ///
+    /// Returns `None` if no parts have been received.
+    pub fn progress(&self) -> Option<(usize, usize)> {
+        self.state
+            .as_ref()
+            .map(|state| (state.solved_count, state.fragment_count as usize))
+    }
+
+    /// Reassembles and parses the PCZT.
+    pub fn finish(self) -> Result<Pczt, DecodeError> {
+        let message = self.finish_bytes()?;
+        Pczt::parse(&message).map_err(DecodeError::Pczt)
+    }
+
+    fn finish_bytes(self) -> Result<Vec<u8>, DecodeError> {
+        let state = self
+            .state
+            .filter(|state| state.is_complete())
+            .ok_or(DecodeError::Incomplete)?;
+
+        let message = state.message();
+        if message_digest(&message) != state.digest {
+            return Err(DecodeError::DigestMismatch);
+        }
+        Ok(message)
+    }
+}
+
+impl DecoderState {
+    fn is_complete(&self) -> bool {
+        self.solved_count == self.solved.len()
+    }
+
+    fn message(&self) -> Vec<u8> {
+        let mut message = self
+            .solved
+            .iter()
+            .flat_map(|fragment| fragment.as_ref().expect("complete"))
+            .copied()
+            .collect::<Vec<_>>();
+        message.truncate(self.message_len as usize);
+        message
+    }
+
+    /// Adds a received combination of fragments, and recovers as many further fragments
+    /// as possible by peeling solved fragments out of the pending mixed parts.
+    fn add(&mut self, indices: BTreeSet<usize>, data: Vec<u8>) {
+        let mut queue = vec![(indices, data)];
+
+        while let Some((mut indices, mut data)) = queue.pop() {
+            // Remove any fragments we already know.
+            indices.retain(|&i| match &self.solved[i] {
+                Some(fragment) => {
+                    xor_into(&mut data, fragment);
+                    false
+                }
+                None => true,
+            });
+
+            match indices.len() {
+                // Carries no new information.
+                0 => (),
+                1 => {
+                    let index = *indices.first().expect("non-empty");
+                    self.solved[index] = Some(data);
+                    self.solved_count += 1;
+
+                    // Any pending mixed part involving this fragment can now be reduced.
+                    let (reducible, pending): (Vec<_>, Vec<_>) = core::mem::take(&mut self.mixed)
+                        .into_iter()
+                        .partition(|(mixed, _)| mixed.contains(&index));
+                    self.mixed = pending;
+                    queue.extend(reducible);
+                }
+                _ => {
+                    if !self.mixed.iter().any(|(mixed, _)| *mixed == indices) {
+                        self.mixed.push((indices, data));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A single part of a multi-part encoding, prior to Bech32m encoding.
+struct Part {
+    seq_num: u32,
+    fragment_count: u32,
+    message_len: u32,
+    digest: [u8; 32],
+    data: Vec<u8>,
+}
+
+impl Part {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PART_HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(&self.seq_num.to_le_bytes());
+        bytes.extend_from_slice(&self.fragment_count.to_le_bytes());
+        bytes.extend_from_slice(&self.message_len.to_le_bytes());
+        bytes.extend_from_slice(&self.digest);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.len() <= PART_HEADER_LEN {
+            return Err(DecodeError::InvalidPart);
+        }
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes(
+                bytes[offset..offset + 4]
+                    .try_into()
+                    .expect("correct length"),
+            )
+        };
+
+        let part = Self {
+            seq_num: read_u32(0),
+            fragment_count: read_u32(4),
+            message_len: read_u32(8),
+            digest: bytes[12..PART_HEADER_LEN]
+                .try_into()
+                .expect("correct length"),
+            data: bytes[PART_HEADER_LEN..].to_vec(),
+        };
+
+        // The fragments must exactly cover the message, with at most one fragment's worth
+        // of padding.
+        let fragment_count = part.fragment_count as u64;
+        let fragment_len = part.data.len() as u64;
+        let message_len = part.message_len as u64;
+        if part.seq_num == 0
+            || fragment_count == 0
+            || fragment_count > MAX_FRAGMENT_COUNT as u64
+            || fragment_count * fragment_len < message_len
+            || (fragment_count - 1) * fragment_len >= message_len.max(1)
+        {
+            return Err(DecodeError::InvalidPart);
+        }
+
+        Ok(part)
+    }
+}
+
+/// Returns the indices of the fragments combined into the part with the given sequence
+/// number.
+///
+/// Parts `1..=fragment_count` each carry a single fragment in order. Later parts carry a
+/// pseudo-random combination of fragments, with the number of fragments sampled from the
+/// ideal soliton distribution.
+fn choose_fragments(seq_num: u32, fragment_count: usize, digest: &[u8; 32]) -> Vec<usize> {
+    if seq_num as usize <= fragment_count {
+        return vec![seq_num as usize - 1];
+    }
+
+    let mut rng = Prng::new(seq_num, digest);
+
+    // Sample the degree with probability proportional to 1/d.
+    let total: f64 = (1..=fragment_count).map(|d| 1.0 / d as f64).sum();
+    let target = rng.next_f64() * total;
+    let mut acc = 0.0;
+    let degree = (1..=fragment_count)
+        .find(|&d| {
+            acc += 1.0 / d as f64;
+            target < acc
+        })
+        .unwrap_or(fragment_count);
+
+    // Choose `degree` distinct fragments with a partial Fisher-Yates shuffle.
+    let mut indices = (0..fragment_count).collect::<Vec<_>>();
+    for i in 0..degree {
+        let j = i + rng.below(fragment_count - i);
+        indices.swap(i, j);
+    }
+    indices.truncate(degree);
+    indices.sort_unstable();
+    indices
+}
+
+/// A deterministic pseudo-random number generator, seeded from a part's sequence number
+/// and the message digest so that encoder and decoder agree on each part's contents.
+struct Prng {
+    block: [u8; 64],
+    offset: usize,
+}
+
+impl Prng {
+    fn new(seq_num: u32, digest: &[u8; 32]) -> Self {
+        let block = blake2b_simd::Params::new()
+            .hash_length(64)
+            .personal(FOUNTAIN_PRNG_PERSONALIZATION)
+            .to_state()
+            .update(&seq_num.to_le_bytes())
+            .update(digest)
+            .finalize();
+        Self {
+            block: *block.as_array(),
+            offset: 0,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.offset == self.block.len() {
+            let block = blake2b_simd::Params::new()
+                .hash_length(64)
+                .personal(FOUNTAIN_PRNG_PERSONALIZATION)
+                .hash(&self.block);
+            self.block = *block.as_array();
+            self.offset = 0;
+        }
+        let value = u64::from_le_bytes(
+            self.block[self.offset..self.offset + 8]
+                .try_into()
+                .expect("correct length"),
+        );
+        self.offset += 8;
+        value
+    }
+
+    /// Returns a value uniformly distributed in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a value in `[0, bound)`.
+    ///
+    /// The modulo bias is negligible for the fragment counts used here.
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+fn xor_into(acc: &mut [u8], fragment: &[u8]) {
+    for (a, b) in acc.iter_mut().zip(fragment) {
+        *a ^= b;
+    }
+}
+
+/// Errors that can occur while decoding a text-encoded PCZT.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The string is not a valid Bech32m string.
+    NotBech32m,
+    /// The string has an unexpected human-readable part.
+    UnexpectedPrefix(String),
+    /// A part of a multi-part encoding is malformed.
+    InvalidPart,
+    /// A part of a multi-part encoding belongs to a different PCZT than the parts
+    /// previously received.
+    MismatchedPart,
+    /// Not enough parts have been received to recover the PCZT.
+    Incomplete,
+    /// The reassembled PCZT does not match the digest carried in its parts.
+    DigestMismatch,
+    /// The decoded bytes are not a valid PCZT.
+    Pczt(ParseError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotBech32m => write!(f, "Not a valid Bech32m string"),
+            DecodeError::UnexpectedPrefix(hrp) => {
+                write!(f, "Unexpected human-readable part \"{hrp}\"")
+            }
+            DecodeError::InvalidPart => write!(f, "Malformed part of a multi-part PCZT"),
+            DecodeError::MismatchedPart => {
+                write!(f, "Part belongs to a different PCZT than earlier parts")
+            }
+            DecodeError::Incomplete => write!(f, "Not enough parts received to recover the PCZT"),
+            DecodeError::DigestMismatch => {
+                write!(f, "Reassembled PCZT does not match the digest in its parts")
+            }
+            DecodeError::Pczt(e) => write!(f, "Invalid PCZT: {e:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use core::num::NonZeroUsize;
+
+    use zcash_protocol::consensus::BranchId;
+
+    use super::{decode, encode, DecodeError, Decoder, Encoder, Part, MAX_FRAGMENT_COUNT};
+    use crate::roles::creator::Creator;
+
+    fn message(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + 3) as u8).collect()
+    }
+
+    #[test]
+    fn single_string_round_trip() {
+        let pczt = Creator::new(BranchId::Nu6.into(), 10_000_000, 133, [0; 32], [0; 32]).build();
+        let encoded = encode(&pczt);
+        assert!(encoded.starts_with("pczt1"));
+        assert_eq!(decode(&encoded).unwrap().serialize(), pczt.serialize());
+        assert_eq!(
+            decode(&encoded.to_uppercase()).unwrap().serialize(),
+            pczt.serialize()
+        );
+
+        let mut corrupted = encoded.clone();
+        let last = corrupted.pop().unwrap();
+        corrupted.push(if last == 'q' { 'p' } else { 'q' });
+        assert!(matches!(decode(&corrupted), Err(DecodeError::NotBech32m)));
+    }
+
+    #[test]
+    fn multi_part_round_trip() {
+        let pczt = Creator::new(BranchId::Nu6.into(), 10_000_000, 133, [0; 32], [0; 32]).build();
+        let mut encoder = Encoder::new(&pczt, NonZeroUsize::new(16).unwrap());
+        assert!(!encoder.is_single_part());
+
+        let mut decoder = Decoder::new();
+        assert_eq!(decoder.progress(), None);
+        for _ in 0..encoder.fragment_count() {
+            decoder
+                .receive(&encoder.next_part().to_uppercase())
+                .unwrap();
+        }
+        assert!(decoder.is_complete());
+        assert_eq!(decoder.finish().unwrap().serialize(), pczt.serialize());
+
+        // A single part is rejected by the single-string decoder.
+        assert!(matches!(
+            decode(&encoder.next_part()),
+            Err(DecodeError::UnexpectedPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn recovers_from_missed_parts_in_any_order() {
+        let message = message(1000);
+        let mut encoder = Encoder::from_bytes(message.clone(), NonZeroUsize::new(64).unwrap());
+        let fragment_count = encoder.fragment_count();
+        assert_eq!(fragment_count, 16);
+
+        // Skip every other single-fragment part, and feed the rest in reverse order.
+        let mut parts = (0..fragment_count * 10)
+            .map(|_| encoder.next_part())
+            .enumerate()
+            .filter(|(i, _)| *i >= fragment_count || i % 2 == 0)
+            .map(|(_, part)| part)
+            .collect::<Vec<_>>();
+        parts.reverse();
+
+        let mut decoder = Decoder::new();
+        for part in &parts {
+            decoder.receive(part).unwrap();
+            if decoder.is_complete() {
+                break;
+            }
+        }
+        assert_eq!(decoder.progress(), Some((fragment_count, fragment_count)));
+        assert_eq!(decoder.finish_bytes().unwrap(), message);
+    }
+
+    #[test]
+    fn rejects_mismatched_and_incomplete() {
+        let mut encoder_a = Encoder::from_bytes(message(100), NonZeroUsize::new(10).unwrap());
+        let mut encoder_b = Encoder::from_bytes(message(101), NonZeroUsize::new(10).unwrap());
+
+        let mut decoder = Decoder::new();
+        decoder.receive(&encoder_a.next_part()).unwrap();
+        assert!(matches!(
+            decoder.receive(&encoder_b.next_part()),
+            Err(DecodeError::MismatchedPart)
+        ));
+        assert_eq!(decoder.progress(), Some((1, 10)));
+        assert!(matches!(
+            decoder.finish_bytes(),
+            Err(DecodeError::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn rejects_excessive_fragment_count() {
+        let part = |fragment_count: usize| Part {
+            seq_num: 1,
+            fragment_count: fragment_count as u32,
+            message_len: fragment_count as u32,
+            digest: [0; 32],
+            data: vec![0],
+        };
+
+        assert!(Part::from_bytes(&part(MAX_FRAGMENT_COUNT).to_bytes()).is_ok());
+        assert!(matches!(
+            Part::from_bytes(&part(MAX_FRAGMENT_COUNT + 1).to_bytes()),
+            Err(DecodeError::InvalidPart)
+        ));
+        assert!(matches!(
+            Part::from_bytes(&part(u32::MAX as usize).to_bytes()),
+            Err(DecodeError::InvalidPart)
+        ));
+    }
+}
@@ -21,6 +21,9 @@
 #[macro_use]
 extern crate alloc;
 
+#[cfg(feature = "std")]
+extern crate std;
+
 use alloc::vec::Vec;
 
 use getset::Getters;
@@ -31,13 +34,18 @@ use {roles::signer::EffectsOnly, zcash_primitives::transaction::TransactionData}
 
 pub mod roles;
 
+#[cfg(feature = "text-encoding")]
+pub mod encoding;
+
 pub mod common;
 pub mod orchard;
 pub mod sapling;
 pub mod transparent;
 
+pub mod version;
+pub use version::{DowngradeError, Version};
+
 const MAGIC_BYTES: &[u8] = b"PCZT";
-const PCZT_VERSION_1: u32 = 1;
 
 /// A partially-created Zcash transaction.
 #[derive(Clone, Debug, Serialize, Deserialize, Getters)]
@@ -64,28 +72,30 @@ pub struct Pczt {
 
 impl Pczt {
     /// Parses a PCZT from its encoding.
+    ///
+    /// PCZTs encoded with any [`Version`] known to this library are accepted, and are
+    /// upgraded to the current representation.
     pub fn parse(bytes: &[u8]) -> Result<Self, ParseError> {
-        if bytes.len() < 8 {
-            return Err(ParseError::TooShort);
-        }
-        if &bytes[..4] != MAGIC_BYTES {
-            return Err(ParseError::NotPczt);
-        }
-        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
-        if version != PCZT_VERSION_1 {
-            return Err(ParseError::UnknownVersion(version));
-        }
-
-        // This is a v1 PCZT.
-        postcard::from_bytes(&bytes[8..]).map_err(ParseError::Invalid)
+        let version = Version::of(bytes)?;
+        version::decode(version, &bytes[8..])
     }
 
-    /// Serializes this PCZT.
+    /// Serializes this PCZT, using [`Version::CURRENT`].
     pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_as(Version::CURRENT)
+            .expect("the current version can represent any PCZT")
+    }
+
+    /// Serializes this PCZT using the given version of the encoding.
+    ///
+    /// This enables handing a PCZT to a party running an older version of this library.
+    /// Returns an error if the PCZT contains data that cannot be represented in the
+    /// requested version.
+    pub fn serialize_as(&self, version: Version) -> Result<Vec<u8>, DowngradeError> {
         let mut bytes = vec![];
         bytes.extend_from_slice(MAGIC_BYTES);
-        bytes.extend_from_slice(&PCZT_VERSION_1.to_le_bytes());
-        postcard::to_extend(self, bytes).expect("can serialize into memory")
+        bytes.extend_from_slice(&version.to_u32().to_le_bytes());
+        version::encode(self, version, bytes)
     }
 
     /// Gets the effects of this transaction.
@@ -111,6 +121,11 @@ impl Pczt {
 pub enum ParseError {
     /// The bytes do not contain a PCZT.
     NotPczt,
+    /// The bytes are not in the expected encoding.
+    ///
+    /// This is returned when parsing an encoding other than a PCZT, such as an external
+    /// signing request, from bytes that start with the wrong magic bytes.
+    UnknownEncoding,
     /// The PCZT encoding was invalid.
     Invalid(postcard::Error),
     /// The bytes are too short to contain a PCZT.
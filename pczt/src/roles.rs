@@ -1,15 +1,9 @@
 //! Implementations of the PCZT roles.
-//!
-//! The roles currently without an implementation are:
-//! - Constructor (anyone can contribute)
-//!   - Adds spends and outputs to the PCZT.
-//!   - Before any input or output may be added, the constructor must check the
-//!     `Global.tx_modifiable` field. Inputs may only be added if the Inputs Modifiable
-//!     flag is True. Outputs may only be added if the Outputs Modifiable flag is True.
-//!   - A single entity is likely to be both a Creator and Constructor.
 
 pub mod creator;
 
+pub mod constructor;
+
 #[cfg(feature = "io-finalizer")]
 pub mod io_finalizer;
 
@@ -25,6 +19,9 @@ pub mod prover;
 #[cfg(feature = "signer")]
 pub mod signer;
 
+#[cfg(feature = "signer")]
+pub mod external_signer;
+
 pub mod low_level_signer;
 
 pub mod combiner;
@@ -7,26 +7,50 @@ use alloc::vec::Vec;
 
 use crate::Pczt;
 
+use super::constructor::verify_contribution;
+
 pub struct Combiner {
     pczts: Vec<Pczt>,
+    contributions: Vec<Pczt>,
 }
 
 impl Combiner {
     /// Instantiates the Combiner role with the given PCZTs.
     pub fn new(pczts: Vec<Pczt>) -> Self {
-        Self { pczts }
+        Self {
+            pczts,
+            contributions: vec![],
+        }
+    }
+
+    /// Configures the Combiner to check that the spends and outputs of each of the given
+    /// contributions (as added by the [`Constructor`]) are present and unaltered in the
+    /// combined PCZT.
+    ///
+    /// [`Constructor`]: super::constructor::Constructor
+    pub fn with_contributions(mut self, contributions: Vec<Pczt>) -> Self {
+        self.contributions = contributions;
+        self
     }
 
     /// Combines the PCZTs.
     pub fn combine(self) -> Result<Pczt, Error> {
-        self.pczts
+        let pczt = self
+            .pczts
             .into_iter()
             .try_fold(None, |acc, pczt| match acc {
                 None => Ok(Some(pczt)),
                 Some(acc) => merge(acc, pczt).map(Some),
             })
             .transpose()
-            .unwrap_or(Err(Error::NoPczts))
+            .unwrap_or(Err(Error::NoPczts))?;
+
+        for (index, contribution) in self.contributions.iter().enumerate() {
+            verify_contribution(&pczt, contribution)
+                .map_err(|_| Error::ContributionMismatch(index))?;
+        }
+
+        Ok(pczt)
     }
 }
 
@@ -104,4 +128,7 @@ pub(crate) fn merge_map<K: Ord, V: PartialEq>(
 pub enum Error {
     NoPczts,
     DataMismatch,
+    /// The spends or outputs of the contribution at the given index are missing from, or
+    /// have been altered in, the combined PCZT.
+    ContributionMismatch(usize),
 }
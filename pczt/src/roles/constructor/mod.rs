@@ -0,0 +1,337 @@
+//! The Constructor role (anyone can contribute).
+//!
+//! - Adds spends and outputs to the PCZT.
+//! - Before any input or output may be added, the constructor must check the
+//!   `Global.tx_modifiable` field. Inputs may only be added if the Inputs Modifiable
+//!   flag is True. Outputs may only be added if the Outputs Modifiable flag is True.
+//!
+//! This enables several parties to each contribute their own spends and outputs to a
+//! shared transaction (for example, a collaborative payment or batched transaction):
+//!
+//! 1. A coordinator uses the [`Creator`] to create a base PCZT, choosing the anchors that
+//!    every party must use for their shielded spends.
+//! 2. Each party independently creates a PCZT containing only their own spends and
+//!    outputs (for example, with `Creator::build_from_parts`), using the same
+//!    transaction parameters and anchors.
+//! 3. The coordinator appends every contribution to the base PCZT with
+//!    [`Constructor::add_contribution`], and then runs the IO Finalizer, which computes
+//!    the binding signing keys from the value commitment randomness of every party's
+//!    spends and outputs.
+//! 4. Each party checks with [`verify_contribution`] that their spends and outputs are
+//!    present and unaltered before signing, and the [`Combiner`] can be configured with
+//!    [`Combiner::with_contributions`] to check every contribution when merging the
+//!    signed PCZTs.
+//!
+//! Contributions must not have been IO-finalized or signed, because appending spends and
+//! outputs changes the transaction's sighash.
+//!
+//! [`Creator`]: super::creator::Creator
+//! [`Combiner`]: super::combiner::Combiner
+//! [`Combiner::with_contributions`]: super::combiner::Combiner::with_contributions
+
+use alloc::collections::BTreeSet;
+
+use crate::{
+    common::{Global, FLAG_HAS_SIGHASH_SINGLE},
+    Pczt,
+};
+
+use super::combiner::{merge_map, merge_optional};
+
+pub struct Constructor {
+    pczt: Pczt,
+}
+
+impl Constructor {
+    /// Instantiates the Constructor role with the given PCZT.
+    pub fn new(pczt: Pczt) -> Self {
+        Self { pczt }
+    }
+
+    /// Appends the spends and outputs of another party's PCZT to this PCZT.
+    ///
+    /// The contribution must describe the same transaction parameters as this PCZT (the
+    /// transaction version, consensus branch, expiry height and coin type), and must use
+    /// the same anchors and Orchard flags for any shielded spends or outputs it contains.
+    pub fn add_contribution(mut self, contribution: Pczt) -> Result<Self, Error> {
+        let Pczt {
+            global,
+            transparent,
+            sapling,
+            orchard,
+        } = contribution;
+
+        let has_shielded = !(sapling.spends.is_empty()
+            && sapling.outputs.is_empty()
+            && orchard.actions.is_empty());
+
+        // Check that this PCZT permits the contributed spends and outputs.
+        if !transparent.inputs.is_empty() && !self.pczt.global.inputs_modifiable() {
+            return Err(Error::TransparentInputsNotModifiable);
+        }
+        if !transparent.outputs.is_empty() && !self.pczt.global.outputs_modifiable() {
+            return Err(Error::TransparentOutputsNotModifiable);
+        }
+        if has_shielded && !self.pczt.global.shielded_modifiable() {
+            return Err(Error::ShieldedNotModifiable);
+        }
+
+        // `SIGHASH_SINGLE` pairs inputs with outputs by index, which appending would break.
+        if self.pczt.global.has_sighash_single()
+            || (global.tx_modifiable & FLAG_HAS_SIGHASH_SINGLE) != 0
+        {
+            return Err(Error::SighashSingle);
+        }
+
+        // Appending spends and outputs changes the sighash, so the contribution must not
+        // have been IO-finalized or signed.
+        if sapling.bsk.is_some()
+            || orchard.bsk.is_some()
+            || orchard.zkproof.is_some()
+            || transparent
+                .inputs
+                .iter()
+                .any(|input| !input.partial_signatures.is_empty())
+            || sapling
+                .spends
+                .iter()
+                .any(|spend| spend.spend_auth_sig.is_some())
+            || orchard
+                .actions
+                .iter()
+                .any(|action| action.spend.spend_auth_sig.is_some())
+        {
+            return Err(Error::AlreadyFinalized);
+        }
+
+        if !merge_globals(&mut self.pczt.global, global) {
+            return Err(Error::GlobalMismatch);
+        }
+
+        if !sapling.spends.is_empty() && sapling.anchor != self.pczt.sapling.anchor {
+            return Err(Error::AnchorMismatch);
+        }
+        if !orchard.actions.is_empty() {
+            if orchard.anchor != self.pczt.orchard.anchor {
+                return Err(Error::AnchorMismatch);
+            }
+            if orchard.flags != self.pczt.orchard.flags {
+                return Err(Error::OrchardFlagsMismatch);
+            }
+        }
+
+        // Reject spends that are already present in this PCZT.
+        let mut prevouts = self
+            .pczt
+            .transparent
+            .inputs
+            .iter()
+            .map(|input| (input.prevout_txid, input.prevout_index))
+            .collect::<BTreeSet<_>>();
+        let mut sapling_nullifiers = self
+            .pczt
+            .sapling
+            .spends
+            .iter()
+            .map(|spend| spend.nullifier)
+            .collect::<BTreeSet<_>>();
+        let mut orchard_nullifiers = self
+            .pczt
+            .orchard
+            .actions
+            .iter()
+            .map(|action| action.spend.nullifier)
+            .collect::<BTreeSet<_>>();
+        if !(transparent
+            .inputs
+            .iter()
+            .all(|input| prevouts.insert((input.prevout_txid, input.prevout_index)))
+            && sapling
+                .spends
+                .iter()
+                .all(|spend| sapling_nullifiers.insert(spend.nullifier))
+            && orchard
+                .actions
+                .iter()
+                .all(|action| orchard_nullifiers.insert(action.spend.nullifier)))
+        {
+            return Err(Error::DuplicateSpend);
+        }
+
+        // Update the value sums.
+        let sapling_value_sum = self
+            .pczt
+            .sapling
+            .value_sum
+            .checked_add(sapling.value_sum)
+            .ok_or(Error::ValueSumOverflow)?;
+        let orchard_value_sum = {
+            let to_i128 = |(magnitude, is_negative): (u64, bool)| {
+                if is_negative {
+                    -i128::from(magnitude)
+                } else {
+                    i128::from(magnitude)
+                }
+            };
+            let sum = to_i128(self.pczt.orchard.value_sum) + to_i128(orchard.value_sum);
+            (
+                u64::try_from(sum.unsigned_abs()).map_err(|_| Error::ValueSumOverflow)?,
+                sum < 0,
+            )
+        };
+
+        self.pczt.transparent.inputs.extend(transparent.inputs);
+        self.pczt.transparent.outputs.extend(transparent.outputs);
+        self.pczt.sapling.spends.extend(sapling.spends);
+        self.pczt.sapling.outputs.extend(sapling.outputs);
+        self.pczt.sapling.value_sum = sapling_value_sum;
+        self.pczt.orchard.actions.extend(orchard.actions);
+        self.pczt.orchard.value_sum = orchard_value_sum;
+
+        Ok(self)
+    }
+
+    /// Finishes the Constructor role, returning the updated PCZT.
+    pub fn finish(self) -> Pczt {
+        self.pczt
+    }
+}
+
+/// Merges the global fields of a contribution into the PCZT being constructed.
+///
+/// Unlike [`Global::merge`], this ignores the contribution's `tx_modifiable` flags: a
+/// party's own PCZT is typically not modifiable, but that does not prevent it from being
+/// appended to a modifiable one.
+fn merge_globals(lhs: &mut Global, rhs: Global) -> bool {
+    let Global {
+        tx_version,
+        version_group_id,
+        consensus_branch_id,
+        fallback_lock_time,
+        expiry_height,
+        coin_type,
+        tx_modifiable: _,
+        proprietary,
+    } = rhs;
+
+    lhs.tx_version == tx_version
+        && lhs.version_group_id == version_group_id
+        && lhs.consensus_branch_id == consensus_branch_id
+        && lhs.expiry_height == expiry_height
+        && lhs.coin_type == coin_type
+        && merge_optional(&mut lhs.fallback_lock_time, fallback_lock_time)
+        && merge_map(&mut lhs.proprietary, proprietary)
+}
+
+/// Checks that every spend and output in `contribution` is present in `pczt` with the
+/// same effects.
+///
+/// Only the fields that affect the transaction are compared, so `pczt` may have been
+/// further updated (for example, IO-finalized, proven, or signed) since the contribution
+/// was added.
+pub fn verify_contribution(pczt: &Pczt, contribution: &Pczt) -> Result<(), Error> {
+    let inputs_ok = contribution.transparent.inputs.iter().all(|c| {
+        pczt.transparent.inputs.iter().any(|input| {
+            input.prevout_txid == c.prevout_txid
+                && input.prevout_index == c.prevout_index
+                && input.value == c.value
+                && input.script_pubkey == c.script_pubkey
+                && (c.sequence.is_none() || input.sequence == c.sequence)
+                && input.required_time_lock_time == c.required_time_lock_time
+                && input.required_height_lock_time == c.required_height_lock_time
+        })
+    });
+
+    // Transparent outputs have no unique identifier, so match them as a multiset.
+    let outputs_ok = {
+        let mut matched = BTreeSet::new();
+        contribution.transparent.outputs.iter().all(|c| {
+            pczt.transparent
+                .outputs
+                .iter()
+                .enumerate()
+                .find(|(i, output)| {
+                    !matched.contains(i)
+                        && output.value == c.value
+                        && output.script_pubkey == c.script_pubkey
+                })
+                .map(|(i, _)| matched.insert(i))
+                .is_some()
+        })
+    };
+
+    let sapling_spends_ok = contribution.sapling.spends.iter().all(|c| {
+        pczt.sapling
+            .spends
+            .iter()
+            .any(|spend| spend.nullifier == c.nullifier && spend.cv == c.cv && spend.rk == c.rk)
+    });
+
+    let sapling_outputs_ok = contribution.sapling.outputs.iter().all(|c| {
+        pczt.sapling.outputs.iter().any(|output| {
+            output.cmu == c.cmu
+                && output.cv == c.cv
+                && output.ephemeral_key == c.ephemeral_key
+                && output.enc_ciphertext == c.enc_ciphertext
+                && output.out_ciphertext == c.out_ciphertext
+        })
+    });
+
+    let orchard_actions_ok = contribution.orchard.actions.iter().all(|c| {
+        pczt.orchard.actions.iter().any(|action| {
+            action.spend.nullifier == c.spend.nullifier
+                && action.output.cmx == c.output.cmx
+                && action.cv_net == c.cv_net
+                && action.spend.rk == c.spend.rk
+                && action.output.ephemeral_key == c.output.ephemeral_key
+                && action.output.enc_ciphertext == c.output.enc_ciphertext
+                && action.output.out_ciphertext == c.output.out_ciphertext
+        })
+    });
+
+    let anchors_ok = (contribution.sapling.spends.is_empty()
+        || contribution.sapling.anchor == pczt.sapling.anchor)
+        && (contribution.orchard.actions.is_empty()
+            || (contribution.orchard.anchor == pczt.orchard.anchor
+                && contribution.orchard.flags == pczt.orchard.flags));
+
+    if inputs_ok
+        && outputs_ok
+        && sapling_spends_ok
+        && sapling_outputs_ok
+        && orchard_actions_ok
+        && anchors_ok
+    {
+        Ok(())
+    } else {
+        Err(Error::ContributionMismatch)
+    }
+}
+
+/// Errors that can occur while adding contributions to a PCZT.
+#[derive(Debug)]
+pub enum Error {
+    /// The contribution has been IO-finalized or signed.
+    AlreadyFinalized,
+    /// The contribution uses a different anchor than the PCZT.
+    AnchorMismatch,
+    /// The contribution's spends or outputs are not present in the PCZT, or have been
+    /// altered.
+    ContributionMismatch,
+    /// The contribution spends an input that the PCZT already spends.
+    DuplicateSpend,
+    /// The contribution describes different transaction parameters than the PCZT.
+    GlobalMismatch,
+    /// The contribution uses different Orchard flags than the PCZT.
+    OrchardFlagsMismatch,
+    /// The PCZT does not permit shielded spends or outputs to be added.
+    ShieldedNotModifiable,
+    /// The PCZT or the contribution has a `SIGHASH_SINGLE` transparent signature.
+    SighashSingle,
+    /// The PCZT does not permit transparent inputs to be added.
+    TransparentInputsNotModifiable,
+    /// The PCZT does not permit transparent outputs to be added.
+    TransparentOutputsNotModifiable,
+    /// The combined value sum of a shielded bundle is out of range.
+    ValueSumOverflow,
+}
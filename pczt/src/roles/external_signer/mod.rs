@@ -0,0 +1,747 @@
+//! A variant of the Signer role for signers that hold their keys outside of the process
+//! holding the PCZT, such as hardware wallets.
+//!
+//! Devices of this kind generally cannot parse a full PCZT, and do not need to: to create
+//! a spend authorization signature they need only the signature hash, the randomizer
+//! `alpha` (for shielded spends), and the derivation path of the key to sign with. This
+//! module defines:
+//!
+//! - [`SigningRequest`], the minimal per-input data sent to a device;
+//! - [`SigningResponse`], the signatures a device returns;
+//! - [`ExternalSigner`], a trait for devices (or the transports that reach them);
+//! - [`sign_with`], which drives an [`ExternalSigner`] and applies the returned
+//!   signatures to a PCZT via the [low-level Signer].
+//!
+//! Signatures are verified before they are applied to the PCZT, so a faulty or malicious
+//! device cannot cause an invalid signature to be included in the transaction.
+//!
+//! [`InProcessSigner`] is an implementation of [`ExternalSigner`] that holds its keys
+//! in memory, for use in tests and as a reference for device integrations.
+//!
+//! [low-level Signer]: crate::roles::low_level_signer
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+use blake2b_simd::Hash as Blake2bHash;
+use ff::PrimeField;
+use rand_core::OsRng;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use orchard::primitives::redpallas;
+use zcash_primitives::transaction::{
+    sighash::SignableInput, sighash_v5::v5_signature_hash, txid::TxIdDigester, TransactionData,
+    TxDigests,
+};
+
+use crate::{
+    common::Zip32Derivation,
+    roles::{
+        low_level_signer,
+        signer::{
+            self, pczt_to_tx_data, update_tx_modifiable_for_shielded_signature,
+            update_tx_modifiable_for_transparent_signature, EffectsOnly,
+        },
+    },
+    Pczt,
+};
+
+const REQUEST_MAGIC_BYTES: &[u8] = b"PZSQ";
+const RESPONSE_MAGIC_BYTES: &[u8] = b"PZSR";
+const ENCODING_VERSION_1: u32 = 1;
+
+/// A ZIP 32 or BIP 32 derivation path, along with the fingerprint of the seed from which
+/// the path is derived.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DerivationPath {
+    seed_fingerprint: [u8; 32],
+    path: Vec<u32>,
+}
+
+impl DerivationPath {
+    /// Returns the [ZIP 32 seed fingerprint](https://zips.z.cash/zip-0032#seed-fingerprints)
+    /// of the seed from which this path is derived.
+    pub fn seed_fingerprint(&self) -> &[u8; 32] {
+        &self.seed_fingerprint
+    }
+
+    /// Returns the sequence of child indices along the path. Hardened indices have the
+    /// hardened flag bit set.
+    pub fn path(&self) -> &[u32] {
+        &self.path
+    }
+
+    fn from_pczt(derivation: &Zip32Derivation) -> Self {
+        Self {
+            seed_fingerprint: derivation.seed_fingerprint,
+            path: derivation.derivation_path.clone(),
+        }
+    }
+}
+
+/// A request for a signature over a transparent input.
+///
+/// One request is created for each pubkey that has a BIP 32 derivation recorded in the
+/// input and for which no signature is yet present.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransparentSigningRequest {
+    index: u32,
+    sighash: [u8; 32],
+    sighash_type: u8,
+    #[serde_as(as = "[_; 33]")]
+    pubkey: [u8; 33],
+    derivation: DerivationPath,
+}
+
+impl TransparentSigningRequest {
+    /// Returns the index of the input within the transparent bundle.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the signature hash to be signed.
+    pub fn sighash(&self) -> &[u8; 32] {
+        &self.sighash
+    }
+
+    /// Returns the sighash type that the sighash commits to.
+    pub fn sighash_type(&self) -> u8 {
+        self.sighash_type
+    }
+
+    /// Returns the compressed encoding of the pubkey whose secret key must create the
+    /// signature.
+    pub fn pubkey(&self) -> &[u8; 33] {
+        &self.pubkey
+    }
+
+    /// Returns the [BIP 44] derivation path of the secret key.
+    ///
+    /// [BIP 44]: https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki
+    pub fn derivation(&self) -> &DerivationPath {
+        &self.derivation
+    }
+}
+
+/// A request for a spend authorization signature over a Sapling spend or an Orchard
+/// action.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShieldedSigningRequest {
+    index: u32,
+    sighash: [u8; 32],
+    alpha: [u8; 32],
+    derivation: Option<DerivationPath>,
+}
+
+impl ShieldedSigningRequest {
+    /// Returns the index of the spend within the Sapling bundle, or of the action within
+    /// the Orchard bundle.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the signature hash to be signed.
+    pub fn sighash(&self) -> &[u8; 32] {
+        &self.sighash
+    }
+
+    /// Returns the encoding of the spend authorization randomizer `alpha`.
+    pub fn alpha(&self) -> &[u8; 32] {
+        &self.alpha
+    }
+
+    /// Returns the [ZIP 32] derivation path of the spending key, if it was recorded in the
+    /// PCZT.
+    ///
+    /// [ZIP 32]: https://zips.z.cash/zip-0032
+    pub fn derivation(&self) -> Option<&DerivationPath> {
+        self.derivation.as_ref()
+    }
+}
+
+/// The data that an external signer needs in order to sign the inputs of a PCZT.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningRequest {
+    transparent: Vec<TransparentSigningRequest>,
+    sapling: Vec<ShieldedSigningRequest>,
+    orchard: Vec<ShieldedSigningRequest>,
+}
+
+impl SigningRequest {
+    /// Extracts the inputs of the given PCZT that still require signatures.
+    ///
+    /// Dummy spends, and spends that already have a spend authorization signature, are
+    /// omitted. Returns an error if a shielded spend that requires a signature does not
+    /// yet have its spend authorization randomizer set (that is, if the IO Finalizer has
+    /// not yet been run).
+    pub fn from_pczt(pczt: &Pczt) -> Result<Self, Error<Infallible>> {
+        let sighashes = Sighashes::new(pczt)?;
+
+        let transparent = pczt
+            .transparent
+            .inputs
+            .iter()
+            .zip(sighashes.transparent.iter())
+            .enumerate()
+            .flat_map(|(index, (input, sighash))| {
+                input
+                    .bip32_derivation
+                    .iter()
+                    .filter(|(pubkey, _)| !input.partial_signatures.contains_key(*pubkey))
+                    .map(move |(pubkey, derivation)| TransparentSigningRequest {
+                        index: index as u32,
+                        sighash: *sighash,
+                        sighash_type: input.sighash_type,
+                        pubkey: *pubkey,
+                        derivation: DerivationPath::from_pczt(derivation),
+                    })
+            })
+            .collect();
+
+        let sapling = pczt
+            .sapling
+            .spends
+            .iter()
+            .enumerate()
+            .filter(|(_, spend)| spend.spend_auth_sig.is_none() && spend.dummy_ask.is_none())
+            .map(|(index, spend)| {
+                Ok(ShieldedSigningRequest {
+                    index: index as u32,
+                    sighash: sighashes.shielded,
+                    alpha: spend
+                        .alpha
+                        .ok_or(Error::<Infallible>::MissingSpendAuthRandomizer)?,
+                    derivation: spend
+                        .zip32_derivation
+                        .as_ref()
+                        .map(DerivationPath::from_pczt),
+                })
+            })
+            .collect::<Result<_, Error<Infallible>>>()?;
+
+        let orchard = pczt
+            .orchard
+            .actions
+            .iter()
+            .map(|action| &action.spend)
+            .enumerate()
+            .filter(|(_, spend)| spend.spend_auth_sig.is_none() && spend.dummy_sk.is_none())
+            .map(|(index, spend)| {
+                Ok(ShieldedSigningRequest {
+                    index: index as u32,
+                    sighash: sighashes.shielded,
+                    alpha: spend
+                        .alpha
+                        .ok_or(Error::<Infallible>::MissingSpendAuthRandomizer)?,
+                    derivation: spend
+                        .zip32_derivation
+                        .as_ref()
+                        .map(DerivationPath::from_pczt),
+                })
+            })
+            .collect::<Result<_, Error<Infallible>>>()?;
+
+        Ok(Self {
+            transparent,
+            sapling,
+            orchard,
+        })
+    }
+
+    /// Returns the requests for transparent input signatures.
+    pub fn transparent(&self) -> &[TransparentSigningRequest] {
+        &self.transparent
+    }
+
+    /// Returns the requests for Sapling spend authorization signatures.
+    pub fn sapling(&self) -> &[ShieldedSigningRequest] {
+        &self.sapling
+    }
+
+    /// Returns the requests for Orchard spend authorization signatures.
+    pub fn orchard(&self) -> &[ShieldedSigningRequest] {
+        &self.orchard
+    }
+
+    /// Returns `true` if no inputs require signatures.
+    pub fn is_empty(&self) -> bool {
+        self.transparent.is_empty() && self.sapling.is_empty() && self.orchard.is_empty()
+    }
+
+    /// Parses a signing request from its encoding.
+    pub fn parse(bytes: &[u8]) -> Result<Self, crate::ParseError> {
+        parse_encoding(REQUEST_MAGIC_BYTES, bytes)
+    }
+
+    /// Serializes this signing request, for transmission to an external signer.
+    pub fn serialize(&self) -> Vec<u8> {
+        serialize_encoding(REQUEST_MAGIC_BYTES, self)
+    }
+}
+
+/// A signature over a transparent input, created by an external signer.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransparentSignature {
+    index: u32,
+    #[serde_as(as = "[_; 33]")]
+    pubkey: [u8; 33],
+    signature: Vec<u8>,
+}
+
+impl TransparentSignature {
+    /// Constructs a transparent signature.
+    ///
+    /// - `index`: the index of the input within the transparent bundle.
+    /// - `pubkey`: the compressed encoding of the pubkey under which the signature is valid.
+    /// - `signature`: the DER encoding of the ECDSA signature, without a trailing sighash
+    ///   type byte.
+    pub fn new(index: u32, pubkey: [u8; 33], signature: Vec<u8>) -> Self {
+        Self {
+            index,
+            pubkey,
+            signature,
+        }
+    }
+}
+
+/// A spend authorization signature over a Sapling spend or an Orchard action, created by
+/// an external signer.
+#[serde_as]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShieldedSignature {
+    index: u32,
+    #[serde_as(as = "[_; 64]")]
+    signature: [u8; 64],
+}
+
+impl ShieldedSignature {
+    /// Constructs a shielded spend authorization signature.
+    ///
+    /// - `index`: the index of the spend within the Sapling bundle, or of the action
+    ///   within the Orchard bundle.
+    /// - `signature`: the encoding of the RedJubjub or RedPallas signature.
+    pub fn new(index: u32, signature: [u8; 64]) -> Self {
+        Self { index, signature }
+    }
+}
+
+/// The signatures returned by an external signer in response to a [`SigningRequest`].
+///
+/// A signer may return signatures for a subset of the requested inputs (for example, if
+/// it does not hold the keys for some of them).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningResponse {
+    transparent: Vec<TransparentSignature>,
+    sapling: Vec<ShieldedSignature>,
+    orchard: Vec<ShieldedSignature>,
+}
+
+impl SigningResponse {
+    /// Constructs a signing response from its parts.
+    pub fn from_parts(
+        transparent: Vec<TransparentSignature>,
+        sapling: Vec<ShieldedSignature>,
+        orchard: Vec<ShieldedSignature>,
+    ) -> Self {
+        Self {
+            transparent,
+            sapling,
+            orchard,
+        }
+    }
+
+    /// Parses a signing response from its encoding.
+    pub fn parse(bytes: &[u8]) -> Result<Self, crate::ParseError> {
+        parse_encoding(RESPONSE_MAGIC_BYTES, bytes)
+    }
+
+    /// Serializes this signing response, for transmission from an external signer.
+    pub fn serialize(&self) -> Vec<u8> {
+        serialize_encoding(RESPONSE_MAGIC_BYTES, self)
+    }
+}
+
+fn parse_encoding<T: for<'a> Deserialize<'a>>(
+    magic: &[u8],
+    bytes: &[u8],
+) -> Result<T, crate::ParseError> {
+    if bytes.len() < 8 {
+        return Err(crate::ParseError::TooShort);
+    }
+    if &bytes[..4] != magic {
+        return Err(crate::ParseError::UnknownEncoding);
+    }
+    let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+    if version != ENCODING_VERSION_1 {
+        return Err(crate::ParseError::UnknownVersion(version));
+    }
+
+    postcard::from_bytes(&bytes[8..]).map_err(crate::ParseError::Invalid)
+}
+
+fn serialize_encoding<T: Serialize>(magic: &[u8], value: &T) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&ENCODING_VERSION_1.to_le_bytes());
+    postcard::to_extend(value, bytes).expect("can serialize into memory")
+}
+
+/// A signer that holds its keys outside of the process holding the PCZT.
+pub trait ExternalSigner {
+    /// The type of errors produced by the signer or its transport.
+    type Error;
+
+    /// Signs the requested inputs.
+    ///
+    /// Implementations should display the transaction's effects to the user for
+    /// confirmation where possible; this trait does not provide them, because a device
+    /// that relies on the host's description of the transaction gains no protection from
+    /// doing so.
+    fn sign(&mut self, request: &SigningRequest) -> Result<SigningResponse, Self::Error>;
+}
+
+/// Signs the inputs of the given PCZT with an external signer.
+///
+/// This extracts a [`SigningRequest`] from the PCZT, passes it to `signer`, and applies
+/// the returned signatures with [`apply_signatures`].
+///
+/// It is the caller's responsibility to perform any semantic validity checks on the PCZT
+/// (for example, confirming that the change amounts are correct) before calling this
+/// method.
+pub fn sign_with<S: ExternalSigner>(pczt: Pczt, signer: &mut S) -> Result<Pczt, Error<S::Error>> {
+    let request = SigningRequest::from_pczt(&pczt).map_err(Error::widen)?;
+    if request.is_empty() {
+        return Ok(pczt);
+    }
+
+    let response = signer.sign(&request).map_err(Error::Device)?;
+    apply_signatures(pczt, &response).map_err(Error::widen)
+}
+
+/// Verifies the signatures in the given response, and applies them to the PCZT.
+pub fn apply_signatures(pczt: Pczt, response: &SigningResponse) -> Result<Pczt, Error<Infallible>> {
+    let sighashes = Sighashes::new(&pczt)?;
+    let secp = secp256k1::Secp256k1::verification_only();
+
+    let signer = low_level_signer::Signer::new(pczt)
+        .sign_transparent_with::<Error<Infallible>, _>(|_, bundle, tx_modifiable| {
+            for sig in &response.transparent {
+                let input = bundle
+                    .inputs_mut()
+                    .get_mut(sig.index as usize)
+                    .ok_or(Error::Signer(signer::Error::InvalidIndex))?;
+                let pubkey = secp256k1::PublicKey::from_slice(&sig.pubkey)
+                    .map_err(|_| Error::InvalidSignatureEncoding)?;
+                let signature = secp256k1::ecdsa::Signature::from_der(&sig.signature)
+                    .map_err(|_| Error::InvalidSignatureEncoding)?;
+
+                input
+                    .append_signature(
+                        sig.index as usize,
+                        |input| sighashes.transparent_sighash(input),
+                        &pubkey,
+                        &signature,
+                        &secp,
+                    )
+                    .map_err(|e| Error::Signer(signer::Error::TransparentSign(e)))?;
+
+                update_tx_modifiable_for_transparent_signature(
+                    tx_modifiable,
+                    input.sighash_type().encode(),
+                );
+            }
+            Ok(())
+        })?;
+
+    let mut pczt = signer.finish();
+
+    // The Sapling and Orchard Signer APIs only support signing with a spend authorizing
+    // key, so externally-created signatures are verified against the spend's randomized
+    // verification key and then set directly.
+    for sig in &response.sapling {
+        let spend = pczt
+            .sapling
+            .spends
+            .get_mut(sig.index as usize)
+            .ok_or(Error::Signer(signer::Error::InvalidIndex))?;
+
+        let rk = redjubjub::VerificationKey::<redjubjub::SpendAuth>::try_from(spend.rk)
+            .map_err(|_| Error::InvalidSignature)?;
+        rk.verify(
+            &sighashes.shielded,
+            &redjubjub::Signature::from(sig.signature),
+        )
+        .map_err(|_| Error::InvalidSignature)?;
+
+        spend.spend_auth_sig = Some(sig.signature);
+        update_tx_modifiable_for_shielded_signature(&mut pczt.global.tx_modifiable);
+    }
+
+    for sig in &response.orchard {
+        let action = pczt
+            .orchard
+            .actions
+            .get_mut(sig.index as usize)
+            .ok_or(Error::Signer(signer::Error::InvalidIndex))?;
+
+        let rk = redpallas::VerificationKey::<redpallas::SpendAuth>::try_from(action.spend.rk)
+            .map_err(|_| Error::InvalidSignature)?;
+        rk.verify(
+            &sighashes.shielded,
+            &redpallas::Signature::from(sig.signature),
+        )
+        .map_err(|_| Error::InvalidSignature)?;
+
+        action.spend.spend_auth_sig = Some(sig.signature);
+        update_tx_modifiable_for_shielded_signature(&mut pczt.global.tx_modifiable);
+    }
+
+    Ok(pczt)
+}
+
+/// The signature hashes of a PCZT.
+struct Sighashes {
+    tx_data: TransactionData<EffectsOnly>,
+    txid_parts: TxDigests<Blake2bHash>,
+    shielded: [u8; 32],
+    transparent: Vec<[u8; 32]>,
+}
+
+impl Sighashes {
+    fn new<E>(pczt: &Pczt) -> Result<Self, Error<E>> {
+        let transparent = pczt.transparent.clone().into_parsed()?;
+        let sapling = pczt.sapling.clone().into_parsed()?;
+        let orchard = pczt.orchard.clone().into_parsed()?;
+
+        let tx_data = pczt_to_tx_data(&pczt.global, &transparent, &sapling, &orchard)
+            .map_err(Error::Signer)?;
+        let txid_parts = tx_data.digest(TxIdDigester);
+
+        let mut sighashes = Self {
+            shielded: v5_signature_hash(&tx_data, &SignableInput::Shielded, &txid_parts)
+                .as_ref()
+                .try_into()
+                .expect("correct length"),
+            tx_data,
+            txid_parts,
+            transparent: vec![],
+        };
+
+        sighashes.transparent = transparent
+            .inputs()
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                sighashes.transparent_sighash(::transparent::sighash::SignableInput::from_parts(
+                    *input.sighash_type(),
+                    index,
+                    input
+                        .redeem_script()
+                        .as_ref()
+                        .unwrap_or(input.script_pubkey()),
+                    input.script_pubkey(),
+                    *input.value(),
+                ))
+            })
+            .collect();
+
+        Ok(sighashes)
+    }
+
+    fn transparent_sighash(&self, input: ::transparent::sighash::SignableInput) -> [u8; 32] {
+        v5_signature_hash(
+            &self.tx_data,
+            &SignableInput::Transparent(input),
+            &self.txid_parts,
+        )
+        .as_ref()
+        .try_into()
+        .expect("correct length")
+    }
+}
+
+/// A signer that holds its keys in memory.
+///
+/// This is intended for tests, and as a reference for [`ExternalSigner`] implementations;
+/// applications that hold keys in process should use the [`Signer`] role directly.
+///
+/// [`Signer`]: crate::roles::signer::Signer
+#[derive(Default)]
+pub struct InProcessSigner {
+    transparent: BTreeMap<[u8; 33], secp256k1::SecretKey>,
+    sapling: Option<sapling::keys::SpendAuthorizingKey>,
+    orchard: Option<orchard::keys::SpendAuthorizingKey>,
+}
+
+impl InProcessSigner {
+    /// Constructs a signer that holds no keys.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a transparent secret key to the signer.
+    pub fn with_transparent_key(mut self, sk: secp256k1::SecretKey) -> Self {
+        let secp = secp256k1::Secp256k1::signing_only();
+        self.transparent
+            .insert(sk.public_key(&secp).serialize(), sk);
+        self
+    }
+
+    /// Sets the key with which the signer signs all Sapling spends.
+    pub fn with_sapling_key(mut self, ask: sapling::keys::SpendAuthorizingKey) -> Self {
+        self.sapling = Some(ask);
+        self
+    }
+
+    /// Sets the key with which the signer signs all Orchard spends.
+    pub fn with_orchard_key(mut self, ask: orchard::keys::SpendAuthorizingKey) -> Self {
+        self.orchard = Some(ask);
+        self
+    }
+}
+
+impl ExternalSigner for InProcessSigner {
+    type Error = Infallible;
+
+    fn sign(&mut self, request: &SigningRequest) -> Result<SigningResponse, Self::Error> {
+        let secp = secp256k1::Secp256k1::signing_only();
+
+        let transparent = request
+            .transparent
+            .iter()
+            .filter_map(|req| {
+                let sk = self.transparent.get(&req.pubkey)?;
+                let msg = secp256k1::Message::from_digest(req.sighash);
+                let signature = secp.sign_ecdsa(&msg, sk);
+                Some(TransparentSignature::new(
+                    req.index,
+                    req.pubkey,
+                    signature.serialize_der().to_vec(),
+                ))
+            })
+            .collect();
+
+        let sapling = self.sapling.as_ref().map_or_else(Vec::new, |ask| {
+            request
+                .sapling
+                .iter()
+                .filter_map(|req| {
+                    let alpha = Option::from(jubjub::Scalar::from_bytes(&req.alpha))?;
+                    let rsk = ask.randomize(&alpha);
+                    let signature = rsk.sign(OsRng, &req.sighash);
+                    Some(ShieldedSignature::new(req.index, signature.into()))
+                })
+                .collect()
+        });
+
+        let orchard = self.orchard.as_ref().map_or_else(Vec::new, |ask| {
+            request
+                .orchard
+                .iter()
+                .filter_map(|req| {
+                    let alpha = Option::from(pasta_curves::pallas::Scalar::from_repr(req.alpha))?;
+                    let rsk = ask.randomize(&alpha);
+                    let signature = rsk.sign(OsRng, &req.sighash);
+                    Some(ShieldedSignature::new(req.index, (&signature).into()))
+                })
+                .collect()
+        });
+
+        Ok(SigningResponse::from_parts(transparent, sapling, orchard))
+    }
+}
+
+/// Errors that can occur while signing a PCZT with an external signer.
+#[derive(Debug)]
+pub enum Error<E> {
+    /// An error produced by the external signer.
+    Device(E),
+    /// A signature returned by the external signer could not be decoded.
+    InvalidSignatureEncoding,
+    /// A shielded signature returned by the external signer is not valid for the spend
+    /// it was returned for.
+    InvalidSignature,
+    /// A shielded spend that requires a signature is missing its spend authorization
+    /// randomizer. This indicates that the IO Finalizer has not been run.
+    MissingSpendAuthRandomizer,
+    /// An error occurred while parsing the PCZT, computing its signature hashes, or
+    /// applying a signature to it.
+    Signer(signer::Error),
+}
+
+impl Error<Infallible> {
+    fn widen<E>(self) -> Error<E> {
+        match self {
+            Error::Device(e) => match e {},
+            Error::InvalidSignatureEncoding => Error::InvalidSignatureEncoding,
+            Error::InvalidSignature => Error::InvalidSignature,
+            Error::MissingSpendAuthRandomizer => Error::MissingSpendAuthRandomizer,
+            Error::Signer(e) => Error::Signer(e),
+        }
+    }
+}
+
+impl<E> From<orchard::pczt::ParseError> for Error<E> {
+    fn from(e: orchard::pczt::ParseError) -> Self {
+        Error::Signer(signer::Error::OrchardParse(e))
+    }
+}
+
+impl<E> From<sapling::pczt::ParseError> for Error<E> {
+    fn from(e: sapling::pczt::ParseError) -> Self {
+        Error::Signer(signer::Error::SaplingParse(e))
+    }
+}
+
+impl<E> From<transparent::pczt::ParseError> for Error<E> {
+    fn from(e: transparent::pczt::ParseError) -> Self {
+        Error::Signer(signer::Error::TransparentParse(e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::BranchId;
+
+    use super::{SigningRequest, SigningResponse};
+    use crate::{roles::creator::Creator, ParseError};
+
+    #[test]
+    fn parse_distinguishes_wrong_encoding_from_corruption() {
+        let response = SigningResponse::default().serialize();
+        assert_eq!(
+            SigningResponse::parse(&response).unwrap(),
+            SigningResponse::default()
+        );
+
+        // A response, or a PCZT, is not a signing request.
+        assert!(matches!(
+            SigningRequest::parse(&response),
+            Err(ParseError::UnknownEncoding)
+        ));
+        let pczt = Creator::new(BranchId::Nu6.into(), 10_000_000, 133, [0; 32], [0; 32])
+            .build()
+            .serialize();
+        assert!(matches!(
+            SigningResponse::parse(&pczt),
+            Err(ParseError::UnknownEncoding)
+        ));
+
+        // A response with a corrupt body is reported as invalid.
+        let mut corrupt = response[..8].to_vec();
+        corrupt.push(0xff);
+        assert!(matches!(
+            SigningResponse::parse(&corrupt),
+            Err(ParseError::Invalid(_))
+        ));
+
+        assert!(matches!(
+            SigningResponse::parse(&response[..7]),
+            Err(ParseError::TooShort)
+        ));
+    }
+}
@@ -0,0 +1,450 @@
+//! Pluggable backends for the Prover role.
+//!
+//! A [`ProvingBackend`] creates the missing proofs for a batch of PCZTs. This enables a
+//! device that holds spending keys (but lacks the resources to create proofs) to delegate
+//! proving to another machine: the PCZTs sent to a backend contain proof generation keys
+//! and full viewing keys, but never spend authorizing keys, so spend authority stays on
+//! the device.
+//!
+//! Provided backends:
+//! - [`LocalProver`] creates proofs in-process. Proving keys are borrowed for the
+//!   lifetime of the backend, so loading them is amortised across every batch.
+//! - [`RemoteProver`] sends PCZTs over a caller-provided [`Transport`] to a
+//!   [`ProvingServer`], which wraps any other backend. A [`ProvingServer`] is itself a
+//!   [`Transport`], so it can stand in for a real proving service in-process.
+//!
+//! [`prove_batch`] checks every PCZT returned by a backend against the PCZT that was
+//! sent, and rejects any that were altered other than by adding proofs.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::Prover;
+use crate::{
+    roles::combiner::{self, Combiner},
+    ParseError, Pczt,
+};
+
+/// The version of the [`ProvingServer`] RPC message format.
+const RPC_VERSION: u32 = 1;
+
+/// A backend that can create the proofs required by PCZTs.
+pub trait ProvingBackend {
+    /// The type of errors that can occur while proving.
+    type Error;
+
+    /// Creates every missing Sapling and Orchard proof in the given PCZTs.
+    ///
+    /// Returns the proven PCZTs in the same order.
+    fn prove(&mut self, pczts: Vec<Pczt>) -> Result<Vec<Pczt>, Self::Error>;
+}
+
+impl Prover {
+    /// Creates any missing proofs using the given backend.
+    pub fn create_proofs_with<B: ProvingBackend>(
+        self,
+        backend: &mut B,
+    ) -> Result<Self, BatchError<B::Error>> {
+        let pczt = prove_batch(vec![self.pczt], backend)?
+            .pop()
+            .expect("one PCZT in, one PCZT out");
+        Ok(Self { pczt })
+    }
+}
+
+/// Creates the missing proofs for a batch of PCZTs using the given backend.
+///
+/// PCZTs that do not require any proofs are not sent to the backend. Each PCZT returned
+/// by the backend is combined with the PCZT that was sent, which fails if the backend
+/// altered anything other than adding proofs.
+pub fn prove_batch<B: ProvingBackend>(
+    pczts: Vec<Pczt>,
+    backend: &mut B,
+) -> Result<Vec<Pczt>, BatchError<B::Error>> {
+    let (to_prove, indices): (Vec<_>, Vec<_>) = pczts
+        .iter()
+        .enumerate()
+        .filter(|(_, pczt)| requires_proofs(pczt))
+        .map(|(i, pczt)| (pczt.clone(), i))
+        .unzip();
+
+    if to_prove.is_empty() {
+        return Ok(pczts);
+    }
+
+    let proven = backend.prove(to_prove).map_err(BatchError::Backend)?;
+    if proven.len() != indices.len() {
+        return Err(BatchError::WrongCount);
+    }
+
+    let mut pczts = pczts.into_iter().map(Some).collect::<Vec<_>>();
+    for (index, proven) in indices.into_iter().zip(proven) {
+        let original = pczts[index].take().expect("indices are distinct");
+        let combined = Combiner::new(vec![original, proven])
+            .combine()
+            .map_err(|e| BatchError::Altered(index, e))?;
+        if requires_proofs(&combined) {
+            return Err(BatchError::MissingProofs(index));
+        }
+        pczts[index] = Some(combined);
+    }
+
+    Ok(pczts.into_iter().map(|pczt| pczt.expect("set")).collect())
+}
+
+fn requires_proofs(pczt: &Pczt) -> bool {
+    let sapling = pczt.sapling();
+    let orchard = pczt.orchard();
+
+    sapling.spends().iter().any(|s| s.zkproof.is_none())
+        || sapling.outputs().iter().any(|o| o.zkproof.is_none())
+        || (!orchard.actions().is_empty() && orchard.zkproof.is_none())
+}
+
+/// Errors that can occur while proving a batch of PCZTs.
+#[derive(Debug)]
+pub enum BatchError<E> {
+    /// The PCZT at the given index was altered by the backend.
+    Altered(usize, combiner::Error),
+    /// The backend returned an error.
+    Backend(E),
+    /// The backend did not create every required proof for the PCZT at the given index.
+    MissingProofs(usize),
+    /// The backend returned a different number of PCZTs than it was given.
+    WrongCount,
+}
+
+/// A backend that creates proofs in-process.
+#[cfg(all(feature = "orchard", feature = "sapling"))]
+pub struct LocalProver<'a, S, O> {
+    spend_prover: &'a S,
+    output_prover: &'a O,
+    orchard_pk: &'a ::orchard::circuit::ProvingKey,
+}
+
+#[cfg(all(feature = "orchard", feature = "sapling"))]
+impl<'a, S, O> LocalProver<'a, S, O>
+where
+    S: ::sapling::prover::SpendProver,
+    O: ::sapling::prover::OutputProver,
+{
+    /// Constructs a local backend from the given Sapling provers and Orchard proving key.
+    pub fn new(
+        spend_prover: &'a S,
+        output_prover: &'a O,
+        orchard_pk: &'a ::orchard::circuit::ProvingKey,
+    ) -> Self {
+        Self {
+            spend_prover,
+            output_prover,
+            orchard_pk,
+        }
+    }
+}
+
+#[cfg(all(feature = "orchard", feature = "sapling"))]
+impl<S, O> ProvingBackend for LocalProver<'_, S, O>
+where
+    S: ::sapling::prover::SpendProver,
+    O: ::sapling::prover::OutputProver,
+{
+    type Error = LocalProverError;
+
+    fn prove(&mut self, pczts: Vec<Pczt>) -> Result<Vec<Pczt>, Self::Error> {
+        pczts
+            .into_iter()
+            .map(|pczt| {
+                let mut prover = Prover::new(pczt);
+                if prover.requires_sapling_proofs() {
+                    prover = prover
+                        .create_sapling_proofs(self.spend_prover, self.output_prover)
+                        .map_err(LocalProverError::Sapling)?;
+                }
+                if prover.requires_orchard_proof() {
+                    prover = prover
+                        .create_orchard_proof(self.orchard_pk)
+                        .map_err(LocalProverError::Orchard)?;
+                }
+                Ok(prover.finish())
+            })
+            .collect()
+    }
+}
+
+/// Errors that can occur while creating proofs in-process.
+#[cfg(all(feature = "orchard", feature = "sapling"))]
+#[derive(Debug)]
+pub enum LocalProverError {
+    /// An error occurred while creating the Orchard proof.
+    Orchard(super::OrchardError),
+    /// An error occurred while creating a Sapling proof.
+    Sapling(super::SaplingError),
+}
+
+/// A channel over which requests can be sent to a [`ProvingServer`].
+pub trait Transport {
+    /// The type of errors that can occur while sending a request.
+    type Error;
+
+    /// Sends the given request to the server, and returns its response.
+    fn call(&mut self, request: Vec<u8>) -> Result<Vec<u8>, Self::Error>;
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProveRequest {
+    version: u32,
+    pczts: Vec<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize)]
+enum ProveResponse {
+    Proved(Vec<Vec<u8>>),
+    Failed(String),
+}
+
+/// A backend that delegates proving to a remote [`ProvingServer`].
+pub struct RemoteProver<T> {
+    transport: T,
+}
+
+impl<T: Transport> RemoteProver<T> {
+    /// Constructs a backend that sends requests over the given transport.
+    pub fn new(transport: T) -> Self {
+        Self { transport }
+    }
+}
+
+impl<T: Transport> ProvingBackend for RemoteProver<T> {
+    type Error = RemoteError<T::Error>;
+
+    fn prove(&mut self, pczts: Vec<Pczt>) -> Result<Vec<Pczt>, Self::Error> {
+        let request = ProveRequest {
+            version: RPC_VERSION,
+            pczts: pczts.iter().map(|pczt| pczt.serialize()).collect(),
+        };
+        let request = postcard::to_allocvec(&request).expect("can serialize into memory");
+
+        let response = self
+            .transport
+            .call(request)
+            .map_err(RemoteError::Transport)?;
+
+        match postcard::from_bytes(&response).map_err(|_| RemoteError::InvalidResponse)? {
+            ProveResponse::Proved(pczts) => pczts
+                .iter()
+                .map(|pczt| Pczt::parse(pczt).map_err(RemoteError::Parse))
+                .collect(),
+            ProveResponse::Failed(e) => Err(RemoteError::Server(e)),
+        }
+    }
+}
+
+/// Errors that can occur while proving with a [`RemoteProver`].
+#[derive(Debug)]
+pub enum RemoteError<E> {
+    /// The server's response could not be decoded.
+    InvalidResponse,
+    /// The server returned an invalid PCZT.
+    Parse(ParseError),
+    /// The server reported an error.
+    Server(String),
+    /// The transport returned an error.
+    Transport(E),
+}
+
+/// A server that answers [`RemoteProver`] requests using another backend.
+pub struct ProvingServer<B> {
+    backend: B,
+}
+
+impl<B> ProvingServer<B>
+where
+    B: ProvingBackend,
+    B::Error: fmt::Debug,
+{
+    /// Constructs a server that proves with the given backend.
+    pub fn new(backend: B) -> Self {
+        Self { backend }
+    }
+
+    /// Handles a single request, returning the response to send back to the client.
+    pub fn handle(&mut self, request: &[u8]) -> Vec<u8> {
+        let response = match self.prove(request) {
+            Ok(pczts) => ProveResponse::Proved(pczts),
+            Err(e) => ProveResponse::Failed(e),
+        };
+        postcard::to_allocvec(&response).expect("can serialize into memory")
+    }
+
+    fn prove(&mut self, request: &[u8]) -> Result<Vec<Vec<u8>>, String> {
+        let request = postcard::from_bytes::<ProveRequest>(request)
+            .map_err(|e| format!("Invalid request: {}", e))?;
+        if request.version != RPC_VERSION {
+            return Err(format!("Unsupported request version {}", request.version));
+        }
+
+        let pczts = request
+            .pczts
+            .iter()
+            .map(|pczt| Pczt::parse(pczt))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid PCZT: {:?}", e))?;
+
+        let proven = self
+            .backend
+            .prove(pczts)
+            .map_err(|e| format!("Proving failed: {:?}", e))?;
+
+        Ok(proven.iter().map(|pczt| pczt.serialize()).collect())
+    }
+}
+
+impl<B> Transport for ProvingServer<B>
+where
+    B: ProvingBackend,
+    B::Error: fmt::Debug,
+{
+    type Error = Infallible;
+
+    fn call(&mut self, request: Vec<u8>) -> Result<Vec<u8>, Self::Error> {
+        Ok(self.handle(&request))
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "orchard",
+    feature = "transparent",
+    feature = "zcp-builder"
+))]
+mod tests {
+    use alloc::vec;
+    use alloc::vec::Vec;
+    use core::convert::Infallible;
+
+    use ::transparent::keys::{AccountPrivKey, IncomingViewingKey};
+    use rand_core::OsRng;
+    use zcash_primitives::transaction::{
+        builder::{BuildConfig, Builder, PcztResult},
+        fees::zip317,
+    };
+    use zcash_protocol::{consensus::MainNetwork, memo::MemoBytes, value::Zatoshis};
+
+    use super::{
+        prove_batch, BatchError, ProveRequest, ProveResponse, ProvingBackend, ProvingServer,
+        RPC_VERSION,
+    };
+    use crate::{roles::creator::Creator, Pczt};
+
+    /// Returns a PCZT that requires an Orchard proof.
+    fn unproven_pczt() -> Pczt {
+        let orchard_sk = ::orchard::keys::SpendingKey::from_bytes([0; 32]).unwrap();
+        let orchard_fvk = ::orchard::keys::FullViewingKey::from(&orchard_sk);
+        let recipient = orchard_fvk.address_at(0u32, ::orchard::keys::Scope::External);
+
+        let transparent_account_sk =
+            AccountPrivKey::from_seed(&MainNetwork, &[1; 32], zip32::AccountId::ZERO).unwrap();
+        let (transparent_addr, address_index) = transparent_account_sk
+            .to_account_pubkey()
+            .derive_external_ivk()
+            .unwrap()
+            .default_address();
+        let transparent_pubkey = transparent_account_sk
+            .derive_external_secret_key(address_index)
+            .unwrap()
+            .public_key(&secp256k1::Secp256k1::signing_only());
+        let coin = ::transparent::bundle::TxOut {
+            value: Zatoshis::const_from_u64(1_000_000),
+            script_pubkey: transparent_addr.script(),
+        };
+
+        let mut builder = Builder::new(
+            MainNetwork,
+            10_000_000.into(),
+            BuildConfig::Standard {
+                sapling_anchor: None,
+                orchard_anchor: Some(::orchard::Anchor::empty_tree()),
+            },
+        );
+        builder
+            .add_transparent_input(
+                transparent_pubkey,
+                ::transparent::bundle::OutPoint::fake(),
+                coin,
+            )
+            .unwrap();
+        builder
+            .add_orchard_output::<zip317::FeeRule>(None, recipient, 985_000, MemoBytes::empty())
+            .unwrap();
+        let PcztResult { pczt_parts, .. } = builder
+            .build_for_pczt(OsRng, &zip317::FeeRule::standard())
+            .unwrap();
+        Creator::build_from_parts(pczt_parts).unwrap()
+    }
+
+    /// A backend that returns the result of applying a function to its input.
+    struct FakeBackend<F>(F);
+
+    impl<F: FnMut(Vec<Pczt>) -> Vec<Pczt>> ProvingBackend for FakeBackend<F> {
+        type Error = Infallible;
+
+        fn prove(&mut self, pczts: Vec<Pczt>) -> Result<Vec<Pczt>, Self::Error> {
+            Ok((self.0)(pczts))
+        }
+    }
+
+    #[test]
+    fn prove_batch_rejects_wrong_count() {
+        let mut backend = FakeBackend(|_| vec![]);
+        assert!(matches!(
+            prove_batch(vec![unproven_pczt()], &mut backend),
+            Err(BatchError::WrongCount)
+        ));
+    }
+
+    #[test]
+    fn prove_batch_rejects_altered_pczts() {
+        let mut backend = FakeBackend(|pczts: Vec<Pczt>| {
+            pczts
+                .into_iter()
+                .map(|mut pczt| {
+                    pczt.global.consensus_branch_id ^= 1;
+                    pczt
+                })
+                .collect()
+        });
+        assert!(matches!(
+            prove_batch(vec![unproven_pczt()], &mut backend),
+            Err(BatchError::Altered(0, _))
+        ));
+    }
+
+    #[test]
+    fn prove_batch_rejects_missing_proofs() {
+        let mut backend = FakeBackend(|pczts| pczts);
+        assert!(matches!(
+            prove_batch(vec![unproven_pczt()], &mut backend),
+            Err(BatchError::MissingProofs(0))
+        ));
+    }
+
+    #[test]
+    fn server_rejects_unsupported_version() {
+        let mut server = ProvingServer::new(FakeBackend(|pczts| pczts));
+        let request = postcard::to_allocvec(&ProveRequest {
+            version: RPC_VERSION + 1,
+            pczts: vec![unproven_pczt().serialize()],
+        })
+        .unwrap();
+
+        let response = server.handle(&request);
+        assert!(matches!(
+            postcard::from_bytes(&response).unwrap(),
+            ProveResponse::Failed(_)
+        ));
+    }
+}
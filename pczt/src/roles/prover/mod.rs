@@ -7,6 +7,8 @@
 
 use crate::Pczt;
 
+pub mod backend;
+
 #[cfg(feature = "orchard")]
 mod orchard;
 #[cfg(feature = "orchard")]
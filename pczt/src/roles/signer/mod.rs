@@ -127,27 +127,10 @@ impl Signer {
             )
             .map_err(Error::TransparentSign)?;
 
-        // Update transaction modifiability:
-        // - If the Signer added a signature that does not use `SIGHASH_ANYONECANPAY`, the
-        //   Transparent Inputs Modifiable Flag must be set to False (because the
-        //   signature commits to all inputs, not just the one at `index`).
-        if input.sighash_type().encode() & SIGHASH_ANYONECANPAY == 0 {
-            self.global.tx_modifiable &= !FLAG_TRANSPARENT_INPUTS_MODIFIABLE;
-        }
-        // - If the Signer added a signature that does not use `SIGHASH_NONE`, the
-        //   Transparent Outputs Modifiable Flag must be set to False. Note that this
-        //   applies to `SIGHASH_SINGLE` because we could otherwise remove the output at
-        //   `index`, which would not remove the signature.
-        if (input.sighash_type().encode() & !SIGHASH_ANYONECANPAY) != SIGHASH_NONE {
-            self.global.tx_modifiable &= !FLAG_TRANSPARENT_OUTPUTS_MODIFIABLE;
-        }
-        // - If the Signer added a signature that uses `SIGHASH_SINGLE`, the Has
-        //   `SIGHASH_SINGLE` flag must be set to True.
-        if (input.sighash_type().encode() & !SIGHASH_ANYONECANPAY) == SIGHASH_SINGLE {
-            self.global.tx_modifiable |= FLAG_HAS_SIGHASH_SINGLE;
-        }
-        // - Always set the Shielded Modifiable Flag to False.
-        self.global.tx_modifiable &= !FLAG_SHIELDED_MODIFIABLE;
+        update_tx_modifiable_for_transparent_signature(
+            &mut self.global.tx_modifiable,
+            input.sighash_type().encode(),
+        );
 
         Ok(())
     }
@@ -186,11 +169,7 @@ impl Signer {
             .sign(self.shielded_sighash, ask, OsRng)
             .map_err(Error::SaplingSign)?;
 
-        // Update transaction modifiability: all transaction effects have been committed
-        // to by the signature.
-        self.global.tx_modifiable &= !(FLAG_TRANSPARENT_INPUTS_MODIFIABLE
-            | FLAG_TRANSPARENT_OUTPUTS_MODIFIABLE
-            | FLAG_SHIELDED_MODIFIABLE);
+        update_tx_modifiable_for_shielded_signature(&mut self.global.tx_modifiable);
 
         Ok(())
     }
@@ -229,11 +208,7 @@ impl Signer {
             .sign(self.shielded_sighash, ask, OsRng)
             .map_err(Error::OrchardSign)?;
 
-        // Update transaction modifiability: all transaction effects have been committed
-        // to by the signature.
-        self.global.tx_modifiable &= !(FLAG_TRANSPARENT_INPUTS_MODIFIABLE
-            | FLAG_TRANSPARENT_OUTPUTS_MODIFIABLE
-            | FLAG_SHIELDED_MODIFIABLE);
+        update_tx_modifiable_for_shielded_signature(&mut self.global.tx_modifiable);
 
         Ok(())
     }
@@ -249,6 +224,43 @@ impl Signer {
     }
 }
 
+/// Updates transaction modifiability after a transparent signature with the given
+/// sighash type has been added.
+pub(crate) fn update_tx_modifiable_for_transparent_signature(
+    tx_modifiable: &mut u8,
+    sighash_type: u8,
+) {
+    // - If the Signer added a signature that does not use `SIGHASH_ANYONECANPAY`, the
+    //   Transparent Inputs Modifiable Flag must be set to False (because the
+    //   signature commits to all inputs, not just the one at `index`).
+    if sighash_type & SIGHASH_ANYONECANPAY == 0 {
+        *tx_modifiable &= !FLAG_TRANSPARENT_INPUTS_MODIFIABLE;
+    }
+    // - If the Signer added a signature that does not use `SIGHASH_NONE`, the
+    //   Transparent Outputs Modifiable Flag must be set to False. Note that this
+    //   applies to `SIGHASH_SINGLE` because we could otherwise remove the output at
+    //   `index`, which would not remove the signature.
+    if (sighash_type & !SIGHASH_ANYONECANPAY) != SIGHASH_NONE {
+        *tx_modifiable &= !FLAG_TRANSPARENT_OUTPUTS_MODIFIABLE;
+    }
+    // - If the Signer added a signature that uses `SIGHASH_SINGLE`, the Has
+    //   `SIGHASH_SINGLE` flag must be set to True.
+    if (sighash_type & !SIGHASH_ANYONECANPAY) == SIGHASH_SINGLE {
+        *tx_modifiable |= FLAG_HAS_SIGHASH_SINGLE;
+    }
+    // - Always set the Shielded Modifiable Flag to False.
+    *tx_modifiable &= !FLAG_SHIELDED_MODIFIABLE;
+}
+
+/// Updates transaction modifiability after a Sapling or Orchard spend authorization
+/// signature has been added: all transaction effects have been committed to by the
+/// signature.
+pub(crate) fn update_tx_modifiable_for_shielded_signature(tx_modifiable: &mut u8) {
+    *tx_modifiable &= !(FLAG_TRANSPARENT_INPUTS_MODIFIABLE
+        | FLAG_TRANSPARENT_OUTPUTS_MODIFIABLE
+        | FLAG_SHIELDED_MODIFIABLE);
+}
+
 /// Extracts an unauthorized `TransactionData` from the PCZT.
 ///
 /// We don't care about existing proofs or signatures here, because they do not affect the
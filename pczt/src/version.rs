@@ -0,0 +1,308 @@
+//! Versioning of the PCZT encoding.
+//!
+//! Every encoded PCZT starts with the magic bytes `PCZT` followed by a little-endian
+//! `u32` format version. [`Pczt::parse`] accepts every version listed in [`Version`],
+//! upgrading older encodings to the current in-memory [`Pczt`] representation, so that
+//! PCZTs created before a library upgrade (for example, during a long-lived multisig
+//! session) remain usable afterwards. [`Pczt::serialize_as`] goes the other way,
+//! encoding a PCZT in an older version for parties that have not yet upgraded, provided
+//! the PCZT does not rely on anything that version cannot represent.
+//!
+//! # Adding a new version
+//!
+//! The serde layout of [`Pczt`] is always the layout of [`Version::CURRENT`]. When the
+//! layout changes:
+//! - freeze the previous layout in a private `vN` module, as standalone types that
+//!   derive `Serialize` and `Deserialize`;
+//! - add the new variant to [`Version`], and make it [`Version::CURRENT`];
+//! - in `version::decode`, deserialize the previous version into its frozen types and
+//!   convert them into a [`Pczt`];
+//! - in `version::encode`, convert the [`Pczt`] into the frozen types, returning
+//!   [`DowngradeError`] if it contains data the previous version cannot represent.
+//!
+//! The test-only `V0` version, whose frozen layout lacks the global proprietary fields,
+//! follows this pattern and exercises both directions of the conversion.
+
+use alloc::vec::Vec;
+
+use crate::{ParseError, Pczt, MAGIC_BYTES};
+
+/// A version of the PCZT encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Version {
+    /// A synthetic version preceding [`Version::V1`], used to test version dispatch.
+    ///
+    /// This cannot represent global proprietary fields.
+    #[cfg(test)]
+    V0,
+    /// The initial PCZT encoding.
+    V1,
+}
+
+impl Version {
+    /// The version used by [`Pczt::serialize`].
+    pub const CURRENT: Self = Version::V1;
+
+    /// Returns the version with the given numeric identifier, if it is known to this
+    /// library.
+    pub fn from_u32(version: u32) -> Option<Self> {
+        match version {
+            #[cfg(test)]
+            0 => Some(Version::V0),
+            1 => Some(Version::V1),
+            _ => None,
+        }
+    }
+
+    /// Returns the numeric identifier of this version, as written in the encoding.
+    pub fn to_u32(self) -> u32 {
+        match self {
+            #[cfg(test)]
+            Version::V0 => 0,
+            Version::V1 => 1,
+        }
+    }
+
+    /// Reads the version of an encoded PCZT, without parsing the rest of it.
+    ///
+    /// Returns [`ParseError::UnknownVersion`] if the PCZT was created by a newer version
+    /// of this library.
+    pub fn of(bytes: &[u8]) -> Result<Self, ParseError> {
+        if bytes.len() < 8 {
+            return Err(ParseError::TooShort);
+        }
+        if &bytes[..4] != MAGIC_BYTES {
+            return Err(ParseError::NotPczt);
+        }
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        Version::from_u32(version).ok_or(ParseError::UnknownVersion(version))
+    }
+}
+
+/// Re-encodes a PCZT in the current version of the encoding.
+///
+/// This is equivalent to parsing the PCZT and serializing it again, and is provided for
+/// tools that store PCZTs and want to migrate them eagerly.
+pub fn upgrade(bytes: &[u8]) -> Result<Vec<u8>, ParseError> {
+    Pczt::parse(bytes).map(|pczt| pczt.serialize())
+}
+
+/// Parses the version-specific body of an encoded PCZT (following the magic bytes and
+/// version), upgrading it to the current representation.
+pub(crate) fn decode(version: Version, body: &[u8]) -> Result<Pczt, ParseError> {
+    match version {
+        #[cfg(test)]
+        Version::V0 => postcard::from_bytes::<v0::Pczt>(body)
+            .map(Pczt::from)
+            .map_err(ParseError::Invalid),
+        Version::V1 => postcard::from_bytes(body).map_err(ParseError::Invalid),
+    }
+}
+
+/// Appends the version-specific body of the encoding of `pczt` in the given version.
+pub(crate) fn encode(
+    pczt: &Pczt,
+    version: Version,
+    bytes: Vec<u8>,
+) -> Result<Vec<u8>, DowngradeError> {
+    match version {
+        #[cfg(test)]
+        Version::V0 => v0::Pczt::try_from(pczt)
+            .map(|v0| postcard::to_extend(&v0, bytes).expect("can serialize into memory")),
+        Version::V1 => Ok(postcard::to_extend(pczt, bytes).expect("can serialize into memory")),
+    }
+}
+
+/// Errors that can occur while encoding a PCZT in an older version.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DowngradeError {
+    /// The PCZT contains data that cannot be represented in the requested version.
+    Unrepresentable {
+        /// The requested version.
+        version: Version,
+        /// A description of the data that cannot be represented.
+        reason: &'static str,
+    },
+}
+
+/// The frozen layout of the test-only [`Version::V0`].
+#[cfg(test)]
+mod v0 {
+    use alloc::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::{DowngradeError, Version};
+    use crate::{common, orchard, sapling, transparent};
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Pczt {
+        global: Global,
+        transparent: transparent::Bundle,
+        sapling: sapling::Bundle,
+        orchard: orchard::Bundle,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Global {
+        tx_version: u32,
+        version_group_id: u32,
+        consensus_branch_id: u32,
+        fallback_lock_time: Option<u32>,
+        expiry_height: u32,
+        coin_type: u32,
+        tx_modifiable: u8,
+    }
+
+    impl From<Pczt> for crate::Pczt {
+        fn from(pczt: Pczt) -> Self {
+            let Global {
+                tx_version,
+                version_group_id,
+                consensus_branch_id,
+                fallback_lock_time,
+                expiry_height,
+                coin_type,
+                tx_modifiable,
+            } = pczt.global;
+
+            crate::Pczt {
+                global: common::Global {
+                    tx_version,
+                    version_group_id,
+                    consensus_branch_id,
+                    fallback_lock_time,
+                    expiry_height,
+                    coin_type,
+                    tx_modifiable,
+                    proprietary: BTreeMap::new(),
+                },
+                transparent: pczt.transparent,
+                sapling: pczt.sapling,
+                orchard: pczt.orchard,
+            }
+        }
+    }
+
+    impl TryFrom<&crate::Pczt> for Pczt {
+        type Error = DowngradeError;
+
+        fn try_from(pczt: &crate::Pczt) -> Result<Self, Self::Error> {
+            let global = &pczt.global;
+            if !global.proprietary.is_empty() {
+                return Err(DowngradeError::Unrepresentable {
+                    version: Version::V0,
+                    reason: "global proprietary fields",
+                });
+            }
+
+            Ok(Pczt {
+                global: Global {
+                    tx_version: global.tx_version,
+                    version_group_id: global.version_group_id,
+                    consensus_branch_id: global.consensus_branch_id,
+                    fallback_lock_time: global.fallback_lock_time,
+                    expiry_height: global.expiry_height,
+                    coin_type: global.coin_type,
+                    tx_modifiable: global.tx_modifiable,
+                },
+                transparent: pczt.transparent.clone(),
+                sapling: pczt.sapling.clone(),
+                orchard: pczt.orchard.clone(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::BranchId;
+
+    use super::{upgrade, DowngradeError, Version};
+    use crate::{roles::creator::Creator, ParseError, Pczt};
+
+    fn pczt() -> Pczt {
+        Creator::new(BranchId::Nu6.into(), 10_000_000, 133, [0; 32], [0; 32]).build()
+    }
+
+    #[test]
+    fn version_ids_round_trip() {
+        for version in [Version::V0, Version::V1] {
+            assert_eq!(Version::from_u32(version.to_u32()), Some(version));
+        }
+        assert_eq!(Version::from_u32(2), None);
+        assert!(Version::V0 < Version::CURRENT);
+        assert_eq!(Version::CURRENT.to_u32(), 1);
+    }
+
+    #[test]
+    fn serialize_as_each_version_round_trips() {
+        let pczt = pczt();
+
+        let v1 = pczt.serialize_as(Version::V1).unwrap();
+        assert_eq!(Version::of(&v1).unwrap(), Version::V1);
+        assert_eq!(Pczt::parse(&v1).unwrap().serialize(), pczt.serialize());
+        assert_eq!(upgrade(&v1).unwrap(), pczt.serialize());
+
+        assert_eq!(
+            pczt.serialize_as(Version::CURRENT).unwrap(),
+            pczt.serialize()
+        );
+
+        // The older version has a different layout, but parses to the same PCZT and is
+        // upgraded to the current version.
+        let v0 = pczt.serialize_as(Version::V0).unwrap();
+        assert_eq!(Version::of(&v0).unwrap(), Version::V0);
+        assert_ne!(v0[8..], v1[8..]);
+        assert_eq!(Pczt::parse(&v0).unwrap().serialize(), pczt.serialize());
+        assert_eq!(upgrade(&v0).unwrap(), pczt.serialize());
+    }
+
+    #[test]
+    fn downgrade_rejects_unrepresentable_data() {
+        let mut pczt = pczt();
+        pczt.global
+            .proprietary
+            .insert("example".into(), vec![1, 2, 3]);
+
+        assert!(matches!(
+            pczt.serialize_as(Version::V0),
+            Err(DowngradeError::Unrepresentable {
+                version: Version::V0,
+                ..
+            })
+        ));
+
+        // The current version can represent the proprietary fields.
+        let v1 = pczt.serialize_as(Version::V1).unwrap();
+        assert_eq!(
+            Pczt::parse(&v1)
+                .unwrap()
+                .global()
+                .proprietary()
+                .get("example"),
+            Some(&vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let mut encoded = pczt().serialize();
+        encoded[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            Version::of(&encoded),
+            Err(ParseError::UnknownVersion(2))
+        ));
+        assert!(matches!(
+            Pczt::parse(&encoded),
+            Err(ParseError::UnknownVersion(2))
+        ));
+        assert!(matches!(Version::of(b"PCZT"), Err(ParseError::TooShort)));
+        assert!(matches!(
+            Version::of(b"PSBT\x01\x00\x00\x00"),
+            Err(ParseError::NotPczt)
+        ));
+    }
+}
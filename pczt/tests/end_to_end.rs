@@ -8,8 +8,18 @@ use ::transparent::{
 use orchard::tree::MerkleHashOrchard;
 use pczt::{
     roles::{
-        combiner::Combiner, creator::Creator, io_finalizer::IoFinalizer, prover::Prover,
-        signer::Signer, spend_finalizer::SpendFinalizer, tx_extractor::TransactionExtractor,
+        combiner::{self, Combiner},
+        constructor::{self, Constructor},
+        creator::Creator,
+        external_signer::{self, InProcessSigner, SigningRequest},
+        io_finalizer::IoFinalizer,
+        prover::{
+            backend::{self, LocalProver, ProvingServer, RemoteProver},
+            Prover,
+        },
+        signer::Signer,
+        spend_finalizer::SpendFinalizer,
+        tx_extractor::TransactionExtractor,
         updater::Updater,
     },
     Pczt,
@@ -22,7 +32,7 @@ use zcash_primitives::transaction::{
 };
 use zcash_proofs::prover::LocalTxProver;
 use zcash_protocol::{
-    consensus::MainNetwork,
+    consensus::{BranchId, MainNetwork},
     memo::{Memo, MemoBytes},
     value::Zatoshis,
 };
@@ -276,6 +286,21 @@ fn sapling_to_orchard() {
         .finish();
     check_round_trip(&pczt_with_orchard_proof);
 
+    // Alternatively, create all proofs by delegating to a proving service.
+    let mut remote_prover = RemoteProver::new(ProvingServer::new(LocalProver::new(
+        &sapling_prover,
+        &sapling_prover,
+        orchard_proving_key(),
+    )));
+    let pczt_with_remote_proofs = backend::prove_batch(vec![pczt.clone()], &mut remote_prover)
+        .unwrap()
+        .pop()
+        .unwrap();
+    check_round_trip(&pczt_with_remote_proofs);
+    let remote = Prover::new(pczt_with_remote_proofs.clone());
+    assert!(!remote.requires_sapling_proofs());
+    assert!(!remote.requires_orchard_proof());
+
     // Pass the PCZT to be signed through a serialization cycle to ensure we don't lose
     // any information. This emulates passing it to another device.
     let pczt = Pczt::parse(&pczt.serialize()).unwrap();
@@ -296,7 +321,7 @@ fn sapling_to_orchard() {
     let pczt = Combiner::new(vec![
         pczt_with_sapling_proofs,
         pczt_with_orchard_proof,
-        pczt_with_sapling_signatures,
+        pczt_with_sapling_signatures.clone(),
     ])
     .combine()
     .unwrap();
@@ -310,6 +335,16 @@ fn sapling_to_orchard() {
         .unwrap();
 
     assert_eq!(u32::from(tx.expiry_height()), 10_000_040);
+
+    // The remotely-created proofs are equally valid.
+    let pczt = Combiner::new(vec![pczt_with_remote_proofs, pczt_with_sapling_signatures])
+        .combine()
+        .unwrap();
+    let remotely_proven_tx = TransactionExtractor::new(pczt)
+        .with_sapling(&spend_vk, &output_vk)
+        .extract()
+        .unwrap();
+    assert_eq!(remotely_proven_tx.txid(), tx.txid());
 }
 
 #[test]
@@ -413,6 +448,20 @@ fn orchard_to_orchard() {
         .finish();
     check_round_trip(&pczt);
 
+    // Apply signatures via an external signer.
+    let request = SigningRequest::from_pczt(&pczt).unwrap();
+    assert_eq!(request.orchard().len(), 1);
+    assert_eq!(
+        SigningRequest::parse(&request.serialize()).unwrap(),
+        request
+    );
+    let mut external_signer = InProcessSigner::new().with_orchard_key(orchard_ask.clone());
+    let externally_signed = external_signer::sign_with(pczt.clone(), &mut external_signer).unwrap();
+    check_round_trip(&externally_signed);
+    assert!(SigningRequest::from_pczt(&externally_signed)
+        .unwrap()
+        .is_empty());
+
     // Apply signatures.
     let index = orchard_meta.spend_action_index(0).unwrap();
     let mut signer = Signer::new(pczt).unwrap();
@@ -420,8 +469,205 @@ fn orchard_to_orchard() {
     let pczt = signer.finish();
     check_round_trip(&pczt);
 
-    // We should now be able to extract the fully authorized transaction.
+    // We should now be able to extract the fully authorized transaction, whichever signer
+    // was used.
     let tx = TransactionExtractor::new(pczt).extract().unwrap();
+    let externally_signed_tx = TransactionExtractor::new(externally_signed)
+        .extract()
+        .unwrap();
 
     assert_eq!(u32::from(tx.expiry_height()), 10_000_040);
+    assert_eq!(externally_signed_tx.txid(), tx.txid());
+}
+
+#[test]
+fn orchard_multi_party() {
+    let mut rng = OsRng;
+
+    struct Party {
+        ask: orchard::keys::SpendAuthorizingKey,
+        fvk: orchard::keys::FullViewingKey,
+        note: orchard::Note,
+    }
+
+    // Create two parties, each of which has already received a note.
+    let parties = [[1; 32], [2; 32]].map(|sk_bytes| {
+        let sk = orchard::keys::SpendingKey::from_bytes(sk_bytes).unwrap();
+        let fvk = orchard::keys::FullViewingKey::from(&sk);
+        let recipient = fvk.address_at(0u32, orchard::keys::Scope::External);
+
+        let mut orchard_builder = orchard::builder::Builder::new(
+            orchard::builder::BundleType::DEFAULT,
+            orchard::Anchor::empty_tree(),
+        );
+        orchard_builder
+            .add_output(
+                None,
+                recipient,
+                orchard::value::NoteValue::from_raw(1_000_000),
+                Memo::Empty.encode().into_bytes(),
+            )
+            .unwrap();
+        let (bundle, meta) = orchard_builder.build::<i64>(&mut rng).unwrap().unwrap();
+        let action = bundle
+            .actions()
+            .get(meta.output_action_index(0).unwrap())
+            .unwrap();
+        let domain = orchard::note_encryption::OrchardDomain::for_action(action);
+        let ivk = fvk.to_ivk(orchard::keys::Scope::External).prepare();
+        let (note, _, _) = try_note_decryption(&domain, &ivk, action).unwrap();
+
+        Party {
+            ask: orchard::keys::SpendAuthorizingKey::from(&sk),
+            fvk,
+            note,
+        }
+    });
+
+    // Both notes are in the same tree, so the parties can agree on an anchor.
+    let leaves = parties.each_ref().map(|party| {
+        let cmx: orchard::note::ExtractedNoteCommitment = party.note.commitment().into();
+        MerkleHashOrchard::from_cmx(&cmx)
+    });
+    let mut tree =
+        ShardTree::<_, 32, 16>::new(MemoryShardStore::<MerkleHashOrchard, u32>::empty(), 100);
+    for leaf in leaves {
+        tree.append(leaf, incrementalmerkletree::Retention::Marked)
+            .unwrap();
+    }
+    tree.checkpoint(9_999_999).unwrap();
+    let anchor: orchard::Anchor = tree
+        .witness_at_checkpoint_depth(0.into(), 0)
+        .unwrap()
+        .unwrap()
+        .root(leaves[0])
+        .into();
+
+    // The coordinator creates the base PCZT.
+    let base = Creator::new(
+        BranchId::for_height(&MainNetwork, 10_000_000.into()).into(),
+        10_000_040,
+        133,
+        sapling::Anchor::empty_tree().to_bytes(),
+        anchor.to_bytes(),
+    )
+    .build();
+
+    // Each party independently creates their own contribution, paying the other party.
+    let contributions = parties
+        .iter()
+        .enumerate()
+        .map(|(i, party)| {
+            let merkle_path = tree
+                .witness_at_checkpoint_depth((i as u64).into(), 0)
+                .unwrap()
+                .unwrap();
+            let payee = &parties[1 - i].fvk;
+
+            let mut builder = Builder::new(
+                MainNetwork,
+                10_000_000.into(),
+                BuildConfig::Standard {
+                    sapling_anchor: None,
+                    orchard_anchor: Some(anchor),
+                },
+            );
+            builder
+                .add_orchard_spend::<zip317::FeeRule>(
+                    party.fvk.clone(),
+                    party.note,
+                    merkle_path.into(),
+                )
+                .unwrap();
+            builder
+                .add_orchard_output::<zip317::FeeRule>(
+                    None,
+                    payee.address_at(0u32, orchard::keys::Scope::External),
+                    500_000,
+                    MemoBytes::empty(),
+                )
+                .unwrap();
+            builder
+                .add_orchard_output::<zip317::FeeRule>(
+                    Some(party.fvk.to_ovk(zip32::Scope::Internal)),
+                    party.fvk.address_at(0u32, orchard::keys::Scope::Internal),
+                    490_000,
+                    MemoBytes::empty(),
+                )
+                .unwrap();
+            let PcztResult {
+                pczt_parts,
+                orchard_meta,
+                ..
+            } = builder
+                .build_for_pczt(OsRng, &zip317::FeeRule::standard())
+                .unwrap();
+
+            (
+                Creator::build_from_parts(pczt_parts).unwrap(),
+                orchard_meta.spend_action_index(0).unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // The coordinator combines the contributions.
+    let mut constructor = Constructor::new(base);
+    for (contribution, _) in &contributions {
+        constructor = constructor.add_contribution(contribution.clone()).unwrap();
+    }
+    let pczt = constructor.finish();
+    check_round_trip(&pczt);
+
+    // A contribution cannot be added twice.
+    assert!(matches!(
+        Constructor::new(pczt.clone()).add_contribution(contributions[0].0.clone()),
+        Err(constructor::Error::DuplicateSpend)
+    ));
+
+    // Finalize the I/O, computing the combined bsk, and create proofs.
+    let pczt = IoFinalizer::new(pczt).finalize_io().unwrap();
+    check_round_trip(&pczt);
+    let pczt = Prover::new(pczt)
+        .create_orchard_proof(orchard_proving_key())
+        .unwrap()
+        .finish();
+    check_round_trip(&pczt);
+
+    // Each party checks their contribution, and signs their own spend.
+    let first_len = contributions[0].0.orchard().actions().len();
+    let signed = parties
+        .iter()
+        .zip(&contributions)
+        .enumerate()
+        .map(|(i, (party, (contribution, spend_index)))| {
+            constructor::verify_contribution(&pczt, contribution).unwrap();
+            let index = if i == 0 {
+                *spend_index
+            } else {
+                first_len + spend_index
+            };
+            let mut signer = Signer::new(pczt.clone()).unwrap();
+            signer.sign_orchard(index, &party.ask).unwrap();
+            signer.finish()
+        })
+        .collect::<Vec<_>>();
+
+    // A PCZT signed by only one party is missing the other party's contribution.
+    assert!(matches!(
+        Combiner::new(vec![contributions[0].0.clone()])
+            .with_contributions(vec![contributions[1].0.clone()])
+            .combine(),
+        Err(combiner::Error::ContributionMismatch(0))
+    ));
+
+    // Combine the signatures, checking every contribution.
+    let pczt = Combiner::new(signed)
+        .with_contributions(contributions.into_iter().map(|(c, _)| c).collect())
+        .combine()
+        .unwrap();
+    check_round_trip(&pczt);
+
+    // We should now be able to extract the fully authorized transaction.
+    let tx = TransactionExtractor::new(pczt).extract().unwrap();
+    assert_eq!(tx.orchard_bundle().unwrap().actions().len(), 4);
 }
@@ -41,13 +41,18 @@ fn build() -> io::Result<()> {
         "src/proto/compact_formats.rs",
     )?;
 
-    // Build the gRPC types and client.
+    // Build the gRPC types, client, and server.
     tonic_build::configure()
-        .build_server(false)
+        .build_server(true)
+        .generate_default_stubs(true)
         .client_mod_attribute(
             "cash.z.wallet.sdk.rpc",
             r#"#[cfg(feature = "lightwalletd-tonic")]"#,
         )
+        .server_mod_attribute(
+            "cash.z.wallet.sdk.rpc",
+            r#"#[cfg(feature = "lightwalletd-tonic-server")]"#,
+        )
         .extern_path(
             ".cash.z.wallet.sdk.rpc.ChainMetadata",
             "crate::proto::compact_formats::ChainMetadata",
@@ -72,6 +77,14 @@ fn build() -> io::Result<()> {
             ".cash.z.wallet.sdk.rpc.CompactOrchardAction",
             "crate::proto::compact_formats::CompactOrchardAction",
         )
+        .extern_path(
+            ".cash.z.wallet.sdk.rpc.CompactTxIn",
+            "crate::proto::compact_formats::CompactTxIn",
+        )
+        .extern_path(
+            ".cash.z.wallet.sdk.rpc.TxOut",
+            "crate::proto::compact_formats::TxOut",
+        )
         .compile_protos(&[SERVICE_PROTO], &["proto/"])?;
 
     // Build the proposal types.
@@ -77,7 +77,10 @@ use zcash_keys::{
 use zcash_primitives::{block::BlockHash, transaction::Transaction};
 use zcash_protocol::{
     consensus::BlockHeight,
-    memo::{Memo, MemoBytes},
+    memo::{
+        structured::{MessageId, StructuredMemo},
+        Memo, MemoBytes,
+    },
     value::{BalanceError, Zatoshis},
     ShieldedProtocol, TxId,
 };
@@ -88,7 +91,7 @@ use self::{
     scanning::ScanRange,
 };
 use crate::{
-    decrypt::DecryptedOutput,
+    decrypt::{reassemble_structured_memos, DecryptedOutput},
     proto::service::TreeState,
     wallet::{Note, NoteId, ReceivedNote, Recipient, WalletTransparentOutput, WalletTx},
 };
@@ -111,8 +114,10 @@ use ambassador::delegatable_trait;
 #[cfg(any(test, feature = "test-dependencies"))]
 use zcash_protocol::consensus::NetworkUpgrade;
 
+pub mod birthday;
 pub mod chain;
 pub mod error;
+pub mod events;
 pub mod scanning;
 pub mod wallet;
 
@@ -152,6 +157,26 @@ pub enum NullifierQuery {
 #[derive(Debug, Clone, Copy)]
 pub enum TargetValue {
     AtLeast(Zatoshis),
+    /// All of the spendable funds in the wallet, selected according to the given
+    /// [`MaxSpendMode`].
+    AllFunds(MaxSpendMode),
+}
+
+/// Specifies which notes should be selected when spending all of the funds in a wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxSpendMode {
+    /// Select every spendable note that is economic to spend, leaving behind any notes
+    /// having value insufficient to pay the marginal fee for their inclusion in a
+    /// transaction.
+    MaxSpendable,
+    /// Select every spendable note, including those that are uneconomic to spend.
+    ///
+    /// [`wallet::propose_send_max`] does not spend uneconomic notes in this mode; instead,
+    /// the proposal fails with [`ChangeError::DustInputs`] identifying them, so that funds
+    /// are never left behind without the caller's knowledge.
+    ///
+    /// [`ChangeError::DustInputs`]: crate::fees::ChangeError::DustInputs
+    Everything,
 }
 
 /// Balance information for a value within a single pool in an account.
@@ -1178,6 +1203,70 @@ impl NoteFilter {
     }
 }
 
+/// Merkle witnesses for a set of the wallet's notes, all computed at a common anchor height.
+///
+/// This is returned by [`WalletRead::get_note_witnesses`].
+#[derive(Clone, Debug)]
+pub struct NoteWitnesses {
+    anchor_height: BlockHeight,
+    sapling_anchor: Option<sapling::Anchor>,
+    sapling_witnesses: Vec<(NoteId, sapling::MerklePath)>,
+    #[cfg(feature = "orchard")]
+    orchard_anchor: Option<orchard::Anchor>,
+    #[cfg(feature = "orchard")]
+    orchard_witnesses: Vec<(NoteId, orchard::tree::MerklePath)>,
+}
+
+impl NoteWitnesses {
+    /// Constructs a `NoteWitnesses` value from its constituent parts.
+    pub fn from_parts(
+        anchor_height: BlockHeight,
+        sapling_anchor: Option<sapling::Anchor>,
+        sapling_witnesses: Vec<(NoteId, sapling::MerklePath)>,
+        #[cfg(feature = "orchard")] orchard_anchor: Option<orchard::Anchor>,
+        #[cfg(feature = "orchard")] orchard_witnesses: Vec<(NoteId, orchard::tree::MerklePath)>,
+    ) -> Self {
+        Self {
+            anchor_height,
+            sapling_anchor,
+            sapling_witnesses,
+            #[cfg(feature = "orchard")]
+            orchard_anchor,
+            #[cfg(feature = "orchard")]
+            orchard_witnesses,
+        }
+    }
+
+    /// Returns the height of the block as of which the witnesses were computed.
+    pub fn anchor_height(&self) -> BlockHeight {
+        self.anchor_height
+    }
+
+    /// Returns the root of the Sapling note commitment tree at the anchor height, if the
+    /// wallet retains a checkpoint of the Sapling tree at that height.
+    pub fn sapling_anchor(&self) -> Option<sapling::Anchor> {
+        self.sapling_anchor
+    }
+
+    /// Returns the Merkle paths for the requested Sapling notes.
+    pub fn sapling_witnesses(&self) -> &[(NoteId, sapling::MerklePath)] {
+        &self.sapling_witnesses
+    }
+
+    /// Returns the root of the Orchard note commitment tree at the anchor height, if the
+    /// wallet retains a checkpoint of the Orchard tree at that height.
+    #[cfg(feature = "orchard")]
+    pub fn orchard_anchor(&self) -> Option<orchard::Anchor> {
+        self.orchard_anchor
+    }
+
+    /// Returns the Merkle paths for the requested Orchard notes.
+    #[cfg(feature = "orchard")]
+    pub fn orchard_witnesses(&self) -> &[(NoteId, orchard::tree::MerklePath)] {
+        &self.orchard_witnesses
+    }
+}
+
 /// A trait representing the capability to query a data store for unspent transaction outputs
 /// belonging to a account.
 #[cfg_attr(feature = "test-dependencies", delegatable_trait)]
@@ -1331,6 +1420,20 @@ pub trait WalletRead {
         seed: &SecretVec<u8>,
     ) -> Result<SeedRelevance<Self::AccountId>, Self::Error>;
 
+    /// Checks whether the seed corresponding to the given mnemonic phrase and passphrase
+    /// is relevant to any of the derived accounts in the wallet.
+    ///
+    /// This is equivalent to calling [`Self::seed_relevance_to_derived_accounts`] with the
+    /// seed returned by [`zcash_keys::mnemonic::Mnemonic::to_seed`].
+    #[cfg(feature = "mnemonic")]
+    fn mnemonic_relevance_to_derived_accounts(
+        &self,
+        mnemonic: &zcash_keys::mnemonic::Mnemonic,
+        passphrase: &str,
+    ) -> Result<SeedRelevance<Self::AccountId>, Self::Error> {
+        self.seed_relevance_to_derived_accounts(&mnemonic.to_seed(passphrase))
+    }
+
     /// Returns the account corresponding to a given [`UnifiedFullViewingKey`], if any.
     fn get_account_for_ufvk(
         &self,
@@ -1469,6 +1572,22 @@ pub trait WalletRead {
         query: NullifierQuery,
     ) -> Result<Vec<(Self::AccountId, orchard::note::Nullifier)>, Self::Error>;
 
+    /// Returns Merkle witnesses for the specified notes, computed with respect to the state
+    /// of the note commitment trees as of the end of the block at `anchor_height`.
+    ///
+    /// This allows a party that does not have access to the wallet's commitment trees (such
+    /// as a co-signer, or a PCZT `Updater` running on another machine) to supply the
+    /// witnesses required to spend the notes. The anchor height must correspond to a
+    /// checkpoint retained by the wallet's commitment trees.
+    ///
+    /// Notes that are not known to the wallet, or that have not yet been mined, are omitted
+    /// from the result.
+    fn get_note_witnesses(
+        &self,
+        note_ids: &[NoteId],
+        anchor_height: BlockHeight,
+    ) -> Result<NoteWitnesses, Self::Error>;
+
     /// Returns the set of non-ephemeral transparent receivers associated with the given
     /// account controlled by this wallet.
     ///
@@ -1487,6 +1606,20 @@ pub trait WalletRead {
         Ok(HashMap::new())
     }
 
+    /// Returns the outpoints of the transparent outputs that the wallet is tracking, along with
+    /// their associated account IDs, that are either unspent or have not yet been confirmed as
+    /// spent (in that a spending transaction known to the wallet has not yet been included in a
+    /// block).
+    ///
+    /// These play the role of nullifiers when scanning compact blocks that include transparent
+    /// transaction data, enabling the detection of transparent spends.
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_outpoints(
+        &self,
+    ) -> Result<Vec<(Self::AccountId, OutPoint)>, Self::Error> {
+        Ok(vec![])
+    }
+
     /// Returns a mapping from each transparent receiver associated with the specified account
     /// to its not-yet-shielded UTXO balance as of the end of the block at the provided
     /// `max_height`, when that balance is non-zero.
@@ -2002,6 +2135,30 @@ impl<'a, AccountId> DecryptedTransaction<'a, AccountId> {
         &self.orchard_outputs
     }
 
+    /// Returns the structured memos that were reassembled from the memos of the decrypted
+    /// outputs, along with the account that decrypted them and their message identifiers.
+    ///
+    /// Chunks are grouped by the account that decrypted them, so a message is only
+    /// reassembled from chunks that were all decrypted by the same account. Messages for
+    /// which not all chunks could be decrypted, or that could not be decoded, are omitted;
+    /// their chunks remain available via the memos of the individual outputs.
+    pub fn structured_memos(&self) -> Vec<(AccountId, MessageId, StructuredMemo)>
+    where
+        AccountId: Copy + PartialEq,
+    {
+        let memos = self
+            .sapling_outputs
+            .iter()
+            .map(|o| (*o.account(), o.memo()));
+        #[cfg(feature = "orchard")]
+        let memos = memos.chain(
+            self.orchard_outputs
+                .iter()
+                .map(|o| (*o.account(), o.memo())),
+        );
+        reassemble_structured_memos(memos)
+    }
+
     /// Returns whether the transaction has decrypted outputs
     pub fn has_decrypted_outputs(&self) -> bool {
         let has_sapling = !self.sapling_outputs.is_empty();
@@ -2163,6 +2320,7 @@ pub struct AccountBirthday {
 }
 
 /// Errors that can occur in the construction of an [`AccountBirthday`] from a [`TreeState`].
+#[derive(Debug)]
 pub enum BirthdayError {
     HeightInvalid(TryFromIntError),
     Decode(io::Error),
@@ -2335,8 +2493,9 @@ impl AccountBirthday {
 /// # Creating a new wallet
 ///
 /// To create a new wallet:
-/// - Generate a new [BIP 39] mnemonic phrase, using a crate like [`bip0039`].
-/// - Derive the corresponding seed from the mnemonic phrase.
+/// - Generate a new [BIP 39] mnemonic phrase, for example using `Mnemonic::generate` from
+///   the `zcash_keys::mnemonic` module (enabled by the `mnemonic` feature of `zcash_keys`).
+/// - Derive the corresponding seed from the mnemonic phrase (`Mnemonic::to_seed`).
 /// - Use [`WalletWrite::create_account`] with the resulting seed.
 ///
 /// Callers should construct the [`AccountBirthday`] using [`AccountBirthday::from_treestate`] for
@@ -2349,7 +2508,10 @@ impl AccountBirthday {
 /// # Restoring a wallet from backup
 ///
 /// To restore a backed-up wallet:
-/// - Derive the seed from its BIP 39 mnemonic phrase.
+/// - Derive the seed from its BIP 39 mnemonic phrase. The seed can be passed to
+///   [`WalletRead::seed_relevance_to_derived_accounts`] to check whether it corresponds to
+///   accounts that already exist in the wallet, and `Mnemonic::seed_fingerprint` returns
+///   the fingerprint that is recorded in the [`Zip32Derivation`] of such accounts.
 /// - Use [`WalletWrite::import_account_hd`] once for each ZIP 32 account index that the
 ///   user wants to restore.
 /// - If the highest previously-used ZIP 32 account index was _not_ restored by the user,
@@ -2359,10 +2521,12 @@ impl AccountBirthday {
 /// - [`WalletWrite::create_account`] can be used to generate subsequent new accounts in
 ///   the restored wallet.
 ///
-/// Automated account recovery has not yet been implemented by this crate. A wallet app
-/// that supports multiple accounts can implement it manually by tracking account balances
-/// relative to [`WalletSummary::fully_scanned_height`], and creating new accounts as
-/// funds appear in existing accounts.
+/// With the `sync` feature enabled, `sync::discovery::discover_accounts` can be used in
+/// place of the manual steps above; it probes successive ZIP 32 account indices for
+/// on-chain activity and imports the accounts that have been used. Alternatively, a wallet
+/// app that supports multiple accounts can implement account recovery manually by tracking
+/// account balances relative to [`WalletSummary::fully_scanned_height`], and creating new
+/// accounts as funds appear in existing accounts.
 ///
 /// If the number of accounts is known in advance, the wallet should create all accounts before
 /// scanning the chain so that the scan can be done in a single pass for all accounts.
@@ -2370,7 +2534,6 @@ impl AccountBirthday {
 /// [ZIP 32]: https://zips.z.cash/zip-0032
 /// [zcash/librustzcash#1284]: https://github.com/zcash/librustzcash/issues/1284
 /// [BIP 39]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
-/// [`bip0039`]: https://crates.io/crates/bip0039
 #[cfg_attr(feature = "test-dependencies", delegatable_trait)]
 pub trait WalletWrite: WalletRead {
     /// The type of identifiers used to look up transparent UTXOs.
@@ -2497,6 +2660,36 @@ pub trait WalletWrite: WalletRead {
         key_source: Option<&str>,
     ) -> Result<Self::Account, Self::Error>;
 
+    /// Extends the viewing key of an existing account with additional key components, and
+    /// rescans the chain for transactions involving those components.
+    ///
+    /// `unified_key` must contain every component of the account's existing full viewing key,
+    /// unchanged, along with at least one component that the account does not yet have (for
+    /// example, an Orchard full viewing key for an account that was imported with only a
+    /// Sapling key). The wallet begins tracking the new components immediately, and schedules
+    /// a rescan of the chain from `birthday` (which describes when the new components were
+    /// first used) to the chain tip. If `birthday` precedes the account's birthday, the
+    /// account's birthday is moved back to it.
+    ///
+    /// The rescan reconciles notes and spends found by the new components with the wallet's
+    /// existing history: transactions that the wallet already knows about are updated in place
+    /// rather than duplicated, and no data above `birthday` is discarded.
+    ///
+    /// Only accounts without ZIP 32 derivation metadata may be extended: for an account whose
+    /// keys are derived from a known seed, the additional components would not match the
+    /// recorded derivation.
+    ///
+    /// Returns details about the updated account, or an error if the account identifier does
+    /// not correspond to a known account, the account has ZIP 32 derivation metadata,
+    /// `unified_key` does not satisfy the above conditions, or any of the new components
+    /// belongs to another account in the wallet.
+    fn extend_account_viewing_key(
+        &mut self,
+        account: Self::AccountId,
+        unified_key: &UnifiedFullViewingKey,
+        birthday: &AccountBirthday,
+    ) -> Result<Self::Account, Self::Error>;
+
     /// Generates, persists, and marks as exposed the next available diversified address for the
     /// specified account, given the current addresses known to the wallet.
     ///
@@ -0,0 +1,664 @@
+//! Estimation of account birthdays.
+//!
+//! Importing an account requires an [`AccountBirthday`], which is constructed from the
+//! note commitment tree state as of the block prior to the account's birthday height.
+//! Users rarely know the height at which their wallet was created, but can usually give
+//! an approximate date. This module provides:
+//!
+//! - a bundled table of [`Checkpoint`]s for mainnet and testnet, from which
+//!   [`estimate_height`] can map a point in time to an approximate block height without
+//!   network access;
+//! - with the `lightwalletd-tonic` feature enabled, `height_at_time`, which refines such
+//!   an estimate against the timestamps of blocks fetched from a lightwalletd server, and
+//!   `birthday_from_height` and `birthday_from_time`, which fetch the corresponding
+//!   [`TreeState`] and construct the [`AccountBirthday`] from it.
+//!
+//! [`AccountBirthday`]: super::AccountBirthday
+//! [`TreeState`]: crate::proto::service::TreeState
+
+use zcash_protocol::consensus::{BlockHeight, NetworkType, NetworkUpgrade, Parameters};
+
+#[cfg(feature = "lightwalletd-tonic")]
+use {
+    super::{AccountBirthday, BirthdayError},
+    crate::proto::service::{self, compact_tx_streamer_client::CompactTxStreamerClient},
+    std::fmt,
+    std::time::{SystemTime, UNIX_EPOCH},
+    tonic::{
+        body::Body as TonicBody,
+        client::GrpcService,
+        codegen::{Body, Bytes, StdError},
+    },
+};
+
+/// The target block spacing, in seconds, prior to the activation of Blossom.
+const PRE_BLOSSOM_TARGET_SPACING: u32 = 150;
+
+/// The target block spacing, in seconds, after the activation of Blossom.
+const POST_BLOSSOM_TARGET_SPACING: u32 = 75;
+
+/// A known point on a network's chain, relating a block height to the approximate time at
+/// which the block at that height was mined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    height: BlockHeight,
+    time: u32,
+}
+
+impl Checkpoint {
+    const fn new(height: u32, time: u32) -> Self {
+        Self {
+            height: BlockHeight::from_u32(height),
+            time,
+        }
+    }
+
+    /// Returns the height of the checkpoint block.
+    pub fn height(&self) -> BlockHeight {
+        self.height
+    }
+
+    /// Returns the time at which the checkpoint block was mined, in seconds since the
+    /// Unix epoch.
+    ///
+    /// Except for genesis blocks, checkpoint times are rounded down to the start of the
+    /// UTC day on which the block was mined.
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+}
+
+/// Checkpoints for the Zcash mainnet: the genesis block, and the activation block of each
+/// network upgrade.
+pub const MAIN_NETWORK_CHECKPOINTS: &[Checkpoint] = &[
+    // Genesis
+    Checkpoint::new(0, 1477641360),
+    // Overwinter (2018-06-26)
+    Checkpoint::new(347_500, 1529971200),
+    // Sapling (2018-10-28)
+    Checkpoint::new(419_200, 1540684800),
+    // Blossom (2019-12-11)
+    Checkpoint::new(653_600, 1576022400),
+    // Heartwood (2020-07-16)
+    Checkpoint::new(903_000, 1594857600),
+    // Canopy (2020-11-18)
+    Checkpoint::new(1_046_400, 1605657600),
+    // NU5 (2022-05-31)
+    Checkpoint::new(1_687_104, 1653955200),
+    // NU6 (2024-11-23)
+    Checkpoint::new(2_726_400, 1732320000),
+];
+
+/// Checkpoints for the Zcash testnet: the genesis block, the last checkpoint block known
+/// to `zcashd`, and the activation block of each network upgrade from Sapling onwards.
+///
+/// Block production on testnet has been irregular, with extended periods of stalled or
+/// accelerated mining, so the times of testnet checkpoints are only accurate to within a
+/// few days, and estimates between them may be off by considerably more. Estimates for
+/// testnet should be refined against a server using `height_at_time`.
+pub const TEST_NETWORK_CHECKPOINTS: &[Checkpoint] = &[
+    // Genesis
+    Checkpoint::new(0, 1477648033),
+    // zcashd checkpoint (2017-02-12)
+    Checkpoint::new(38_000, 1486857600),
+    // Sapling (2018-08-26)
+    Checkpoint::new(280_000, 1535241600),
+    // Blossom (2019-08-29)
+    Checkpoint::new(584_000, 1567036800),
+    // Heartwood (2020-05-20)
+    Checkpoint::new(903_800, 1589932800),
+    // Canopy (2020-08-28)
+    Checkpoint::new(1_028_500, 1598572800),
+    // NU5 (2022-03-01)
+    Checkpoint::new(1_842_420, 1646092800),
+    // NU6 (2024-08-28)
+    Checkpoint::new(2_976_000, 1724803200),
+];
+
+/// Returns the bundled checkpoints for the given network, in order of increasing height.
+///
+/// Returns an empty slice for [`NetworkType::Regtest`].
+pub fn checkpoints(network: NetworkType) -> &'static [Checkpoint] {
+    match network {
+        NetworkType::Main => MAIN_NETWORK_CHECKPOINTS,
+        NetworkType::Test => TEST_NETWORK_CHECKPOINTS,
+        NetworkType::Regtest => &[],
+    }
+}
+
+/// Estimates the height of the chain at the given time, in seconds since the Unix epoch,
+/// using only the bundled [`checkpoints`].
+///
+/// Between two checkpoints, the height is linearly interpolated. After the last checkpoint,
+/// the height is extrapolated using the network's target block spacing. Times prior to the
+/// genesis block map to the genesis height.
+///
+/// The result is approximate, and may be either earlier or later than the true height at
+/// the given time by up to a few days' worth of blocks. Returns `None` if no checkpoints
+/// are available for the network.
+pub fn estimate_height<P: Parameters>(params: &P, time: u32) -> Option<BlockHeight> {
+    let checkpoints = checkpoints(params.network_type());
+    let idx = checkpoints.partition_point(|cp| cp.time <= time);
+    let prior = match idx.checked_sub(1) {
+        Some(i) => checkpoints[i],
+        None => return checkpoints.first().map(|cp| cp.height),
+    };
+
+    let elapsed = time - prior.time;
+    Some(match checkpoints.get(idx) {
+        Some(next) => {
+            let blocks = u64::from(next.height - prior.height) * u64::from(elapsed)
+                / u64::from(next.time - prior.time);
+            prior.height + u32::try_from(blocks).expect("bounded by checkpoint interval")
+        }
+        None => extrapolate(params, prior.height, elapsed),
+    })
+}
+
+/// Returns the height reached by mining at the target spacing for `elapsed` seconds after
+/// the block at `from`.
+fn extrapolate<P: Parameters>(params: &P, from: BlockHeight, elapsed: u32) -> BlockHeight {
+    match params.activation_height(NetworkUpgrade::Blossom) {
+        Some(blossom) if from < blossom => {
+            let pre_blossom_blocks = blossom - from;
+            let pre_blossom_time = pre_blossom_blocks.saturating_mul(PRE_BLOSSOM_TARGET_SPACING);
+            if elapsed < pre_blossom_time {
+                from + elapsed / PRE_BLOSSOM_TARGET_SPACING
+            } else {
+                blossom + (elapsed - pre_blossom_time) / POST_BLOSSOM_TARGET_SPACING
+            }
+        }
+        _ => from + elapsed / POST_BLOSSOM_TARGET_SPACING,
+    }
+}
+
+/// Errors that can occur while estimating a birthday using a lightwalletd server.
+#[cfg(feature = "lightwalletd-tonic")]
+#[derive(Debug)]
+pub enum Error {
+    /// The lightwalletd server returned invalid information, and is misbehaving.
+    MisbehavingServer,
+    /// An error while communicating with the lightwalletd server.
+    Server(tonic::Status),
+    /// The tree state returned by the server could not be parsed.
+    TreeState(BirthdayError),
+}
+
+#[cfg(feature = "lightwalletd-tonic")]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MisbehavingServer => write!(f, "lightwalletd server is misbehaving"),
+            Error::Server(e) => {
+                write!(f, "Error while communicating with lightwalletd server: {e}")
+            }
+            Error::TreeState(e) => write!(f, "Invalid tree state: {e:?}"),
+        }
+    }
+}
+
+#[cfg(feature = "lightwalletd-tonic")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "lightwalletd-tonic")]
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Server(status)
+    }
+}
+
+#[cfg(feature = "lightwalletd-tonic")]
+impl From<BirthdayError> for Error {
+    fn from(e: BirthdayError) -> Self {
+        Error::TreeState(e)
+    }
+}
+
+/// Constructs the [`AccountBirthday`] for an account whose birthday height is `height`,
+/// by fetching the tree state as of the end of the preceding block.
+#[cfg(feature = "lightwalletd-tonic")]
+pub async fn birthday_from_height<ChT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    height: BlockHeight,
+    recover_until: Option<BlockHeight>,
+) -> Result<AccountBirthday, Error>
+where
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let tree_state = client
+        .get_tree_state(service::BlockId {
+            height: height.saturating_sub(1).into(),
+            hash: vec![],
+        })
+        .await?
+        .into_inner();
+
+    Ok(AccountBirthday::from_treestate(tree_state, recover_until)?)
+}
+
+/// Constructs the [`AccountBirthday`] for an account that was created at the given time.
+///
+/// The birthday height is the height of the last block mined at or before `time`, as
+/// determined by [`height_at_time`]. Callers should pass a time that is safely before the
+/// account first received funds; the date on which the user's wallet was installed is a
+/// reasonable choice.
+#[cfg(feature = "lightwalletd-tonic")]
+pub async fn birthday_from_time<P, ChT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    time: SystemTime,
+    recover_until: Option<BlockHeight>,
+) -> Result<AccountBirthday, Error>
+where
+    P: Parameters,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let time = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u32::try_from(d.as_secs()).unwrap_or(u32::MAX));
+    let height = height_at_time(client, params, time).await?;
+    birthday_from_height(client, height, recover_until).await
+}
+
+/// Returns the height of the last block that was mined at or before the given time, in
+/// seconds since the Unix epoch.
+///
+/// The search starts from [`estimate_height`], and brackets the target time using the
+/// timestamps of blocks fetched from the server before narrowing the bracket by bisection,
+/// so that only a few dozen blocks are fetched. Heights prior to Sapling activation are
+/// never returned, because no shielded outputs can exist before that height; if `time`
+/// precedes Sapling activation, the Sapling activation height is returned.
+///
+/// Block timestamps are not strictly monotonic, so the result may differ from the true
+/// answer by a few blocks.
+#[cfg(feature = "lightwalletd-tonic")]
+pub async fn height_at_time<P, ChT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    time: u32,
+) -> Result<BlockHeight, Error>
+where
+    P: Parameters,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let tip: BlockHeight = client
+        .get_latest_block(service::ChainSpec::default())
+        .await?
+        .get_ref()
+        .height
+        .try_into()
+        .map_err(|_| Error::MisbehavingServer)?;
+    let floor = params
+        .activation_height(NetworkUpgrade::Sapling)
+        .unwrap_or(BlockHeight::from_u32(1))
+        .min(tip);
+
+    let guess = estimate_height(params, time)
+        .unwrap_or(floor)
+        .clamp(floor, tip);
+
+    // Approximately one day of blocks at the post-Blossom target spacing.
+    let mut step = 24 * 60 * 60 / POST_BLOSSOM_TARGET_SPACING;
+
+    // Find a lower bound `lo` that was mined at or before the target time.
+    let mut lo = guess;
+    while block_time(client, lo).await? > time {
+        if lo == floor {
+            return Ok(floor);
+        }
+        lo = std::cmp::max(lo.saturating_sub(step), floor);
+        step = step.saturating_mul(2);
+    }
+
+    // Find an upper bound `hi` that was mined after the target time.
+    let mut hi = lo;
+    loop {
+        if hi == tip {
+            return Ok(tip);
+        }
+        let next = std::cmp::min(hi + step, tip);
+        if block_time(client, next).await? > time {
+            hi = next;
+            break;
+        }
+        lo = next;
+        hi = next;
+        step = step.saturating_mul(2);
+    }
+
+    // Bisect, maintaining `time(lo) <= time < time(hi)`.
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if block_time(client, mid).await? <= time {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    Ok(lo)
+}
+
+/// Returns the timestamp of the block at the given height.
+#[cfg(feature = "lightwalletd-tonic")]
+async fn block_time<ChT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    height: BlockHeight,
+) -> Result<u32, Error>
+where
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let block = client
+        .get_block(service::BlockId {
+            height: height.into(),
+            hash: vec![],
+        })
+        .await?
+        .into_inner();
+
+    if block.height() != height {
+        return Err(Error::MisbehavingServer);
+    }
+
+    Ok(block.time)
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::consensus::{
+        BlockHeight, Network, NetworkType, NetworkUpgrade, Parameters, MAIN_NETWORK, TEST_NETWORK,
+    };
+
+    use super::{checkpoints, estimate_height, MAIN_NETWORK_CHECKPOINTS, TEST_NETWORK_CHECKPOINTS};
+
+    #[test]
+    fn checkpoints_per_network() {
+        assert_eq!(checkpoints(NetworkType::Main), MAIN_NETWORK_CHECKPOINTS);
+        assert_eq!(checkpoints(NetworkType::Test), TEST_NETWORK_CHECKPOINTS);
+        assert!(checkpoints(NetworkType::Regtest).is_empty());
+
+        // Each network's checkpoints start at its genesis block.
+        for network in [NetworkType::Main, NetworkType::Test] {
+            assert_eq!(checkpoints(network)[0].height(), BlockHeight::from_u32(0));
+        }
+    }
+
+    #[test]
+    fn checkpoints_are_ordered() {
+        for network in [NetworkType::Main, NetworkType::Test] {
+            for pair in checkpoints(network).windows(2) {
+                assert!(pair[0].height() < pair[1].height());
+                assert!(pair[0].time() < pair[1].time());
+            }
+        }
+    }
+
+    #[test]
+    fn estimate_height_at_checkpoints() {
+        for cp in MAIN_NETWORK_CHECKPOINTS {
+            assert_eq!(estimate_height(&MAIN_NETWORK, cp.time()), Some(cp.height()));
+        }
+        for cp in TEST_NETWORK_CHECKPOINTS {
+            assert_eq!(estimate_height(&TEST_NETWORK, cp.time()), Some(cp.height()));
+        }
+    }
+
+    #[test]
+    fn estimate_height_interpolates_per_network() {
+        // Halfway between two checkpoints is halfway between their heights.
+        for (network, checkpoints) in [
+            (Network::MainNetwork, MAIN_NETWORK_CHECKPOINTS),
+            (Network::TestNetwork, TEST_NETWORK_CHECKPOINTS),
+        ] {
+            for pair in checkpoints.windows(2) {
+                let midpoint = pair[0].time() + (pair[1].time() - pair[0].time()) / 2;
+                let height = estimate_height(&network, midpoint).unwrap();
+                let expected = pair[0].height() + (pair[1].height() - pair[0].height()) / 2;
+                assert!(u32::from(height).abs_diff(u32::from(expected)) <= 1);
+            }
+        }
+    }
+
+    #[test]
+    fn estimate_height_extrapolates_testnet() {
+        // One day after the last testnet checkpoint is approximately 1152 blocks later.
+        let last = TEST_NETWORK_CHECKPOINTS.last().unwrap();
+        assert_eq!(
+            estimate_height(&TEST_NETWORK, last.time() + 86400),
+            Some(last.height() + 1152)
+        );
+    }
+
+    #[test]
+    fn testnet_checkpoints_include_network_upgrades() {
+        for nu in [
+            NetworkUpgrade::Sapling,
+            NetworkUpgrade::Blossom,
+            NetworkUpgrade::Heartwood,
+            NetworkUpgrade::Canopy,
+            NetworkUpgrade::Nu5,
+            NetworkUpgrade::Nu6,
+        ] {
+            let activation = TEST_NETWORK.activation_height(nu).unwrap();
+            assert!(TEST_NETWORK_CHECKPOINTS
+                .iter()
+                .any(|cp| cp.height() == activation));
+        }
+    }
+
+    #[test]
+    fn estimate_height_is_monotonic() {
+        let mut prev = BlockHeight::from_u32(0);
+        for network in [Network::MainNetwork, Network::TestNetwork] {
+            // From 2016-10-01 to 2030-01-01, in steps of roughly a week.
+            for time in (1475280000u32..1893456000).step_by(600_000) {
+                let height = estimate_height(&network, time).unwrap();
+                assert!(height >= prev);
+                prev = height;
+            }
+            prev = BlockHeight::from_u32(0);
+        }
+    }
+
+    #[test]
+    fn estimate_height_extrapolates() {
+        // One day after NU6 activation is approximately 1152 blocks later.
+        let nu6 = MAIN_NETWORK_CHECKPOINTS.last().unwrap();
+        assert_eq!(
+            estimate_height(&MAIN_NETWORK, nu6.time() + 86400),
+            Some(nu6.height() + 1152)
+        );
+
+        // Before genesis, we clamp to the genesis height.
+        assert_eq!(
+            estimate_height(&MAIN_NETWORK, 0),
+            Some(BlockHeight::from_u32(0))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "lightwalletd-tonic-server"))]
+mod server_tests {
+    use std::convert::Infallible;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use zcash_protocol::consensus::{
+        BlockHeight, NetworkUpgrade, Parameters, TestNetwork, TEST_NETWORK,
+    };
+
+    use super::{
+        birthday_from_height, birthday_from_time, height_at_time, TEST_NETWORK_CHECKPOINTS,
+    };
+    use crate::{
+        data_api::chain::{error::Error, BlockSource},
+        proto::{
+            compact_formats::CompactBlock,
+            server::LocalLightwalletd,
+            service::{
+                compact_tx_streamer_client::CompactTxStreamerClient,
+                compact_tx_streamer_server::CompactTxStreamerServer, TreeState,
+            },
+        },
+    };
+
+    /// The amount by which block times on the synthetic chain lag the bundled checkpoints,
+    /// so that the initial estimates of `height_at_time` are never exact.
+    const LAG: u32 = 6 * 60 * 60;
+
+    const TIP: u32 = 3_000_000;
+
+    /// Returns the time of the block at the given height on a synthetic chain that follows
+    /// the testnet checkpoints, lagging them by [`LAG`].
+    fn block_time(height: u32) -> u32 {
+        let idx = TEST_NETWORK_CHECKPOINTS.partition_point(|cp| u32::from(cp.height()) <= height);
+        let prior = TEST_NETWORK_CHECKPOINTS[idx - 1];
+        let elapsed = height - u32::from(prior.height());
+        let time = match TEST_NETWORK_CHECKPOINTS.get(idx) {
+            Some(next) => {
+                u64::from(elapsed) * u64::from(next.time() - prior.time())
+                    / u64::from(next.height() - prior.height())
+            }
+            None => u64::from(elapsed) * 75,
+        };
+        prior.time() + u32::try_from(time).unwrap() + LAG
+    }
+
+    /// A block source that generates the blocks of the synthetic chain on demand.
+    struct SyntheticChain;
+
+    impl BlockSource for SyntheticChain {
+        type Error = Infallible;
+
+        fn with_blocks<F, WalletErrT>(
+            &self,
+            from_height: Option<BlockHeight>,
+            limit: Option<usize>,
+            mut with_block: F,
+        ) -> Result<(), Error<WalletErrT, Self::Error>>
+        where
+            F: FnMut(CompactBlock) -> Result<(), Error<WalletErrT, Self::Error>>,
+        {
+            let from = from_height.map_or(0, u32::from);
+            (from..=TIP)
+                .take(limit.unwrap_or(usize::MAX))
+                .try_for_each(|height| {
+                    with_block(CompactBlock {
+                        height: height.into(),
+                        hash: height.to_le_bytes().repeat(8),
+                        time: block_time(height),
+                        ..Default::default()
+                    })
+                })
+        }
+    }
+
+    /// The heights of the testnet network upgrades whose activations are checkpointed.
+    fn activation_heights() -> Vec<BlockHeight> {
+        [
+            NetworkUpgrade::Sapling,
+            NetworkUpgrade::Blossom,
+            NetworkUpgrade::Heartwood,
+            NetworkUpgrade::Canopy,
+            NetworkUpgrade::Nu5,
+            NetworkUpgrade::Nu6,
+        ]
+        .into_iter()
+        .map(|nu| TEST_NETWORK.activation_height(nu).unwrap())
+        .collect()
+    }
+
+    fn server() -> Arc<LocalLightwalletd<TestNetwork, SyntheticChain>> {
+        let server = Arc::new(LocalLightwalletd::new(TEST_NETWORK, SyntheticChain));
+        server.set_chain_tip(TIP.into());
+        for height in activation_heights() {
+            server.insert_tree_state(TreeState {
+                network: "test".to_owned(),
+                height: u64::from(height - 1),
+                hash: "00".repeat(32),
+                time: block_time(u32::from(height - 1)),
+                ..Default::default()
+            });
+        }
+        server
+    }
+
+    #[test]
+    fn height_at_time_at_activation_boundaries() {
+        let server = server();
+        let sapling = TEST_NETWORK
+            .activation_height(NetworkUpgrade::Sapling)
+            .unwrap();
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut client =
+                CompactTxStreamerClient::new(CompactTxStreamerServer::from_arc(server));
+            for height in activation_heights() {
+                let time = block_time(u32::from(height));
+                assert_eq!(
+                    height_at_time(&mut client, &TEST_NETWORK, time)
+                        .await
+                        .unwrap(),
+                    height
+                );
+
+                // Just before the activation block was mined, the chain was one block
+                // shorter, but no earlier than Sapling activation.
+                assert_eq!(
+                    height_at_time(&mut client, &TEST_NETWORK, time - 1)
+                        .await
+                        .unwrap(),
+                    std::cmp::max(height - 1, sapling)
+                );
+            }
+
+            // Times after the chain tip map to the tip.
+            assert_eq!(
+                height_at_time(&mut client, &TEST_NETWORK, u32::MAX)
+                    .await
+                    .unwrap(),
+                BlockHeight::from(TIP)
+            );
+        });
+    }
+
+    #[test]
+    fn birthday_at_activation_boundaries() {
+        let server = server();
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut client =
+                CompactTxStreamerClient::new(CompactTxStreamerServer::from_arc(server));
+            for height in activation_heights() {
+                let birthday = birthday_from_height(&mut client, height, None)
+                    .await
+                    .unwrap();
+                assert_eq!(birthday.height(), height);
+
+                let time = UNIX_EPOCH + Duration::from_secs(block_time(u32::from(height)).into());
+                let birthday = birthday_from_time(&mut client, &TEST_NETWORK, time, None)
+                    .await
+                    .unwrap();
+                assert_eq!(birthday.height(), height);
+            }
+
+            // No tree state is available for heights other than the activation heights.
+            let missing = activation_heights()[1] + 1;
+            assert!(birthday_from_height(&mut client, missing, None)
+                .await
+                .is_err());
+        });
+    }
+}
@@ -151,7 +151,7 @@
 //! # }
 //! ```
 
-use std::ops::Range;
+use std::{collections::VecDeque, num::NonZeroUsize, ops::Range};
 
 use incrementalmerkletree::frontier::Frontier;
 use subtle::ConditionallySelectable;
@@ -159,15 +159,21 @@ use zcash_primitives::block::BlockHash;
 use zcash_protocol::consensus::{self, BlockHeight};
 
 use crate::{
-    data_api::{NullifierQuery, WalletWrite},
+    data_api::{
+        scanning::ScanRange, NullifierQuery, ScannedBlock, WalletWrite, SAPLING_SHARD_HEIGHT,
+    },
     proto::compact_formats::CompactBlock,
     scanning::{scan_block_with_runners, BatchRunners, Nullifiers, ScanningKeys},
 };
 
+#[cfg(feature = "orchard")]
+use super::ORCHARD_SHARD_HEIGHT;
+
+#[cfg(feature = "transparent-inputs")]
+use {std::collections::HashSet, transparent::bundle::OutPoint};
+
 #[cfg(feature = "sync")]
-use {
-    super::scanning::ScanPriority, crate::data_api::scanning::ScanRange, async_trait::async_trait,
-};
+use {super::scanning::ScanPriority, async_trait::async_trait};
 
 pub mod error;
 use error::Error;
@@ -597,11 +603,7 @@ where
 {
     assert_eq!(from_height, from_state.block_height + 1);
 
-    // Fetch the UnifiedFullViewingKeys we are tracking
-    let account_ufvks = data_db
-        .get_unified_full_viewing_keys()
-        .map_err(Error::Wallet)?;
-    let scanning_keys = ScanningKeys::from_account_ufvks(account_ufvks);
+    let scanning_keys = tracked_scanning_keys(data_db).map_err(Error::Wallet)?;
     let mut runners = BatchRunners::<_, (), ()>::for_keys(100, &scanning_keys);
 
     block_source.with_blocks::<_, DbT::Error>(Some(from_height), Some(limit), |block| {
@@ -609,6 +611,244 @@ where
     })?;
     runners.flush();
 
+    let mut nullifiers = tracked_nullifiers(data_db).map_err(Error::Wallet)?;
+    let (scan_summary, scanned_blocks) = scan_with_runners(
+        params,
+        block_source,
+        data_db,
+        from_height,
+        limit,
+        &scanning_keys,
+        &mut nullifiers,
+        &mut runners,
+    )?;
+
+    data_db
+        .put_blocks(from_state, scanned_blocks)
+        .map_err(Error::Wallet)?;
+    Ok(scan_summary)
+}
+
+/// Scans several disjoint ranges of blocks from the provided block source in order to find
+/// transactions received by the accounts tracked in the provided wallet database.
+///
+/// Each element of `ranges` is a range of blocks to scan, together with the chain state as of
+/// the block immediately preceding the start of that range. The ranges need not be adjacent
+/// to one another or to previously-scanned ranges; for example, they may be a selection of
+/// the ranges returned by [`WalletRead::suggest_scan_ranges`], in the order in which they
+/// were returned.
+///
+/// Trial decryption of up to `max_concurrent_ranges` ranges proceeds at once on the `rayon`
+/// thread pool, so the decryption throughput of this function scales with the number of
+/// available cores. Only the decrypted outputs of those ranges are held in memory; as each
+/// range is stored in the wallet with [`WalletWrite::put_blocks`], decryption of the next
+/// range is queued.
+///
+/// Ranges are stored in the order in which they are provided, except that storage is
+/// serialized per note commitment tree shard: a range is never stored before a lower range
+/// that appends notes to the Sapling or Orchard shard in which it begins, so that the notes
+/// of each shard are inserted in order. Ranges in different shards are stored independently of one another;
+/// spends of notes that are received in a range stored later are detected by the wallet when
+/// that range is stored.
+///
+/// Returns a [`ScanSummary`] for each range, in the order in which the ranges were stored. If
+/// scanning a range fails, the ranges stored before it will remain in the wallet, and the
+/// remaining ranges will not have been scanned.
+///
+/// ## Panics
+///
+/// This method will panic if, for any element of `ranges`,
+/// `range.block_range().start != chain_state.block_height() + 1`, or if any two of the ranges
+/// overlap.
+#[tracing::instrument(skip_all, fields(ranges = ranges.len()))]
+pub fn scan_cached_block_ranges<ParamsT, DbT, BlockSourceT>(
+    params: &ParamsT,
+    block_source: &BlockSourceT,
+    data_db: &mut DbT,
+    ranges: &[(ScanRange, ChainState)],
+    max_concurrent_ranges: NonZeroUsize,
+) -> Result<Vec<ScanSummary>, Error<DbT::Error, BlockSourceT::Error>>
+where
+    ParamsT: consensus::Parameters + Send + 'static,
+    BlockSourceT: BlockSource,
+    DbT: WalletWrite,
+    <DbT as WalletRead>::AccountId: ConditionallySelectable + Default + Send + 'static,
+{
+    for (range, from_state) in ranges {
+        assert_eq!(range.block_range().start, from_state.block_height + 1);
+    }
+    let mut by_height = ranges.iter().map(|(range, _)| range).collect::<Vec<_>>();
+    by_height.sort_by_key(|range| range.block_range().start);
+    for pair in by_height.windows(2) {
+        assert!(
+            pair[0].block_range().end <= pair[1].block_range().start,
+            "Scan ranges must not overlap."
+        );
+    }
+
+    let scanning_keys = tracked_scanning_keys(data_db).map_err(Error::Wallet)?;
+    let mut nullifiers = tracked_nullifiers(data_db).map_err(Error::Wallet)?;
+
+    let mut to_queue = shard_ordered(ranges).into_iter();
+    let mut queued = VecDeque::with_capacity(max_concurrent_ranges.get());
+    let mut summaries = Vec::with_capacity(ranges.len());
+    loop {
+        // Keep up to `max_concurrent_ranges` ranges decrypting in the background.
+        while queued.len() < max_concurrent_ranges.get() {
+            let Some((range, from_state)) = to_queue.next() else {
+                break;
+            };
+            let mut runners = BatchRunners::<_, (), ()>::for_keys(100, &scanning_keys);
+            block_source.with_blocks::<_, DbT::Error>(
+                Some(range.block_range().start),
+                Some(range.len()),
+                |block| runners.add_block(params, block).map_err(|e| e.into()),
+            )?;
+            runners.flush();
+            queued.push_back((range, from_state, runners));
+        }
+
+        let Some((range, from_state, mut runners)) = queued.pop_front() else {
+            break;
+        };
+        let (scan_summary, scanned_blocks) = scan_with_runners(
+            params,
+            block_source,
+            data_db,
+            range.block_range().start,
+            range.len(),
+            &scanning_keys,
+            &mut nullifiers,
+            &mut runners,
+        )?;
+
+        data_db
+            .put_blocks(from_state, scanned_blocks)
+            .map_err(Error::Wallet)?;
+        summaries.push(scan_summary);
+    }
+
+    Ok(summaries)
+}
+
+/// Returns the given ranges in the order in which they should be stored: the order in which
+/// they are given, except that a range is moved after every lower range that appends notes
+/// to the Sapling or Orchard note commitment tree shard in which it begins.
+fn shard_ordered(ranges: &[(ScanRange, ChainState)]) -> Vec<&(ScanRange, ChainState)> {
+    // Returns whether the lower of the two ranges appends notes to a shard in which the
+    // higher range begins. A range appends notes to a tree if the tree is larger at the
+    // start of the next range than at its own start.
+    let shares_shard = |lower: &ChainState, higher: &ChainState| {
+        let shares = |lower_size: u64, higher_size: u64, shard_height: u8| {
+            lower_size < higher_size && lower_size >> shard_height == higher_size >> shard_height
+        };
+        #[cfg(feature = "orchard")]
+        if shares(
+            lower.final_orchard_tree().tree_size(),
+            higher.final_orchard_tree().tree_size(),
+            ORCHARD_SHARD_HEIGHT,
+        ) {
+            return true;
+        }
+        shares(
+            lower.final_sapling_tree().tree_size(),
+            higher.final_sapling_tree().tree_size(),
+            SAPLING_SHARD_HEIGHT,
+        )
+    };
+
+    let mut remaining = ranges.iter().collect::<Vec<_>>();
+    let mut ordered = Vec::with_capacity(ranges.len());
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .position(|(range, state)| {
+                !remaining.iter().any(|(other_range, other_state)| {
+                    other_range.block_range().start < range.block_range().start
+                        && shares_shard(other_state, state)
+                })
+            })
+            .expect("the lowest remaining range has no predecessor");
+        ordered.push(remaining.remove(next));
+    }
+    ordered
+}
+
+/// The keys with which the wallet's accounts are scanned, identified by account and scope.
+type WalletScanningKeys<AccountId> = ScanningKeys<AccountId, (AccountId, zip32::Scope)>;
+
+/// Returns the keys with which to scan for outputs received by the wallet.
+fn tracked_scanning_keys<DbT: WalletRead>(
+    data_db: &DbT,
+) -> Result<WalletScanningKeys<DbT::AccountId>, DbT::Error>
+where
+    DbT::AccountId: 'static,
+{
+    // Fetch the UnifiedFullViewingKeys we are tracking
+    let account_ufvks = data_db.get_unified_full_viewing_keys()?;
+    let scanning_keys = ScanningKeys::from_account_ufvks(account_ufvks);
+
+    // Fetch the transparent receivers we are tracking, so that transparent outputs can be
+    // detected in compact blocks that include transparent transaction data.
+    #[cfg(feature = "transparent-inputs")]
+    let scanning_keys = {
+        let mut receivers = vec![];
+        for account_id in data_db.get_account_ids()? {
+            receivers.extend(
+                data_db
+                    .get_transparent_receivers(account_id, true)?
+                    .into_keys()
+                    .map(|addr| (addr, account_id)),
+            );
+            receivers.extend(
+                data_db
+                    .get_known_ephemeral_addresses(account_id, None)?
+                    .into_iter()
+                    .map(|(addr, _)| (addr, account_id)),
+            );
+        }
+        scanning_keys.with_transparent_receivers(receivers)
+    };
+
+    Ok(scanning_keys)
+}
+
+/// Returns the nullifiers for the unspent notes we are tracking.
+fn tracked_nullifiers<DbT: WalletRead>(
+    data_db: &DbT,
+) -> Result<Nullifiers<DbT::AccountId>, DbT::Error> {
+    Ok(Nullifiers::new(
+        data_db.get_sapling_nullifiers(NullifierQuery::Unspent)?,
+        #[cfg(feature = "orchard")]
+        data_db.get_orchard_nullifiers(NullifierQuery::Unspent)?,
+        #[cfg(feature = "transparent-inputs")]
+        data_db.get_unspent_transparent_outpoints()?,
+    ))
+}
+
+/// Scans the blocks in the given range, for which trial decryption has already been queued
+/// with the given batch runners.
+///
+/// The tracked nullifier set is updated with the spends and received notes detected in the
+/// scanned blocks.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+fn scan_with_runners<ParamsT, DbT, BlockSourceT>(
+    params: &ParamsT,
+    block_source: &BlockSourceT,
+    data_db: &DbT,
+    from_height: BlockHeight,
+    limit: usize,
+    scanning_keys: &WalletScanningKeys<DbT::AccountId>,
+    nullifiers: &mut Nullifiers<DbT::AccountId>,
+    runners: &mut BatchRunners<(DbT::AccountId, zip32::Scope), (), ()>,
+) -> Result<(ScanSummary, Vec<ScannedBlock<DbT::AccountId>>), Error<DbT::Error, BlockSourceT::Error>>
+where
+    ParamsT: consensus::Parameters + Send + 'static,
+    BlockSourceT: BlockSource,
+    DbT: WalletRead,
+    DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
+{
     let mut prior_block_metadata = if from_height > BlockHeight::from(0) {
         data_db
             .block_metadata(from_height - 1)
@@ -617,17 +857,6 @@ where
         None
     };
 
-    // Get the nullifiers for the unspent notes we are tracking
-    let mut nullifiers = Nullifiers::new(
-        data_db
-            .get_sapling_nullifiers(NullifierQuery::Unspent)
-            .map_err(Error::Wallet)?,
-        #[cfg(feature = "orchard")]
-        data_db
-            .get_orchard_nullifiers(NullifierQuery::Unspent)
-            .map_err(Error::Wallet)?,
-    );
-
     let mut scanned_blocks = vec![];
     let mut scan_summary = ScanSummary::for_range(from_height..from_height);
     block_source.with_blocks::<_, DbT::Error>(
@@ -638,13 +867,12 @@ where
             let scanned_block = scan_block_with_runners::<_, _, _, (), ()>(
                 params,
                 block,
-                &scanning_keys,
-                &nullifiers,
+                scanning_keys,
+                nullifiers,
                 prior_block_metadata.as_ref(),
-                Some(&mut runners),
+                Some(&mut *runners),
             )
             .map_err(Error::Scan)?;
-
             for wtx in &scanned_block.transactions {
                 scan_summary.spent_sapling_note_count += wtx.sapling_spends().len();
                 scan_summary.received_sapling_note_count += wtx.sapling_outputs().len();
@@ -683,6 +911,25 @@ where
                 }));
             }
 
+            #[cfg(feature = "transparent-inputs")]
+            {
+                let transparent_spent: HashSet<&OutPoint> = scanned_block
+                    .transactions
+                    .iter()
+                    .flat_map(|tx| tx.transparent_spends().iter().map(|spend| spend.nf()))
+                    .collect();
+
+                nullifiers.retain_transparent(|outpoint| !transparent_spent.contains(outpoint));
+                nullifiers.extend_transparent(scanned_block.transactions.iter().flat_map(|tx| {
+                    tx.transparent_outputs().iter().filter_map(|out| {
+                        scanning_keys
+                            .transparent()
+                            .get(out.recipient_address())
+                            .map(|account_id| (*account_id, out.outpoint().clone()))
+                    })
+                }));
+            }
+
             prior_block_metadata = Some(scanned_block.to_block_metadata());
             scanned_blocks.push(scanned_block);
 
@@ -690,10 +937,7 @@ where
         },
     )?;
 
-    data_db
-        .put_blocks(from_state, scanned_blocks)
-        .map_err(Error::Wallet)?;
-    Ok(scan_summary)
+    Ok((scan_summary, scanned_blocks))
 }
 
 #[cfg(feature = "test-dependencies")]
@@ -723,3 +967,57 @@ pub mod testing {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use incrementalmerkletree::{frontier::Frontier, Hashable, Level, Position};
+    use zcash_primitives::block::BlockHash;
+
+    use super::{shard_ordered, ChainState};
+    use crate::data_api::scanning::{ScanPriority, ScanRange};
+
+    /// Returns a scan range starting at `start`, preceded by a Sapling note commitment tree
+    /// containing `tree_size` notes.
+    fn scan_range(start: u32, end: u32, tree_size: u64) -> (ScanRange, ChainState) {
+        let position = Position::from(tree_size - 1);
+        let ommers = (0..u64::from(position).count_ones())
+            .map(|_| sapling::Node::empty_root(Level::from(0)))
+            .collect();
+        let final_sapling_tree =
+            Frontier::from_parts(position, sapling::Node::empty_leaf(), ommers).unwrap();
+        (
+            ScanRange::from_parts(start.into()..end.into(), ScanPriority::Historic),
+            ChainState::new(
+                (start - 1).into(),
+                BlockHash([0; 32]),
+                final_sapling_tree,
+                #[cfg(feature = "orchard")]
+                Frontier::empty(),
+            ),
+        )
+    }
+
+    /// Returns the start heights of the given ranges, in the order in which they are stored.
+    fn storage_order(ranges: &[(ScanRange, ChainState)]) -> Vec<u32> {
+        shard_ordered(ranges)
+            .into_iter()
+            .map(|(range, _)| range.block_range().start.into())
+            .collect()
+    }
+
+    #[test]
+    fn shard_ordering() {
+        // The first two ranges begin in shard 0, and the third in shard 1.
+        let low = scan_range(10, 20, 1);
+        let mid = scan_range(20, 30, 10);
+        let high = scan_range(30, 40, (1 << 16) + 1);
+
+        // Ranges in different shards keep the order in which they are given, but a range is
+        // stored after any lower range in its shard.
+        assert_eq!(storage_order(&[high, mid.clone(), low]), vec![30, 10, 20]);
+
+        // A lower range that appends no notes to the shard does not constrain the order.
+        let empty_low = scan_range(10, 20, 10);
+        assert_eq!(storage_order(&[mid, empty_low]), vec![20, 10]);
+    }
+}
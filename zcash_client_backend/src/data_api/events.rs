@@ -0,0 +1,315 @@
+//! Structured notifications of changes to wallet state.
+//!
+//! Rather than diffing [`WalletSummary`]s and transaction lists after each sync, applications
+//! can register a [`WalletEventSubscriber`] with a wallet backend that supports it, and react
+//! to each [`WalletEvent`] as it occurs. Backends emit events only after the change that they
+//! describe has been durably recorded.
+//!
+//! The helper constructors on [`WalletEvent`] derive the events implied by the data passed to
+//! [`WalletWrite::put_blocks`] and [`WalletWrite::store_decrypted_tx`], so that backends
+//! report these changes consistently.
+//!
+//! [`WalletSummary`]: super::WalletSummary
+//! [`WalletWrite::put_blocks`]: super::WalletWrite::put_blocks
+//! [`WalletWrite::store_decrypted_tx`]: super::WalletWrite::store_decrypted_tx
+
+use zcash_primitives::transaction::TxId;
+use zcash_protocol::{consensus::BlockHeight, value::Zatoshis, ShieldedProtocol};
+
+use super::{DecryptedTransaction, ScannedBlock};
+
+use crate::{wallet::NoteId, TransferType};
+#[cfg(feature = "transparent-inputs")]
+use transparent::address::TransparentAddress;
+
+/// A change to the state of the wallet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WalletEvent<AccountId> {
+    /// A shielded note was received by an account.
+    NoteReceived {
+        /// The account that received the note.
+        account_id: AccountId,
+        /// The identifier of the received note.
+        note_id: NoteId,
+        /// The value of the note.
+        value: Zatoshis,
+        /// Whether the note is change from a transaction sent by the wallet.
+        is_change: bool,
+    },
+    /// A shielded note belonging to an account was spent.
+    NoteSpent {
+        /// The account to which the spent note belonged.
+        account_id: AccountId,
+        /// The identifier of the spent note.
+        note_id: NoteId,
+        /// The transaction that spent the note.
+        spending_txid: TxId,
+    },
+    /// A transaction involving the wallet was mined in the main chain.
+    TransactionMined {
+        /// The ID of the mined transaction.
+        txid: TxId,
+        /// The height of the block in which the transaction was mined.
+        height: BlockHeight,
+    },
+    /// An unmined transaction involving the wallet has expired, and can no longer be mined.
+    TransactionExpired {
+        /// The ID of the expired transaction.
+        txid: TxId,
+    },
+    /// The wallet discarded all chain data above the given height in response to a chain
+    /// reorganization.
+    Reorg {
+        /// The height of the most recent block retained by the wallet.
+        truncated_to: BlockHeight,
+    },
+    /// The balance of an account may have changed.
+    BalanceChanged {
+        /// The account whose balance may have changed.
+        account_id: AccountId,
+    },
+}
+
+impl<AccountId: Clone + PartialEq> WalletEvent<AccountId> {
+    /// Returns the events implied by storing the given scanned blocks.
+    ///
+    /// For each wallet transaction in the blocks, a [`WalletEvent::TransactionMined`] event
+    /// is followed by the [`WalletEvent::NoteSpent`] and [`WalletEvent::NoteReceived`] events
+    /// for its shielded spends and outputs. A [`WalletEvent::BalanceChanged`] event is then
+    /// produced for each account involved in any of the transactions.
+    ///
+    /// Scanned blocks identify spent notes only by their nullifiers, and received transparent
+    /// outputs only by their recipient addresses. Notes received within `blocks` are resolved
+    /// directly; everything else is resolved against previously stored wallet state via
+    /// `lookup`. Spends that cannot be resolved produce no [`WalletEvent::NoteSpent`] event,
+    /// but still produce a [`WalletEvent::BalanceChanged`] event for the spending account.
+    pub fn for_scanned_blocks<L: ScannedBlockLookup<AccountId>>(
+        blocks: &[ScannedBlock<AccountId>],
+        lookup: &L,
+    ) -> Result<Vec<Self>, L::Error> {
+        // Notes may be spent in the same batch of blocks in which they were received.
+        let mut received = vec![];
+        for block in blocks {
+            for tx in block.transactions() {
+                for output in tx.sapling_outputs() {
+                    if let Some(nf) = output.nf() {
+                        received.push((
+                            ShieldedProtocol::Sapling,
+                            nf.0,
+                            NoteId::new(
+                                tx.txid(),
+                                ShieldedProtocol::Sapling,
+                                output.index() as u16,
+                            ),
+                        ));
+                    }
+                }
+                #[cfg(feature = "orchard")]
+                for output in tx.orchard_outputs() {
+                    if let Some(nf) = output.nf() {
+                        received.push((
+                            ShieldedProtocol::Orchard,
+                            nf.to_bytes(),
+                            NoteId::new(
+                                tx.txid(),
+                                ShieldedProtocol::Orchard,
+                                output.index() as u16,
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+        let resolve = |protocol: ShieldedProtocol, nf: [u8; 32]| match received
+            .iter()
+            .find(|(p, n, _)| *p == protocol && *n == nf)
+        {
+            Some((_, _, note_id)) => Ok(Some(*note_id)),
+            None => lookup.spent_note(protocol, &nf),
+        };
+
+        let mut events = vec![];
+        let mut accounts = vec![];
+        for block in blocks {
+            for tx in block.transactions() {
+                let txid = tx.txid();
+                events.push(WalletEvent::TransactionMined {
+                    txid,
+                    height: block.height(),
+                });
+
+                for spend in tx.sapling_spends() {
+                    accounts.push(spend.account_id().clone());
+                    if let Some(note_id) = resolve(ShieldedProtocol::Sapling, spend.nf().0)? {
+                        events.push(WalletEvent::NoteSpent {
+                            account_id: spend.account_id().clone(),
+                            note_id,
+                            spending_txid: txid,
+                        });
+                    }
+                }
+                #[cfg(feature = "orchard")]
+                for spend in tx.orchard_spends() {
+                    accounts.push(spend.account_id().clone());
+                    if let Some(note_id) =
+                        resolve(ShieldedProtocol::Orchard, spend.nf().to_bytes())?
+                    {
+                        events.push(WalletEvent::NoteSpent {
+                            account_id: spend.account_id().clone(),
+                            note_id,
+                            spending_txid: txid,
+                        });
+                    }
+                }
+
+                for output in tx.sapling_outputs() {
+                    events.push(WalletEvent::NoteReceived {
+                        account_id: output.account_id().clone(),
+                        note_id: NoteId::new(
+                            txid,
+                            ShieldedProtocol::Sapling,
+                            output.index() as u16,
+                        ),
+                        value: Zatoshis::from_u64(output.note().value().inner())
+                            .expect("Sapling note values are validated by consensus."),
+                        is_change: output.is_change(),
+                    });
+                }
+                #[cfg(feature = "orchard")]
+                for output in tx.orchard_outputs() {
+                    events.push(WalletEvent::NoteReceived {
+                        account_id: output.account_id().clone(),
+                        note_id: NoteId::new(
+                            txid,
+                            ShieldedProtocol::Orchard,
+                            output.index() as u16,
+                        ),
+                        value: Zatoshis::from_u64(output.note().value().inner())
+                            .expect("Orchard note values are validated by consensus."),
+                        is_change: output.is_change(),
+                    });
+                }
+
+                // Transparent spends and receipts also affect account balances.
+                #[cfg(feature = "transparent-inputs")]
+                {
+                    accounts.extend(
+                        tx.transparent_spends()
+                            .iter()
+                            .map(|spend| spend.account_id().clone()),
+                    );
+                    for output in tx.transparent_outputs() {
+                        if let Some(account_id) =
+                            lookup.transparent_receiver_account(output.recipient_address())?
+                        {
+                            accounts.push(account_id);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Self::with_balance_changes(events, accounts))
+    }
+
+    /// Returns the events implied by storing the given decrypted transaction.
+    ///
+    /// If the transaction has been mined, a [`WalletEvent::TransactionMined`] event is
+    /// followed by a [`WalletEvent::NoteReceived`] event for each output received by the
+    /// wallet. A [`WalletEvent::BalanceChanged`] event is then produced for each account
+    /// that decrypted any of the transaction's outputs.
+    pub fn for_decrypted_transaction(tx: &DecryptedTransaction<'_, AccountId>) -> Vec<Self> {
+        let txid = tx.tx().txid();
+        let mut events = vec![];
+        let mut accounts = vec![];
+        if let Some(height) = tx.mined_height() {
+            events.push(WalletEvent::TransactionMined { txid, height });
+        }
+
+        for output in tx.sapling_outputs() {
+            accounts.push(output.account().clone());
+            if output.transfer_type() != TransferType::Outgoing {
+                events.push(WalletEvent::NoteReceived {
+                    account_id: output.account().clone(),
+                    note_id: NoteId::new(txid, ShieldedProtocol::Sapling, output.index() as u16),
+                    value: output.note_value(),
+                    is_change: output.transfer_type() == TransferType::WalletInternal,
+                });
+            }
+        }
+        #[cfg(feature = "orchard")]
+        for output in tx.orchard_outputs() {
+            accounts.push(output.account().clone());
+            if output.transfer_type() != TransferType::Outgoing {
+                events.push(WalletEvent::NoteReceived {
+                    account_id: output.account().clone(),
+                    note_id: NoteId::new(txid, ShieldedProtocol::Orchard, output.index() as u16),
+                    value: output.note_value(),
+                    is_change: output.transfer_type() == TransferType::WalletInternal,
+                });
+            }
+        }
+
+        Self::with_balance_changes(events, accounts)
+    }
+
+    /// Appends a [`WalletEvent::BalanceChanged`] event for each of the given accounts, and
+    /// for each account that received or spent a note in `events`.
+    fn with_balance_changes(mut events: Vec<Self>, involved: Vec<AccountId>) -> Vec<Self> {
+        let mut accounts: Vec<AccountId> = vec![];
+        let noted = events.iter().filter_map(|event| match event {
+            WalletEvent::NoteReceived { account_id, .. }
+            | WalletEvent::NoteSpent { account_id, .. } => Some(account_id),
+            _ => None,
+        });
+        for account_id in involved.iter().chain(noted) {
+            if !accounts.contains(account_id) {
+                accounts.push(account_id.clone());
+            }
+        }
+
+        events.extend(
+            accounts
+                .into_iter()
+                .map(|account_id| WalletEvent::BalanceChanged { account_id }),
+        );
+        events
+    }
+}
+
+/// Access to previously stored wallet state, used by [`WalletEvent::for_scanned_blocks`] to
+/// resolve the notes and accounts referred to by scanned blocks.
+pub trait ScannedBlockLookup<AccountId> {
+    /// The type of errors produced by lookups.
+    type Error;
+
+    /// Returns the identifier of the stored note of the given protocol having the given
+    /// nullifier, if any.
+    fn spent_note(
+        &self,
+        protocol: ShieldedProtocol,
+        nf: &[u8; 32],
+    ) -> Result<Option<NoteId>, Self::Error>;
+
+    /// Returns the account that owns the given transparent receiver, if any.
+    #[cfg(feature = "transparent-inputs")]
+    fn transparent_receiver_account(
+        &self,
+        address: &TransparentAddress,
+    ) -> Result<Option<AccountId>, Self::Error>;
+}
+
+/// A receiver of [`WalletEvent`]s.
+///
+/// Subscribers are invoked synchronously by the wallet backend, so implementations that do
+/// significant work (such as delivering webhooks) should hand events off to another task.
+pub trait WalletEventSubscriber<AccountId> {
+    /// Handles an event emitted by the wallet.
+    fn on_event(&self, event: &WalletEvent<AccountId>);
+}
+
+impl<AccountId, F: Fn(&WalletEvent<AccountId>)> WalletEventSubscriber<AccountId> for F {
+    fn on_event(&self, event: &WalletEvent<AccountId>) {
+        self(event)
+    }
+}
@@ -5,7 +5,8 @@ use std::{
     convert::Infallible,
     fmt,
     hash::Hash,
-    num::NonZeroU32,
+    num::{NonZeroU32, NonZeroUsize},
+    ops::Range,
 };
 
 use assert_matches::assert_matches;
@@ -45,19 +46,22 @@ use zip32::{fingerprint::SeedFingerprint, DiversifierIndex};
 use zip321::Payment;
 
 use super::{
-    chain::{scan_cached_blocks, BlockSource, ChainState, CommitmentTreeRoot, ScanSummary},
+    chain::{
+        scan_cached_block_ranges, scan_cached_blocks, BlockSource, ChainState, CommitmentTreeRoot,
+        ScanSummary,
+    },
     error::Error,
-    scanning::ScanRange,
+    scanning::{ScanPriority, ScanRange},
     wallet::{
         create_proposed_transactions,
         input_selection::{GreedyInputSelector, InputSelector},
-        propose_standard_transfer_to_address, propose_transfer,
+        propose_send_max, propose_standard_transfer_to_address, propose_transfer,
     },
     Account, AccountBalance, AccountBirthday, AccountMeta, AccountPurpose, AccountSource,
-    AddressInfo, BlockMetadata, DecryptedTransaction, InputSource, NoteFilter, NullifierQuery,
-    ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes, TransactionDataRequest,
-    TransactionStatus, WalletCommitmentTrees, WalletRead, WalletSummary, WalletTest, WalletWrite,
-    SAPLING_SHARD_HEIGHT,
+    AddressInfo, BlockMetadata, DecryptedTransaction, InputSource, MaxSpendMode, NoteFilter,
+    NoteWitnesses, NullifierQuery, ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes,
+    TransactionDataRequest, TransactionStatus, WalletCommitmentTrees, WalletRead, WalletSummary,
+    WalletTest, WalletWrite, SAPLING_SHARD_HEIGHT,
 };
 use crate::{
     data_api::TargetValue,
@@ -77,7 +81,6 @@ use {
     super::wallet::input_selection::ShieldingSelector,
     crate::wallet::TransparentAddressMetadata,
     ::transparent::{address::TransparentAddress, keys::NonHardenedChildIndex},
-    std::ops::Range,
     transparent::GapLimits,
 };
 
@@ -342,6 +345,11 @@ impl CachedBlock {
         self.chain_state.block_height()
     }
 
+    /// Returns the note commitment tree state as of the end of this block.
+    pub fn chain_state(&self) -> &ChainState {
+        &self.chain_state
+    }
+
     /// Returns the size of the Sapling note commitment tree as of the end of this block.
     pub fn sapling_end_size(&self) -> u32 {
         self.sapling_end_size
@@ -790,6 +798,40 @@ where
         (height, res)
     }
 
+    /// Creates a fake block at the expected next height containing only the given compact
+    /// transaction, and inserts it into the cache.
+    ///
+    /// This can be used to construct blocks containing data that cannot be derived from a
+    /// wallet [`Transaction`], such as the transparent data of third-party transactions.
+    ///
+    /// This generated block will be treated as the latest block, and subsequent calls to
+    /// [`Self::generate_next_block`] will build on it.
+    pub fn generate_next_block_from_compact_tx(
+        &mut self,
+        ctx: CompactTx,
+    ) -> (BlockHeight, Cache::InsertResult) {
+        let prior_cached_block = self
+            .latest_cached_block()
+            .cloned()
+            .unwrap_or_else(|| CachedBlock::none(self.sapling_activation_height() - 1));
+        let height = prior_cached_block.height() + 1;
+
+        let cb = fake_compact_block_from_compact_tx(
+            ctx,
+            height,
+            prior_cached_block.chain_state.block_hash(),
+            prior_cached_block.sapling_end_size,
+            prior_cached_block.orchard_end_size,
+            &mut self.rng,
+        );
+        assert_eq!(cb.height(), height);
+
+        let res = self.cache_block(&prior_cached_block, cb);
+        self.latest_block_height = Some(height);
+
+        (height, res)
+    }
+
     /// Truncates the test wallet and block cache to the specified height, discarding all data from
     /// blocks at heights greater than the specified height, excluding transaction data that may
     /// not be recoverable from the chain.
@@ -853,6 +895,33 @@ where
         result
     }
 
+    /// Invokes [`scan_cached_block_ranges`] with the given block ranges, expecting success.
+    pub fn scan_cached_block_ranges(&mut self, ranges: &[Range<BlockHeight>]) -> Vec<ScanSummary> {
+        let ranges = ranges
+            .iter()
+            .map(|range| {
+                let prior_cached_block = self
+                    .latest_cached_block_below_height(range.start)
+                    .cloned()
+                    .unwrap_or_else(|| CachedBlock::none(range.start - 1));
+                (
+                    ScanRange::from_parts(range.clone(), ScanPriority::Historic),
+                    prior_cached_block.chain_state,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let result = scan_cached_block_ranges(
+            &self.network,
+            self.cache.block_source(),
+            &mut self.wallet_data,
+            &ranges,
+            NonZeroUsize::new(ranges.len()).unwrap_or(NonZeroUsize::MIN),
+        );
+        assert_matches!(result, Ok(_));
+        result.unwrap()
+    }
+
     /// Insert shard roots for both trees.
     pub fn put_subtree_roots(
         &mut self,
@@ -1043,6 +1112,42 @@ where
         result
     }
 
+    /// Invokes [`propose_send_max`] with the given arguments.
+    #[allow(clippy::type_complexity)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn propose_send_max<CommitmentTreeErrT>(
+        &mut self,
+        spend_from_account: <DbT as InputSource>::AccountId,
+        spend_pools: &[ShieldedProtocol],
+        fee_rule: StandardFeeRule,
+        recipient: ZcashAddress,
+        memo: Option<MemoBytes>,
+        mode: MaxSpendMode,
+        min_confirmations: NonZeroU32,
+    ) -> Result<
+        Proposal<StandardFeeRule, <DbT as InputSource>::NoteRef>,
+        super::wallet::ProposeSendMaxErrT<DbT, CommitmentTreeErrT, StandardFeeRule>,
+    > {
+        let network = self.network().clone();
+        let result = propose_send_max::<_, _, _, CommitmentTreeErrT>(
+            self.wallet(),
+            &network,
+            spend_from_account,
+            spend_pools,
+            &fee_rule,
+            recipient,
+            memo,
+            mode,
+            min_confirmations,
+        );
+
+        if let Ok(proposal) = &result {
+            check_proposal_serialization_roundtrip(self.wallet(), proposal);
+        }
+
+        result
+    }
+
     /// Invokes [`propose_shielding`] with the given arguments.
     ///
     /// [`propose_shielding`]: crate::data_api::wallet::propose_shielding
@@ -2657,6 +2762,22 @@ impl WalletRead for MockWalletDb {
         Ok(Vec::new())
     }
 
+    fn get_note_witnesses(
+        &self,
+        _note_ids: &[NoteId],
+        anchor_height: BlockHeight,
+    ) -> Result<NoteWitnesses, Self::Error> {
+        Ok(NoteWitnesses::from_parts(
+            anchor_height,
+            None,
+            vec![],
+            #[cfg(feature = "orchard")]
+            None,
+            #[cfg(feature = "orchard")]
+            vec![],
+        ))
+    }
+
     #[cfg(feature = "transparent-inputs")]
     fn get_transparent_receivers(
         &self,
@@ -2749,6 +2870,15 @@ impl WalletWrite for MockWalletDb {
         todo!()
     }
 
+    fn extend_account_viewing_key(
+        &mut self,
+        _account: Self::AccountId,
+        _unified_key: &UnifiedFullViewingKey,
+        _birthday: &AccountBirthday,
+    ) -> Result<Self::Account, Self::Error> {
+        todo!()
+    }
+
     fn get_next_available_address(
         &mut self,
         _account: Self::AccountId,
@@ -23,9 +23,12 @@ use zcash_primitives::{
 use zcash_protocol::{
     consensus::{self, BlockHeight, NetworkUpgrade, Parameters},
     local_consensus::LocalNetwork,
-    memo::{Memo, MemoBytes},
+    memo::{
+        structured::{MessageId, StructuredMemo},
+        Memo, MemoBytes,
+    },
     value::Zatoshis,
-    ShieldedProtocol,
+    ShieldedProtocol, TxId,
 };
 use zip32::Scope;
 use zip321::{Payment, TransactionRequest};
@@ -42,15 +45,15 @@ use crate::{
         wallet::{
             decrypt_and_store_transaction, input_selection::GreedyInputSelector, TransferErrT,
         },
-        Account as _, AccountBirthday, BoundedU8, DecryptedTransaction, InputSource, NoteFilter,
-        Ratio, TargetValue, WalletCommitmentTrees, WalletRead, WalletSummary, WalletTest,
-        WalletWrite,
+        Account as _, AccountBirthday, BoundedU8, DecryptedTransaction, InputSource, MaxSpendMode,
+        NoteFilter, Ratio, TargetValue, WalletCommitmentTrees, WalletRead, WalletSummary,
+        WalletTest, WalletWrite,
     },
     decrypt_transaction,
     fees::{
         self,
         standard::{self, SingleOutputChangeStrategy},
-        DustOutputPolicy, SplitPolicy, StandardFeeRule,
+        ChangeError, DustOutputPolicy, SplitPolicy, StandardFeeRule,
     },
     scanning::ScanError,
     wallet::{Note, NoteId, OvkPolicy, ReceivedNote},
@@ -342,6 +345,369 @@ pub fn send_single_step_proposed_transfer<T: ShieldedPoolTester>(
     );
 }
 
+pub fn send_structured_memo<T: ShieldedPoolTester>(
+    dsf: impl DataStoreFactory,
+    cache: impl TestCache,
+) {
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(dsf)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet in a single note
+    let value = Zatoshis::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    // A memo that is too long to fit in a single memo field.
+    let memo = StructuredMemo::new()
+        .with_text("Thanks for lunch! ".repeat(40))
+        .with_invoice_id(vec![0x42; 16]);
+    let message_id = MessageId::from_bytes([7; 8]);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let payments = Payment::with_structured_memo(
+        to.to_zcash_address(st.network()),
+        Zatoshis::const_from_u64(10000),
+        &memo,
+        message_id,
+    )
+    .unwrap();
+    assert_eq!(payments.len(), 2);
+    let request = zip321::TransactionRequest::new(payments).unwrap();
+
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+        DustOutputPolicy::default(),
+    );
+    let input_selector = GreedyInputSelector::new();
+
+    let proposal = st
+        .propose_transfer(
+            account.id(),
+            &input_selector,
+            &change_strategy,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+
+    let create_proposed_result = st.create_proposed_transactions::<Infallible, _, Infallible>(
+        account.usk(),
+        OvkPolicy::Sender,
+        &proposal,
+    );
+    assert_matches!(&create_proposed_result, Ok(txids) if txids.len() == 1);
+    let sent_tx_id = create_proposed_result.unwrap()[0];
+
+    // The sender can recover the chunks using its OVK, and they are reassembled on decryption.
+    let tx = st
+        .wallet()
+        .get_transaction(sent_tx_id)
+        .unwrap()
+        .expect("Created transaction was stored.");
+    let ufvks = [(account.id(), account.usk().to_unified_full_viewing_key())]
+        .into_iter()
+        .collect();
+    let d_tx = decrypt_transaction(st.network(), None, Some(h), &tx, &ufvks);
+    assert_eq!(T::decrypted_pool_outputs_count(&d_tx), 3);
+    assert_eq!(
+        d_tx.structured_memos(),
+        vec![(account.id(), message_id, memo)]
+    );
+}
+
+/// Checks that a transaction created by the wallet passes the consensus checks in
+/// [`zcash_primitives::transaction::check`], including verification of its proofs and
+/// signatures.
+#[cfg(feature = "orchard")]
+pub fn created_transaction_passes_consensus_checks<T: ShieldedPoolTester>(
+    dsf: impl DataStoreFactory,
+    cache: impl TestCache,
+) {
+    use zcash_primitives::transaction::check::{
+        check_context_free, check_contextual, check_zip317_fee, zip317_conventional_fee,
+        BatchVerifier,
+    };
+
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(dsf)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    let value = Zatoshis::const_from_u64(60000);
+    let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(h, 1);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let request = zip321::TransactionRequest::new(vec![Payment::without_memo(
+        to.to_zcash_address(st.network()),
+        Zatoshis::const_from_u64(10000),
+    )])
+    .unwrap();
+
+    let change_strategy = standard::SingleOutputChangeStrategy::new(
+        StandardFeeRule::Zip317,
+        None,
+        T::SHIELDED_PROTOCOL,
+        DustOutputPolicy::default(),
+    );
+    let input_selector = GreedyInputSelector::new();
+    let proposal = st
+        .propose_transfer(
+            account.id(),
+            &input_selector,
+            &change_strategy,
+            request,
+            NonZeroU32::new(1).unwrap(),
+        )
+        .unwrap();
+    let txids = st
+        .create_proposed_transactions::<Infallible, _, Infallible>(
+            account.usk(),
+            OvkPolicy::Sender,
+            &proposal,
+        )
+        .unwrap();
+    let tx = st
+        .wallet()
+        .get_transaction(txids[0])
+        .unwrap()
+        .expect("Created transaction was stored.");
+
+    assert_eq!(check_context_free(&tx), Ok(()));
+    assert_eq!(check_contextual(st.network(), &tx, h + 1), Ok(()));
+    assert_eq!(
+        check_zip317_fee(&tx, &[]),
+        Ok(zcash_protocol::value::ZatBalance::from(
+            zip317_conventional_fee(&tx).unwrap()
+        ))
+    );
+
+    let sapling_prover = zcash_proofs::prover::LocalTxProver::bundled();
+    let (spend_vk, output_vk) = sapling_prover.verifying_keys();
+    let orchard_vk = ::orchard::circuit::VerifyingKey::build();
+
+    let mut verifier = BatchVerifier::new();
+    verifier.queue(&tx, &[]).unwrap();
+    assert_eq!(verifier.queued_txids(), &[tx.txid()]);
+    assert!(verifier.validate(&spend_vk, &output_vk, &orchard_vk, rand_core::OsRng));
+
+    // A transaction whose shielded components have been altered must fail verification.
+    let mut tx_bytes = vec![];
+    tx.write(&mut tx_bytes).unwrap();
+    let expiry_offset = 4 + 4 + 4 + 4;
+    tx_bytes[expiry_offset] ^= 1;
+    let altered = Transaction::read(&tx_bytes[..], tx.consensus_branch_id()).unwrap();
+    let mut verifier = BatchVerifier::new();
+    verifier.queue(&altered, &[]).unwrap();
+    assert!(!verifier.validate(&spend_vk, &output_vk, &orchard_vk, rand_core::OsRng));
+}
+
+pub fn max_sendable_deducts_fee<T: ShieldedPoolTester>(
+    dsf: impl DataStoreFactory,
+    cache: impl TestCache,
+) {
+    use crate::fees::{
+        estimate::{max_sendable, EstimateError, RecipientPool},
+        DustAction,
+    };
+
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(dsf)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // One note that is worth spending, and one that would cost as much to spend as it is
+    // worth.
+    let (h1, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        Zatoshis::const_from_u64(60000),
+    );
+    st.generate_next_block(&dfvk, AddressType::DefaultExternal, MARGINAL_FEE);
+    st.scan_cached_blocks(h1, 2);
+
+    let (_, anchor_height) = st
+        .wallet()
+        .get_target_and_anchor_heights(NonZeroU32::MIN)
+        .unwrap()
+        .unwrap();
+
+    let fee_rule = Zip317FeeRule::standard();
+    let recipient = RecipientPool::Shielded(T::SHIELDED_PROTOCOL);
+    let max = |policy: &DustOutputPolicy| {
+        max_sendable(
+            st.wallet(),
+            &fee_rule,
+            account.id(),
+            anchor_height,
+            recipient,
+            &[T::SHIELDED_PROTOCOL],
+            policy,
+        )
+    };
+
+    // The single economic note is spent to one (padded) output with no change, so the fee
+    // is that of the two grace actions.
+    let result = max(&DustOutputPolicy::default()).unwrap();
+    assert_eq!(result.fee().fee(), Zatoshis::const_from_u64(10000));
+    assert_eq!(result.fee().logical_actions(), 2);
+    assert_eq!(result.value(), Zatoshis::const_from_u64(50000));
+    assert_eq!(result.uneconomic_value(), MARGINAL_FEE);
+
+    // A value exactly at the dust threshold is permitted.
+    let at_threshold =
+        DustOutputPolicy::new(DustAction::Reject, Some(Zatoshis::const_from_u64(50000)));
+    assert_eq!(
+        max(&at_threshold).unwrap().value(),
+        Zatoshis::const_from_u64(50000)
+    );
+
+    // A value below the dust threshold is rejected...
+    let above_value = Some(Zatoshis::const_from_u64(50001));
+    assert_matches!(
+        max(&DustOutputPolicy::new(DustAction::Reject, above_value)),
+        Err(EstimateError::InsufficientFunds { available, required })
+            if available == Zatoshis::const_from_u64(60000)
+                && required == Zatoshis::const_from_u64(60001)
+    );
+
+    // ...unless the policy allows dust.
+    assert_eq!(
+        max(&DustOutputPolicy::new(
+            DustAction::AllowDustChange,
+            above_value
+        ))
+        .unwrap()
+        .value(),
+        Zatoshis::const_from_u64(50000)
+    );
+}
+
+pub fn send_max_spends_all_notes<T: ShieldedPoolTester>(
+    dsf: impl DataStoreFactory,
+    cache: impl TestCache,
+) {
+    use crate::fees::estimate::{max_sendable, RecipientPool};
+
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(dsf)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Add funds to the wallet in two economic notes and one dust note.
+    let (h, _, _) = st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        Zatoshis::const_from_u64(60000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        Zatoshis::const_from_u64(40000),
+    );
+    st.generate_next_block(
+        &dfvk,
+        AddressType::DefaultExternal,
+        Zatoshis::const_from_u64(4000),
+    );
+    st.scan_cached_blocks(h, 3);
+
+    let to_extsk = T::sk(&[0xf5; 32]);
+    let to: Address = T::sk_default_address(&to_extsk);
+    let min_confirmations = NonZeroU32::new(1).unwrap();
+
+    // Attempting to send everything fails, because the dust note would be stranded.
+    assert_matches!(
+        st.propose_send_max::<Infallible>(
+            account.id(),
+            &[T::SHIELDED_PROTOCOL],
+            StandardFeeRule::Zip317,
+            to.to_zcash_address(st.network()),
+            None,
+            MaxSpendMode::Everything,
+            min_confirmations,
+        ),
+        Err(Error::Change(ChangeError::DustInputs { .. }))
+    );
+
+    // Sending the maximum spendable value leaves the dust note behind.
+    let proposal = st
+        .propose_send_max::<Infallible>(
+            account.id(),
+            &[T::SHIELDED_PROTOCOL],
+            StandardFeeRule::Zip317,
+            to.to_zcash_address(st.network()),
+            None,
+            MaxSpendMode::MaxSpendable,
+            min_confirmations,
+        )
+        .unwrap();
+
+    let step = &proposal.steps().head;
+    assert!(step.balance().proposed_change().is_empty());
+    assert_eq!(step.balance().fee_required(), MINIMUM_FEE);
+    assert_eq!(
+        step.transaction_request().total().unwrap(),
+        Zatoshis::const_from_u64(90000)
+    );
+    assert_eq!(
+        step.shielded_inputs().map(|inputs| inputs.notes().len()),
+        Some(2)
+    );
+
+    // The proposal agrees with the estimate of the maximum sendable value.
+    let (_, anchor_height) = st
+        .wallet()
+        .get_target_and_anchor_heights(min_confirmations)
+        .unwrap()
+        .unwrap();
+    let estimate = max_sendable(
+        st.wallet(),
+        &Zip317FeeRule::standard(),
+        account.id(),
+        anchor_height,
+        RecipientPool::Shielded(T::SHIELDED_PROTOCOL),
+        &[T::SHIELDED_PROTOCOL],
+        &DustOutputPolicy::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        estimate.value(),
+        step.transaction_request().total().unwrap()
+    );
+    assert_eq!(estimate.fee().fee(), step.balance().fee_required());
+    assert_eq!(estimate.uneconomic_value(), Zatoshis::const_from_u64(4000));
+
+    let create_proposed_result = st.create_proposed_transactions::<Infallible, _, Infallible>(
+        account.usk(),
+        OvkPolicy::Sender,
+        &proposal,
+    );
+    assert_matches!(&create_proposed_result, Ok(txids) if txids.len() == 1);
+}
+
 pub fn send_with_multiple_change_outputs<T: ShieldedPoolTester>(
     dsf: impl DataStoreFactory,
     cache: impl TestCache,
@@ -2992,6 +3358,67 @@ pub fn scan_cached_blocks_detects_spends_out_of_order<T: ShieldedPoolTester, DSF
     );
 }
 
+pub fn scan_cached_block_ranges_detects_spends_across_ranges<T: ShieldedPoolTester, DSF>(
+    ds_factory: DSF,
+    cache: impl TestCache,
+) where
+    DSF: DataStoreFactory,
+    <DSF as DataStoreFactory>::AccountId: std::fmt::Debug,
+{
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(ds_factory)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Create a fake CompactBlock sending value to the address
+    let value = Zatoshis::const_from_u64(5);
+    let (received_height, _, nf) =
+        st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+
+    // Create a fake CompactBlock that is not relevant to the wallet
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf5; 32]));
+    let (unrelated_height, _, _) =
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, value);
+
+    // Create a fake CompactBlock spending value from the address
+    let to2 = T::fvk_default_address(&not_our_key);
+    let value2 = Zatoshis::const_from_u64(2);
+    let (spent_height, _) = st.generate_next_block_spending(&dfvk, (nf, value), to2, value2);
+
+    // Scan the spending block and the receiving block together, leaving a gap between them.
+    let summaries = st.scan_cached_block_ranges(&[
+        spent_height..spent_height + 1,
+        received_height..unrelated_height,
+    ]);
+
+    // Both ranges begin in the same shard, so the lower range is stored first.
+    assert_eq!(summaries.len(), 2);
+    assert_eq!(
+        summaries[0].scanned_range(),
+        received_height..unrelated_height
+    );
+    assert_eq!(summaries[1].scanned_range(), spent_height..spent_height + 1);
+
+    // Account balance should equal the change
+    assert_eq!(
+        st.get_total_balance(account.id()),
+        (value - value2).unwrap()
+    );
+
+    // Now fill in the gap.
+    st.scan_cached_blocks(unrelated_height, 1);
+
+    // Account balance should be the same.
+    assert_eq!(
+        st.get_total_balance(account.id()),
+        (value - value2).unwrap()
+    );
+}
+
 pub fn metadata_queries_exclude_unwanted_notes<T: ShieldedPoolTester, DSF, TC>(
     ds_factory: DSF,
     cache: TC,
@@ -3192,3 +3619,102 @@ pub fn pczt_single_step<P0: ShieldedPoolTester, P1: ShieldedPoolTester, DSF>(
     let (h, _) = st.generate_next_block_including(txid);
     st.scan_cached_blocks(h, 1);
 }
+
+/// Tests that Merkle witnesses for the wallet's notes can be obtained at a chosen anchor
+/// height.
+pub fn note_witnesses<T: ShieldedPoolTester, DSF>(ds_factory: DSF, cache: impl TestCache)
+where
+    DSF: DataStoreFactory,
+    <DSF as DataStoreFactory>::AccountId: std::fmt::Debug,
+{
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(ds_factory)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let dfvk = T::test_account_fvk(&st);
+
+    // Receive a note, and then advance the chain by a block that is not relevant to the
+    // wallet.
+    let value = Zatoshis::const_from_u64(50000);
+    let (received_height, _, _) =
+        st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+    let not_our_key = T::sk_to_fvk(&T::sk(&[0xf5; 32]));
+    let (anchor_height, _, _) =
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, value);
+    st.scan_cached_blocks(received_height, 2);
+
+    let notes = T::select_spendable_notes(
+        &st,
+        account.id(),
+        TargetValue::AtLeast(value),
+        anchor_height,
+        &[],
+    )
+    .unwrap();
+    assert_eq!(notes.len(), 1);
+    let note = &notes[0];
+    let note_id = NoteId::new(*note.txid(), T::SHIELDED_PROTOCOL, note.output_index());
+
+    // Notes that are not known to the wallet are omitted.
+    let unknown_id = NoteId::new(TxId::from_bytes([7; 32]), T::SHIELDED_PROTOCOL, 0);
+    let witnesses = st
+        .wallet()
+        .get_note_witnesses(&[note_id, unknown_id], anchor_height)
+        .unwrap();
+    assert_eq!(witnesses.anchor_height(), anchor_height);
+
+    // Each witness is the path from the note's commitment to the returned anchor.
+    let spendable = st
+        .wallet()
+        .select_spendable_notes(
+            account.id(),
+            TargetValue::AtLeast(value),
+            &[T::SHIELDED_PROTOCOL],
+            anchor_height,
+            &[],
+        )
+        .unwrap();
+    let witnessed = match T::SHIELDED_PROTOCOL {
+        ShieldedProtocol::Sapling => {
+            let cmu = spendable.sapling()[0].note().cmu();
+            let anchor = witnesses.sapling_anchor().unwrap();
+            witnesses
+                .sapling_witnesses()
+                .iter()
+                .map(|(id, path)| {
+                    assert_eq!(path.position(), note.note_commitment_tree_position());
+                    assert_eq!(
+                        ::sapling::Anchor::from(path.root(::sapling::Node::from_cmu(&cmu))),
+                        anchor
+                    );
+                    *id
+                })
+                .collect::<Vec<_>>()
+        }
+        #[cfg(feature = "orchard")]
+        ShieldedProtocol::Orchard => {
+            let cmx = ::orchard::note::ExtractedNoteCommitment::from(
+                spendable.orchard()[0].note().commitment(),
+            );
+            let anchor = witnesses.orchard_anchor().unwrap();
+            witnesses
+                .orchard_witnesses()
+                .iter()
+                .map(|(id, path)| {
+                    assert_eq!(
+                        u64::from(path.position()),
+                        u64::from(note.note_commitment_tree_position())
+                    );
+                    assert_eq!(path.root(cmx), anchor);
+                    *id
+                })
+                .collect::<Vec<_>>()
+        }
+        #[cfg(not(feature = "orchard"))]
+        ShieldedProtocol::Orchard => unreachable!(),
+    };
+    assert_eq!(witnessed, vec![note_id]);
+}
@@ -9,6 +9,7 @@ use crate::{
         Account as _, Balance, InputSource, WalletRead, WalletWrite,
     },
     fees::{standard, DustOutputPolicy, StandardFeeRule},
+    proto::compact_formats::{CompactTx, CompactTxIn},
     wallet::WalletTransparentOutput,
 };
 use assert_matches::assert_matches;
@@ -19,7 +20,7 @@ use ::transparent::{
 };
 use sapling::zip32::ExtendedSpendingKey;
 use zcash_keys::{address::Address, keys::UnifiedAddressRequest};
-use zcash_primitives::block::BlockHash;
+use zcash_primitives::{block::BlockHash, transaction::TxId};
 use zcash_protocol::{local_consensus::LocalNetwork, value::Zatoshis};
 
 use super::TestAccount;
@@ -285,6 +286,95 @@ where
     check_balance::<DSF>(&st, &account, taddr, 0, &zero_or_one_conf_value);
 }
 
+/// Checks that transparent outputs received by the wallet, and spends of those outputs, are
+/// detected when scanning compact blocks that include transparent transaction data.
+pub fn transparent_detected_in_compact_blocks<DSF>(dsf: DSF, cache: impl TestCache)
+where
+    DSF: DataStoreFactory,
+{
+    let mut st = TestBuilder::new()
+        .with_data_store_factory(dsf)
+        .with_block_cache(cache)
+        .with_account_from_sapling_activation(BlockHash([0; 32]))
+        .build();
+
+    let account = st.test_account().cloned().unwrap();
+    let uaddr = st
+        .wallet()
+        .get_last_generated_address_matching(account.id(), UnifiedAddressRequest::AllAvailableKeys)
+        .unwrap()
+        .unwrap();
+    let taddr = *uaddr.transparent().unwrap();
+    let not_our_taddr = TransparentAddress::PublicKeyHash([7; 20]);
+
+    // Initialize the wallet with chain data that has no shielded notes for us.
+    let not_our_key = ExtendedSpendingKey::master(&[]).to_diversifiable_full_viewing_key();
+    let not_our_value = Zatoshis::const_from_u64(10000);
+    let (start_height, _, _) =
+        st.generate_next_block(&not_our_key, AddressType::DefaultExternal, not_our_value);
+    st.scan_cached_blocks(start_height, 1);
+    check_balance::<DSF>(&st, &account, &taddr, 0, &Balance::ZERO);
+
+    // A third-party transaction pays to both our transparent address and another address.
+    let value = Zatoshis::const_from_u64(100000);
+    let receiving_txid = TxId::from_bytes([1; 32]);
+    let (receiving_height, _) = st.generate_next_block_from_compact_tx(CompactTx {
+        index: 1,
+        hash: receiving_txid.as_ref().to_vec(),
+        vin: vec![CompactTxIn {
+            prevout_txid: vec![2; 32],
+            prevout_index: 0,
+        }],
+        vout: [not_our_taddr, taddr]
+            .into_iter()
+            .map(|addr| {
+                (&TxOut {
+                    value,
+                    script_pubkey: addr.script(),
+                })
+                    .into()
+            })
+            .collect(),
+        ..Default::default()
+    });
+    st.scan_cached_blocks(receiving_height, 1);
+
+    // Only the output paying to our address is detected.
+    let mut expected = Balance::ZERO;
+    expected.add_spendable_value(value).unwrap();
+    check_balance::<DSF>(&st, &account, &taddr, 0, &expected);
+    let mempool_height = st.wallet().chain_height().unwrap().unwrap() + 1;
+    let utxos = st
+        .wallet()
+        .get_spendable_transparent_outputs(&taddr, mempool_height, 0)
+        .unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(
+        utxos[0].outpoint(),
+        &OutPoint::new(*receiving_txid.as_ref(), 1)
+    );
+    assert_eq!(utxos[0].mined_height(), Some(receiving_height));
+
+    // A transaction that spends the received output is detected, and the output is no
+    // longer counted in the wallet's balance.
+    let (spending_height, _) = st.generate_next_block_from_compact_tx(CompactTx {
+        index: 1,
+        hash: vec![3; 32],
+        vin: vec![CompactTxIn {
+            prevout_txid: receiving_txid.as_ref().to_vec(),
+            prevout_index: 1,
+        }],
+        vout: vec![(&TxOut {
+            value: Zatoshis::const_from_u64(90000),
+            script_pubkey: not_our_taddr.script(),
+        })
+            .into()],
+        ..Default::default()
+    });
+    st.scan_cached_blocks(spending_height, 1);
+    check_balance::<DSF>(&st, &account, &taddr, 0, &Balance::ZERO);
+}
+
 /// This test attempts to verify that transparent funds spendability is
 /// accounted for properly given the different minimum confirmations values
 /// that can be set when querying for balances.
@@ -35,11 +35,11 @@ to a wallet-internal shielded address, as described in [ZIP 316](https://zips.z.
 
 use nonempty::NonEmpty;
 use rand_core::OsRng;
-use std::num::NonZeroU32;
+use std::{collections::BTreeMap, num::NonZeroU32};
 
 use shardtree::error::{QueryError, ShardTreeError};
 
-use super::InputSource;
+use super::{InputSource, MaxSpendMode, SimpleNoteRetention, TargetValue};
 use crate::{
     data_api::{
         error::Error, Account, SentTransaction, SentTransactionOutput, WalletCommitmentTrees,
@@ -47,9 +47,10 @@ use crate::{
     },
     decrypt_transaction,
     fees::{
-        standard::SingleOutputChangeStrategy, ChangeStrategy, DustOutputPolicy, StandardFeeRule,
+        estimate::RecipientPool, standard::SingleOutputChangeStrategy, zip317::Zip317FeeRule,
+        ChangeError, ChangeStrategy, DustOutputPolicy, StandardFeeRule, TransactionBalance,
     },
-    proposal::{Proposal, ProposalError, Step, StepOutputIndex},
+    proposal::{Proposal, ProposalError, ShieldedInputs, Step, StepOutputIndex},
     wallet::{Note, OvkPolicy, Recipient},
 };
 use ::sapling::{
@@ -57,7 +58,9 @@ use ::sapling::{
     prover::{OutputProver, SpendProver},
 };
 use ::transparent::{
-    address::TransparentAddress, builder::TransparentSigningSet, bundle::OutPoint,
+    address::TransparentAddress,
+    builder::TransparentSigningSet,
+    bundle::{OutPoint, TxOut},
 };
 use zcash_address::ZcashAddress;
 use zcash_keys::{
@@ -67,13 +70,16 @@ use zcash_keys::{
 use zcash_primitives::transaction::{
     builder::{BuildConfig, BuildResult, Builder},
     components::sapling::zip212_enforcement,
-    fees::FeeRule,
+    fees::{
+        transparent::{InputSize, OutputView},
+        FeeRule,
+    },
     Transaction, TxId,
 };
 use zcash_protocol::{
     consensus::{self, BlockHeight},
     memo::MemoBytes,
-    value::Zatoshis,
+    value::{BalanceError, Zatoshis},
     PoolType, ShieldedProtocol,
 };
 use zip32::Scope;
@@ -82,7 +88,6 @@ use zip321::Payment;
 #[cfg(feature = "transparent-inputs")]
 use {
     crate::{fees::ChangeValue, proposal::StepOutput, wallet::TransparentAddressMetadata},
-    ::transparent::bundle::TxOut,
     core::convert::Infallible,
     input_selection::ShieldingSelector,
     std::collections::HashMap,
@@ -102,10 +107,7 @@ use {
     sapling::note_encryption::SaplingDomain,
     serde::{Deserialize, Serialize},
     zcash_note_encryption::try_output_recovery_with_pkd_esk,
-    zcash_protocol::{
-        consensus::NetworkConstants,
-        value::{BalanceError, ZatBalance},
-    },
+    zcash_protocol::{consensus::NetworkConstants, value::ZatBalance},
 };
 
 pub mod input_selection;
@@ -204,6 +206,17 @@ pub type ProposeTransferErrT<DbT, CommitmentTreeErrT, InputsT, ChangeT> = Error<
     <<InputsT as InputSelector>::InputSource as InputSource>::NoteRef,
 >;
 
+/// Errors that may be generated in construction of proposals that send all of the funds in
+/// an account to a single recipient.
+pub type ProposeSendMaxErrT<DbT, CommitmentTreeErrT, FeeRuleT> = Error<
+    <DbT as WalletRead>::Error,
+    CommitmentTreeErrT,
+    std::convert::Infallible,
+    <FeeRuleT as FeeRule>::Error,
+    <FeeRuleT as FeeRule>::Error,
+    <DbT as InputSource>::NoteRef,
+>;
+
 /// Errors that may be generated in construction of proposals for transparent->shielded
 /// wallet-internal transfers.
 #[cfg(feature = "transparent-inputs")]
@@ -388,6 +401,206 @@ where
     )
 }
 
+/// Proposes a transaction that sends all of the spendable funds in the given shielded pools
+/// of an account to a single recipient, without creating any change output.
+///
+/// Returns the proposal, which may then be executed using [`create_proposed_transactions`].
+///
+/// Parameters:
+/// * `wallet_db`: A reference to the wallet database.
+/// * `params`: Consensus parameters.
+/// * `spend_from_account`: The unified account that controls the funds that will be spent
+///   in the resulting transaction.
+/// * `spend_pools`: The shielded pools from which notes will be selected.
+/// * `fee_rule`: The fee rule to use in creating the transaction.
+/// * `recipient`: The address to which the funds will be sent.
+/// * `memo`: A memo to be included in the output to the recipient.
+/// * `mode`: Determines how notes that are uneconomic to spend are treated. If
+///   [`MaxSpendMode::Everything`] is specified and the account holds any such notes in the
+///   given pools, this returns [`ChangeError::DustInputs`] identifying the notes that would
+///   otherwise be left behind.
+/// * `min_confirmations`: The minimum number of confirmations that a previously
+///   received note must have in the blockchain in order to be considered for being
+///   spent.
+///
+/// Payments to TEX addresses require an additional transaction and are not supported;
+/// [`Error::ProposalNotSupported`] is returned for such recipients. If the value of the
+/// selected notes does not exceed the required fee, [`Error::InsufficientFunds`] is
+/// returned.
+#[allow(clippy::too_many_arguments)]
+#[allow(clippy::type_complexity)]
+pub fn propose_send_max<DbT, ParamsT, FeeRuleT, CommitmentTreeErrT>(
+    wallet_db: &DbT,
+    params: &ParamsT,
+    spend_from_account: <DbT as InputSource>::AccountId,
+    spend_pools: &[ShieldedProtocol],
+    fee_rule: &FeeRuleT,
+    recipient: ZcashAddress,
+    memo: Option<MemoBytes>,
+    mode: MaxSpendMode,
+    min_confirmations: NonZeroU32,
+) -> Result<
+    Proposal<FeeRuleT, <DbT as InputSource>::NoteRef>,
+    ProposeSendMaxErrT<DbT, CommitmentTreeErrT, FeeRuleT>,
+>
+where
+    DbT: WalletRead + InputSource<Error = <DbT as WalletRead>::Error>,
+    <DbT as InputSource>::NoteRef: Copy + Eq + Ord,
+    ParamsT: consensus::Parameters,
+    FeeRuleT: Zip317FeeRule + Clone,
+{
+    let (target_height, anchor_height) = wallet_db
+        .get_target_and_anchor_heights(min_confirmations)
+        .map_err(Error::DataSource)?
+        .ok_or(Error::ScanRequired)?;
+
+    let recipient_address: Address = recipient
+        .clone()
+        .convert_if_network(params.network_type())?;
+    let payment_pool = match RecipientPool::for_address(&recipient_address) {
+        Some(RecipientPool::Transparent) => PoolType::TRANSPARENT,
+        Some(RecipientPool::Shielded(protocol)) => PoolType::Shielded(protocol),
+        // A payment to a TEX address cannot be made in a single step.
+        Some(RecipientPool::Tex) => return Err(Error::ProposalNotSupported),
+        None => {
+            return Err(match recipient_address {
+                Address::Unified(ua) => Error::NoSupportedReceivers(Box::new(ua)),
+                _ => unreachable!("only unified addresses can lack a supported receiver"),
+            })
+        }
+    };
+
+    // Every note is selected regardless of `mode`, so that uneconomic notes are identified
+    // using the marginal fee of `fee_rule`, as is done by `fees::estimate::max_sendable`.
+    let notes = wallet_db
+        .select_spendable_notes(
+            spend_from_account,
+            TargetValue::AllFunds(MaxSpendMode::Everything),
+            spend_pools,
+            anchor_height,
+            &[],
+        )
+        .map_err(Error::DataSource)?
+        .into_vec(&SimpleNoteRetention {
+            sapling: true,
+            #[cfg(feature = "orchard")]
+            orchard: true,
+        });
+
+    // Notes that cannot pay the marginal fee for their own inclusion would be stranded.
+    let (notes, dust): (Vec<_>, Vec<_>) = notes
+        .into_iter()
+        .partition(|n| n.note().value() > fee_rule.marginal_fee());
+    if mode == MaxSpendMode::Everything && !dust.is_empty() {
+        let dust_ids = |protocol| {
+            dust.iter()
+                .filter(|n| n.note().protocol() == protocol)
+                .map(|n| *n.internal_note_id())
+                .collect::<Vec<_>>()
+        };
+        return Err(Error::Change(ChangeError::DustInputs {
+            transparent: vec![],
+            sapling: dust_ids(ShieldedProtocol::Sapling),
+            #[cfg(feature = "orchard")]
+            orchard: dust_ids(ShieldedProtocol::Orchard),
+        }));
+    }
+
+    let input_count = |protocol| {
+        notes
+            .iter()
+            .filter(|n| n.note().protocol() == protocol)
+            .count()
+    };
+    let sapling_input_count = input_count(ShieldedProtocol::Sapling);
+    #[cfg(feature = "orchard")]
+    let orchard_input_count = input_count(ShieldedProtocol::Orchard);
+
+    let transparent_output_sizes = match &recipient_address {
+        Address::Transparent(addr) => Some(*addr),
+        Address::Unified(ua) if payment_pool == PoolType::TRANSPARENT => ua.transparent().copied(),
+        _ => None,
+    }
+    .map(|addr| {
+        TxOut {
+            value: Zatoshis::ZERO,
+            script_pubkey: addr.script(),
+        }
+        .serialized_size()
+    });
+
+    let sapling_bundle_type = ::sapling::builder::BundleType::DEFAULT;
+    let sapling_spend_count = sapling_bundle_type
+        .num_spends(sapling_input_count)
+        .map_err(|e| Error::Change(ChangeError::BundleError(e)))?;
+    let sapling_output_count = sapling_bundle_type
+        .num_outputs(
+            sapling_input_count,
+            usize::from(payment_pool == PoolType::SAPLING),
+        )
+        .map_err(|e| Error::Change(ChangeError::BundleError(e)))?;
+    #[cfg(feature = "orchard")]
+    let orchard_action_count = ::orchard::builder::BundleType::DEFAULT
+        .num_actions(
+            orchard_input_count,
+            usize::from(payment_pool == PoolType::ORCHARD),
+        )
+        .map_err(|e| Error::Change(ChangeError::BundleError(e)))?;
+    #[cfg(not(feature = "orchard"))]
+    let orchard_action_count = 0;
+
+    let fee = fee_rule
+        .fee_required(
+            params,
+            target_height,
+            std::iter::empty::<InputSize>(),
+            transparent_output_sizes,
+            sapling_spend_count,
+            sapling_output_count,
+            orchard_action_count,
+        )
+        .map_err(|e| Error::Change(ChangeError::StrategyError(e)))?;
+
+    let available = notes
+        .iter()
+        .map(|n| n.note().value())
+        .sum::<Option<Zatoshis>>()
+        .ok_or(Error::BalanceError(BalanceError::Overflow))?;
+    let value = (available - fee)
+        .filter(|value| value.is_positive())
+        .ok_or(Error::InsufficientFunds {
+            available,
+            required: fee,
+        })?;
+
+    let request = zip321::TransactionRequest::new(vec![Payment::new(
+        recipient,
+        value,
+        memo,
+        None,
+        None,
+        vec![],
+    )
+    .ok_or(Error::MemoForbidden)?])
+    .expect(
+        "It should not be possible for this to violate ZIP 321 request construction invariants.",
+    );
+    let balance = TransactionBalance::new(vec![], fee)
+        .map_err(|_| Error::BalanceError(BalanceError::Overflow))?;
+
+    Proposal::single_step(
+        request,
+        BTreeMap::from([(0, payment_pool)]),
+        vec![],
+        NonEmpty::from_vec(notes).map(|notes| ShieldedInputs::from_parts(anchor_height, notes)),
+        balance,
+        fee_rule.clone(),
+        target_height,
+        false,
+    )
+    .map_err(Error::Proposal)
+}
+
 /// Constructs a proposal to shield all of the funds belonging to the provided set of
 /// addresses.
 #[cfg(feature = "transparent-inputs")]
@@ -8,7 +8,10 @@ use zcash_primitives::{
 };
 use zcash_protocol::{
     consensus::{self, BlockHeight, NetworkUpgrade},
-    memo::MemoBytes,
+    memo::{
+        structured::{self, Chunk, MessageId, StructuredMemo},
+        Memo, MemoBytes,
+    },
     value::Zatoshis,
 };
 use zip32::Scope;
@@ -101,6 +104,40 @@ impl<A> DecryptedOutput<orchard::note::Note, A> {
     }
 }
 
+/// Groups the structured memo chunks among the given memos by receiving account and
+/// message, and reassembles each message for which all chunks are present.
+///
+/// Chunks are only combined with other chunks decrypted by the same account, so that one
+/// account's chunks cannot be used to complete another account's message. Messages that
+/// are incomplete or fail to decode are skipped.
+pub(crate) fn reassemble_structured_memos<'m, AccountId: Copy + PartialEq>(
+    memos: impl Iterator<Item = (AccountId, &'m MemoBytes)>,
+) -> Vec<(AccountId, MessageId, StructuredMemo)> {
+    let mut messages: Vec<(AccountId, MessageId, Vec<Chunk>)> = vec![];
+    for (account, memo) in memos {
+        let chunk = match Memo::try_from(memo).ok().and_then(|m| Chunk::from_memo(&m)) {
+            Some(chunk) => chunk,
+            None => continue,
+        };
+        match messages
+            .iter_mut()
+            .find(|(a, id, _)| *a == account && *id == chunk.message_id())
+        {
+            Some((_, _, chunks)) => chunks.push(chunk),
+            None => messages.push((account, chunk.message_id(), vec![chunk])),
+        }
+    }
+
+    messages
+        .into_iter()
+        .filter_map(|(account, message_id, chunks)| {
+            structured::reassemble(chunks)
+                .ok()
+                .map(|memo| (account, message_id, memo))
+        })
+        .collect()
+}
+
 /// Scans a [`Transaction`] for any information that can be decrypted by the set of
 /// [`UnifiedFullViewingKey`]s.
 ///
@@ -114,6 +151,9 @@ impl<A> DecryptedOutput<orchard::note::Note, A> {
 /// - `tx`: The transaction to decrypt.
 /// - `ufvks`: The [`UnifiedFullViewingKey`]s to use in trial decryption, keyed
 ///   by the identifiers for the wallet accounts they correspond to.
+///
+/// Structured memos that were split across multiple decrypted outputs are reassembled
+/// and made available via [`DecryptedTransaction::structured_memos`].
 pub fn decrypt_transaction<'a, P: consensus::Parameters, AccountId: Copy>(
     params: &P,
     mined_height: Option<BlockHeight>,
@@ -247,3 +287,41 @@ pub fn decrypt_transaction<'a, P: consensus::Parameters, AccountId: Copy>(
         orchard_outputs,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use zcash_protocol::memo::{
+        structured::{MessageId, StructuredMemo},
+        MemoBytes,
+    };
+
+    use super::reassemble_structured_memos;
+
+    #[test]
+    fn structured_memos_are_grouped_by_account() {
+        let message_id = MessageId::from_bytes([3; 8]);
+        let memo = StructuredMemo::new().with_text("a".repeat(600));
+        let chunks = memo
+            .to_memos(message_id)
+            .unwrap()
+            .into_iter()
+            .map(MemoBytes::from)
+            .collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 2);
+
+        // Each account has decrypted only one of the chunks, so neither can reassemble
+        // the message.
+        assert_eq!(
+            reassemble_structured_memos([(0u32, &chunks[0]), (1u32, &chunks[1])].into_iter()),
+            vec![]
+        );
+
+        // An account that decrypted every chunk reassembles the message.
+        assert_eq!(
+            reassemble_structured_memos(
+                [(0u32, &chunks[0]), (1u32, &chunks[1]), (1u32, &chunks[0])].into_iter()
+            ),
+            vec![(1, message_id, memo)]
+        );
+    }
+}
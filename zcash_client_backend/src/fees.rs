@@ -19,6 +19,7 @@ use zcash_protocol::{
 use crate::data_api::InputSource;
 
 pub mod common;
+pub mod estimate;
 #[cfg(feature = "non-standard-fees")]
 pub mod fixed;
 #[cfg(feature = "orchard")]
@@ -0,0 +1,732 @@
+//! Estimation of ZIP 317 fees without constructing a transaction proposal.
+//!
+//! The functions in this module compute the fee that would be required by a transaction
+//! (or sequence of transactions, in the case of payments to [ZIP 320] TEX addresses)
+//! constructed by the [`GreedyInputSelector`] together with a ZIP 317 change strategy
+//! that produces a single change output. They are intended for use in user interfaces
+//! that need to display an expected fee before the details of a payment are known, and
+//! are substantially cheaper than constructing a throwaway [`Proposal`].
+//!
+//! The estimates produced here assume that all transparent inputs and outputs are of the
+//! standard P2PKH size, and do not take change splitting into account; the fee of a
+//! proposal constructed for the same payment may therefore differ from the estimate.
+//!
+//! [ZIP 320]: https://zips.z.cash/zip-0320
+//! [`GreedyInputSelector`]: crate::data_api::wallet::input_selection::GreedyInputSelector
+//! [`Proposal`]: crate::proposal::Proposal
+
+use std::{
+    cmp::max,
+    convert::Infallible,
+    fmt::{self, Debug, Display},
+};
+
+use nonempty::NonEmpty;
+use zcash_keys::address::Address;
+use zcash_protocol::{
+    consensus::BlockHeight,
+    value::{BalanceError, Zatoshis},
+    PoolType, ShieldedProtocol,
+};
+
+use crate::data_api::{InputSource, MaxSpendMode, NoteFilter, SpendableNotes, TargetValue};
+
+use super::{zip317::Zip317FeeRule, DustAction, DustOutputPolicy};
+
+/// Errors that can occur in fee estimation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EstimateError<DbErrT> {
+    /// An error occurred retrieving data from the wallet's input source.
+    DataSource(DbErrT),
+    /// A value computed during estimation was outside the valid range of zatoshi values.
+    Balance(BalanceError),
+    /// The transaction structure implied by the estimate would violate bundle type
+    /// construction rules.
+    BundleError(&'static str),
+    /// A payment to a TEX address was requested, but the `transparent-inputs` feature is
+    /// not enabled.
+    UnsupportedTexAddress,
+    /// The wallet does not contain sufficient spendable funds to pay for the requested
+    /// outputs and the required fees.
+    InsufficientFunds {
+        /// The total value of spendable notes that were available.
+        available: Zatoshis,
+        /// The total value required to pay for the requested outputs and fees.
+        required: Zatoshis,
+    },
+}
+
+impl<DbErrT: Display> Display for EstimateError<DbErrT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EstimateError::DataSource(e) => {
+                write!(f, "The underlying datasource produced an error: {e}")
+            }
+            EstimateError::Balance(e) => write!(
+                f,
+                "The value lies outside the valid range of Zcash amounts: {e:?}."
+            ),
+            EstimateError::BundleError(e) => write!(
+                f,
+                "The estimated transaction structure violates bundle type constraints: {e}"
+            ),
+            EstimateError::UnsupportedTexAddress => write!(
+                f,
+                "Support for transparent-source-only (TEX) addresses requires the transparent-inputs feature."
+            ),
+            EstimateError::InsufficientFunds {
+                available,
+                required,
+            } => write!(
+                f,
+                "Insufficient balance (have {}, need {} including fee)",
+                u64::from(*available),
+                u64::from(*required)
+            ),
+        }
+    }
+}
+
+impl<DbErrT> std::error::Error for EstimateError<DbErrT>
+where
+    DbErrT: Debug + Display + std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EstimateError::DataSource(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<DbErrT> From<BalanceError> for EstimateError<DbErrT> {
+    fn from(e: BalanceError) -> Self {
+        EstimateError::Balance(e)
+    }
+}
+
+/// The pool to which a payment will be sent, for the purpose of fee estimation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecipientPool {
+    /// A payment to a transparent address.
+    Transparent,
+    /// A payment to a [ZIP 320] TEX address, which requires an additional transaction
+    /// that spends from an ephemeral transparent output.
+    ///
+    /// [ZIP 320]: https://zips.z.cash/zip-0320
+    Tex,
+    /// A payment to the given shielded pool.
+    Shielded(ShieldedProtocol),
+}
+
+impl RecipientPool {
+    /// Returns the pool that a payment to the given address would be sent to by the
+    /// [`GreedyInputSelector`], or `None` if the address has no receiver that the wallet
+    /// can send to.
+    ///
+    /// [`GreedyInputSelector`]: crate::data_api::wallet::input_selection::GreedyInputSelector
+    pub fn for_address(addr: &Address) -> Option<Self> {
+        match addr {
+            Address::Sapling(_) => Some(RecipientPool::Shielded(ShieldedProtocol::Sapling)),
+            Address::Transparent(_) => Some(RecipientPool::Transparent),
+            Address::Tex(_) => Some(RecipientPool::Tex),
+            Address::Unified(ua) => {
+                #[cfg(feature = "orchard")]
+                if ua.has_orchard() {
+                    return Some(RecipientPool::Shielded(ShieldedProtocol::Orchard));
+                }
+
+                if ua.has_sapling() {
+                    Some(RecipientPool::Shielded(ShieldedProtocol::Sapling))
+                } else {
+                    ua.transparent().map(|_| RecipientPool::Transparent)
+                }
+            }
+        }
+    }
+}
+
+impl From<PoolType> for RecipientPool {
+    fn from(pool: PoolType) -> Self {
+        match pool {
+            PoolType::Transparent => RecipientPool::Transparent,
+            PoolType::Shielded(protocol) => RecipientPool::Shielded(protocol),
+        }
+    }
+}
+
+/// The fee required by a single transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StepFee {
+    fee: Zatoshis,
+    logical_actions: usize,
+}
+
+impl StepFee {
+    /// Returns the fee required by the transaction.
+    pub fn fee(&self) -> Zatoshis {
+        self.fee
+    }
+
+    /// Returns the number of [ZIP 317] logical actions in the transaction, including any
+    /// actions that result from padding of shielded bundles.
+    ///
+    /// [ZIP 317]: https://zips.z.cash/zip-0317
+    pub fn logical_actions(&self) -> usize {
+        self.logical_actions
+    }
+}
+
+/// An estimate of the fees required to make a payment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeeEstimate {
+    steps: NonEmpty<StepFee>,
+    fee: Zatoshis,
+    sapling_input_count: usize,
+    orchard_input_count: usize,
+}
+
+impl FeeEstimate {
+    fn new(
+        steps: NonEmpty<StepFee>,
+        sapling_input_count: usize,
+        orchard_input_count: usize,
+    ) -> Result<Self, BalanceError> {
+        let fee = steps
+            .iter()
+            .map(|step| step.fee)
+            .sum::<Option<Zatoshis>>()
+            .ok_or(BalanceError::Overflow)?;
+
+        Ok(Self {
+            steps,
+            fee,
+            sapling_input_count,
+            orchard_input_count,
+        })
+    }
+
+    /// Returns the total fee required across all of the transactions that would be
+    /// created to make the payment.
+    pub fn fee(&self) -> Zatoshis {
+        self.fee
+    }
+
+    /// Returns the total number of [ZIP 317] logical actions across all of the
+    /// transactions that would be created to make the payment.
+    ///
+    /// [ZIP 317]: https://zips.z.cash/zip-0317
+    pub fn logical_actions(&self) -> usize {
+        self.steps.iter().map(|step| step.logical_actions).sum()
+    }
+
+    /// Returns the estimated fee for each of the transactions that would be created to
+    /// make the payment.
+    ///
+    /// This will contain two steps if the payment includes outputs to [ZIP 320] TEX
+    /// addresses, the second of which spends the ephemeral output created by the first.
+    ///
+    /// [ZIP 320]: https://zips.z.cash/zip-0320
+    pub fn steps(&self) -> &NonEmpty<StepFee> {
+        &self.steps
+    }
+
+    /// Returns the number of Sapling notes that the estimate assumes will be spent.
+    pub fn sapling_input_count(&self) -> usize {
+        self.sapling_input_count
+    }
+
+    /// Returns the number of Orchard notes that the estimate assumes will be spent.
+    pub fn orchard_input_count(&self) -> usize {
+        self.orchard_input_count
+    }
+}
+
+/// The maximum value that can be sent from an account in a single payment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaxSendable {
+    value: Zatoshis,
+    fee: FeeEstimate,
+    uneconomic_value: Zatoshis,
+}
+
+impl MaxSendable {
+    /// Returns the maximum value that can be sent to the recipient.
+    pub fn value(&self) -> Zatoshis {
+        self.value
+    }
+
+    /// Returns the fee estimate for a payment of [`Self::value`].
+    pub fn fee(&self) -> &FeeEstimate {
+        &self.fee
+    }
+
+    /// Returns the total value of the notes that were excluded from the computation
+    /// because they would cost more in fees to spend than they are worth.
+    pub fn uneconomic_value(&self) -> Zatoshis {
+        self.uneconomic_value
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct OutputCounts {
+    transparent: usize,
+    tex: usize,
+    sapling: usize,
+    orchard: usize,
+}
+
+impl OutputCounts {
+    fn new(recipients: &[RecipientPool]) -> Self {
+        let mut counts = Self::default();
+        for recipient in recipients {
+            match recipient {
+                RecipientPool::Transparent => counts.transparent += 1,
+                RecipientPool::Tex => counts.tex += 1,
+                RecipientPool::Shielded(ShieldedProtocol::Sapling) => counts.sapling += 1,
+                RecipientPool::Shielded(ShieldedProtocol::Orchard) => counts.orchard += 1,
+            }
+        }
+        counts
+    }
+}
+
+fn step_fee<R: Zip317FeeRule, E>(
+    fee_rule: &R,
+    transparent_inputs: usize,
+    transparent_outputs: usize,
+    sapling_inputs: usize,
+    sapling_outputs: usize,
+    orchard_inputs: usize,
+    orchard_outputs: usize,
+) -> Result<StepFee, EstimateError<E>> {
+    let sapling_bundle = ::sapling::builder::BundleType::DEFAULT;
+    let sapling_spend_count = sapling_bundle
+        .num_spends(sapling_inputs)
+        .map_err(EstimateError::BundleError)?;
+    let sapling_output_count = sapling_bundle
+        .num_outputs(sapling_inputs, sapling_outputs)
+        .map_err(EstimateError::BundleError)?;
+
+    #[cfg(feature = "orchard")]
+    let orchard_action_count = ::orchard::builder::BundleType::DEFAULT
+        .num_actions(orchard_inputs, orchard_outputs)
+        .map_err(EstimateError::BundleError)?;
+    #[cfg(not(feature = "orchard"))]
+    let orchard_action_count = if orchard_inputs == 0 && orchard_outputs == 0 {
+        0
+    } else {
+        return Err(EstimateError::BundleError(
+            "Orchard inputs or outputs requested but the `orchard` feature is not enabled.",
+        ));
+    };
+
+    // All transparent inputs and outputs are assumed to be of the standard P2PKH size, so
+    // each contributes exactly one logical action.
+    let logical_actions = max(transparent_inputs, transparent_outputs)
+        + max(sapling_spend_count, sapling_output_count)
+        + orchard_action_count;
+
+    let fee = (fee_rule.marginal_fee() * max(fee_rule.grace_actions(), logical_actions))
+        .ok_or(BalanceError::Overflow)?;
+
+    Ok(StepFee {
+        fee,
+        logical_actions,
+    })
+}
+
+fn estimate_for_inputs<R: Zip317FeeRule, E>(
+    fee_rule: &R,
+    outputs: &OutputCounts,
+    sapling_input_count: usize,
+    orchard_input_count: usize,
+    with_change: bool,
+) -> Result<FeeEstimate, EstimateError<E>> {
+    // Payments to TEX addresses are made from a second transaction that spends a single
+    // ephemeral P2PKH output created by the first.
+    let tex_step = if outputs.tex == 0 {
+        None
+    } else if cfg!(feature = "transparent-inputs") {
+        Some(step_fee(fee_rule, 1, outputs.tex, 0, 0, 0, 0)?)
+    } else {
+        return Err(EstimateError::UnsupportedTexAddress);
+    };
+
+    // Change is sent to the same pool as `select_change_pool` would choose.
+    let change_to_orchard = cfg!(feature = "orchard")
+        && (orchard_input_count > 0
+            || outputs.orchard > 0
+            || (sapling_input_count == 0 && outputs.sapling == 0));
+    let change_count = usize::from(with_change);
+    let (sapling_change, orchard_change) = if change_to_orchard {
+        (0, change_count)
+    } else {
+        (change_count, 0)
+    };
+
+    let first_step = step_fee(
+        fee_rule,
+        0,
+        outputs.transparent + usize::from(tex_step.is_some()),
+        sapling_input_count,
+        outputs.sapling + sapling_change,
+        orchard_input_count,
+        outputs.orchard + orchard_change,
+    )?;
+
+    Ok(FeeEstimate::new(
+        NonEmpty {
+            head: first_step,
+            tail: tex_step.into_iter().collect(),
+        },
+        sapling_input_count,
+        orchard_input_count,
+    )?)
+}
+
+/// Computes the fee that would be required to pay the given recipients from a transaction
+/// spending the specified numbers of Sapling and Orchard notes.
+///
+/// If `with_change` is `true`, the estimate includes a single shielded change output.
+pub fn estimate_fee_for_inputs<R: Zip317FeeRule>(
+    fee_rule: &R,
+    recipients: &[RecipientPool],
+    sapling_input_count: usize,
+    orchard_input_count: usize,
+    with_change: bool,
+) -> Result<FeeEstimate, EstimateError<Infallible>> {
+    estimate_for_inputs(
+        fee_rule,
+        &OutputCounts::new(recipients),
+        sapling_input_count,
+        orchard_input_count,
+        with_change,
+    )
+}
+
+fn selectable_pools() -> &'static [ShieldedProtocol] {
+    #[cfg(not(feature = "orchard"))]
+    return &[ShieldedProtocol::Sapling];
+
+    #[cfg(feature = "orchard")]
+    return &[ShieldedProtocol::Sapling, ShieldedProtocol::Orchard];
+}
+
+/// Returns the identifiers and values of the Sapling and Orchard notes in the given set.
+#[allow(clippy::type_complexity)]
+fn note_values<NoteRef: Copy>(
+    notes: &SpendableNotes<NoteRef>,
+) -> Result<(Vec<(NoteRef, Zatoshis)>, Vec<(NoteRef, Zatoshis)>), BalanceError> {
+    let sapling = notes
+        .sapling()
+        .iter()
+        .map(|n| Ok((*n.internal_note_id(), n.note_value()?)))
+        .collect::<Result<Vec<_>, BalanceError>>()?;
+
+    #[cfg(feature = "orchard")]
+    let orchard = notes
+        .orchard()
+        .iter()
+        .map(|n| Ok((*n.internal_note_id(), n.note_value()?)))
+        .collect::<Result<Vec<_>, BalanceError>>()?;
+    #[cfg(not(feature = "orchard"))]
+    let orchard = vec![];
+
+    Ok((sapling, orchard))
+}
+
+fn total_value<NoteRef>(notes: &[(NoteRef, Zatoshis)]) -> Result<Zatoshis, BalanceError> {
+    notes
+        .iter()
+        .map(|(_, value)| *value)
+        .sum::<Option<Zatoshis>>()
+        .ok_or(BalanceError::Overflow)
+}
+
+/// Estimates the fee required to send `amount` from the given account to the given
+/// recipients, using a single change output.
+///
+/// If `amount` is provided, the wallet's spendable notes are selected in the same fashion
+/// as by the [`GreedyInputSelector`] in order to determine the number of inputs required.
+/// If `amount` is `None`, the estimate assumes that a single note will be spent, chosen
+/// from the pool that the account's metadata indicates would be preferred; this is the
+/// minimum fee that a payment to the given recipients can incur.
+///
+/// Notes with value less than or equal to the marginal fee are never selected, as they
+/// would cost more to spend than they are worth.
+///
+/// [`GreedyInputSelector`]: crate::data_api::wallet::input_selection::GreedyInputSelector
+pub fn estimate_fee<DbT, R>(
+    wallet_db: &DbT,
+    fee_rule: &R,
+    account: DbT::AccountId,
+    anchor_height: BlockHeight,
+    recipients: &[RecipientPool],
+    amount: Option<Zatoshis>,
+) -> Result<FeeEstimate, EstimateError<DbT::Error>>
+where
+    DbT: InputSource,
+    R: Zip317FeeRule,
+{
+    let outputs = OutputCounts::new(recipients);
+
+    let amount = match amount {
+        Some(amount) => amount,
+        None => {
+            let meta = wallet_db
+                .get_account_metadata(
+                    account,
+                    &NoteFilter::ExceedsMinValue(fee_rule.marginal_fee()),
+                    &[],
+                )
+                .map_err(EstimateError::DataSource)?;
+            let has_notes = |protocol| meta.note_count(protocol).is_some_and(|n| n > 0);
+
+            let use_orchard = cfg!(feature = "orchard")
+                && ((outputs.orchard > 0 && has_notes(ShieldedProtocol::Orchard))
+                    || !has_notes(ShieldedProtocol::Sapling));
+
+            return if use_orchard {
+                estimate_for_inputs(fee_rule, &outputs, 0, 1, true)
+            } else {
+                estimate_for_inputs(fee_rule, &outputs, 1, 0, true)
+            };
+        }
+    };
+
+    let mut exclude: Vec<DbT::NoteRef> = vec![];
+    let mut prior_available = Zatoshis::ZERO;
+    let mut amount_required = amount;
+
+    // This loop terminates because on each iteration either the set of excluded notes
+    // strictly grows, or the value of the selected notes strictly increases.
+    loop {
+        let notes = wallet_db
+            .select_spendable_notes(
+                account,
+                TargetValue::AtLeast(amount_required),
+                selectable_pools(),
+                anchor_height,
+                &exclude,
+            )
+            .map_err(EstimateError::DataSource)?;
+        let (sapling, orchard) = note_values(&notes)?;
+
+        let uneconomic = sapling
+            .iter()
+            .chain(orchard.iter())
+            .filter(|(_, value)| *value <= fee_rule.marginal_fee())
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        if !uneconomic.is_empty() {
+            exclude.extend(uneconomic);
+            continue;
+        }
+
+        let sapling_value = total_value(&sapling)?;
+        let orchard_value = total_value(&orchard)?;
+
+        // Mirror the pool preferences of the greedy input selector.
+        let use_sapling = outputs.orchard == 0 || amount_required > orchard_value;
+        let use_orchard = !use_sapling || amount_required > sapling_value;
+
+        let (sapling_count, sapling_value) = if use_sapling {
+            (sapling.len(), sapling_value)
+        } else {
+            (0, Zatoshis::ZERO)
+        };
+        let (orchard_count, orchard_value) = if use_orchard {
+            (orchard.len(), orchard_value)
+        } else {
+            (0, Zatoshis::ZERO)
+        };
+
+        let estimate = estimate_for_inputs(fee_rule, &outputs, sapling_count, orchard_count, true)?;
+        let available = (sapling_value + orchard_value).ok_or(BalanceError::Overflow)?;
+        let required = (amount + estimate.fee()).ok_or(BalanceError::Overflow)?;
+
+        if available >= required {
+            return Ok(estimate);
+        }
+
+        let selected = notes.total_value()?;
+        if selected <= prior_available {
+            return Err(EstimateError::InsufficientFunds {
+                available: selected,
+                required,
+            });
+        }
+        prior_available = selected;
+        amount_required = required;
+    }
+}
+
+/// Computes the maximum value that can be sent from the given account to a single
+/// recipient, spending all of the account's spendable notes in the given pools without
+/// producing change.
+///
+/// Notes with value less than or equal to the marginal fee are excluded, as they would
+/// cost more to spend than they are worth; their total value is reported by
+/// [`MaxSendable::uneconomic_value`].
+///
+/// If the resulting value would be less than the dust threshold of the given
+/// [`DustOutputPolicy`] (which defaults to the marginal fee) and the policy's action is
+/// [`DustAction::Reject`], this returns [`EstimateError::InsufficientFunds`].
+pub fn max_sendable<DbT, R>(
+    wallet_db: &DbT,
+    fee_rule: &R,
+    account: DbT::AccountId,
+    anchor_height: BlockHeight,
+    recipient: RecipientPool,
+    sources: &[ShieldedProtocol],
+    dust_output_policy: &DustOutputPolicy,
+) -> Result<MaxSendable, EstimateError<DbT::Error>>
+where
+    DbT: InputSource,
+    R: Zip317FeeRule,
+{
+    // Uneconomic notes are selected too, so that their value can be reported.
+    let notes = wallet_db
+        .select_spendable_notes(
+            account,
+            TargetValue::AllFunds(MaxSpendMode::Everything),
+            sources,
+            anchor_height,
+            &[],
+        )
+        .map_err(EstimateError::DataSource)?;
+    let (sapling, orchard) = note_values(&notes)?;
+
+    let is_economic = |(_, value): &(DbT::NoteRef, Zatoshis)| *value > fee_rule.marginal_fee();
+    let (sapling, sapling_uneconomic): (Vec<_>, Vec<_>) =
+        sapling.into_iter().partition(is_economic);
+    let (orchard, orchard_uneconomic): (Vec<_>, Vec<_>) =
+        orchard.into_iter().partition(is_economic);
+
+    let available =
+        (total_value(&sapling)? + total_value(&orchard)?).ok_or(BalanceError::Overflow)?;
+    let uneconomic_value = (total_value(&sapling_uneconomic)? + total_value(&orchard_uneconomic)?)
+        .ok_or(BalanceError::Overflow)?;
+
+    let fee = estimate_for_inputs(
+        fee_rule,
+        &OutputCounts::new(&[recipient]),
+        sapling.len(),
+        orchard.len(),
+        false,
+    )?;
+
+    let dust_threshold = dust_output_policy
+        .dust_threshold()
+        .unwrap_or(fee_rule.marginal_fee());
+    let min_value = match dust_output_policy.action() {
+        DustAction::Reject => max(dust_threshold, Zatoshis::const_from_u64(1)),
+        DustAction::AllowDustChange | DustAction::AddDustToFee => Zatoshis::const_from_u64(1),
+    };
+
+    match (available - fee.fee()).filter(|value| *value >= min_value) {
+        Some(value) => Ok(MaxSendable {
+            value,
+            fee,
+            uneconomic_value,
+        }),
+        None => Err(EstimateError::InsufficientFunds {
+            available,
+            required: (fee.fee() + min_value).ok_or(BalanceError::Overflow)?,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use zcash_primitives::transaction::fees::zip317::FeeRule as Zip317FeeRule;
+    use zcash_protocol::{
+        consensus::{BlockHeight, Network},
+        value::Zatoshis,
+        ShieldedProtocol,
+    };
+
+    use super::{estimate_fee, estimate_fee_for_inputs, EstimateError, RecipientPool};
+    use crate::data_api::testing::MockWalletDb;
+
+    const SAPLING: RecipientPool = RecipientPool::Shielded(ShieldedProtocol::Sapling);
+
+    #[test]
+    fn sapling_to_sapling() {
+        let fee_rule = Zip317FeeRule::standard();
+
+        // One spend, one payment and one change output fit within the grace actions.
+        let estimate = estimate_fee_for_inputs(&fee_rule, &[SAPLING], 1, 0, true).unwrap();
+        assert_eq!(estimate.fee(), Zatoshis::const_from_u64(10000));
+        assert_eq!(estimate.logical_actions(), 2);
+        assert_eq!(estimate.steps().len(), 1);
+
+        // Three spends require three logical actions.
+        let estimate = estimate_fee_for_inputs(&fee_rule, &[SAPLING], 3, 0, true).unwrap();
+        assert_eq!(estimate.fee(), Zatoshis::const_from_u64(15000));
+        assert_eq!(estimate.logical_actions(), 3);
+
+        // A single spend with no outputs is still padded to two outputs.
+        let estimate = estimate_fee_for_inputs(&fee_rule, &[], 1, 0, false).unwrap();
+        assert_eq!(estimate.logical_actions(), 2);
+    }
+
+    #[test]
+    fn sapling_to_transparent() {
+        let fee_rule = Zip317FeeRule::standard();
+
+        let estimate = estimate_fee_for_inputs(
+            &fee_rule,
+            &[RecipientPool::Transparent, RecipientPool::Transparent],
+            1,
+            0,
+            true,
+        )
+        .unwrap();
+        // Two transparent outputs, plus a Sapling spend and (padded) change output.
+        assert_eq!(estimate.logical_actions(), 4);
+        assert_eq!(estimate.fee(), Zatoshis::const_from_u64(20000));
+    }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn sapling_to_tex() {
+        let fee_rule = Zip317FeeRule::standard();
+
+        let estimate =
+            estimate_fee_for_inputs(&fee_rule, &[RecipientPool::Tex], 1, 0, true).unwrap();
+        assert_eq!(estimate.steps().len(), 2);
+        // The first transaction has an ephemeral transparent output, a Sapling spend and a
+        // (padded) change output.
+        assert_eq!(estimate.steps().head.logical_actions(), 3);
+        assert_eq!(estimate.steps().head.fee(), Zatoshis::const_from_u64(15000));
+        // The second transaction spends the ephemeral output to the TEX address.
+        assert_eq!(estimate.steps().tail[0].logical_actions(), 1);
+        assert_eq!(
+            estimate.steps().tail[0].fee(),
+            Zatoshis::const_from_u64(10000)
+        );
+        assert_eq!(estimate.fee(), Zatoshis::const_from_u64(25000));
+        assert_eq!(estimate.logical_actions(), 4);
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let result = estimate_fee(
+            &MockWalletDb::new(Network::TestNetwork),
+            &Zip317FeeRule::standard(),
+            0,
+            BlockHeight::from_u32(1),
+            &[SAPLING],
+            Some(Zatoshis::const_from_u64(50000)),
+        );
+        assert_eq!(
+            result,
+            Err(EstimateError::InsufficientFunds {
+                available: Zatoshis::ZERO,
+                required: Zatoshis::const_from_u64(60000),
+            })
+        );
+    }
+}
@@ -52,6 +52,9 @@ pub mod proposal;
 #[allow(clippy::derive_partial_eq_without_eq)]
 pub mod service;
 
+#[cfg(feature = "lightwalletd-tonic-server")]
+pub mod server;
+
 impl compact_formats::CompactBlock {
     /// Returns the [`BlockHash`] for this block.
     ///
@@ -120,6 +123,43 @@ impl compact_formats::CompactTx {
     }
 }
 
+impl compact_formats::CompactTxIn {
+    /// Returns the outpoint of the transparent output spent by this input.
+    ///
+    /// A convenience method that parses [`CompactTxIn.prevout_txid`] and
+    /// [`CompactTxIn.prevout_index`].
+    ///
+    /// [`CompactTxIn.prevout_txid`]: #structfield.prevout_txid
+    /// [`CompactTxIn.prevout_index`]: #structfield.prevout_index
+    pub fn prevout(&self) -> Result<transparent::bundle::OutPoint, ()> {
+        let hash = self.prevout_txid[..].try_into().map_err(|_| ())?;
+        Ok(transparent::bundle::OutPoint::new(hash, self.prevout_index))
+    }
+}
+
+impl compact_formats::TxOut {
+    /// Returns the transparent output represented by this value.
+    ///
+    /// Returns an error if [`TxOut.value`] exceeds the maximum money supply.
+    ///
+    /// [`TxOut.value`]: #structfield.value
+    pub fn to_txout(&self) -> Result<transparent::bundle::TxOut, ()> {
+        Ok(transparent::bundle::TxOut {
+            value: Zatoshis::from_u64(self.value).map_err(|_| ())?,
+            script_pubkey: transparent::address::Script(self.script_pub_key.clone()),
+        })
+    }
+}
+
+impl From<&transparent::bundle::TxOut> for compact_formats::TxOut {
+    fn from(txout: &transparent::bundle::TxOut) -> Self {
+        compact_formats::TxOut {
+            value: txout.value.into_u64(),
+            script_pub_key: txout.script_pubkey.0.clone(),
+        }
+    }
+}
+
 impl compact_formats::CompactSaplingOutput {
     /// Returns the note commitment for this output.
     ///
@@ -72,6 +72,40 @@ pub struct CompactTx {
     pub outputs: ::prost::alloc::vec::Vec<CompactSaplingOutput>,
     #[prost(message, repeated, tag = "6")]
     pub actions: ::prost::alloc::vec::Vec<CompactOrchardAction>,
+    /// `CompactTxIn` values corresponding to the `vin` entries of the full transaction.
+    ///
+    /// Note: the single null-outpoint input for coinbase transactions is omitted. Light
+    /// clients can test `CompactTx.index == 0` to determine whether a `CompactTx`
+    /// represents a coinbase transaction, as the coinbase transaction is always the
+    /// first transaction in any block.
+    #[prost(message, repeated, tag = "7")]
+    pub vin: ::prost::alloc::vec::Vec<CompactTxIn>,
+    /// A sequence of transparent outputs being created by the transaction.
+    #[prost(message, repeated, tag = "8")]
+    pub vout: ::prost::alloc::vec::Vec<TxOut>,
+}
+/// A compact representation of a transparent transaction input.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CompactTxIn {
+    /// The id of the transaction that generated the output being spent.
+    #[prost(bytes = "vec", tag = "1")]
+    pub prevout_txid: ::prost::alloc::vec::Vec<u8>,
+    /// The index of the output being spent in the `vout` array of that transaction.
+    #[prost(uint32, tag = "2")]
+    pub prevout_index: u32,
+}
+/// A transparent output being created by the transaction.
+///
+/// This contains identical data to the `TxOut` type in the transaction itself, and
+/// thus it is not "compact".
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxOut {
+    /// The value of the output, in Zatoshis.
+    #[prost(uint64, tag = "1")]
+    pub value: u64,
+    /// The script pubkey that must be satisfied in order to spend this output.
+    #[prost(bytes = "vec", tag = "2")]
+    pub script_pub_key: ::prost::alloc::vec::Vec<u8>,
 }
 /// A compact representation of a [Sapling Spend](<https://zips.z.cash/protocol/protocol.pdf#spendencodingandconsensus>).
 ///
@@ -0,0 +1,676 @@
+//! A `CompactTxStreamer` gRPC server backed by a local block store.
+//!
+//! [`LocalLightwalletd`] implements the subset of the `lightwalletd` protocol that light
+//! clients need in order to sync a wallet and submit transactions:
+//!
+//! - `GetLatestBlock`, `GetBlock`, `GetBlockNullifiers`, `GetBlockRange` and
+//!   `GetBlockRangeNullifiers` are served from a [`BlockSource`], such as an `FsBlockDb`.
+//! - `GetTreeState`, `GetLatestTreeState` and `GetSubtreeRoots` are served from tree
+//!   states and subtree roots provided with [`LocalLightwalletd::insert_tree_state`] and
+//!   [`LocalLightwalletd::insert_subtree_roots`].
+//! - `GetTransaction` and `SendTransaction` are served from an in-memory raw transaction
+//!   store, populated with [`LocalLightwalletd::insert_transaction`]. Sent transactions are
+//!   added to the store (as unmined) and are not relayed anywhere; they can be inspected
+//!   with [`LocalLightwalletd::sent_transactions`].
+//! - `GetLightdInfo` describes the configured network and chain tip.
+//!
+//! Transparent address indexing is not supported: `GetAddressUtxos` and
+//! `GetAddressUtxosStream` return no results, and the remaining methods return
+//! `Unimplemented`.
+//!
+//! Because the server is deterministic and runs in-process, it is suitable for
+//! integration tests of [`sync::run`]. A [`CompactTxStreamerServer`] can be used directly
+//! as the channel of a [`CompactTxStreamerClient`], without any network transport.
+//!
+//! [`BlockSource`]: crate::data_api::chain::BlockSource
+//! [`sync::run`]: crate::sync::run
+//! [`CompactTxStreamerServer`]: super::service::compact_tx_streamer_server::CompactTxStreamerServer
+//! [`CompactTxStreamerClient`]: super::service::compact_tx_streamer_client::CompactTxStreamerClient
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::sync::{Arc, Mutex};
+
+use tonic::{codegen::BoxStream, Request, Response, Status};
+
+use zcash_primitives::transaction::{Transaction, TxId};
+use zcash_protocol::{
+    consensus::{self, BlockHeight, BranchId},
+    ShieldedProtocol,
+};
+
+use crate::data_api::chain::BlockSource;
+
+use super::{
+    compact_formats::{CompactBlock, CompactTx},
+    service::{
+        self, compact_tx_streamer_server::CompactTxStreamer, BlockId, BlockRange, ChainSpec, Empty,
+        GetAddressUtxosArg, GetAddressUtxosReply, GetAddressUtxosReplyList, GetSubtreeRootsArg,
+        LightdInfo, RawTransaction, SendResponse, SubtreeRoot, TreeState, TxFilter,
+    },
+};
+
+/// An implementation of the `CompactTxStreamer` service backed by local data.
+pub struct LocalLightwalletd<P, BlockSourceT> {
+    params: P,
+    blocks: Arc<Mutex<BlockSourceT>>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    chain_tip: Option<BlockHeight>,
+    tree_states: BTreeMap<BlockHeight, TreeState>,
+    sapling_subtree_roots: Vec<SubtreeRoot>,
+    orchard_subtree_roots: Vec<SubtreeRoot>,
+    transactions: HashMap<TxId, RawTransaction>,
+    sent: Vec<TxId>,
+}
+
+impl<P, BlockSourceT> LocalLightwalletd<P, BlockSourceT>
+where
+    P: consensus::Parameters,
+    BlockSourceT: BlockSource,
+    BlockSourceT::Error: Display,
+{
+    /// Constructs a server for the given network that serves blocks from the given
+    /// source.
+    ///
+    /// The chain tip must be set with [`Self::set_chain_tip`] before blocks can be
+    /// served.
+    pub fn new(params: P, blocks: BlockSourceT) -> Self {
+        Self {
+            params,
+            blocks: Arc::new(Mutex::new(blocks)),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Sets the height of the chain tip.
+    ///
+    /// The block at this height must be available from the block source.
+    pub fn set_chain_tip(&self, height: BlockHeight) {
+        self.state.lock().unwrap().chain_tip = Some(height);
+    }
+
+    /// Adds the tree state for the block at `tree_state.height`.
+    pub fn insert_tree_state(&self, tree_state: TreeState) {
+        let height = BlockHeight::from(tree_state.height as u32);
+        self.state
+            .lock()
+            .unwrap()
+            .tree_states
+            .insert(height, tree_state);
+    }
+
+    /// Adds subtree roots for the given protocol, starting at subtree index
+    /// `start_index`.
+    ///
+    /// Any existing roots at the same indices are replaced.
+    pub fn insert_subtree_roots(
+        &self,
+        protocol: ShieldedProtocol,
+        start_index: u32,
+        roots: Vec<SubtreeRoot>,
+    ) {
+        let mut state = self.state.lock().unwrap();
+        let stored = match protocol {
+            ShieldedProtocol::Sapling => &mut state.sapling_subtree_roots,
+            ShieldedProtocol::Orchard => &mut state.orchard_subtree_roots,
+        };
+        let start_index = start_index as usize;
+        stored.truncate(start_index);
+        stored.resize(start_index, SubtreeRoot::default());
+        stored.extend(roots);
+    }
+
+    /// Adds a raw transaction to the transaction store.
+    ///
+    /// `tx.height` should be the height of the block in which the transaction was mined,
+    /// or zero if it is unmined.
+    pub fn insert_transaction(&self, txid: TxId, tx: RawTransaction) {
+        self.state.lock().unwrap().transactions.insert(txid, tx);
+    }
+
+    /// Returns the IDs of the transactions submitted via `SendTransaction`, in the order
+    /// in which they were received.
+    pub fn sent_transactions(&self) -> Vec<TxId> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    fn chain_tip(&self) -> Result<BlockHeight, Status> {
+        self.state
+            .lock()
+            .unwrap()
+            .chain_tip
+            .ok_or_else(|| Status::unavailable("Chain tip is not yet known"))
+    }
+
+    fn block_at(&self, height: BlockHeight) -> Result<CompactBlock, Status> {
+        read_block(&self.blocks, height)?
+            .ok_or_else(|| Status::not_found(format!("Block {} not found", height)))
+    }
+
+    /// Returns the heights of the blocks requested by `range`, in the order in which they
+    /// should be returned.
+    fn block_range_heights(
+        &self,
+        range: BlockRange,
+    ) -> Result<Box<dyn Iterator<Item = BlockHeight> + Send>, Status> {
+        let (start, end) = match (range.start, range.end) {
+            (Some(start), Some(end)) => Ok((start.height, end.height)),
+            _ => Err(Status::invalid_argument(
+                "Must specify start and end heights",
+            )),
+        }?;
+        let height =
+            |h: u64| u32::try_from(h).map_err(|_| Status::invalid_argument("Height out of range"));
+        let (start, end) = (height(start)?, height(end)?);
+        let chain_tip = u32::from(self.chain_tip()?);
+
+        // As with `lightwalletd`, a range with `start > end` is returned in descending
+        // order.
+        Ok(if start <= end {
+            Box::new((start..=end.min(chain_tip)).map(BlockHeight::from))
+        } else {
+            Box::new((end..=start.min(chain_tip)).rev().map(BlockHeight::from))
+        })
+    }
+
+    fn tree_state_at(&self, height: BlockHeight) -> Result<TreeState, Status> {
+        self.state
+            .lock()
+            .unwrap()
+            .tree_states
+            .get(&height)
+            .cloned()
+            .ok_or_else(|| Status::not_found(format!("No tree state for height {}", height)))
+    }
+}
+
+/// Strips a compact block down to its nullifiers.
+fn nullifiers_only(mut block: CompactBlock) -> CompactBlock {
+    for tx in &mut block.vtx {
+        let CompactTx {
+            outputs,
+            actions,
+            vout,
+            ..
+        } = tx;
+        outputs.clear();
+        vout.clear();
+        for action in actions {
+            action.cmx.clear();
+            action.ephemeral_key.clear();
+            action.ciphertext.clear();
+        }
+    }
+    block
+}
+
+/// Reads the block at the given height from the block source, if it is present.
+fn read_block<BlockSourceT>(
+    blocks: &Mutex<BlockSourceT>,
+    height: BlockHeight,
+) -> Result<Option<CompactBlock>, Status>
+where
+    BlockSourceT: BlockSource,
+    BlockSourceT::Error: Display,
+{
+    let mut found = None;
+    blocks
+        .lock()
+        .unwrap()
+        .with_blocks::<_, Infallible>(Some(height), Some(1), |block| {
+            found = Some(block);
+            Ok(())
+        })
+        .map_err(|e| Status::internal(format!("Error reading blocks: {}", e)))?;
+
+    Ok(found.filter(|block| block.height() == height))
+}
+
+/// Returns a stream that reads each of the blocks at the given heights from the block
+/// source as it is polled, so that only one block is held in memory at a time.
+///
+/// Heights for which the block source has no block are skipped.
+fn block_stream<BlockSourceT>(
+    blocks: Arc<Mutex<BlockSourceT>>,
+    heights: Box<dyn Iterator<Item = BlockHeight> + Send>,
+    f: fn(CompactBlock) -> CompactBlock,
+) -> Response<BoxStream<CompactBlock>>
+where
+    BlockSourceT: BlockSource + Send + 'static,
+    BlockSourceT::Error: Display,
+{
+    Response::new(Box::pin(tonic::codegen::tokio_stream::iter(
+        heights.filter_map(move |height| {
+            read_block(&blocks, height)
+                .transpose()
+                .map(|res| res.map(f))
+        }),
+    )))
+}
+
+fn stream<T: Send + 'static>(items: Vec<T>) -> Response<BoxStream<T>> {
+    Response::new(Box::pin(tonic::codegen::tokio_stream::iter(
+        items.into_iter().map(Ok),
+    )))
+}
+
+#[tonic::async_trait]
+impl<P, BlockSourceT> CompactTxStreamer for LocalLightwalletd<P, BlockSourceT>
+where
+    P: consensus::Parameters + Send + Sync + 'static,
+    BlockSourceT: BlockSource + Send + 'static,
+    BlockSourceT::Error: Display,
+{
+    type GetBlockRangeStream = BoxStream<CompactBlock>;
+    type GetBlockRangeNullifiersStream = BoxStream<CompactBlock>;
+    type GetTaddressTxidsStream = BoxStream<RawTransaction>;
+    type GetMempoolTxStream = BoxStream<CompactTx>;
+    type GetMempoolStreamStream = BoxStream<RawTransaction>;
+    type GetSubtreeRootsStream = BoxStream<SubtreeRoot>;
+    type GetAddressUtxosStreamStream = BoxStream<GetAddressUtxosReply>;
+
+    async fn get_latest_block(
+        &self,
+        _request: Request<ChainSpec>,
+    ) -> Result<Response<BlockId>, Status> {
+        let block = self.block_at(self.chain_tip()?)?;
+        Ok(Response::new(BlockId {
+            height: block.height,
+            hash: block.hash,
+        }))
+    }
+
+    async fn get_block(&self, request: Request<BlockId>) -> Result<Response<CompactBlock>, Status> {
+        let height = u32::try_from(request.into_inner().height)
+            .map_err(|_| Status::invalid_argument("Height out of range"))?;
+        self.block_at(height.into()).map(Response::new)
+    }
+
+    async fn get_block_nullifiers(
+        &self,
+        request: Request<BlockId>,
+    ) -> Result<Response<CompactBlock>, Status> {
+        self.get_block(request)
+            .await
+            .map(|response| response.map(nullifiers_only))
+    }
+
+    async fn get_block_range(
+        &self,
+        request: Request<BlockRange>,
+    ) -> Result<Response<Self::GetBlockRangeStream>, Status> {
+        self.block_range_heights(request.into_inner())
+            .map(|heights| block_stream(self.blocks.clone(), heights, |block| block))
+    }
+
+    async fn get_block_range_nullifiers(
+        &self,
+        request: Request<BlockRange>,
+    ) -> Result<Response<Self::GetBlockRangeNullifiersStream>, Status> {
+        self.block_range_heights(request.into_inner())
+            .map(|heights| block_stream(self.blocks.clone(), heights, nullifiers_only))
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<TxFilter>,
+    ) -> Result<Response<RawTransaction>, Status> {
+        let txid = <[u8; 32]>::try_from(request.into_inner().hash)
+            .map(TxId::from_bytes)
+            .map_err(|_| Status::invalid_argument("Transaction hash must be 32 bytes"))?;
+        self.state
+            .lock()
+            .unwrap()
+            .transactions
+            .get(&txid)
+            .cloned()
+            .map(Response::new)
+            .ok_or_else(|| Status::not_found(format!("Transaction {} not found", txid)))
+    }
+
+    async fn send_transaction(
+        &self,
+        request: Request<RawTransaction>,
+    ) -> Result<Response<SendResponse>, Status> {
+        let data = request.into_inner().data;
+        let branch_id = BranchId::for_height(&self.params, self.chain_tip()? + 1);
+        let tx = Transaction::read(&data[..], branch_id)
+            .map_err(|e| Status::invalid_argument(format!("Invalid transaction: {}", e)))?;
+
+        let mut state = self.state.lock().unwrap();
+        state
+            .transactions
+            .insert(tx.txid(), RawTransaction { data, height: 0 });
+        state.sent.push(tx.txid());
+
+        Ok(Response::new(SendResponse {
+            error_code: 0,
+            error_message: String::new(),
+        }))
+    }
+
+    async fn get_tree_state(
+        &self,
+        request: Request<BlockId>,
+    ) -> Result<Response<TreeState>, Status> {
+        let height = u32::try_from(request.into_inner().height)
+            .map_err(|_| Status::invalid_argument("Height out of range"))?;
+        self.tree_state_at(height.into()).map(Response::new)
+    }
+
+    async fn get_latest_tree_state(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<TreeState>, Status> {
+        self.tree_state_at(self.chain_tip()?).map(Response::new)
+    }
+
+    async fn get_subtree_roots(
+        &self,
+        request: Request<GetSubtreeRootsArg>,
+    ) -> Result<Response<Self::GetSubtreeRootsStream>, Status> {
+        let request = request.into_inner();
+        let state = self.state.lock().unwrap();
+        let roots = match service::ShieldedProtocol::try_from(request.shielded_protocol) {
+            Ok(service::ShieldedProtocol::Sapling) => &state.sapling_subtree_roots,
+            Ok(service::ShieldedProtocol::Orchard) => &state.orchard_subtree_roots,
+            Err(_) => return Err(Status::invalid_argument("Unknown shielded protocol")),
+        };
+
+        let roots = roots
+            .iter()
+            .skip(request.start_index as usize)
+            .take(match request.max_entries {
+                0 => usize::MAX,
+                n => n as usize,
+            })
+            .cloned()
+            .collect();
+        Ok(stream(roots))
+    }
+
+    async fn get_address_utxos(
+        &self,
+        _request: Request<GetAddressUtxosArg>,
+    ) -> Result<Response<GetAddressUtxosReplyList>, Status> {
+        Ok(Response::new(GetAddressUtxosReplyList::default()))
+    }
+
+    async fn get_address_utxos_stream(
+        &self,
+        _request: Request<GetAddressUtxosArg>,
+    ) -> Result<Response<Self::GetAddressUtxosStreamStream>, Status> {
+        Ok(stream(vec![]))
+    }
+
+    async fn get_lightd_info(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<LightdInfo>, Status> {
+        let chain_tip = self.chain_tip()?;
+        let chain_name = match self.params.network_type() {
+            consensus::NetworkType::Main => "main",
+            consensus::NetworkType::Test => "test",
+            consensus::NetworkType::Regtest => "regtest",
+        };
+        let sapling_activation_height = self
+            .params
+            .activation_height(consensus::NetworkUpgrade::Sapling)
+            .map_or(0, u64::from);
+        let consensus_branch_id = u32::from(BranchId::for_height(&self.params, chain_tip + 1));
+
+        Ok(Response::new(LightdInfo {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            vendor: "zcash_client_backend LocalLightwalletd".to_owned(),
+            taddr_support: false,
+            chain_name: chain_name.to_owned(),
+            sapling_activation_height,
+            consensus_branch_id: format!("{:x}", consensus_branch_id),
+            block_height: u64::from(chain_tip),
+            estimated_height: u64::from(chain_tip),
+            ..Default::default()
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use tonic::{codegen::tokio_stream::StreamExt, Request};
+
+    use zcash_protocol::{
+        consensus::{BlockHeight, Network},
+        ShieldedProtocol,
+    };
+
+    use super::{CompactTxStreamer, LocalLightwalletd};
+    use crate::{
+        data_api::chain::{error::Error, BlockSource},
+        proto::{
+            compact_formats::CompactBlock,
+            service::{
+                self, compact_tx_streamer_client::CompactTxStreamerClient,
+                compact_tx_streamer_server::CompactTxStreamerServer, BlockId, BlockRange,
+                ChainSpec, GetSubtreeRootsArg, RawTransaction, SubtreeRoot, TreeState, TxFilter,
+            },
+        },
+    };
+
+    struct MemoryBlockSource(Vec<CompactBlock>, Arc<AtomicUsize>);
+
+    impl MemoryBlockSource {
+        fn new(blocks: Vec<CompactBlock>) -> Self {
+            Self(blocks, Arc::new(AtomicUsize::new(0)))
+        }
+    }
+
+    impl BlockSource for MemoryBlockSource {
+        type Error = Infallible;
+
+        fn with_blocks<F, WalletErrT>(
+            &self,
+            from_height: Option<BlockHeight>,
+            limit: Option<usize>,
+            mut with_block: F,
+        ) -> Result<(), Error<WalletErrT, Self::Error>>
+        where
+            F: FnMut(CompactBlock) -> Result<(), Error<WalletErrT, Self::Error>>,
+        {
+            self.0
+                .iter()
+                .filter(|block| from_height.map_or(true, |h| block.height() >= h))
+                .take(limit.unwrap_or(usize::MAX))
+                .try_for_each(|block| {
+                    self.1.fetch_add(1, Ordering::SeqCst);
+                    with_block(block.clone())
+                })
+        }
+    }
+
+    fn block(height: u32) -> CompactBlock {
+        CompactBlock {
+            height: height.into(),
+            hash: vec![height as u8; 32],
+            prev_hash: vec![height.wrapping_sub(1) as u8; 32],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn serves_blocks_and_tree_state_in_process() {
+        let server = Arc::new(LocalLightwalletd::new(
+            Network::TestNetwork,
+            MemoryBlockSource::new((100..110).map(block).collect()),
+        ));
+        server.set_chain_tip(BlockHeight::from(109));
+        server.insert_tree_state(TreeState {
+            height: 105,
+            hash: "05".repeat(32),
+            ..Default::default()
+        });
+        server.insert_subtree_roots(
+            ShieldedProtocol::Orchard,
+            0,
+            vec![SubtreeRoot {
+                root_hash: vec![7; 32],
+                completing_block_hash: vec![103; 32],
+                completing_block_height: 103,
+            }],
+        );
+        server.insert_transaction(
+            zcash_primitives::transaction::TxId::from_bytes([9; 32]),
+            RawTransaction {
+                data: vec![1, 2, 3],
+                height: 104,
+            },
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut client =
+                CompactTxStreamerClient::new(CompactTxStreamerServer::from_arc(server.clone()));
+
+            let latest = client
+                .get_latest_block(ChainSpec::default())
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(latest.height, 109);
+            assert_eq!(latest.hash, vec![109; 32]);
+
+            let mut blocks = client
+                .get_block_range(BlockRange {
+                    start: Some(BlockId {
+                        height: 102,
+                        hash: vec![],
+                    }),
+                    end: Some(BlockId {
+                        height: 200,
+                        hash: vec![],
+                    }),
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            let mut heights = vec![];
+            while let Some(block) = blocks.message().await.unwrap() {
+                heights.push(block.height);
+            }
+            assert_eq!(heights, (102..110).collect::<Vec<_>>());
+
+            let tree_state = client
+                .get_tree_state(BlockId {
+                    height: 105,
+                    hash: vec![],
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(tree_state.height, 105);
+            assert!(client
+                .get_tree_state(BlockId {
+                    height: 106,
+                    hash: vec![],
+                })
+                .await
+                .is_err());
+
+            let mut roots = client
+                .get_subtree_roots(GetSubtreeRootsArg {
+                    start_index: 0,
+                    shielded_protocol: service::ShieldedProtocol::Orchard.into(),
+                    max_entries: 0,
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(
+                roots
+                    .message()
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .completing_block_height,
+                103
+            );
+            assert!(roots.message().await.unwrap().is_none());
+
+            let tx = client
+                .get_transaction(TxFilter {
+                    block: None,
+                    index: 0,
+                    hash: vec![9; 32],
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(tx.height, 104);
+
+            // Unparseable transactions are rejected.
+            assert!(client
+                .send_transaction(RawTransaction {
+                    data: vec![0; 4],
+                    height: 0,
+                })
+                .await
+                .is_err());
+        });
+
+        assert!(server.sent_transactions().is_empty());
+    }
+
+    #[test]
+    fn streams_block_ranges_lazily() {
+        let source = MemoryBlockSource::new((100..110).map(block).collect());
+        let reads = source.1.clone();
+        let server = LocalLightwalletd::new(Network::TestNetwork, source);
+        server.set_chain_tip(BlockHeight::from(109));
+
+        let range = |start, end| {
+            Request::new(BlockRange {
+                start: Some(BlockId {
+                    height: start,
+                    hash: vec![],
+                }),
+                end: Some(BlockId {
+                    height: end,
+                    hash: vec![],
+                }),
+            })
+        };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            // Blocks are read from the source only as the stream is consumed.
+            let mut blocks = server
+                .get_block_range(range(100, 109))
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(reads.load(Ordering::SeqCst), 0);
+            assert_eq!(blocks.next().await.unwrap().unwrap().height, 100);
+            assert_eq!(reads.load(Ordering::SeqCst), 1);
+
+            // A range with `start > end` is returned in descending order.
+            let heights = server
+                .get_block_range_nullifiers(range(200, 105))
+                .await
+                .unwrap()
+                .into_inner()
+                .map(|block| block.unwrap().height)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(heights, (105..110).rev().collect::<Vec<_>>());
+        });
+    }
+}
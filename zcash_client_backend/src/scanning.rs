@@ -38,6 +38,12 @@ use orchard::{
 #[cfg(not(feature = "orchard"))]
 use std::marker::PhantomData;
 
+#[cfg(feature = "transparent-inputs")]
+use {
+    crate::wallet::WalletTransparentOutput,
+    transparent::{address::TransparentAddress, bundle::OutPoint},
+};
+
 /// A key that can be used to perform trial decryption and nullifier
 /// computation for a [`CompactSaplingOutput`] or [`CompactOrchardAction`].
 ///
@@ -193,6 +199,8 @@ pub struct ScanningKeys<AccountId, IvkTag> {
         IvkTag,
         Box<dyn ScanningKeyOps<OrchardDomain, AccountId, orchard::note::Nullifier>>,
     >,
+    #[cfg(feature = "transparent-inputs")]
+    transparent: HashMap<TransparentAddress, AccountId>,
 }
 
 impl<AccountId, IvkTag> ScanningKeys<AccountId, IvkTag> {
@@ -211,6 +219,8 @@ impl<AccountId, IvkTag> ScanningKeys<AccountId, IvkTag> {
             sapling,
             #[cfg(feature = "orchard")]
             orchard,
+            #[cfg(feature = "transparent-inputs")]
+            transparent: HashMap::new(),
         }
     }
 
@@ -220,9 +230,27 @@ impl<AccountId, IvkTag> ScanningKeys<AccountId, IvkTag> {
             sapling: HashMap::new(),
             #[cfg(feature = "orchard")]
             orchard: HashMap::new(),
+            #[cfg(feature = "transparent-inputs")]
+            transparent: HashMap::new(),
         }
     }
 
+    /// Adds the given transparent receivers to the set of addresses for which transparent
+    /// outputs will be detected, along with the accounts to which they belong.
+    ///
+    /// Transparent outputs can only be detected in compact blocks that include
+    /// transparent transaction data (see [`CompactTx::vout`]).
+    ///
+    /// [`CompactTx::vout`]: crate::proto::compact_formats::CompactTx::vout
+    #[cfg(feature = "transparent-inputs")]
+    pub fn with_transparent_receivers(
+        mut self,
+        receivers: impl IntoIterator<Item = (TransparentAddress, AccountId)>,
+    ) -> Self {
+        self.transparent.extend(receivers);
+        self
+    }
+
     /// Returns the Sapling keys to be used for incoming note detection.
     pub fn sapling(
         &self,
@@ -239,6 +267,12 @@ impl<AccountId, IvkTag> ScanningKeys<AccountId, IvkTag> {
     {
         &self.orchard
     }
+
+    /// Returns the transparent receivers to be used for incoming output detection.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent(&self) -> &HashMap<TransparentAddress, AccountId> {
+        &self.transparent
+    }
 }
 
 impl<AccountId: Copy + Eq + Hash + 'static> ScanningKeys<AccountId, (AccountId, Scope)> {
@@ -294,15 +328,22 @@ impl<AccountId: Copy + Eq + Hash + 'static> ScanningKeys<AccountId, (AccountId,
             sapling,
             #[cfg(feature = "orchard")]
             orchard,
+            #[cfg(feature = "transparent-inputs")]
+            transparent: HashMap::new(),
         }
     }
 }
 
 /// The set of nullifiers being tracked by a wallet.
+///
+/// For the transparent pool, the outpoints of the wallet's unspent transparent outputs
+/// play the role of nullifiers.
 pub struct Nullifiers<AccountId> {
     sapling: Vec<(AccountId, sapling::Nullifier)>,
     #[cfg(feature = "orchard")]
     orchard: Vec<(AccountId, orchard::note::Nullifier)>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent: HashMap<OutPoint, AccountId>,
 }
 
 impl<AccountId> Nullifiers<AccountId> {
@@ -312,6 +353,8 @@ impl<AccountId> Nullifiers<AccountId> {
             sapling: vec![],
             #[cfg(feature = "orchard")]
             orchard: vec![],
+            #[cfg(feature = "transparent-inputs")]
+            transparent: HashMap::new(),
         }
     }
 
@@ -319,11 +362,17 @@ impl<AccountId> Nullifiers<AccountId> {
     pub(crate) fn new(
         sapling: Vec<(AccountId, sapling::Nullifier)>,
         #[cfg(feature = "orchard")] orchard: Vec<(AccountId, orchard::note::Nullifier)>,
+        #[cfg(feature = "transparent-inputs")] transparent: Vec<(AccountId, OutPoint)>,
     ) -> Self {
         Self {
             sapling,
             #[cfg(feature = "orchard")]
             orchard,
+            #[cfg(feature = "transparent-inputs")]
+            transparent: transparent
+                .into_iter()
+                .map(|(account_id, outpoint)| (outpoint, account_id))
+                .collect(),
         }
     }
 
@@ -338,6 +387,13 @@ impl<AccountId> Nullifiers<AccountId> {
         self.orchard.as_ref()
     }
 
+    /// Returns the outpoints of the unspent transparent outputs that the wallet is
+    /// tracking, along with the accounts to which they belong.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent(&self) -> &HashMap<OutPoint, AccountId> {
+        &self.transparent
+    }
+
     /// Discards Sapling nullifiers from the tracked nullifier set, retaining only those that
     /// satisfy the given predicate.
     pub(crate) fn retain_sapling(&mut self, f: impl Fn(&(AccountId, sapling::Nullifier)) -> bool) {
@@ -367,6 +423,23 @@ impl<AccountId> Nullifiers<AccountId> {
     ) {
         self.orchard.extend(nfs);
     }
+
+    #[cfg(feature = "transparent-inputs")]
+    pub(crate) fn retain_transparent(&mut self, f: impl Fn(&OutPoint) -> bool) {
+        self.transparent.retain(|outpoint, _| f(outpoint));
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    pub(crate) fn extend_transparent(
+        &mut self,
+        outpoints: impl IntoIterator<Item = (AccountId, OutPoint)>,
+    ) {
+        self.transparent.extend(
+            outpoints
+                .into_iter()
+                .map(|(account_id, outpoint)| (outpoint, account_id)),
+        );
+    }
 }
 
 /// Errors that may occur in chain scanning
@@ -380,6 +453,16 @@ pub enum ScanError {
         index: usize,
     },
 
+    /// The encoding of a compact transparent input or output was invalid.
+    #[cfg(feature = "transparent-inputs")]
+    TransparentEncodingInvalid {
+        at_height: BlockHeight,
+        txid: TxId,
+        /// Whether the improperly encoded value is an input (`true`) or an output (`false`).
+        is_input: bool,
+        index: usize,
+    },
+
     /// The hash of the parent block given by a proposed new chain tip does not match the hash of
     /// the current chain tip.
     PrevHashMismatch { at_height: BlockHeight },
@@ -423,6 +506,8 @@ impl ScanError {
         use ScanError::*;
         match self {
             EncodingInvalid { .. } => false,
+            #[cfg(feature = "transparent-inputs")]
+            TransparentEncodingInvalid { .. } => false,
             PrevHashMismatch { .. } => true,
             BlockHeightDiscontinuity { .. } => true,
             TreeSizeMismatch { .. } => true,
@@ -436,6 +521,8 @@ impl ScanError {
         use ScanError::*;
         match self {
             EncodingInvalid { at_height, .. } => *at_height,
+            #[cfg(feature = "transparent-inputs")]
+            TransparentEncodingInvalid { at_height, .. } => *at_height,
             PrevHashMismatch { at_height } => *at_height,
             BlockHeightDiscontinuity { new_height, .. } => *new_height,
             TreeSizeMismatch { at_height, .. } => *at_height,
@@ -453,6 +540,12 @@ impl fmt::Display for ScanError {
                 f,
                 "{pool_type:?} output {index} of transaction {txid} was improperly encoded."
             ),
+            #[cfg(feature = "transparent-inputs")]
+            TransparentEncodingInvalid { txid, is_input, index, .. } => write!(
+                f,
+                "Transparent {} {index} of transaction {txid} was improperly encoded.",
+                if *is_input { "input" } else { "output" }
+            ),
             PrevHashMismatch { at_height } => write!(
                 f,
                 "The parent hash of proposed block does not correspond to the block hash at height {at_height}."
@@ -846,11 +939,40 @@ where
             orchard_spends
         };
 
+        // Transparent spends are detected by matching the outpoints of the wallet's unspent
+        // transparent outputs. Transparent data is public, so unlike nullifiers these are
+        // not compared in constant time.
+        #[cfg(feature = "transparent-inputs")]
+        let transparent_spends = tx
+            .vin
+            .iter()
+            .enumerate()
+            .map(|(index, txin)| {
+                let prevout =
+                    txin.prevout()
+                        .map_err(|_| ScanError::TransparentEncodingInvalid {
+                            at_height: cur_height,
+                            txid,
+                            is_input: true,
+                            index,
+                        })?;
+                Ok(nullifiers
+                    .transparent
+                    .get(&prevout)
+                    .copied()
+                    .map(|account_id| WalletSpend::from_parts(index, prevout, account_id)))
+            })
+            .filter_map(Result::transpose)
+            .collect::<Result<Vec<_>, _>>()?;
+
         // Collect the set of accounts that were spent from in this transaction
         let spent_from_accounts = sapling_spends.iter().map(|spend| spend.account_id());
         #[cfg(feature = "orchard")]
         let spent_from_accounts =
             spent_from_accounts.chain(orchard_spends.iter().map(|spend| spend.account_id()));
+        #[cfg(feature = "transparent-inputs")]
+        let spent_from_accounts =
+            spent_from_accounts.chain(transparent_spends.iter().map(|spend| spend.account_id()));
         let spent_from_accounts = spent_from_accounts.copied().collect::<HashSet<_>>();
 
         let (sapling_outputs, mut sapling_nc) = find_received(
@@ -923,8 +1045,43 @@ where
         #[cfg(not(feature = "orchard"))]
         let has_orchard = false;
 
-        if has_sapling || has_orchard {
-            wtxs.push(WalletTx::new(
+        #[cfg(feature = "transparent-inputs")]
+        let transparent_outputs =
+            tx.vout
+                .iter()
+                .enumerate()
+                .map(|(index, vout)| {
+                    let txout =
+                        vout.to_txout()
+                            .map_err(|_| ScanError::TransparentEncodingInvalid {
+                                at_height: cur_height,
+                                txid,
+                                is_input: false,
+                                index,
+                            })?;
+                    let outpoint = OutPoint::new(
+                        *txid.as_ref(),
+                        u32::try_from(index).expect("vout fits in u32"),
+                    );
+                    Ok(
+                        WalletTransparentOutput::from_parts(outpoint, txout, Some(cur_height))
+                            .filter(|output| {
+                                scanning_keys
+                                    .transparent
+                                    .contains_key(output.recipient_address())
+                            }),
+                    )
+                })
+                .filter_map(Result::transpose)
+                .collect::<Result<Vec<_>, _>>()?;
+
+        #[cfg(feature = "transparent-inputs")]
+        let has_transparent = !(transparent_spends.is_empty() && transparent_outputs.is_empty());
+        #[cfg(not(feature = "transparent-inputs"))]
+        let has_transparent = false;
+
+        if has_sapling || has_orchard || has_transparent {
+            let wtx = WalletTx::new(
                 txid,
                 tx_index as usize,
                 sapling_spends,
@@ -933,7 +1090,12 @@ where
                 orchard_spends,
                 #[cfg(feature = "orchard")]
                 orchard_outputs,
-            ));
+            );
+            #[cfg(feature = "transparent-inputs")]
+            let wtx = wtx
+                .with_transparent_spends(transparent_spends)
+                .with_transparent_outputs(transparent_outputs);
+            wtxs.push(wtx);
         }
 
         sapling_commitment_tree_size +=
@@ -1493,6 +1655,8 @@ mod tests {
             vec![(account, nf)],
             #[cfg(feature = "orchard")]
             vec![],
+            #[cfg(feature = "transparent-inputs")]
+            vec![],
         );
 
         let cb = fake_compact_block(
@@ -1534,4 +1698,74 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    #[cfg(feature = "transparent-inputs")]
+    fn scan_block_with_transparent_data() {
+        use transparent::{address::TransparentAddress, bundle::OutPoint};
+        use zcash_primitives::transaction::TxId;
+
+        use crate::proto::compact_formats::{self as compact, CompactTxIn, TxOut};
+
+        let account = AccountId::ZERO;
+        let ours = TransparentAddress::PublicKeyHash([7; 20]);
+        let theirs = TransparentAddress::PublicKeyHash([8; 20]);
+        let tracked = OutPoint::new([1; 32], 0);
+        let untracked = OutPoint::new([2; 32], 0);
+
+        let txid = TxId::from_bytes([3; 32]);
+        let cb = compact::CompactBlock {
+            height: 1,
+            hash: vec![1; 32],
+            vtx: vec![compact::CompactTx {
+                index: 1,
+                hash: txid.as_ref().to_vec(),
+                vin: [&untracked, &tracked]
+                    .into_iter()
+                    .map(|outpoint| CompactTxIn {
+                        prevout_txid: outpoint.hash().to_vec(),
+                        prevout_index: outpoint.n(),
+                    })
+                    .collect(),
+                vout: [theirs, ours]
+                    .into_iter()
+                    .map(|addr| TxOut {
+                        value: 10_000,
+                        script_pub_key: addr.script().0,
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            chain_metadata: Some(compact::ChainMetadata::default()),
+            ..Default::default()
+        };
+
+        let scanning_keys =
+            ScanningKeys::<AccountId, ()>::empty().with_transparent_receivers([(ours, account)]);
+        let nullifiers = Nullifiers::new(
+            vec![],
+            #[cfg(feature = "orchard")]
+            vec![],
+            vec![(account, tracked.clone())],
+        );
+
+        let scanned_block =
+            scan_block(&Network::TestNetwork, cb, &scanning_keys, &nullifiers, None).unwrap();
+
+        assert_eq!(scanned_block.transactions().len(), 1);
+        let tx = &scanned_block.transactions()[0];
+        assert_eq!(tx.txid(), txid);
+
+        assert_eq!(tx.transparent_spends().len(), 1);
+        assert_eq!(tx.transparent_spends()[0].index(), 1);
+        assert_eq!(tx.transparent_spends()[0].nf(), &tracked);
+        assert_eq!(tx.transparent_spends()[0].account_id(), &account);
+
+        assert_eq!(tx.transparent_outputs().len(), 1);
+        let output = &tx.transparent_outputs()[0];
+        assert_eq!(output.outpoint(), &OutPoint::new([3; 32], 1));
+        assert_eq!(output.recipient_address(), &ours);
+        assert_eq!(output.value(), Zatoshis::const_from_u64(10_000));
+        assert_eq!(output.mined_height(), Some(BlockHeight::from(1)));
+    }
 }
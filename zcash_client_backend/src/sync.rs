@@ -10,8 +10,9 @@
 //!   the process.
 
 use std::fmt;
+use std::num::NonZeroUsize;
 
-use futures_util::TryStreamExt;
+use futures_util::{future::try_join_all, TryStreamExt};
 use shardtree::error::ShardTreeError;
 use subtle::ConditionallySelectable;
 use tonic::{
@@ -28,8 +29,8 @@ use zcash_protocol::consensus::{BlockHeight, Parameters};
 use crate::{
     data_api::{
         chain::{
-            error::Error as ChainError, scan_cached_blocks, BlockCache, ChainState,
-            CommitmentTreeRoot,
+            error::Error as ChainError, scan_cached_block_ranges, scan_cached_blocks, BlockCache,
+            ChainState, CommitmentTreeRoot,
         },
         scanning::{ScanPriority, ScanRange},
         WalletCommitmentTrees, WalletRead, WalletWrite,
@@ -51,6 +52,8 @@ use {
     zcash_protocol::value::Zatoshis,
 };
 
+pub mod discovery;
+
 /// Scans the chain until the wallet is up-to-date.
 pub async fn run<P, ChT, CaT, DbT>(
     client: &mut CompactTxStreamerClient<ChT>,
@@ -59,6 +62,76 @@ pub async fn run<P, ChT, CaT, DbT>(
     db_data: &mut DbT,
     batch_size: u32,
 ) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    CaT: BlockCache,
+    CaT::Error: std::error::Error + Send + Sync + 'static,
+    DbT: WalletWrite + WalletCommitmentTrees,
+    DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
+    <DbT as WalletRead>::Error: std::error::Error + Send + Sync + 'static,
+    <DbT as WalletCommitmentTrees>::Error: std::error::Error + Send + Sync + 'static,
+{
+    run_with_clients(
+        std::slice::from_mut(client),
+        params,
+        db_cache,
+        db_data,
+        batch_size,
+    )
+    .await
+}
+
+/// Scans the chain until the wallet is up-to-date, scanning up to `max_concurrent_ranges`
+/// batches of blocks at a time.
+///
+/// Each pass downloads up to `max_concurrent_ranges` batches of at most `batch_size` blocks
+/// from the suggested scan ranges concurrently, using a clone of `client` for each batch,
+/// and then scans them together with [`scan_cached_block_ranges`]. Trial decryption of the
+/// batches proceeds in parallel, and each batch is stored in the wallet as soon as it has
+/// been scanned, subject to the ordering of batches within each note commitment tree shard.
+/// Larger values make better use of multi-core machines and of the available bandwidth
+/// during initial sync, at the cost of holding more downloaded blocks in the block cache at
+/// once.
+///
+/// [`run`] is equivalent to this function with `max_concurrent_ranges` set to 1.
+pub async fn run_concurrent<P, ChT, CaT, DbT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_cache: &CaT,
+    db_data: &mut DbT,
+    batch_size: u32,
+    max_concurrent_ranges: NonZeroUsize,
+) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody> + Clone,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    CaT: BlockCache,
+    CaT::Error: std::error::Error + Send + Sync + 'static,
+    DbT: WalletWrite + WalletCommitmentTrees,
+    DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
+    <DbT as WalletRead>::Error: std::error::Error + Send + Sync + 'static,
+    <DbT as WalletCommitmentTrees>::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut clients = vec![client.clone(); max_concurrent_ranges.get()];
+    run_with_clients(&mut clients, params, db_cache, db_data, batch_size).await
+}
+
+/// Scans the chain until the wallet is up-to-date, downloading up to one batch of blocks
+/// at a time with each of the given clients.
+async fn run_with_clients<P, ChT, CaT, DbT>(
+    clients: &mut [CompactTxStreamerClient<ChT>],
+    params: &P,
+    db_cache: &CaT,
+    db_data: &mut DbT,
+    batch_size: u32,
+) -> Result<(), Error<CaT::Error, <DbT as WalletRead>::Error, <DbT as WalletCommitmentTrees>::Error>>
 where
     P: Parameters + Send + 'static,
     ChT: GrpcService<TonicBody>,
@@ -74,15 +147,15 @@ where
 {
     // 1) Download note commitment tree data from lightwalletd
     // 2) Pass the commitment tree data to the database.
-    update_subtree_roots(client, db_data).await?;
+    update_subtree_roots(&mut clients[0], db_data).await?;
 
-    while running(client, params, db_cache, db_data, batch_size).await? {}
+    while running(clients, params, db_cache, db_data, batch_size).await? {}
 
     Ok(())
 }
 
 async fn running<P, ChT, CaT, DbT, TrErr>(
-    client: &mut CompactTxStreamerClient<ChT>,
+    clients: &mut [CompactTxStreamerClient<ChT>],
     params: &P,
     db_cache: &CaT,
     db_data: &mut DbT,
@@ -100,6 +173,8 @@ where
     DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
     DbT::Error: std::error::Error + Send + Sync + 'static,
 {
+    let client = &mut clients[0];
+
     // 3) Download chain tip metadata from lightwalletd
     // 4) Notify the wallet of the updated chain tip.
     update_chain_tip(client, db_data).await?;
@@ -143,8 +218,13 @@ where
                 // Scan the downloaded blocks and check for scanning errors that
                 // indicate the wallet's chain tip is out of sync with blockchain
                 // history.
-                let scan_ranges_updated =
-                    scan_blocks(params, db_cache, db_data, &chain_state, scan_range).await?;
+                let scan_ranges_updated = scan_blocks(
+                    params,
+                    db_cache,
+                    db_data,
+                    &[(scan_range.clone(), chain_state)],
+                )
+                .await?;
 
                 // Delete the now-scanned blocks, because keeping the entire chain
                 // in CompactBlock files on disk is horrendous for the filesystem.
@@ -170,10 +250,10 @@ where
     }
 
     // 7) Loop over the remaining suggested scan ranges, retrieving the requested data
-    //    and calling `scan_cached_blocks` on each range.
+    //    and scanning up to one batch per client at a time.
     let scan_ranges = db_data.suggest_scan_ranges().map_err(Error::Wallet)?;
     debug!("Suggested ranges: {:?}", scan_ranges);
-    for scan_range in scan_ranges.into_iter().flat_map(|r| {
+    let batches = scan_ranges.into_iter().flat_map(|r| {
         // Limit the number of blocks we download and scan at any one time.
         (0..).scan(r, |acc, _| {
             if acc.is_empty() {
@@ -188,18 +268,30 @@ where
                 Some(cur)
             }
         })
-    }) {
-        // Download the blocks in `scan_range` into the block source.
-        download_blocks(client, db_cache, &scan_range).await?;
+    });
+    let mut batches = batches.peekable();
+    while batches.peek().is_some() {
+        // Download the blocks in each batch into the block source, together with the
+        // chain state preceding the batch, using a separate client for each batch.
+        let to_scan = try_join_all(clients.iter_mut().zip(batches.by_ref()).map(
+            |(client, scan_range)| async move {
+                download_blocks(client, db_cache, &scan_range).await?;
+
+                let chain_state =
+                    download_chain_state(client, scan_range.block_range().start - 1).await?;
 
-        let chain_state = download_chain_state(client, scan_range.block_range().start - 1).await?;
+                Ok::<_, Error<CaT::Error, DbT::Error, TrErr>>((scan_range, chain_state))
+            },
+        ))
+        .await?;
 
         // Scan the downloaded blocks.
-        let scan_ranges_updated =
-            scan_blocks(params, db_cache, db_data, &chain_state, &scan_range).await?;
+        let scan_ranges_updated = scan_blocks(params, db_cache, db_data, &to_scan).await?;
 
         // Delete the now-scanned blocks.
-        block_deletions.push(db_cache.delete(scan_range));
+        for (scan_range, _) in to_scan {
+            block_deletions.push(db_cache.delete(scan_range));
+        }
 
         if scan_ranges_updated {
             // The suggested scan ranges have been updated (either due to a continuity
@@ -369,16 +461,17 @@ where
         .map_err(|_| Error::MisbehavingServer)
 }
 
-/// Scans the given block range and checks for scanning errors that indicate the wallet's
+/// Scans the given block ranges and checks for scanning errors that indicate the wallet's
 /// chain tip is out of sync with blockchain history.
 ///
+/// Each range is paired with the chain state as of the block preceding it.
+///
 /// Returns `true` if scanning these blocks materially changed the suggested scan ranges.
 async fn scan_blocks<P, CaT, DbT, TrErr>(
     params: &P,
     db_cache: &CaT,
     db_data: &mut DbT,
-    initial_chain_state: &ChainState,
-    scan_range: &ScanRange,
+    ranges: &[(ScanRange, ChainState)],
 ) -> Result<bool, Error<CaT::Error, <DbT as WalletRead>::Error, TrErr>>
 where
     P: Parameters + Send + 'static,
@@ -388,15 +481,28 @@ where
     DbT::AccountId: ConditionallySelectable + Default + Send + 'static,
     DbT::Error: std::error::Error + Send + Sync + 'static,
 {
-    info!("Scanning {}", scan_range);
-    let scan_result = scan_cached_blocks(
-        params,
-        db_cache,
-        db_data,
-        scan_range.block_range().start,
-        initial_chain_state,
-        scan_range.len(),
-    );
+    for (scan_range, _) in ranges {
+        info!("Scanning {}", scan_range);
+    }
+    let scan_result = match ranges {
+        [(scan_range, initial_chain_state)] => scan_cached_blocks(
+            params,
+            db_cache,
+            db_data,
+            scan_range.block_range().start,
+            initial_chain_state,
+            scan_range.len(),
+        )
+        .map(|_| ()),
+        _ => scan_cached_block_ranges(
+            params,
+            db_cache,
+            db_data,
+            ranges,
+            NonZeroUsize::new(ranges.len()).unwrap_or(NonZeroUsize::MIN),
+        )
+        .map(|_| ()),
+    };
 
     match scan_result {
         Err(ChainError::Scan(err)) if err.is_continuity_error() => {
@@ -429,15 +535,18 @@ where
             // The database was truncated, invalidating prior suggested ranges.
             Ok(true)
         }
-        Ok(_) => {
+        Ok(()) => {
             // If scanning these blocks caused a suggested range to be added that has a
-            // higher priority than the current range, invalidate the current ranges.
+            // higher priority than the current ranges, invalidate the current ranges.
             let latest_ranges = db_data.suggest_scan_ranges().map_err(Error::Wallet)?;
-
-            Ok(if let Some(range) = latest_ranges.first() {
-                range.priority() > scan_range.priority()
-            } else {
-                false
+            let scanned_priority = ranges
+                .iter()
+                .map(|(scan_range, _)| scan_range.priority())
+                .min();
+
+            Ok(match (latest_ranges.first(), scanned_priority) {
+                (Some(range), Some(priority)) => range.priority() > priority,
+                _ => false,
             })
         }
         Err(e) => Err(e.into()),
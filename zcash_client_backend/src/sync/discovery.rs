@@ -0,0 +1,712 @@
+//! Discovery of the ZIP 32 accounts that have been used with a restored seed.
+//!
+//! When a wallet is restored from a seed, the number of accounts that were previously
+//! derived from that seed is not known. [`discover_accounts`] derives the
+//! [`UnifiedSpendingKey`]s at successive ZIP 32 account indices, probes each of them for
+//! on-chain activity, and imports the accounts that have been used via
+//! [`WalletWrite::import_account_hd`]. Discovery stops once
+//! [`DiscoveryConfig::gap_limit`] consecutive account indices have been found to be unused.
+//!
+//! An account is considered to have been used if:
+//! - any shielded output in the probed block range can be decrypted by the account's
+//!   viewing keys; or
+//! - with the `transparent-inputs` feature enabled, the lightwalletd server reports
+//!   transaction history within the probed block range for any of the account's first
+//!   [`DiscoveryConfig::transparent_address_gap_limit`] external transparent addresses.
+//!
+//! Shielded activity is detected by trial-decrypting the compact blocks in the probed
+//! range; blocks are downloaded afresh for each window of candidate accounts, and are not
+//! cached. Callers that intend to scan the same range afterwards should use a birthday
+//! height that is as recent as the user can reliably provide.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::ops::Range;
+
+use secrecy::{ExposeSecret, SecretVec};
+use tonic::{
+    body::Body as TonicBody,
+    client::GrpcService,
+    codegen::{Body, Bytes, StdError},
+};
+use tracing::{debug, info};
+
+use zcash_keys::keys::{DerivationError, UnifiedFullViewingKey, UnifiedSpendingKey};
+use zcash_protocol::consensus::{BlockHeight, Parameters};
+use zip32::fingerprint::SeedFingerprint;
+
+use crate::{
+    data_api::{Account, AccountBirthday, WalletRead, WalletWrite},
+    proto::service::{self, compact_tx_streamer_client::CompactTxStreamerClient},
+    scanning::{scan_block, Nullifiers, ScanError, ScanningKeys},
+};
+
+#[cfg(feature = "transparent-inputs")]
+use {
+    ::transparent::keys::{IncomingViewingKey, NonHardenedChildIndex},
+    zcash_keys::encoding::AddressCodec as _,
+};
+
+/// Parameters that control the account discovery process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiscoveryConfig {
+    gap_limit: NonZeroU32,
+    transparent_address_gap_limit: u32,
+    batch_size: NonZeroU32,
+}
+
+impl DiscoveryConfig {
+    /// Constructs a new discovery configuration.
+    ///
+    /// - `gap_limit`: the number of consecutive unused account indices after which
+    ///   discovery stops.
+    /// - `transparent_address_gap_limit`: the number of external transparent addresses,
+    ///   starting from address index zero, whose history is checked for each account.
+    ///   Setting this to zero disables transparent probing.
+    /// - `batch_size`: the number of compact blocks requested from the server at a time.
+    pub fn new(
+        gap_limit: NonZeroU32,
+        transparent_address_gap_limit: u32,
+        batch_size: NonZeroU32,
+    ) -> Self {
+        Self {
+            gap_limit,
+            transparent_address_gap_limit,
+            batch_size,
+        }
+    }
+
+    /// Returns the number of consecutive unused account indices after which discovery
+    /// stops.
+    pub fn gap_limit(&self) -> NonZeroU32 {
+        self.gap_limit
+    }
+
+    /// Returns the number of external transparent addresses whose history is checked for
+    /// each account.
+    pub fn transparent_address_gap_limit(&self) -> u32 {
+        self.transparent_address_gap_limit
+    }
+
+    /// Returns the number of compact blocks requested from the server at a time.
+    pub fn batch_size(&self) -> NonZeroU32 {
+        self.batch_size
+    }
+}
+
+impl Default for DiscoveryConfig {
+    /// Returns a configuration that stops after a single unused account, and checks the
+    /// first 20 external transparent addresses of each account, following the
+    /// conventions of [BIP 44].
+    ///
+    /// [BIP 44]: https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki
+    fn default() -> Self {
+        Self {
+            gap_limit: NonZeroU32::MIN,
+            transparent_address_gap_limit: 20,
+            batch_size: NonZeroU32::new(1000).unwrap(),
+        }
+    }
+}
+
+/// An account that was found or imported by [`discover_accounts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiscoveredAccount<AccountId> {
+    account_index: zip32::AccountId,
+    account_id: AccountId,
+    has_activity: bool,
+    imported: bool,
+}
+
+impl<AccountId: Copy> DiscoveredAccount<AccountId> {
+    /// Returns the ZIP 32 account index at which the account is derived.
+    pub fn account_index(&self) -> zip32::AccountId {
+        self.account_index
+    }
+
+    /// Returns the wallet's identifier for the account.
+    pub fn account_id(&self) -> AccountId {
+        self.account_id
+    }
+
+    /// Returns whether on-chain activity was detected for the account.
+    ///
+    /// Accounts without activity are imported when a used account exists at a higher
+    /// index, so that the wallet's account indices remain contiguous.
+    pub fn has_activity(&self) -> bool {
+        self.has_activity
+    }
+
+    /// Returns whether the account was imported by this discovery run, as opposed to
+    /// having already been present in the wallet.
+    pub fn imported(&self) -> bool {
+        self.imported
+    }
+}
+
+/// Discovers and imports the accounts derived from `seed` that have been used on chain.
+///
+/// Account indices are probed in windows of [`DiscoveryConfig::gap_limit`] accounts,
+/// starting from index zero. Every account index up to and including the highest index
+/// in a window at which activity was found is imported with the given `birthday` (or
+/// left as-is if the wallet already contains it), and probing continues with the next
+/// window. Discovery ends at the first window in which no activity is found.
+///
+/// Shielded activity is probed over the block range from the birthday height to the
+/// chain tip reported by the server.
+///
+/// Returns the discovered accounts in order of account index. Accounts that the wallet
+/// already contains are included in the result, but are not re-imported.
+pub async fn discover_accounts<P, ChT, DbT>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    db_data: &mut DbT,
+    seed: &SecretVec<u8>,
+    birthday: &AccountBirthday,
+    key_source: Option<&str>,
+    config: &DiscoveryConfig,
+) -> Result<Vec<DiscoveredAccount<DbT::AccountId>>, Error<<DbT as WalletRead>::Error>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+    DbT: WalletWrite,
+    DbT::Error: std::error::Error + Send + Sync + 'static,
+{
+    let seed_fingerprint =
+        SeedFingerprint::from_seed(seed.expose_secret()).ok_or(Error::InvalidSeedLength)?;
+
+    let tip_height: BlockHeight = client
+        .get_latest_block(service::ChainSpec::default())
+        .await?
+        .get_ref()
+        .height
+        .try_into()
+        .map_err(|_| Error::MisbehavingServer)?;
+    let probe_range = birthday.height()..(tip_height + 1);
+    info!(
+        "Discovering accounts for seed {:?} in blocks {:?}",
+        seed_fingerprint, probe_range
+    );
+
+    let mut discovered = vec![];
+    for (account_index, has_activity) in
+        find_used_accounts(client, params, seed, &probe_range, config).await?
+    {
+        let existing = db_data
+            .get_derived_account(&seed_fingerprint, account_index)
+            .map_err(Error::Wallet)?;
+        let (account_id, imported) = match existing {
+            Some(account) => (account.id(), false),
+            None => {
+                info!("Importing account {:?}", account_index);
+                let (account, _) = db_data
+                    .import_account_hd(
+                        &format!("Account {}", u32::from(account_index)),
+                        seed,
+                        account_index,
+                        birthday,
+                        key_source,
+                    )
+                    .map_err(Error::Wallet)?;
+                (account.id(), true)
+            }
+        };
+
+        discovered.push(DiscoveredAccount {
+            account_index,
+            account_id,
+            has_activity,
+            imported,
+        });
+    }
+
+    Ok(discovered)
+}
+
+/// Probes the accounts derived from `seed` for activity in `probe_range`, in windows of
+/// [`DiscoveryConfig::gap_limit`] account indices.
+///
+/// Returns every account index up to and including the highest index at which activity
+/// was found, in ascending order, along with whether activity was found at that index.
+async fn find_used_accounts<P, ChT, DbErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    seed: &SecretVec<u8>,
+    probe_range: &Range<BlockHeight>,
+    config: &DiscoveryConfig,
+) -> Result<Vec<(zip32::AccountId, bool)>, Error<DbErr>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let mut found = vec![];
+    let mut next_index = 0u32;
+    loop {
+        let window_end = next_index.saturating_add(config.gap_limit.get());
+        let candidates = (next_index..window_end)
+            .map(|index| {
+                let account_index =
+                    zip32::AccountId::try_from(index).map_err(|_| Error::AccountIndexExhausted)?;
+                let usk =
+                    UnifiedSpendingKey::from_seed(params, seed.expose_secret(), account_index)?;
+                Ok((index, usk.to_unified_full_viewing_key()))
+            })
+            .collect::<Result<Vec<_>, Error<DbErr>>>()?;
+
+        #[cfg_attr(not(feature = "transparent-inputs"), allow(unused_mut))]
+        let mut used = probe_shielded(client, params, &candidates, probe_range, config).await?;
+
+        #[cfg(feature = "transparent-inputs")]
+        for (index, ufvk) in &candidates {
+            if !used.contains(index)
+                && probe_transparent(client, params, ufvk, probe_range, config).await?
+            {
+                used.insert(*index);
+            }
+        }
+
+        let Some(&max_used) = used.last() else {
+            debug!(
+                "No activity found for account indices {}..{}",
+                next_index, window_end
+            );
+            break;
+        };
+
+        found.extend((next_index..=max_used).map(|index| {
+            let account_index =
+                zip32::AccountId::try_from(index).expect("index was checked during derivation");
+            (account_index, used.contains(&index))
+        }));
+
+        next_index = max_used + 1;
+    }
+
+    Ok(found)
+}
+
+/// Trial-decrypts the compact blocks in `range` with the viewing keys of the candidate
+/// accounts, and returns the indices of the accounts for which outputs were found.
+async fn probe_shielded<P, ChT, DbErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    candidates: &[(u32, UnifiedFullViewingKey)],
+    range: &Range<BlockHeight>,
+    config: &DiscoveryConfig,
+) -> Result<BTreeSet<u32>, Error<DbErr>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let scanning_keys = ScanningKeys::from_account_ufvks(candidates.iter().cloned());
+    let nullifiers = Nullifiers::empty();
+
+    let mut used = BTreeSet::new();
+    let mut prior_block_metadata = None;
+    let mut batch_start = range.start;
+    while batch_start < range.end && used.len() < candidates.len() {
+        let batch_end = std::cmp::min(batch_start + config.batch_size.get(), range.end);
+        debug!("Probing blocks {}..{}", batch_start, batch_end);
+
+        let mut blocks = client
+            .get_block_range(service::BlockRange {
+                start: Some(service::BlockId {
+                    height: batch_start.into(),
+                    ..Default::default()
+                }),
+                end: Some(service::BlockId {
+                    height: (batch_end - 1).into(),
+                    ..Default::default()
+                }),
+            })
+            .await?
+            .into_inner();
+
+        while let Some(block) = blocks.message().await? {
+            let scanned = scan_block(
+                params,
+                block,
+                &scanning_keys,
+                &nullifiers,
+                prior_block_metadata.as_ref(),
+            )?;
+
+            for tx in scanned.transactions() {
+                used.extend(tx.sapling_outputs().iter().map(|o| *o.account_id()));
+                #[cfg(feature = "orchard")]
+                used.extend(tx.orchard_outputs().iter().map(|o| *o.account_id()));
+            }
+
+            prior_block_metadata = Some(scanned.to_block_metadata());
+        }
+
+        batch_start = batch_end;
+    }
+
+    Ok(used)
+}
+
+/// Returns whether the server reports transaction history in `range` for any of the
+/// first [`DiscoveryConfig::transparent_address_gap_limit`] external transparent
+/// addresses of the given account.
+#[cfg(feature = "transparent-inputs")]
+async fn probe_transparent<P, ChT, DbErr>(
+    client: &mut CompactTxStreamerClient<ChT>,
+    params: &P,
+    ufvk: &UnifiedFullViewingKey,
+    range: &Range<BlockHeight>,
+    config: &DiscoveryConfig,
+) -> Result<bool, Error<DbErr>>
+where
+    P: Parameters + Send + 'static,
+    ChT: GrpcService<TonicBody>,
+    ChT::Error: Into<StdError>,
+    ChT::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <ChT::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    let Some(account_pubkey) = ufvk.transparent() else {
+        return Ok(false);
+    };
+    let external_ivk = account_pubkey
+        .derive_external_ivk()
+        .map_err(|e| Error::Derivation(DerivationError::Transparent(e)))?;
+
+    for index in 0..config.transparent_address_gap_limit {
+        let Some(address) = NonHardenedChildIndex::from_index(index)
+            .and_then(|i| external_ivk.derive_address(i).ok())
+        else {
+            // An invalid child index is astronomically unlikely; BIP 32 specifies that
+            // such indices are skipped.
+            continue;
+        };
+
+        let mut txs = client
+            .get_taddress_txids(service::TransparentAddressBlockFilter {
+                address: address.encode(params),
+                range: Some(service::BlockRange {
+                    start: Some(service::BlockId {
+                        height: range.start.into(),
+                        ..Default::default()
+                    }),
+                    end: Some(service::BlockId {
+                        height: (range.end - 1).into(),
+                        ..Default::default()
+                    }),
+                }),
+            })
+            .await?
+            .into_inner();
+
+        if txs.message().await?.is_some() {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Errors that can occur during account discovery.
+#[derive(Debug)]
+pub enum Error<DbErr> {
+    /// The ZIP 32 account index space was exhausted before discovery completed.
+    AccountIndexExhausted,
+    /// An error occurred deriving keys from the seed.
+    Derivation(DerivationError),
+    /// The seed length was not between 32 and 252 bytes inclusive.
+    InvalidSeedLength,
+    /// The lightwalletd server returned invalid information, and is misbehaving.
+    MisbehavingServer,
+    /// An error while trial-decrypting compact blocks.
+    Scan(ScanError),
+    /// An error while communicating with the lightwalletd server.
+    Server(tonic::Status),
+    /// An error while interacting with a wallet database via [`WalletRead`] or
+    /// [`WalletWrite`].
+    Wallet(DbErr),
+}
+
+impl<DbErr: fmt::Display> fmt::Display for Error<DbErr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::AccountIndexExhausted => {
+                write!(f, "The ZIP 32 account index space has been exhausted")
+            }
+            Error::Derivation(e) => write!(f, "Error deriving account keys: {e}"),
+            Error::InvalidSeedLength => {
+                write!(f, "Seed must be between 32 and 252 bytes in length")
+            }
+            Error::MisbehavingServer => write!(f, "lightwalletd server is misbehaving"),
+            Error::Scan(e) => write!(f, "Error while probing blocks: {e}"),
+            Error::Server(e) => {
+                write!(f, "Error while communicating with lightwalletd server: {e}")
+            }
+            Error::Wallet(e) => write!(f, "Error while interacting with wallet database: {e}"),
+        }
+    }
+}
+
+impl<DbErr: std::error::Error> std::error::Error for Error<DbErr> {}
+
+impl<DbErr> From<DerivationError> for Error<DbErr> {
+    fn from(e: DerivationError) -> Self {
+        Error::Derivation(e)
+    }
+}
+
+impl<DbErr> From<ScanError> for Error<DbErr> {
+    fn from(e: ScanError) -> Self {
+        Error::Scan(e)
+    }
+}
+
+impl<DbErr> From<tonic::Status> for Error<DbErr> {
+    fn from(status: tonic::Status) -> Self {
+        Error::Server(status)
+    }
+}
+
+#[cfg(all(test, feature = "lightwalletd-tonic-server"))]
+mod tests {
+    use std::convert::Infallible;
+    use std::num::NonZeroU32;
+
+    use secrecy::SecretVec;
+    use tonic::{codegen::BoxStream, Request, Response, Status};
+
+    use zcash_keys::keys::{UnifiedFullViewingKey, UnifiedSpendingKey};
+    use zcash_primitives::block::BlockHash;
+    use zcash_protocol::{
+        consensus::{BlockHeight, Network},
+        value::Zatoshis,
+    };
+
+    use super::{find_used_accounts, DiscoveryConfig};
+    use crate::{
+        proto::{
+            compact_formats::{CompactBlock, CompactTx},
+            service::{
+                compact_tx_streamer_client::CompactTxStreamerClient,
+                compact_tx_streamer_server::{CompactTxStreamer, CompactTxStreamerServer},
+                BlockId, BlockRange, ChainSpec, GetAddressUtxosReply, RawTransaction, SubtreeRoot,
+                TransparentAddressBlockFilter,
+            },
+        },
+        scanning::testing::fake_compact_block,
+    };
+
+    const NETWORK: Network = Network::TestNetwork;
+    const START_HEIGHT: u32 = 1_100_000;
+
+    /// A server that serves the given blocks, and reports transaction history for the
+    /// given transparent addresses.
+    struct MockServer {
+        blocks: Vec<CompactBlock>,
+        used_taddrs: Vec<String>,
+    }
+
+    fn stream<T: Send + 'static>(items: Vec<T>) -> Response<BoxStream<T>> {
+        Response::new(Box::pin(tonic::codegen::tokio_stream::iter(
+            items.into_iter().map(Ok),
+        )))
+    }
+
+    #[tonic::async_trait]
+    impl CompactTxStreamer for MockServer {
+        type GetBlockRangeStream = BoxStream<CompactBlock>;
+        type GetBlockRangeNullifiersStream = BoxStream<CompactBlock>;
+        type GetTaddressTxidsStream = BoxStream<RawTransaction>;
+        type GetMempoolTxStream = BoxStream<CompactTx>;
+        type GetMempoolStreamStream = BoxStream<RawTransaction>;
+        type GetSubtreeRootsStream = BoxStream<SubtreeRoot>;
+        type GetAddressUtxosStreamStream = BoxStream<GetAddressUtxosReply>;
+
+        async fn get_latest_block(
+            &self,
+            _request: Request<ChainSpec>,
+        ) -> Result<Response<BlockId>, Status> {
+            let tip = self.blocks.last().unwrap();
+            Ok(Response::new(BlockId {
+                height: tip.height,
+                hash: tip.hash.clone(),
+            }))
+        }
+
+        async fn get_block_range(
+            &self,
+            request: Request<BlockRange>,
+        ) -> Result<Response<Self::GetBlockRangeStream>, Status> {
+            let range = request.into_inner();
+            let (start, end) = (range.start.unwrap().height, range.end.unwrap().height);
+            Ok(stream(
+                self.blocks
+                    .iter()
+                    .filter(|block| (start..=end).contains(&block.height))
+                    .cloned()
+                    .collect(),
+            ))
+        }
+
+        async fn get_taddress_txids(
+            &self,
+            request: Request<TransparentAddressBlockFilter>,
+        ) -> Result<Response<Self::GetTaddressTxidsStream>, Status> {
+            let address = request.into_inner().address;
+            Ok(stream(if self.used_taddrs.contains(&address) {
+                vec![RawTransaction::default()]
+            } else {
+                vec![]
+            }))
+        }
+    }
+
+    fn seed() -> SecretVec<u8> {
+        SecretVec::new(vec![7; 32])
+    }
+
+    fn ufvk(index: u32) -> UnifiedFullViewingKey {
+        UnifiedSpendingKey::from_seed(
+            &NETWORK,
+            &[7; 32],
+            zip32::AccountId::try_from(index).unwrap(),
+        )
+        .unwrap()
+        .to_unified_full_viewing_key()
+    }
+
+    /// Returns a chain containing one block for each of the given account indices, each
+    /// with a Sapling output received by that account, followed by an empty block.
+    fn chain(used: &[u32]) -> Vec<CompactBlock> {
+        let mut blocks: Vec<CompactBlock> = vec![];
+        for (i, index) in used.iter().enumerate() {
+            let prev_hash = blocks
+                .last()
+                .map_or(BlockHash([0; 32]), |block| block.hash());
+            let sapling_tree_size = blocks.last().map_or(0, |block| {
+                block
+                    .chain_metadata
+                    .as_ref()
+                    .unwrap()
+                    .sapling_commitment_tree_size
+            });
+            blocks.push(fake_compact_block(
+                BlockHeight::from(START_HEIGHT + i as u32),
+                prev_hash,
+                sapling::Nullifier([i as u8; 32]),
+                ufvk(*index).sapling().unwrap(),
+                Zatoshis::const_from_u64(10000),
+                false,
+                Some((sapling_tree_size, 0)),
+            ));
+        }
+        let tip_height = START_HEIGHT + used.len() as u32;
+        blocks.push(CompactBlock {
+            height: tip_height.into(),
+            hash: vec![0xff; 32],
+            prev_hash: blocks
+                .last()
+                .map_or(vec![0; 32], |block| block.hash.clone()),
+            chain_metadata: Some(
+                blocks
+                    .last()
+                    .and_then(|block| block.chain_metadata)
+                    .unwrap_or_default(),
+            ),
+            ..Default::default()
+        });
+        blocks
+    }
+
+    fn find(server: MockServer, gap_limit: u32, transparent_gap_limit: u32) -> Vec<(u32, bool)> {
+        let tip_height = server.blocks.last().unwrap().height() + 1;
+        let config = DiscoveryConfig::new(
+            NonZeroU32::new(gap_limit).unwrap(),
+            transparent_gap_limit,
+            NonZeroU32::new(2).unwrap(),
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let mut client = CompactTxStreamerClient::new(CompactTxStreamerServer::new(server));
+            find_used_accounts::<_, _, Infallible>(
+                &mut client,
+                &NETWORK,
+                &seed(),
+                &(BlockHeight::from(START_HEIGHT)..tip_height),
+                &config,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(account_index, has_activity)| (u32::from(account_index), has_activity))
+            .collect()
+        })
+    }
+
+    #[test]
+    fn discovery_stops_at_gap_limit() {
+        let server = || MockServer {
+            blocks: chain(&[0, 2]),
+            used_taddrs: vec![],
+        };
+
+        // With a gap limit of one, the unused account at index 1 ends discovery.
+        assert_eq!(find(server(), 1, 0), vec![(0, true)]);
+
+        // With a gap limit of two, the account at index 2 is found, and the unused
+        // account at index 1 is included so that account indices remain contiguous.
+        assert_eq!(find(server(), 2, 0), vec![(0, true), (1, false), (2, true)]);
+
+        // No accounts are found if there is no activity.
+        assert_eq!(
+            find(
+                MockServer {
+                    blocks: chain(&[]),
+                    used_taddrs: vec![],
+                },
+                3,
+                0
+            ),
+            vec![]
+        );
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    #[test]
+    fn discovery_probes_transparent_addresses() {
+        use transparent::keys::{IncomingViewingKey, NonHardenedChildIndex};
+        use zcash_keys::encoding::AddressCodec;
+
+        // Account 1 has only received funds at its external transparent address with
+        // index 3.
+        let taddr = ufvk(1)
+            .transparent()
+            .unwrap()
+            .derive_external_ivk()
+            .unwrap()
+            .derive_address(NonHardenedChildIndex::from_index(3).unwrap())
+            .unwrap()
+            .encode(&NETWORK);
+        let server = || MockServer {
+            blocks: chain(&[]),
+            used_taddrs: vec![taddr.clone()],
+        };
+
+        // The address is found if it is within the transparent address gap limit.
+        assert_eq!(find(server(), 2, 4), vec![(0, false), (1, true)]);
+
+        // It is not found if it lies beyond the transparent address gap limit, or if
+        // transparent probing is disabled.
+        assert_eq!(find(server(), 2, 3), vec![]);
+        assert_eq!(find(server(), 2, 0), vec![]);
+    }
+}
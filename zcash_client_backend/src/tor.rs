@@ -6,6 +6,9 @@ use arti_client::{config::TorClientConfigBuilder, TorClient};
 use tor_rtcompat::PreferredRuntime;
 use tracing::debug;
 
+#[cfg(feature = "lightwalletd-tonic-tls-webpki-roots")]
+pub mod broadcast;
+
 #[cfg(feature = "lightwalletd-tonic-tls-webpki-roots")]
 mod grpc;
 
@@ -0,0 +1,373 @@
+//! Private transaction broadcast over Tor.
+//!
+//! Submitting transactions over the same connection that a wallet uses for sync allows
+//! the `lightwalletd` server to link the transactions to the wallet's sync session, and
+//! submitting the transactions of a multi-step proposal back-to-back allows the server to
+//! link the steps to one another. The helpers in this module instead submit each
+//! transaction over a freshly isolated Tor circuit, waiting for a randomized delay
+//! between consecutive transactions.
+
+use std::{fmt, ops::RangeInclusive, time::Duration};
+
+use nonempty::NonEmpty;
+use rand::{thread_rng, Rng};
+use tonic::transport::Uri;
+use tracing::debug;
+use zcash_primitives::transaction::{Transaction, TxId};
+
+use super::{grpc::GrpcError, Client, Error};
+use crate::{
+    data_api::WalletRead,
+    proto::service::{RawTransaction, SendResponse},
+};
+
+/// The default range from which the delay between consecutive transaction submissions
+/// is sampled.
+pub const DEFAULT_STEP_DELAY: RangeInclusive<Duration> =
+    Duration::from_secs(5)..=Duration::from_secs(60);
+
+/// A means of submitting transactions to a `lightwalletd` server.
+#[trait_variant::make(Transport: Send)]
+pub trait LocalTransport {
+    /// Submits the given transaction over a connection that cannot be linked to any other
+    /// connection made by this transport.
+    async fn send_transaction_isolated(&self, tx: RawTransaction) -> Result<SendResponse, Error>;
+}
+
+/// A [`Transport`] that connects to a `lightwalletd` server over Tor, using a separate
+/// circuit for each submitted transaction.
+#[derive(Clone)]
+pub struct TorTransport {
+    client: Client,
+    endpoint: Uri,
+}
+
+impl TorTransport {
+    /// Constructs a transport that submits transactions to the `lightwalletd` server at
+    /// the given endpoint.
+    ///
+    /// Each submission is made with a new [`Client::isolated_client`] derived from
+    /// `client`, so it will not share circuits with any other use of `client`.
+    pub fn new(client: Client, endpoint: Uri) -> Self {
+        Self { client, endpoint }
+    }
+}
+
+impl Transport for TorTransport {
+    async fn send_transaction_isolated(&self, tx: RawTransaction) -> Result<SendResponse, Error> {
+        let mut conn = self
+            .client
+            .isolated_client()
+            .connect_to_lightwalletd(self.endpoint.clone())
+            .await?;
+
+        let response = conn.send_transaction(tx).await.map_err(GrpcError::Status)?;
+
+        Ok(response.into_inner())
+    }
+}
+
+/// Submits the given transactions in order, each over an isolated connection.
+///
+/// The first transaction is submitted immediately. Before each subsequent transaction is
+/// submitted, a delay is sampled uniformly at random from `step_delay`.
+///
+/// Submission stops at the first transaction that fails to be submitted or is rejected
+/// by the server, because later steps of a proposal may depend on earlier ones; all
+/// transactions prior to the one identified by the returned error have been accepted.
+pub async fn broadcast_transactions<T: Transport>(
+    transport: &T,
+    transactions: &NonEmpty<Transaction>,
+    step_delay: RangeInclusive<Duration>,
+) -> Result<(), SubmitError> {
+    let raw_txs = transactions
+        .iter()
+        .map(|tx| {
+            let mut data = vec![];
+            tx.write(&mut data).expect("writing to a Vec cannot fail");
+            (tx.txid(), RawTransaction { data, height: 0 })
+        })
+        .collect::<Vec<_>>();
+
+    for (i, (txid, raw_tx)) in raw_txs.into_iter().enumerate() {
+        if i > 0 {
+            let delay = thread_rng().gen_range(step_delay.clone());
+            debug!("Waiting {:?} before submitting transaction {}", delay, txid);
+            tokio::time::sleep(delay).await;
+        }
+
+        debug!("Submitting transaction {}", txid);
+        let response = transport
+            .send_transaction_isolated(raw_tx)
+            .await
+            .map_err(|error| SubmitError::Transport { txid, error })?;
+
+        if response.error_code != 0 {
+            return Err(SubmitError::Rejected {
+                txid,
+                code: response.error_code,
+                message: response.error_message,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Submits the transactions created by [`create_proposed_transactions`] in order, each
+/// over an isolated connection.
+///
+/// All of the transactions are read from the wallet before any of them is submitted. See
+/// [`broadcast_transactions`] for details of how the transactions are submitted.
+///
+/// [`create_proposed_transactions`]: crate::data_api::wallet::create_proposed_transactions
+pub async fn broadcast_proposed_transactions<T, DbT>(
+    transport: &T,
+    wallet_db: &DbT,
+    txids: &NonEmpty<TxId>,
+    step_delay: RangeInclusive<Duration>,
+) -> Result<(), BroadcastError<DbT::Error>>
+where
+    T: Transport,
+    DbT: WalletRead,
+{
+    let transactions = load_transactions(wallet_db, txids)?;
+    broadcast_transactions(transport, &transactions, step_delay)
+        .await
+        .map_err(BroadcastError::Submit)
+}
+
+fn load_transactions<DbT: WalletRead>(
+    wallet_db: &DbT,
+    txids: &NonEmpty<TxId>,
+) -> Result<NonEmpty<Transaction>, BroadcastError<DbT::Error>> {
+    let load = |txid: &TxId| {
+        wallet_db
+            .get_transaction(*txid)
+            .map_err(BroadcastError::Data)?
+            .ok_or(BroadcastError::MissingTransaction(*txid))
+    };
+
+    Ok(NonEmpty {
+        head: load(&txids.head)?,
+        tail: txids.tail.iter().map(load).collect::<Result<_, _>>()?,
+    })
+}
+
+/// Errors that can occur while submitting transactions to a `lightwalletd` server.
+#[derive(Debug)]
+pub enum SubmitError {
+    /// An error occurred while submitting the transaction with the given ID.
+    Transport { txid: TxId, error: Error },
+    /// The server rejected the transaction with the given ID.
+    Rejected {
+        txid: TxId,
+        code: i32,
+        message: String,
+    },
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::Transport { txid, error } => {
+                write!(f, "Error submitting transaction {txid}: {error}")
+            }
+            SubmitError::Rejected {
+                txid,
+                code,
+                message,
+            } => write!(
+                f,
+                "Transaction {txid} was rejected by the server with code {code}: {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SubmitError::Transport { error, .. } => Some(error),
+            SubmitError::Rejected { .. } => None,
+        }
+    }
+}
+
+/// Errors that can occur while broadcasting transactions stored in the wallet.
+#[derive(Debug)]
+pub enum BroadcastError<DbErrT> {
+    /// An error occurred while reading transactions from the wallet.
+    Data(DbErrT),
+    /// The wallet does not contain the transaction with the given ID.
+    MissingTransaction(TxId),
+    /// An error occurred while submitting the transactions.
+    Submit(SubmitError),
+}
+
+impl<DbErrT: fmt::Display> fmt::Display for BroadcastError<DbErrT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BroadcastError::Data(e) => write!(f, "Error reading transactions from the wallet: {e}"),
+            BroadcastError::MissingTransaction(txid) => {
+                write!(f, "Transaction {txid} was not found in the wallet")
+            }
+            BroadcastError::Submit(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<DbErrT> std::error::Error for BroadcastError<DbErrT>
+where
+    DbErrT: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BroadcastError::Data(e) => Some(e),
+            BroadcastError::Submit(e) => Some(e),
+            BroadcastError::MissingTransaction(_) => None,
+        }
+    }
+}
+
+impl<DbErrT> From<SubmitError> for BroadcastError<DbErrT> {
+    fn from(e: SubmitError) -> Self {
+        BroadcastError::Submit(e)
+    }
+}
+
+#[cfg(all(test, feature = "lightwalletd-tonic-server"))]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use nonempty::NonEmpty;
+    use zcash_primitives::transaction::{Transaction, TransactionData, TxVersion};
+    use zcash_protocol::consensus::{BlockHeight, BranchId, Network};
+
+    use super::{broadcast_transactions, SubmitError, Transport};
+    use crate::{
+        data_api::chain::{error::Error as ChainError, BlockSource},
+        proto::{
+            compact_formats::CompactBlock,
+            server::LocalLightwalletd,
+            service::{
+                compact_tx_streamer_client::CompactTxStreamerClient,
+                compact_tx_streamer_server::CompactTxStreamerServer, RawTransaction, SendResponse,
+            },
+        },
+        tor::{grpc::GrpcError, Error},
+    };
+
+    struct NoBlocks;
+
+    impl BlockSource for NoBlocks {
+        type Error = Infallible;
+
+        fn with_blocks<F, WalletErrT>(
+            &self,
+            _: Option<BlockHeight>,
+            _: Option<usize>,
+            _: F,
+        ) -> Result<(), ChainError<WalletErrT, Self::Error>>
+        where
+            F: FnMut(CompactBlock) -> Result<(), ChainError<WalletErrT, Self::Error>>,
+        {
+            Ok(())
+        }
+    }
+
+    /// A stand-in for [`super::TorTransport`] that opens a new in-process connection to a
+    /// [`LocalLightwalletd`] for each submission.
+    struct InProcessTransport {
+        server: Arc<LocalLightwalletd<Network, NoBlocks>>,
+        connections: AtomicUsize,
+    }
+
+    impl Transport for InProcessTransport {
+        async fn send_transaction_isolated(
+            &self,
+            tx: RawTransaction,
+        ) -> Result<SendResponse, Error> {
+            self.connections.fetch_add(1, Ordering::SeqCst);
+            let mut conn = CompactTxStreamerClient::new(CompactTxStreamerServer::from_arc(
+                self.server.clone(),
+            ));
+            let response = conn.send_transaction(tx).await.map_err(GrpcError::Status)?;
+            Ok(response.into_inner())
+        }
+    }
+
+    fn tx(lock_time: u32) -> Transaction {
+        TransactionData::from_parts(
+            TxVersion::V4,
+            BranchId::Canopy,
+            lock_time,
+            BlockHeight::from(0),
+            None,
+            None,
+            None,
+            None,
+        )
+        .freeze()
+        .unwrap()
+    }
+
+    #[test]
+    fn broadcasts_each_transaction_over_its_own_connection() {
+        let server = Arc::new(LocalLightwalletd::new(Network::TestNetwork, NoBlocks));
+        server.set_chain_tip(BlockHeight::from(2_000_000));
+        let transport = InProcessTransport {
+            server: server.clone(),
+            connections: AtomicUsize::new(0),
+        };
+
+        let transactions = NonEmpty::from((tx(1), vec![tx(2), tx(3)]));
+        let txids = transactions.iter().map(|tx| tx.txid()).collect::<Vec<_>>();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(broadcast_transactions(
+                &transport,
+                &transactions,
+                Duration::from_millis(1)..=Duration::from_millis(5),
+            ))
+            .unwrap();
+
+        assert_eq!(server.sent_transactions(), txids);
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn broadcast_stops_at_first_failure() {
+        // Without a chain tip, the server cannot accept transactions.
+        let server = Arc::new(LocalLightwalletd::new(Network::TestNetwork, NoBlocks));
+        let transport = InProcessTransport {
+            server: server.clone(),
+            connections: AtomicUsize::new(0),
+        };
+
+        let transactions = NonEmpty::from((tx(1), vec![tx(2)]));
+        let first_txid = transactions.head.txid();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(broadcast_transactions(
+            &transport,
+            &transactions,
+            Duration::ZERO..=Duration::ZERO,
+        ));
+
+        assert!(matches!(
+            result,
+            Err(SubmitError::Transport { txid, .. }) if txid == first_txid
+        ));
+        assert!(server.sent_transactions().is_empty());
+        assert_eq!(transport.connections.load(Ordering::SeqCst), 1);
+    }
+}
@@ -84,6 +84,8 @@ impl Service<Uri> for HttpTcpConnector {
 pub enum GrpcError {
     /// A [`tonic`] error.
     Tonic(tonic::transport::Error),
+    /// An error status returned by the server in response to a request.
+    Status(tonic::Status),
 }
 
 impl fmt::Display for GrpcError {
@@ -98,6 +100,7 @@ impl fmt::Display for GrpcError {
                     write!(f, "Tonic error: {e}")
                 }
             }
+            GrpcError::Status(status) => write!(f, "gRPC status: {status}"),
         }
     }
 }
@@ -106,6 +109,7 @@ impl std::error::Error for GrpcError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             GrpcError::Tonic(e) => Some(e),
+            GrpcError::Status(status) => Some(status),
         }
     }
 }
@@ -349,6 +349,11 @@ pub enum HttpError {
     /// [`Client::get_latest_zec_to_usd_rate`]. Generic APIs like [`Client::http_get`]
     /// will not return this error variant.
     Unsuccessful(hyper::http::StatusCode),
+    /// The server reported errors in the body of an otherwise successful response.
+    ///
+    /// This is only returned by APIs that make specific queries, such as
+    /// [`Client::get_historical_zec_rate`].
+    Api(Vec<String>),
 }
 
 impl fmt::Display for HttpError {
@@ -361,6 +366,7 @@ impl fmt::Display for HttpError {
             HttpError::Spawn(e) => write!(f, "Failed to spawn task: {e}"),
             HttpError::Tls(e) => write!(f, "TLS error: {e}"),
             HttpError::Unsuccessful(status) => write!(f, "Request was unsuccessful ({status:?})"),
+            HttpError::Api(errors) => write!(f, "Server reported errors: {}", errors.join(", ")),
         }
     }
 }
@@ -375,6 +381,7 @@ impl std::error::Error for HttpError {
             HttpError::Spawn(e) => Some(e),
             HttpError::Tls(e) => Some(e),
             HttpError::Unsuccessful(_) => None,
+            HttpError::Api(_) => None,
         }
     }
 }
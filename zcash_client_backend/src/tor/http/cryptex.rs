@@ -10,10 +10,13 @@ use crate::tor::{Client, Error};
 
 use super::Retry;
 
+pub mod history;
+
 mod binance;
 mod coinbase;
 mod gate_io;
 mod gemini;
+mod kraken;
 mod ku_coin;
 mod mexc;
 
@@ -56,6 +59,7 @@ pub mod exchanges {
     pub use super::coinbase::Coinbase;
     pub use super::gate_io::GateIo;
     pub use super::gemini::Gemini;
+    pub use super::kraken::Kraken;
     pub use super::ku_coin::KuCoin;
     pub use super::mexc::Mexc;
 }
@@ -1,7 +1,10 @@
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use super::{retry_filter, Exchange, ExchangeData, RETRY_LIMIT};
+use super::{
+    history::{Currency, Day, HistoricalExchange, Ohlc},
+    retry_filter, Exchange, ExchangeData, RETRY_LIMIT,
+};
 use crate::tor::{Client, Error};
 
 /// Querier for the Binance exchange.
@@ -63,3 +66,60 @@ impl Exchange for Binance {
         })
     }
 }
+
+/// A single kline, as returned by the Binance klines endpoint.
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct BinanceKline(
+    u64,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    u64,
+    Decimal,
+    u64,
+    Decimal,
+    Decimal,
+    String,
+);
+
+impl HistoricalExchange for Binance {
+    async fn query_zec_daily_ohlc(
+        &self,
+        client: &Client,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Option<Ohlc>, Error> {
+        let symbol = match currency {
+            Currency::Usd => "ZECUSDT",
+            _ => return Ok(None),
+        };
+
+        // API documentation:
+        // https://developers.binance.com/docs/binance-spot-api-docs/rest-api/market-data-endpoints#klinecandlestick-data
+        let start_ms = day.start_unix_time() * 1000;
+        let res = client
+            .http_get_json::<Vec<BinanceKline>>(
+                format!(
+                    "https://api.binance.com/api/v3/klines?symbol={symbol}&interval=1d&startTime={start_ms}&limit=1"
+                )
+                .parse()
+                .unwrap(),
+                RETRY_LIMIT,
+                retry_filter,
+            )
+            .await?;
+        Ok(res
+            .into_body()
+            .into_iter()
+            .find(|k| k.0 == start_ms)
+            .map(|k| Ohlc {
+                open: k.1,
+                high: k.2,
+                low: k.3,
+                close: k.4,
+            }))
+    }
+}
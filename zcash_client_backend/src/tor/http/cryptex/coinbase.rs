@@ -1,7 +1,10 @@
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use super::{retry_filter, Exchange, ExchangeData, RETRY_LIMIT};
+use super::{
+    history::{Currency, Day, HistoricalExchange, Ohlc},
+    retry_filter, Exchange, ExchangeData, RETRY_LIMIT,
+};
 use crate::tor::{Client, Error};
 
 /// Querier for the Coinbase exchange.
@@ -51,3 +54,46 @@ impl Exchange for Coinbase {
         })
     }
 }
+
+/// A single candle, as returned by the Coinbase product candles endpoint.
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct CoinbaseCandle(u64, Decimal, Decimal, Decimal, Decimal, Decimal);
+
+impl HistoricalExchange for Coinbase {
+    async fn query_zec_daily_ohlc(
+        &self,
+        client: &Client,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Option<Ohlc>, Error> {
+        let product = match currency {
+            Currency::Usd => "ZEC-USD",
+            _ => return Ok(None),
+        };
+
+        // API documentation:
+        // https://docs.cdp.coinbase.com/exchange/reference/exchangerestapi_getproductcandles
+        let res = client
+            .http_get_json::<Vec<CoinbaseCandle>>(
+                format!(
+                    "https://api.exchange.coinbase.com/products/{product}/candles?granularity=86400&start={day}T00:00:00Z&end={day}T00:00:00Z"
+                )
+                .parse()
+                .unwrap(),
+                RETRY_LIMIT,
+                retry_filter,
+            )
+            .await?;
+        Ok(res
+            .into_body()
+            .into_iter()
+            .find(|c| c.0 == day.start_unix_time())
+            .map(|c| Ohlc {
+                open: c.3,
+                high: c.2,
+                low: c.1,
+                close: c.4,
+            }))
+    }
+}
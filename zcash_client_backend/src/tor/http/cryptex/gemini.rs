@@ -1,7 +1,10 @@
 use rust_decimal::Decimal;
 use serde::Deserialize;
 
-use super::{retry_filter, Exchange, ExchangeData, RETRY_LIMIT};
+use super::{
+    history::{Currency, Day, HistoricalExchange, Ohlc},
+    retry_filter, Exchange, ExchangeData, RETRY_LIMIT,
+};
 use crate::tor::{Client, Error};
 
 /// Querier for the Gemini exchange.
@@ -47,3 +50,50 @@ impl Exchange for Gemini {
         })
     }
 }
+
+/// A single candle, as returned by the Gemini candles endpoint.
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct GeminiCandle(u64, Decimal, Decimal, Decimal, Decimal, Decimal);
+
+impl HistoricalExchange for Gemini {
+    async fn query_zec_daily_ohlc(
+        &self,
+        client: &Client,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Option<Ohlc>, Error> {
+        let symbol = match currency {
+            Currency::Usd => "zecusd",
+            _ => return Ok(None),
+        };
+
+        // API documentation:
+        // https://docs.gemini.com/rest-api/#candles
+        //
+        // This endpoint always returns the most recent candles (at most around 720 for
+        // the daily interval), and does not support requesting a time range or paging
+        // through older data. Older days will therefore not be found, and are left to
+        // other exchanges.
+        let res = client
+            .http_get_json::<Vec<GeminiCandle>>(
+                format!("https://api.gemini.com/v2/candles/{symbol}/1day")
+                    .parse()
+                    .unwrap(),
+                RETRY_LIMIT,
+                retry_filter,
+            )
+            .await?;
+        let start_ms = day.start_unix_time() * 1000;
+        Ok(res
+            .into_body()
+            .into_iter()
+            .find(|c| c.0 == start_ms)
+            .map(|c| Ohlc {
+                open: c.1,
+                high: c.2,
+                low: c.3,
+                close: c.4,
+            }))
+    }
+}
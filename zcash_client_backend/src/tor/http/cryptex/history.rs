@@ -0,0 +1,744 @@
+//! Historical exchange rates.
+//!
+//! Wallets that display transaction history with fiat values need the exchange rate at
+//! the time of each payment, not the latest rate. This module queries daily OHLC
+//! ("open, high, low, close") data from several exchanges, rejects outlying quotes,
+//! requires a configurable quorum of agreeing sources, and caches the combined rate for
+//! each completed day so that it only needs to be fetched once.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures_util::future::join_all;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use tracing::{error, trace};
+
+use super::exchanges;
+use crate::tor::{Client, Error};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// A fiat currency in which ZEC exchange rates can be quoted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Currency {
+    /// United States dollar.
+    ///
+    /// Exchanges that quote ZEC against USDT rather than USD are treated as quoting USD.
+    Usd,
+    /// Euro.
+    Eur,
+}
+
+impl Currency {
+    /// Returns the ISO 4217 code for this currency.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Eur => "EUR",
+        }
+    }
+}
+
+/// A calendar day in UTC, represented as the number of days since the Unix epoch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Day(u32);
+
+impl Day {
+    /// Returns the day with the given index, counted in days since 1970-01-01.
+    pub fn from_index(days_since_epoch: u32) -> Self {
+        Day(days_since_epoch)
+    }
+
+    /// Returns the day corresponding to the given proleptic Gregorian calendar date, or
+    /// `None` if the date is invalid or precedes the Unix epoch.
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        let days = days_from_civil(i64::from(year), i64::from(month), i64::from(day));
+        u32::try_from(days)
+            .ok()
+            .map(Day)
+            .filter(|d| d.to_ymd() == (year, month, day))
+    }
+
+    /// Returns the day containing the given Unix timestamp (in seconds).
+    pub fn containing_unix_time(secs: u64) -> Self {
+        Day(u32::try_from(secs / SECONDS_PER_DAY).expect("timestamp is within range"))
+    }
+
+    /// Returns the day containing the given time, or `None` if it precedes the Unix
+    /// epoch.
+    pub fn containing(time: SystemTime) -> Option<Self> {
+        time.duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|d| Self::containing_unix_time(d.as_secs()))
+    }
+
+    /// Returns the current day.
+    pub fn today() -> Self {
+        Self::containing(SystemTime::now()).expect("system time is after the Unix epoch")
+    }
+
+    /// Returns the number of days between the Unix epoch and this day.
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns the Unix timestamp (in seconds) of the start of this day.
+    pub fn start_unix_time(&self) -> u64 {
+        u64::from(self.0) * SECONDS_PER_DAY
+    }
+
+    /// Returns the year, month, and day-of-month of this day.
+    pub fn to_ymd(&self) -> (i32, u32, u32) {
+        let (y, m, d) = civil_from_days(i64::from(self.0));
+        (y as i32, m as u32, d as u32)
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (y, m, d) = self.to_ymd();
+        write!(f, "{y:04}-{m:02}-{d:02}")
+    }
+}
+
+// Conversions between days since the Unix epoch and proleptic Gregorian calendar dates,
+// from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(m <= 2), m, d)
+}
+
+/// The open, high, low, and close prices of 1 ZEC over a single day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ohlc {
+    /// The price at the start of the day.
+    pub open: Decimal,
+    /// The highest price during the day.
+    pub high: Decimal,
+    /// The lowest price during the day.
+    pub low: Decimal,
+    /// The price at the end of the day.
+    pub close: Decimal,
+}
+
+/// An exchange that can be queried for historical ZEC data.
+#[trait_variant::make(HistoricalExchange: Send)]
+#[dynosaur::dynosaur(DynHistoricalExchange = dyn HistoricalExchange)]
+#[dynosaur::dynosaur(DynLocalHistoricalExchange = dyn LocalHistoricalExchange)]
+pub trait LocalHistoricalExchange {
+    /// Queries the daily OHLC data for the ZEC pair quoted in the given currency.
+    ///
+    /// Returns `Ok(None)` if the exchange does not list a ZEC pair quoted in `currency`,
+    /// or has no data for `day`.
+    async fn query_zec_daily_ohlc(
+        &self,
+        client: &Client,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Option<Ohlc>, Error>;
+}
+
+/// The conditions under which quotes from several exchanges are combined into a rate.
+#[derive(Clone, Copy, Debug)]
+pub struct Quorum {
+    min_sources: usize,
+    max_deviation: Decimal,
+}
+
+impl Quorum {
+    /// Constructs a quorum requiring at least `min_sources` agreeing quotes, where quotes
+    /// agree if their closing prices are within 10% of the median closing price.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min_sources` is zero.
+    pub fn new(min_sources: usize) -> Self {
+        assert!(min_sources > 0);
+        Self {
+            min_sources,
+            max_deviation: Decimal::new(1, 1),
+        }
+    }
+
+    /// Sets the maximum fractional deviation from the median closing price at which a
+    /// quote is still considered to agree with the others.
+    pub fn with_max_deviation(mut self, max_deviation: Decimal) -> Self {
+        self.max_deviation = max_deviation;
+        self
+    }
+
+    /// Returns the minimum number of agreeing quotes.
+    pub fn min_sources(&self) -> usize {
+        self.min_sources
+    }
+
+    /// Returns the maximum fractional deviation from the median closing price.
+    pub fn max_deviation(&self) -> Decimal {
+        self.max_deviation
+    }
+
+    /// Combines the given quotes into a single rate.
+    ///
+    /// Quotes whose closing prices deviate from the median closing price by more than
+    /// [`Self::max_deviation`] are rejected as outliers. If at least
+    /// [`Self::min_sources`] quotes remain, the combined rate is the median of each of
+    /// their components; otherwise, the number of remaining quotes is returned as an
+    /// error.
+    pub fn combine(&self, quotes: &[Ohlc]) -> Result<Ohlc, usize> {
+        let accepted = match median(quotes.iter().map(|q| q.close)) {
+            Some(median_close) => {
+                let tolerance = median_close * self.max_deviation;
+                quotes
+                    .iter()
+                    .filter(|q| (q.close - median_close).abs() <= tolerance)
+                    .collect::<Vec<_>>()
+            }
+            None => vec![],
+        };
+
+        if accepted.len() < self.min_sources {
+            return Err(accepted.len());
+        }
+
+        let field = |f: fn(&Ohlc) -> Decimal| {
+            median(accepted.iter().copied().map(f)).expect("accepted quotes are non-empty")
+        };
+        Ok(Ohlc {
+            open: field(|q| q.open),
+            high: field(|q| q.high),
+            low: field(|q| q.low),
+            close: field(|q| q.close),
+        })
+    }
+}
+
+impl Default for Quorum {
+    /// Requires two agreeing quotes.
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+fn median(values: impl Iterator<Item = Decimal>) -> Option<Decimal> {
+    let mut values = values.collect::<Vec<_>>();
+    values.sort();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((values[mid - 1] + values[mid]) / Decimal::TWO),
+        _ => Some(values[mid]),
+    }
+}
+
+/// A set of [`HistoricalExchange`]s that can be queried for historical ZEC data.
+pub struct HistoricalExchanges {
+    sources: Vec<Box<DynHistoricalExchange<'static>>>,
+    quorum: Quorum,
+    currency_quorums: BTreeMap<Currency, Quorum>,
+}
+
+impl HistoricalExchanges {
+    /// Unauthenticated connections to all known exchanges that provide historical data.
+    ///
+    /// USD quotes are combined according to the default [`Quorum`]. Kraken is the only
+    /// known exchange that provides EUR quotes, so a single EUR quote is accepted.
+    pub fn unauthenticated_known() -> Self {
+        Self::builder(Quorum::default())
+            .with(exchanges::Binance::unauthenticated())
+            .with(exchanges::Coinbase::unauthenticated())
+            .with(exchanges::Gemini::unauthenticated())
+            .with(exchanges::Kraken::unauthenticated())
+            .with_quorum_for(Currency::Eur, Quorum::new(1))
+            .build()
+    }
+
+    /// Returns a `HistoricalExchanges` builder that will combine quotes according to the
+    /// given quorum.
+    pub fn builder(quorum: Quorum) -> HistoricalExchangesBuilder {
+        HistoricalExchangesBuilder(Self {
+            sources: vec![],
+            quorum,
+            currency_quorums: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the quorum according to which quotes in the given currency are combined.
+    pub fn quorum(&self, currency: Currency) -> Quorum {
+        self.currency_quorums
+            .get(&currency)
+            .copied()
+            .unwrap_or(self.quorum)
+    }
+}
+
+/// Builder type for [`HistoricalExchanges`].
+pub struct HistoricalExchangesBuilder(HistoricalExchanges);
+
+impl HistoricalExchangesBuilder {
+    /// Adds a [`HistoricalExchange`] as a data source.
+    pub fn with(mut self, source: impl HistoricalExchange + 'static) -> Self {
+        self.0.sources.push(DynHistoricalExchange::boxed(source));
+        self
+    }
+
+    /// Combines quotes in the given currency according to `quorum`, instead of the quorum
+    /// passed to [`HistoricalExchanges::builder`].
+    ///
+    /// This is useful for currencies that are quoted by fewer of the sources.
+    pub fn with_quorum_for(mut self, currency: Currency, quorum: Quorum) -> Self {
+        self.0.currency_quorums.insert(currency, quorum);
+        self
+    }
+
+    /// Builds the [`HistoricalExchanges`].
+    pub fn build(self) -> HistoricalExchanges {
+        self.0
+    }
+}
+
+/// A store of previously-fetched daily exchange rates.
+pub trait RateCache {
+    /// The type of errors produced by the cache.
+    type Error;
+
+    /// Returns the cached rate for the given currency and day, if any.
+    fn get(&self, currency: Currency, day: Day) -> Result<Option<Ohlc>, Self::Error>;
+
+    /// Stores the rate for the given currency and day.
+    fn insert(&mut self, currency: Currency, day: Day, rate: Ohlc) -> Result<(), Self::Error>;
+}
+
+/// An in-memory [`RateCache`].
+#[derive(Clone, Debug, Default)]
+pub struct MemoryRateCache {
+    rates: BTreeMap<(Currency, Day), Ohlc>,
+}
+
+impl MemoryRateCache {
+    /// Constructs an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RateCache for MemoryRateCache {
+    type Error = std::convert::Infallible;
+
+    fn get(&self, currency: Currency, day: Day) -> Result<Option<Ohlc>, Self::Error> {
+        Ok(self.rates.get(&(currency, day)).copied())
+    }
+
+    fn insert(&mut self, currency: Currency, day: Day, rate: Ohlc) -> Result<(), Self::Error> {
+        self.rates.insert((currency, day), rate);
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    currency: Currency,
+    day: Day,
+    rate: Ohlc,
+}
+
+/// A [`RateCache`] that persists rates to a JSON file.
+///
+/// The file is read when the cache is opened, and rewritten in full whenever a rate is
+/// inserted.
+#[derive(Debug)]
+pub struct FileRateCache {
+    path: PathBuf,
+    rates: MemoryRateCache,
+}
+
+impl FileRateCache {
+    /// Opens the cache stored at the given path, creating an empty cache if the file does
+    /// not exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut rates = MemoryRateCache::new();
+        match fs::read(&path) {
+            Ok(data) => {
+                let entries: Vec<CacheEntry> = serde_json::from_slice(&data)?;
+                rates
+                    .rates
+                    .extend(entries.into_iter().map(|e| ((e.currency, e.day), e.rate)));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e),
+        }
+        Ok(Self { path, rates })
+    }
+
+    fn persist(&self) -> io::Result<()> {
+        let entries = self
+            .rates
+            .rates
+            .iter()
+            .map(|(&(currency, day), &rate)| CacheEntry {
+                currency,
+                day,
+                rate,
+            })
+            .collect::<Vec<_>>();
+
+        // Write to a temporary file first, so that the cache is never left truncated.
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec(&entries)?)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+impl RateCache for FileRateCache {
+    type Error = io::Error;
+
+    fn get(&self, currency: Currency, day: Day) -> Result<Option<Ohlc>, Self::Error> {
+        Ok(self.rates.rates.get(&(currency, day)).copied())
+    }
+
+    fn insert(&mut self, currency: Currency, day: Day, rate: Ohlc) -> Result<(), Self::Error> {
+        self.rates.rates.insert((currency, day), rate);
+        self.persist()
+    }
+}
+
+/// Errors that can occur while fetching historical exchange rates.
+#[derive(Debug)]
+pub enum HistoryError<CacheErrT> {
+    /// An error occurred while reading from or writing to the rate cache.
+    Cache(CacheErrT),
+    /// None of the exchange queries succeeded.
+    Tor(Error),
+    /// Too few exchanges provided agreeing quotes to satisfy the [`Quorum`].
+    QuorumNotReached {
+        /// The number of agreeing quotes that were obtained.
+        agreeing: usize,
+        /// The number of agreeing quotes required by the quorum.
+        required: usize,
+    },
+}
+
+impl<CacheErrT: fmt::Display> fmt::Display for HistoryError<CacheErrT> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::Cache(e) => write!(f, "Rate cache error: {e}"),
+            HistoryError::Tor(e) => write!(f, "{e}"),
+            HistoryError::QuorumNotReached { agreeing, required } => write!(
+                f,
+                "Only {agreeing} exchanges provided agreeing quotes; {required} are required"
+            ),
+        }
+    }
+}
+
+impl<CacheErrT> std::error::Error for HistoryError<CacheErrT>
+where
+    CacheErrT: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HistoryError::Cache(e) => Some(e),
+            HistoryError::Tor(e) => Some(e),
+            HistoryError::QuorumNotReached { .. } => None,
+        }
+    }
+}
+
+impl Client {
+    /// Fetches the ZEC exchange rate in the given currency over the given day, derived
+    /// from the given exchanges.
+    ///
+    /// If `cache` contains a rate for the day, it is returned without querying any
+    /// exchanges. Otherwise, the exchanges are queried in parallel and their quotes are
+    /// combined according to the exchanges' [`Quorum`] for `currency`. The combined rate
+    /// is added to `cache` if `day` has already ended.
+    pub async fn get_historical_zec_rate<C: RateCache>(
+        &self,
+        exchanges: &HistoricalExchanges,
+        cache: &mut C,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Ohlc, HistoryError<C::Error>> {
+        if let Some(rate) = cache.get(currency, day).map_err(HistoryError::Cache)? {
+            return Ok(rate);
+        }
+
+        self.ensure_bootstrapped()
+            .await
+            .map_err(HistoryError::Tor)?;
+
+        fetch_historical_rate(
+            &exchanges.sources,
+            exchanges.quorum(currency),
+            cache,
+            currency,
+            day,
+            |e| e.query_zec_daily_ohlc(self, currency, day),
+        )
+        .await
+    }
+}
+
+/// Queries each of the given sources in parallel using `fetch`, and combines their quotes
+/// according to `quorum`.
+///
+/// The combined rate is added to `cache` if `day` has already ended.
+async fn fetch_historical_rate<'a, E, C, F, Fut>(
+    sources: &'a [E],
+    quorum: Quorum,
+    cache: &mut C,
+    currency: Currency,
+    day: Day,
+    fetch: F,
+) -> Result<Ohlc, HistoryError<C::Error>>
+where
+    C: RateCache,
+    F: FnMut(&'a E) -> Fut,
+    Fut: Future<Output = Result<Option<Ohlc>, Error>>,
+{
+    // Fetch the data in parallel.
+    let res = join_all(sources.iter().map(fetch)).await;
+    trace!(?res, "Historical data results");
+
+    let mut quotes = vec![];
+    let mut errors = vec![];
+    for res in res {
+        match res {
+            Ok(Some(quote)) => quotes.push(quote),
+            Ok(None) => (),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    if quotes.is_empty() {
+        if let Some(e) = errors.into_iter().next() {
+            error!("All historical exchange requests failed");
+            return Err(HistoryError::Tor(e));
+        }
+    }
+
+    let rate = quorum
+        .combine(&quotes)
+        .map_err(|agreeing| HistoryError::QuorumNotReached {
+            agreeing,
+            required: quorum.min_sources(),
+        })?;
+
+    // Only cache rates for days that have ended, as the rates may still change.
+    if day < Day::today() {
+        cache
+            .insert(currency, day, rate)
+            .map_err(HistoryError::Cache)?;
+    }
+
+    Ok(rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future;
+    use hyper::StatusCode;
+    use rust_decimal::Decimal;
+
+    use super::{
+        fetch_historical_rate, Currency, Day, FileRateCache, HistoricalExchanges, HistoryError,
+        MemoryRateCache, Ohlc, Quorum, RateCache,
+    };
+    use crate::tor::{http::HttpError, Error};
+
+    /// The behaviour of a fake exchange.
+    enum Source {
+        Quote(i64),
+        NoData,
+        Fails,
+    }
+
+    fn fetch(source: &Source) -> future::Ready<Result<Option<Ohlc>, Error>> {
+        future::ready(match source {
+            Source::Quote(close) => Ok(Some(quote(*close))),
+            Source::NoData => Ok(None),
+            Source::Fails => Err(Error::Http(HttpError::Unsuccessful(
+                StatusCode::SERVICE_UNAVAILABLE,
+            ))),
+        })
+    }
+
+    fn quote(close: i64) -> Ohlc {
+        Ohlc {
+            open: Decimal::from(close - 1),
+            high: Decimal::from(close + 2),
+            low: Decimal::from(close - 2),
+            close: Decimal::from(close),
+        }
+    }
+
+    #[test]
+    fn day_conversions() {
+        assert_eq!(Day::from_ymd(1970, 1, 1), Some(Day::from_index(0)));
+        assert_eq!(Day::from_ymd(2016, 10, 28).map(|d| d.index()), Some(17102));
+        assert_eq!(
+            Day::from_ymd(2024, 2, 29).unwrap().to_string(),
+            "2024-02-29"
+        );
+        assert_eq!(Day::from_ymd(2023, 2, 29), None);
+        assert_eq!(Day::from_ymd(1969, 12, 31), None);
+        assert_eq!(
+            Day::containing_unix_time(1_477_699_199),
+            Day::from_ymd(2016, 10, 28).unwrap()
+        );
+        assert_eq!(Day::from_index(17102).start_unix_time(), 1_477_612_800);
+    }
+
+    #[test]
+    fn quorum_rejects_outliers() {
+        let quorum = Quorum::new(3);
+
+        // A single manipulated quote is rejected, leaving enough agreeing quotes.
+        let combined = quorum
+            .combine(&[quote(30), quote(31), quote(90), quote(32)])
+            .unwrap();
+        assert_eq!(combined, quote(31));
+
+        // Without enough agreeing quotes, no rate is produced.
+        assert_eq!(quorum.combine(&[quote(30), quote(90), quote(32)]), Err(2));
+        assert_eq!(quorum.combine(&[]), Err(0));
+
+        // A looser tolerance accepts the outlier.
+        let combined = quorum
+            .with_max_deviation(Decimal::from(2))
+            .combine(&[quote(30), quote(90), quote(32)])
+            .unwrap();
+        assert_eq!(combined, quote(32));
+    }
+
+    #[test]
+    fn file_cache_persists_rates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rates.json");
+        let day = Day::from_ymd(2024, 1, 1).unwrap();
+
+        let mut cache = FileRateCache::open(&path).unwrap();
+        assert_eq!(cache.get(Currency::Usd, day).unwrap(), None);
+        cache.insert(Currency::Usd, day, quote(25)).unwrap();
+
+        let cache = FileRateCache::open(&path).unwrap();
+        assert_eq!(cache.get(Currency::Usd, day).unwrap(), Some(quote(25)));
+        assert_eq!(cache.get(Currency::Eur, day).unwrap(), None);
+    }
+
+    #[test]
+    fn fetch_combines_quotes_and_caches_completed_days() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let sources = [
+                Source::Quote(30),
+                Source::NoData,
+                Source::Fails,
+                Source::Quote(32),
+            ];
+            let mut cache = MemoryRateCache::new();
+
+            // Failing and empty sources are ignored if enough others agree.
+            let day = Day::from_ymd(2024, 1, 1).unwrap();
+            let rate = fetch_historical_rate(
+                &sources,
+                Quorum::new(2),
+                &mut cache,
+                Currency::Usd,
+                day,
+                fetch,
+            )
+            .await
+            .unwrap();
+            assert_eq!(rate.close, Decimal::from(31));
+            assert_eq!(cache.get(Currency::Usd, day).unwrap(), Some(rate));
+
+            // Rates for the current day may still change, so are not cached.
+            let today = Day::today();
+            fetch_historical_rate(
+                &sources,
+                Quorum::new(2),
+                &mut cache,
+                Currency::Usd,
+                today,
+                fetch,
+            )
+            .await
+            .unwrap();
+            assert_eq!(cache.get(Currency::Usd, today).unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn fetch_reports_failures() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let day = Day::from_ymd(2024, 1, 1).unwrap();
+            let mut cache = MemoryRateCache::new();
+
+            // If every source fails, the error is returned.
+            let res = fetch_historical_rate(
+                &[Source::Fails, Source::Fails],
+                Quorum::new(1),
+                &mut cache,
+                Currency::Usd,
+                day,
+                fetch,
+            )
+            .await;
+            assert!(matches!(res, Err(HistoryError::Tor(_))));
+
+            // Too few agreeing quotes do not produce a rate.
+            let res = fetch_historical_rate(
+                &[Source::Quote(30), Source::Fails, Source::NoData],
+                Quorum::new(2),
+                &mut cache,
+                Currency::Usd,
+                day,
+                fetch,
+            )
+            .await;
+            assert!(matches!(
+                res,
+                Err(HistoryError::QuorumNotReached {
+                    agreeing: 1,
+                    required: 2
+                })
+            ));
+            assert_eq!(cache.get(Currency::Usd, day).unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn known_exchanges_quorum_per_currency() {
+        let exchanges = HistoricalExchanges::unauthenticated_known();
+        assert_eq!(exchanges.quorum(Currency::Usd).min_sources(), 2);
+        // Only Kraken provides EUR quotes.
+        assert_eq!(exchanges.quorum(Currency::Eur).min_sources(), 1);
+    }
+}
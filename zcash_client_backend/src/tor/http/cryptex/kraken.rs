@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use super::{
+    history::{Currency, Day, HistoricalExchange, Ohlc},
+    retry_filter, RETRY_LIMIT,
+};
+use crate::tor::{http::HttpError, Client, Error};
+
+/// The maximum number of candles returned by the Kraken OHLC endpoint.
+///
+/// Kraken returns at most this many of the most recent candles, regardless of the
+/// requested start time, and does not support paging through older data.
+const MAX_CANDLES: u32 = 720;
+
+/// Querier for the Kraken exchange.
+///
+/// Kraken is currently only used as a source of historical data. Daily data is only
+/// available for the most recent 720 days.
+pub struct Kraken {
+    _private: (),
+}
+
+impl Kraken {
+    /// Prepares for unauthenticated connections to Kraken.
+    pub fn unauthenticated() -> Self {
+        Self { _private: () }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KrakenResponse {
+    error: Vec<String>,
+    result: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// A single candle, as returned by the Kraken OHLC endpoint.
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct KrakenCandle(
+    u64,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    Decimal,
+    u64,
+);
+
+impl HistoricalExchange for Kraken {
+    async fn query_zec_daily_ohlc(
+        &self,
+        client: &Client,
+        currency: Currency,
+        day: Day,
+    ) -> Result<Option<Ohlc>, Error> {
+        let pair = match currency {
+            Currency::Usd => "ZECUSD",
+            Currency::Eur => "ZECEUR",
+        };
+
+        // Older candles cannot be retrieved, so don't make a request that can't succeed.
+        if Day::today().index().saturating_sub(day.index()) >= MAX_CANDLES {
+            return Ok(None);
+        }
+
+        // API documentation:
+        // https://docs.kraken.com/api/docs/rest-api/get-ohlc-data
+        let since = day.start_unix_time().saturating_sub(1);
+        let res = client
+            .http_get_json::<KrakenResponse>(
+                format!(
+                    "https://api.kraken.com/0/public/OHLC?pair={pair}&interval=1440&since={since}"
+                )
+                .parse()
+                .unwrap(),
+                RETRY_LIMIT,
+                retry_filter,
+            )
+            .await?;
+        let data = res.into_body();
+        if !data.error.is_empty() {
+            return Err(HttpError::Api(data.error).into());
+        }
+
+        // The candles are keyed by Kraken's internal name for the pair, alongside a
+        // `last` field that we don't need.
+        let candles = match data
+            .result
+            .into_iter()
+            .flatten()
+            .find(|(key, _)| key != "last")
+        {
+            Some((_, candles)) => {
+                serde_json::from_value::<Vec<KrakenCandle>>(candles).map_err(HttpError::from)?
+            }
+            None => return Ok(None),
+        };
+
+        Ok(candles
+            .into_iter()
+            .find(|c| c.0 == day.start_unix_time())
+            .map(|c| Ohlc {
+                open: c.1,
+                high: c.2,
+                low: c.3,
+                close: c.4,
+            }))
+    }
+}
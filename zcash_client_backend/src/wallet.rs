@@ -98,6 +98,10 @@ pub struct WalletTx<AccountId> {
     orchard_spends: Vec<WalletOrchardSpend<AccountId>>,
     #[cfg(feature = "orchard")]
     orchard_outputs: Vec<WalletOrchardOutput<AccountId>>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent_spends: Vec<WalletTransparentSpend<AccountId>>,
+    #[cfg(feature = "transparent-inputs")]
+    transparent_outputs: Vec<WalletTransparentOutput>,
 }
 
 impl<AccountId> WalletTx<AccountId> {
@@ -121,9 +125,35 @@ impl<AccountId> WalletTx<AccountId> {
             orchard_spends,
             #[cfg(feature = "orchard")]
             orchard_outputs,
+            #[cfg(feature = "transparent-inputs")]
+            transparent_spends: vec![],
+            #[cfg(feature = "transparent-inputs")]
+            transparent_outputs: vec![],
         }
     }
 
+    /// Sets the records of the wallet's transparent outputs that were spent in the
+    /// transaction.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn with_transparent_spends(
+        mut self,
+        transparent_spends: Vec<WalletTransparentSpend<AccountId>>,
+    ) -> Self {
+        self.transparent_spends = transparent_spends;
+        self
+    }
+
+    /// Sets the records of the transparent outputs received by the wallet in the
+    /// transaction.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn with_transparent_outputs(
+        mut self,
+        transparent_outputs: Vec<WalletTransparentOutput>,
+    ) -> Self {
+        self.transparent_outputs = transparent_outputs;
+        self
+    }
+
     /// Returns the [`TxId`] for the corresponding [`Transaction`].
     ///
     /// [`Transaction`]: zcash_primitives::transaction::Transaction
@@ -161,6 +191,19 @@ impl<AccountId> WalletTx<AccountId> {
     pub fn orchard_outputs(&self) -> &[WalletOrchardOutput<AccountId>] {
         self.orchard_outputs.as_ref()
     }
+
+    /// Returns a record for each transparent output belonging to the wallet that was spent
+    /// in the transaction.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_spends(&self) -> &[WalletTransparentSpend<AccountId>] {
+        self.transparent_spends.as_ref()
+    }
+
+    /// Returns each transparent output received by the wallet in the transaction.
+    #[cfg(feature = "transparent-inputs")]
+    pub fn transparent_outputs(&self) -> &[WalletTransparentOutput] {
+        self.transparent_outputs.as_ref()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -234,8 +277,8 @@ impl<Nf, AccountId> WalletSpend<Nf, AccountId> {
         }
     }
 
-    /// Returns the index of the Sapling spend or Orchard action within the transaction that
-    /// created this spend.
+    /// Returns the index of the Sapling spend, Orchard action, or transparent input within
+    /// the transaction that created this spend.
     pub fn index(&self) -> usize {
         self.index
     }
@@ -256,6 +299,11 @@ pub type WalletSaplingSpend<AccountId> = WalletSpend<sapling::Nullifier, Account
 #[cfg(feature = "orchard")]
 pub type WalletOrchardSpend<AccountId> = WalletSpend<orchard::note::Nullifier, AccountId>;
 
+/// A type alias for transparent [`WalletSpend`]s, in which the outpoint of the spent
+/// output plays the role of the nullifier.
+#[cfg(feature = "transparent-inputs")]
+pub type WalletTransparentSpend<AccountId> = WalletSpend<OutPoint, AccountId>;
+
 /// An output that was successfully decrypted in the process of wallet scanning.
 pub struct WalletOutput<Note, Nullifier, AccountId> {
     index: usize,
@@ -414,4 +414,289 @@ mod tests {
     fn scan_cached_blocks_detects_spends_out_of_order_orchard() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<OrchardPoolTester>()
     }
+
+    #[test]
+    fn scan_cached_block_ranges_detects_spends_across_ranges_sapling() {
+        testing::pool::scan_cached_block_ranges_detects_spends_across_ranges::<SaplingPoolTester>()
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn scan_cached_block_ranges_detects_spends_across_ranges_orchard() {
+        testing::pool::scan_cached_block_ranges_detects_spends_across_ranges::<OrchardPoolTester>()
+    }
+
+    #[cfg(feature = "sync-tests")]
+    mod sync {
+        use std::collections::BTreeMap;
+        use std::convert::Infallible;
+        use std::num::NonZeroUsize;
+        use std::sync::{Arc, Mutex};
+
+        use async_trait::async_trait;
+        use tokio::net::TcpListener;
+        use tonic::transport::{server::TcpIncoming, Endpoint, Server};
+
+        use zcash_client_backend::{
+            data_api::{
+                chain::{error::Error, BlockCache, BlockSource},
+                scanning::ScanRange,
+                testing::{
+                    pool::ShieldedPoolTester, sapling::SaplingPoolTester, AddressType, TestBuilder,
+                    TestCache,
+                },
+                Account as _, WalletRead,
+            },
+            proto::{
+                compact_formats::CompactBlock,
+                server::LocalLightwalletd,
+                service::{
+                    compact_tx_streamer_client::CompactTxStreamerClient,
+                    compact_tx_streamer_server::CompactTxStreamerServer, TreeState,
+                },
+            },
+            sync,
+        };
+        use zcash_primitives::{block::BlockHash, merkle_tree::write_commitment_tree};
+        use zcash_protocol::{consensus::BlockHeight, value::Zatoshis};
+
+        use crate::testing::db::TestDbFactory;
+
+        #[cfg(feature = "orchard")]
+        use orchard::tree::MerkleHashOrchard;
+
+        /// An in-memory block cache, used as the test chain, as the block source of the
+        /// server, and as the wallet's download cache.
+        #[derive(Clone, Default)]
+        struct MemoryBlockCache(Arc<Mutex<BTreeMap<BlockHeight, CompactBlock>>>);
+
+        impl BlockSource for MemoryBlockCache {
+            type Error = Infallible;
+
+            fn with_blocks<F, WalletErrT>(
+                &self,
+                from_height: Option<BlockHeight>,
+                limit: Option<usize>,
+                mut with_block: F,
+            ) -> Result<(), Error<WalletErrT, Self::Error>>
+            where
+                F: FnMut(CompactBlock) -> Result<(), Error<WalletErrT, Self::Error>>,
+            {
+                let blocks = self.0.lock().unwrap();
+                blocks
+                    .range(from_height.unwrap_or(BlockHeight::from(0))..)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .try_for_each(|(_, block)| with_block(block.clone()))
+            }
+        }
+
+        #[async_trait]
+        impl BlockCache for MemoryBlockCache {
+            fn get_tip_height(
+                &self,
+                range: Option<&ScanRange>,
+            ) -> Result<Option<BlockHeight>, Self::Error> {
+                let blocks = self.0.lock().unwrap();
+                Ok(match range {
+                    Some(range) => blocks.range(range.block_range().clone()).next_back(),
+                    None => blocks.iter().next_back(),
+                }
+                .map(|(height, _)| *height))
+            }
+
+            async fn read(&self, range: &ScanRange) -> Result<Vec<CompactBlock>, Self::Error> {
+                Ok(self
+                    .0
+                    .lock()
+                    .unwrap()
+                    .range(range.block_range().clone())
+                    .map(|(_, block)| block.clone())
+                    .collect())
+            }
+
+            async fn insert(&self, compact_blocks: Vec<CompactBlock>) -> Result<(), Self::Error> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .extend(compact_blocks.into_iter().map(|b| (b.height(), b)));
+                Ok(())
+            }
+
+            async fn delete(&self, range: ScanRange) -> Result<(), Self::Error> {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .retain(|height, _| !range.block_range().contains(height));
+                Ok(())
+            }
+        }
+
+        impl TestCache for MemoryBlockCache {
+            type BsError = Infallible;
+            type BlockSource = Self;
+            type InsertResult = ();
+
+            fn block_source(&self) -> &Self::BlockSource {
+                self
+            }
+
+            fn insert(&mut self, cb: &CompactBlock) -> Self::InsertResult {
+                self.0.lock().unwrap().insert(cb.height(), cb.clone());
+            }
+
+            fn truncate_to_height(&mut self, height: BlockHeight) {
+                self.0.lock().unwrap().retain(|h, _| *h <= height);
+            }
+        }
+
+        /// Returns the tree state as of the end of each of the given blocks, starting from
+        /// empty note commitment trees.
+        fn tree_states(blocks: &[CompactBlock]) -> Vec<TreeState> {
+            let mut sapling_tree = ::sapling::CommitmentTree::empty();
+            #[cfg(feature = "orchard")]
+            let mut orchard_tree =
+                incrementalmerkletree::frontier::CommitmentTree::<MerkleHashOrchard, 32>::empty();
+
+            blocks
+                .iter()
+                .map(|block| {
+                    for tx in &block.vtx {
+                        for output in &tx.outputs {
+                            sapling_tree
+                                .append(::sapling::Node::from_cmu(&output.cmu().unwrap()))
+                                .unwrap();
+                        }
+                        #[cfg(feature = "orchard")]
+                        for action in &tx.actions {
+                            orchard_tree
+                                .append(MerkleHashOrchard::from_cmx(&action.cmx().unwrap()))
+                                .unwrap();
+                        }
+                    }
+
+                    let mut sapling_bytes = vec![];
+                    write_commitment_tree(&sapling_tree, &mut sapling_bytes).unwrap();
+                    #[cfg(feature = "orchard")]
+                    let orchard_bytes = {
+                        let mut bytes = vec![];
+                        write_commitment_tree(&orchard_tree, &mut bytes).unwrap();
+                        bytes
+                    };
+                    #[cfg(not(feature = "orchard"))]
+                    let orchard_bytes = vec![];
+
+                    // Block hashes in tree states are byte-reversed, as in zcashd.
+                    let mut hash = block.hash.clone();
+                    hash.reverse();
+                    TreeState {
+                        height: block.height,
+                        hash: hex::encode(hash),
+                        sapling_tree: hex::encode(sapling_bytes),
+                        orchard_tree: hex::encode(orchard_bytes),
+                        ..Default::default()
+                    }
+                })
+                .collect()
+        }
+
+        /// Syncs a wallet from a [`LocalLightwalletd`] serving a chain containing two
+        /// payments, using [`sync::run_concurrent`] if `max_concurrent_ranges` is provided
+        /// and [`sync::run`] otherwise.
+        fn sync_from_local_lightwalletd(max_concurrent_ranges: Option<NonZeroUsize>) {
+            let chain = MemoryBlockCache::default();
+            let mut st = TestBuilder::new()
+                .with_data_store_factory(TestDbFactory::default())
+                .with_block_cache(chain.clone())
+                .with_account_from_sapling_activation(BlockHash([0; 32]))
+                .build();
+
+            let account = st.test_account().cloned().unwrap();
+            let dfvk = SaplingPoolTester::test_account_fvk(&st);
+
+            // Mine two payments to the wallet, separated by empty blocks so that the chain
+            // spans several sync batches.
+            let value = Zatoshis::const_from_u64(50000);
+            st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+            for _ in 0..4 {
+                st.generate_empty_block();
+            }
+            st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+            let (tip, _) = st.generate_empty_block();
+
+            // Serve the generated chain, which the wallet has not yet scanned.
+            let blocks = chain
+                .0
+                .lock()
+                .unwrap()
+                .values()
+                .cloned()
+                .collect::<Vec<_>>();
+            let prior_tree_state = TreeState {
+                height: u64::from(u32::from(st.sapling_activation_height() - 1)),
+                hash: "00".repeat(32),
+                ..Default::default()
+            };
+            let server = LocalLightwalletd::new(*st.network(), chain);
+            server.set_chain_tip(tip);
+            server.insert_tree_state(prior_tree_state);
+            for tree_state in tree_states(&blocks) {
+                server.insert_tree_state(tree_state);
+            }
+
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                tokio::spawn(
+                    Server::builder()
+                        .add_service(CompactTxStreamerServer::from_arc(Arc::new(server)))
+                        .serve_with_incoming(TcpIncoming::from(listener)),
+                );
+
+                let channel = Endpoint::from_shared(format!("http://{}", addr))
+                    .unwrap()
+                    .connect()
+                    .await
+                    .unwrap();
+                let mut client = CompactTxStreamerClient::new(channel);
+
+                let db_cache = MemoryBlockCache::default();
+                let params = *st.network();
+                match max_concurrent_ranges {
+                    Some(max_concurrent_ranges) => sync::run_concurrent(
+                        &mut client,
+                        &params,
+                        &db_cache,
+                        st.wallet_mut(),
+                        3,
+                        max_concurrent_ranges,
+                    )
+                    .await
+                    .unwrap(),
+                    None => sync::run(&mut client, &params, &db_cache, st.wallet_mut(), 3)
+                        .await
+                        .unwrap(),
+                }
+            });
+
+            assert_eq!(
+                st.wallet()
+                    .block_fully_scanned()
+                    .unwrap()
+                    .map(|meta| meta.block_height()),
+                Some(tip)
+            );
+            assert_eq!(st.get_total_balance(account.id()), (value + value).unwrap());
+        }
+
+        #[test]
+        fn run_syncs_from_local_lightwalletd() {
+            sync_from_local_lightwalletd(None);
+        }
+
+        #[test]
+        fn run_concurrent_syncs_from_local_lightwalletd() {
+            sync_from_local_lightwalletd(NonZeroUsize::new(3));
+        }
+    }
 }
@@ -86,6 +86,11 @@ pub enum SqliteClientError {
     /// The collision can be on the seed and ZIP-32 account index, or a shared FVK component.
     AccountCollision(AccountUuid),
 
+    /// The viewing key provided to extend an account does not retain every component of the
+    /// account's existing full viewing key, or does not add any new components, or the
+    /// account's keys are derived from a known seed and so cannot be extended.
+    IncompatibleViewingKey,
+
     /// The account was imported, and ZIP-32 derivation information is not known for it.
     UnknownZip32Derivation,
 
@@ -193,6 +198,7 @@ impl fmt::Display for SqliteClientError {
             SqliteClientError::BadAccountData(e) => write!(f, "Failed to add account: {e}"),
             SqliteClientError::Zip32AccountIndexOutOfRange => write!(f, "ZIP 32 account identifiers must be less than 0x7FFFFFFF."),
             SqliteClientError::AccountCollision(account_uuid) => write!(f, "An account corresponding to the data provided already exists in the wallet with UUID {account_uuid:?}."),
+            SqliteClientError::IncompatibleViewingKey => write!(f, "The viewing key must retain every component of the account's existing full viewing key, and add at least one new component. Accounts derived from a known seed cannot be extended."),
             #[cfg(feature = "transparent-inputs")]
             SqliteClientError::AddressNotRecognized(_) => write!(f, "The address associated with a received txo is not identifiable as belonging to the wallet."),
             SqliteClientError::CommitmentTree(err) => write!(f, "An error occurred accessing or updating note commitment tree data: {err}."),
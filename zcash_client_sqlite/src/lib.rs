@@ -46,6 +46,7 @@ use std::{
     num::NonZeroU32,
     ops::Range,
     path::Path,
+    sync::Arc,
 };
 use subtle::ConditionallySelectable;
 use tracing::{debug, trace, warn};
@@ -56,12 +57,13 @@ use zcash_client_backend::{
     data_api::{
         self,
         chain::{BlockSource, ChainState, CommitmentTreeRoot},
+        events::{ScannedBlockLookup, WalletEvent, WalletEventSubscriber},
         scanning::{ScanPriority, ScanRange},
         Account, AccountBirthday, AccountMeta, AccountPurpose, AccountSource, AddressInfo,
-        BlockMetadata, DecryptedTransaction, InputSource, NoteFilter, NullifierQuery, ScannedBlock,
-        SeedRelevance, SentTransaction, SpendableNotes, TargetValue, TransactionDataRequest,
-        WalletCommitmentTrees, WalletRead, WalletSummary, WalletWrite, Zip32Derivation,
-        SAPLING_SHARD_HEIGHT,
+        BlockMetadata, DecryptedTransaction, InputSource, NoteFilter, NoteWitnesses,
+        NullifierQuery, ScannedBlock, SeedRelevance, SentTransaction, SpendableNotes, TargetValue,
+        TransactionDataRequest, WalletCommitmentTrees, WalletRead, WalletSummary, WalletWrite,
+        Zip32Derivation, SAPLING_SHARD_HEIGHT,
     },
     proto::compact_formats::CompactBlock,
     wallet::{Note, NoteId, ReceivedNote, WalletTransparentOutput},
@@ -84,8 +86,10 @@ use zip32::{fingerprint::SeedFingerprint, DiversifierIndex};
 
 use crate::{error::SqliteClientError, wallet::commitment_tree::SqliteShardStore};
 use wallet::{
-    commitment_tree::{self, put_shard_roots},
+    commitment_tree::{self, put_shard_roots, TreeInconsistency},
     common::{spendable_notes_meta, TableConstants},
+    invoices::{Invoice, InvoiceRequest, InvoiceUuid},
+    pruning::{PruningPolicy, PruningSummary},
     scanning::replace_queue_entries,
     upsert_address, SubtreeProgressEstimator,
 };
@@ -369,8 +373,13 @@ pub struct WalletDb<C, P, CL, R> {
     rng: R,
     #[cfg(feature = "transparent-inputs")]
     gap_limits: GapLimits,
+    pruning_policy: Option<PruningPolicy>,
+    subscribers: Vec<Subscriber>,
 }
 
+/// A shared reference to a receiver of the events emitted by a [`WalletDb`].
+type Subscriber = Arc<dyn WalletEventSubscriber<AccountUuid> + Send + Sync>;
+
 /// A wrapper for a SQLite transaction affecting the wallet database.
 pub struct SqlTransaction<'conn>(pub(crate) &'conn rusqlite::Transaction<'conn>);
 
@@ -405,6 +414,8 @@ impl<P, CL, R> WalletDb<Connection, P, CL, R> {
                 rng,
                 #[cfg(feature = "transparent-inputs")]
                 gap_limits: GapLimits::default(),
+                pruning_policy: None,
+                subscribers: vec![],
             })
         })
     }
@@ -419,6 +430,63 @@ impl<C, P, CL, R> WalletDb<C, P, CL, R> {
     }
 }
 
+impl<C, P, CL, R> WalletDb<C, P, CL, R> {
+    /// Sets the policy governing the data discarded by [`WalletDb::prune`].
+    pub fn with_pruning_policy(mut self, pruning_policy: PruningPolicy) -> Self {
+        self.pruning_policy = Some(pruning_policy);
+        self
+    }
+
+    /// Registers a subscriber that will be notified of each [`WalletEvent`] resulting from
+    /// changes made to the wallet through this `WalletDb`.
+    ///
+    /// Events are emitted only after the database transaction making the corresponding
+    /// change has been committed. A received note is reported only when it is first stored,
+    /// and a mined transaction only when its mined height changes, so rescanning a range or
+    /// enhancing a previously scanned transaction does not repeat these events.
+    pub fn subscribe(
+        &mut self,
+        subscriber: impl WalletEventSubscriber<AccountUuid> + Send + Sync + 'static,
+    ) {
+        self.subscribers.push(Arc::new(subscriber));
+    }
+
+    fn emit(&self, events: &[WalletEvent<AccountUuid>]) {
+        for event in events {
+            for subscriber in &self.subscribers {
+                subscriber.on_event(event);
+            }
+        }
+    }
+}
+
+impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters, CL, R>
+    ScannedBlockLookup<AccountUuid> for WalletDb<C, P, CL, R>
+{
+    type Error = SqliteClientError;
+
+    fn spent_note(
+        &self,
+        protocol: ShieldedProtocol,
+        nf: &[u8; 32],
+    ) -> Result<Option<NoteId>, Self::Error> {
+        wallet::common::find_received_note_id(self.conn.borrow(), protocol, nf)
+    }
+
+    #[cfg(feature = "transparent-inputs")]
+    fn transparent_receiver_account(
+        &self,
+        address: &TransparentAddress,
+    ) -> Result<Option<AccountUuid>, Self::Error> {
+        wallet::transparent::find_account_uuid_for_transparent_address(
+            self.conn.borrow(),
+            &self.params,
+            address,
+        )
+        .map(|res| res.map(|(account_id, _)| account_id))
+    }
+}
+
 impl<C: Borrow<rusqlite::Connection>, P, CL, R> WalletDb<C, P, CL, R> {
     /// Constructs a new wrapper around the given connection.
     ///
@@ -442,6 +510,8 @@ impl<C: Borrow<rusqlite::Connection>, P, CL, R> WalletDb<C, P, CL, R> {
             rng,
             #[cfg(feature = "transparent-inputs")]
             gap_limits: GapLimits::default(),
+            pruning_policy: None,
+            subscribers: vec![],
         }
     }
 }
@@ -459,6 +529,8 @@ impl<C: BorrowMut<Connection>, P, CL, R> WalletDb<C, P, CL, R> {
             rng: &mut self.rng,
             #[cfg(feature = "transparent-inputs")]
             gap_limits: self.gap_limits,
+            pruning_policy: self.pruning_policy,
+            subscribers: vec![],
         };
         let result = f(&mut wdb)?;
         tx.commit()?;
@@ -474,6 +546,40 @@ impl<C: BorrowMut<Connection>, P, CL, R> WalletDb<C, P, CL, R> {
         self.transactionally(|wdb| wallet::commitment_tree::check_witnesses(wdb.conn.0))
     }
 
+    /// Verifies the integrity of the wallet's note commitment trees, and returns each
+    /// inconsistency that was detected along with the range of blocks that it affects.
+    ///
+    /// Stored shard roots, cached node hashes, the tree caps and checkpoints are compared against
+    /// roots recomputed from the shard data and against the tree sizes recorded for scanned
+    /// blocks. Each of the provided chain states, as obtained from the server's `TreeState`s,
+    /// is additionally compared against the tree size and root that the wallet has recorded at
+    /// its height.
+    pub fn verify_commitment_trees(
+        &mut self,
+        chain_states: &[ChainState],
+    ) -> Result<Vec<TreeInconsistency>, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::commitment_tree::verify_commitment_trees(wdb.conn.0, chain_states)
+        })
+    }
+
+    /// Repairs the given note commitment tree inconsistencies, as returned by
+    /// [`Self::verify_commitment_trees`], by resetting only the affected shards and queueing the
+    /// affected block ranges for rescan. Returns the block ranges that were queued.
+    ///
+    /// The stored root hashes of reset shards are discarded along with their contents. If the
+    /// tree cap is rebuilt, the roots of reset shards that are not rescanned are restored when
+    /// subtree roots are next obtained from the server via
+    /// [`WalletCommitmentTrees::put_sapling_subtree_roots`] (or its Orchard equivalent).
+    pub fn repair_commitment_trees(
+        &mut self,
+        inconsistencies: &[TreeInconsistency],
+    ) -> Result<Vec<Range<BlockHeight>>, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::commitment_tree::repair_commitment_trees(wdb.conn.0, inconsistencies)
+        })
+    }
+
     /// Updates the scan queue by inserting scan ranges for the given range of block heights, with
     /// the specified scanning priority.
     pub fn queue_rescans(
@@ -507,6 +613,73 @@ impl<C: BorrowMut<Connection>, P, CL, R> WalletDb<C, P, CL, R> {
     }
 }
 
+impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters, CL, R> WalletDb<C, P, CL, R> {
+    /// Returns the invoice with the given identifier, if it exists.
+    pub fn get_invoice(
+        &self,
+        invoice_id: InvoiceUuid,
+    ) -> Result<Option<Invoice>, SqliteClientError> {
+        wallet::invoices::get_invoice(self.conn.borrow(), &self.params, invoice_id)
+    }
+
+    /// Returns the invoices issued for the given account, in the order in which they were
+    /// created.
+    pub fn get_invoices(&self, account: AccountUuid) -> Result<Vec<Invoice>, SqliteClientError> {
+        wallet::invoices::get_invoices(self.conn.borrow(), &self.params, account)
+    }
+}
+
+impl<C: BorrowMut<Connection>, P: consensus::Parameters, CL: Clock, R> WalletDb<C, P, CL, R> {
+    /// Issues a ZIP 321 payment request for the given account.
+    ///
+    /// The request directs payment to a newly generated shielded address, obtained in the
+    /// same fashion as [`WalletWrite::get_next_available_address`], that is used for no other
+    /// purpose. All outputs subsequently received at that address are treated as payments of
+    /// the returned invoice; see [`wallet::invoices`] for details.
+    pub fn create_invoice(
+        &mut self,
+        account: AccountUuid,
+        request: &InvoiceRequest,
+    ) -> Result<Invoice, SqliteClientError> {
+        self.transactionally(|wdb| {
+            wallet::invoices::create_invoice(
+                wdb.conn.0,
+                &wdb.params,
+                &wdb.clock,
+                account,
+                request,
+                #[cfg(feature = "transparent-inputs")]
+                &wdb.gap_limits,
+            )
+        })
+    }
+}
+
+impl<C: BorrowMut<Connection>, P: consensus::Parameters, CL, R> WalletDb<C, P, CL, R> {
+    /// Discards the wallet data permitted by the configured [`PruningPolicy`], and then
+    /// compacts the database if the policy requires it. Returns a summary of the data that
+    /// was discarded.
+    ///
+    /// If no pruning policy has been configured using [`WalletDb::with_pruning_policy`],
+    /// this method does nothing. See [`wallet::pruning`] for details of the data that may be
+    /// discarded.
+    pub fn prune(&mut self) -> Result<PruningSummary, SqliteClientError> {
+        let Some(policy) = self.pruning_policy else {
+            return Ok(PruningSummary::default());
+        };
+
+        let summary =
+            self.transactionally(|wdb| wallet::pruning::prune(wdb.conn.0, &wdb.params, &policy))?;
+
+        // `VACUUM` cannot be run within a transaction.
+        if policy.compacts() && !summary.is_empty() {
+            self.conn.borrow_mut().execute_batch("VACUUM")?;
+        }
+
+        Ok(summary)
+    }
+}
+
 #[cfg(feature = "transparent-inputs")]
 impl<C: BorrowMut<Connection>, P, CL: Clock, R: rand::RngCore> WalletDb<C, P, CL, R> {
     /// For each ephemeral address in the wallet, ensure that the transaction data request queue
@@ -871,6 +1044,25 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters, CL, R> WalletRea
         wallet::orchard::get_orchard_nullifiers(self.conn.borrow(), query)
     }
 
+    fn get_note_witnesses(
+        &self,
+        note_ids: &[NoteId],
+        anchor_height: BlockHeight,
+    ) -> Result<NoteWitnesses, Self::Error> {
+        // Read the trees within a transaction so that they are observed consistently, unless
+        // the connection is already within one (as it is inside `transactionally`).
+        let conn = self.conn.borrow();
+        let tx = conn
+            .is_autocommit()
+            .then(|| conn.unchecked_transaction())
+            .transpose()?;
+        wallet::commitment_tree::get_note_witnesses(
+            tx.as_deref().unwrap_or(conn),
+            note_ids,
+            anchor_height,
+        )
+    }
+
     #[cfg(feature = "transparent-inputs")]
     fn get_transparent_receivers(
         &self,
@@ -925,6 +1117,13 @@ impl<C: Borrow<rusqlite::Connection>, P: consensus::Parameters, CL, R> WalletRea
         wallet::transparent::utxo_query_height(self.conn.borrow(), account_ref, &self.gap_limits)
     }
 
+    #[cfg(feature = "transparent-inputs")]
+    fn get_unspent_transparent_outpoints(
+        &self,
+    ) -> Result<Vec<(Self::AccountId, OutPoint)>, Self::Error> {
+        wallet::transparent::get_unspent_transparent_outpoints(self.conn.borrow())
+    }
+
     #[cfg(feature = "transparent-inputs")]
     fn get_known_ephemeral_addresses(
         &self,
@@ -1227,6 +1426,25 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
         })
     }
 
+    fn extend_account_viewing_key(
+        &mut self,
+        account: Self::AccountId,
+        unified_key: &UnifiedFullViewingKey,
+        birthday: &AccountBirthday,
+    ) -> Result<Self::Account, Self::Error> {
+        self.transactionally(|wdb| {
+            wallet::extend_account_viewing_key(
+                wdb.conn.0,
+                &wdb.params,
+                account,
+                unified_key,
+                birthday,
+                #[cfg(feature = "transparent-inputs")]
+                &wdb.gap_limits,
+            )
+        })
+    }
+
     fn get_next_available_address(
         &mut self,
         account_uuid: Self::AccountId,
@@ -1304,7 +1522,15 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
             return Ok(());
         }
 
+        let mut events = if self.subscribers.is_empty() {
+            vec![]
+        } else {
+            WalletEvent::for_scanned_blocks(&blocks, self)?
+        };
+
         self.transactionally(|wdb| {
+            wallet::retain_new_events(wdb.conn.0, &mut events)?;
+
             let initial_block = blocks.first().expect("blocks is known to be nonempty");
             assert!(from_state.block_height() + 1 == initial_block.height());
 
@@ -1368,6 +1594,27 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
                     for spend in tx.orchard_spends() {
                         wallet::orchard::mark_orchard_note_spent(wdb.conn.0, tx_ref, spend.nf())?;
                     }
+                    #[cfg(feature = "transparent-inputs")]
+                    for spend in tx.transparent_spends() {
+                        wallet::transparent::mark_transparent_utxo_spent(
+                            wdb.conn.0,
+                            tx_ref,
+                            spend.nf(),
+                        )?;
+                    }
+
+                    // Record any transparent outputs detected in the compact block. Any
+                    // spends of these outputs that we previously detected are linked via
+                    // the transparent spend map.
+                    #[cfg(feature = "transparent-inputs")]
+                    for output in tx.transparent_outputs() {
+                        wallet::transparent::put_transparent_output(
+                            wdb.conn.0,
+                            &wdb.params,
+                            output,
+                            false,
+                        )?;
+                    }
 
                     for output in tx.sapling_outputs() {
                         // Check whether this note was spent in a later block range that
@@ -1746,8 +1993,11 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
                 )?;
             }
 
-            Ok(())
-        })
+            Ok::<_, SqliteClientError>(())
+        })?;
+
+        self.emit(&events);
+        Ok(())
     }
 
     fn put_received_transparent_utxo(
@@ -1787,7 +2037,14 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
         &mut self,
         d_tx: DecryptedTransaction<Self::AccountId>,
     ) -> Result<(), Self::Error> {
+        let mut events = if self.subscribers.is_empty() {
+            vec![]
+        } else {
+            WalletEvent::for_decrypted_transaction(&d_tx)
+        };
+
         self.transactionally(|wdb| {
+            wallet::retain_new_events(wdb.conn.0, &mut events)?;
             wallet::store_decrypted_tx(
                 wdb.conn.0,
                 &wdb.params,
@@ -1795,7 +2052,10 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
                 #[cfg(feature = "transparent-inputs")]
                 &wdb.gap_limits,
             )
-        })
+        })?;
+
+        self.emit(&events);
+        Ok(())
     }
 
     fn store_transactions_to_be_sent(
@@ -1811,15 +2071,30 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
     }
 
     fn truncate_to_height(&mut self, max_height: BlockHeight) -> Result<BlockHeight, Self::Error> {
-        self.transactionally(|wdb| {
-            wallet::truncate_to_height(
+        let (truncated_to, affected) = self.transactionally(|wdb| {
+            let affected = wallet::accounts_affected_by_truncation(wdb.conn.0, max_height)?;
+            let truncated_to = wallet::truncate_to_height(
                 wdb.conn.0,
                 &wdb.params,
                 #[cfg(feature = "transparent-inputs")]
                 &wdb.gap_limits,
                 max_height,
-            )
-        })
+            )?;
+            Ok::<_, SqliteClientError>((truncated_to, affected))
+        })?;
+
+        // Only report a reorg if chain data was actually discarded.
+        if let Some(accounts) = affected {
+            let events = std::iter::once(WalletEvent::Reorg { truncated_to })
+                .chain(
+                    accounts
+                        .into_iter()
+                        .map(|account_id| WalletEvent::BalanceChanged { account_id }),
+                )
+                .collect::<Vec<_>>();
+            self.emit(&events);
+        }
+        Ok(truncated_to)
     }
 
     #[cfg(feature = "transparent-inputs")]
@@ -1848,7 +2123,26 @@ impl<C: BorrowMut<rusqlite::Connection>, P: consensus::Parameters, CL: Clock, R>
         txid: TxId,
         status: data_api::TransactionStatus,
     ) -> Result<(), Self::Error> {
-        self.transactionally(|wdb| wallet::set_transaction_status(wdb.conn.0, txid, status))
+        let events = self.transactionally::<_, _, SqliteClientError>(|wdb| {
+            let mut events = match status {
+                data_api::TransactionStatus::Mined(height) => {
+                    vec![WalletEvent::TransactionMined { txid, height }]
+                }
+                data_api::TransactionStatus::TxidNotRecognized
+                | data_api::TransactionStatus::NotInMainChain => vec![],
+            };
+            wallet::retain_new_events(wdb.conn.0, &mut events)?;
+            wallet::set_transaction_status(wdb.conn.0, txid, status)?;
+            if !matches!(status, data_api::TransactionStatus::Mined(_))
+                && wallet::is_expired_unmined(wdb.conn.0, txid)?
+            {
+                events.push(WalletEvent::TransactionExpired { txid });
+            }
+            Ok(events)
+        })?;
+
+        self.emit(&events);
+        Ok(())
     }
 }
 
@@ -2341,6 +2635,9 @@ mod tests {
     #[cfg(feature = "unstable")]
     use zcash_keys::keys::sapling;
 
+    #[cfg(feature = "orchard")]
+    use {zcash_client_backend::data_api::Zip32Derivation, zip32::fingerprint::SeedFingerprint};
+
     #[test]
     fn validate_seed() {
         let st = TestBuilder::new()
@@ -2371,6 +2668,45 @@ mod tests {
         });
     }
 
+    #[test]
+    fn mnemonic_relevance() {
+        use zcash_client_backend::data_api::SeedRelevance;
+        use zcash_keys::mnemonic::{Language, Mnemonic};
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let birthday = st.test_account().unwrap().birthday().clone();
+
+        let mnemonic = Mnemonic::from_entropy(Language::English, &[0x2a; 32]).unwrap();
+        assert_matches!(
+            st.wallet()
+                .mnemonic_relevance_to_derived_accounts(&mnemonic, "")
+                .unwrap(),
+            SeedRelevance::NotRelevant
+        );
+
+        let (account_id, _) = st
+            .wallet_mut()
+            .create_account("", &mnemonic.to_seed(""), &birthday, None)
+            .unwrap();
+        assert_matches!(
+            st.wallet()
+                .mnemonic_relevance_to_derived_accounts(&mnemonic, "")
+                .unwrap(),
+            SeedRelevance::Relevant { account_ids } if account_ids.len() == 1 && account_ids.head == account_id
+        );
+
+        // The passphrase is part of the seed.
+        assert_matches!(
+            st.wallet()
+                .mnemonic_relevance_to_derived_accounts(&mnemonic, "passphrase")
+                .unwrap(),
+            SeedRelevance::NotRelevant
+        );
+    }
+
     #[test]
     pub(crate) fn get_next_available_address() {
         let mut st = TestBuilder::new()
@@ -2667,6 +3003,225 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn extend_account_viewing_key() {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .build();
+
+        let birthday = AccountBirthday::from_parts(
+            ChainState::empty(st.network().sapling.unwrap() - 1, BlockHash([0; 32])),
+            None,
+        );
+
+        let seed = Secret::new(vec![0u8; 32]);
+        let usk = UnifiedSpendingKey::from_seed(
+            st.network(),
+            seed.expose_secret(),
+            zip32::AccountId::ZERO,
+        )
+        .unwrap();
+        let ufvk = usk.to_unified_full_viewing_key();
+        let sapling_only = UnifiedFullViewingKey::new(
+            #[cfg(feature = "transparent-inputs")]
+            None,
+            ufvk.sapling().cloned(),
+            None,
+        )
+        .unwrap();
+
+        let account = st
+            .wallet_mut()
+            .import_account_ufvk("", &sapling_only, &birthday, AccountPurpose::ViewOnly, None)
+            .unwrap();
+
+        // A key that adds no new components is rejected.
+        assert_matches!(
+            st.wallet_mut()
+                .extend_account_viewing_key(account.id(), &sapling_only, &birthday),
+            Err(SqliteClientError::IncompatibleViewingKey)
+        );
+
+        // A key that replaces an existing component is rejected.
+        let other_usk =
+            UnifiedSpendingKey::from_seed(st.network(), &[1u8; 32], zip32::AccountId::ZERO)
+                .unwrap();
+        assert_matches!(
+            st.wallet_mut().extend_account_viewing_key(
+                account.id(),
+                &other_usk.to_unified_full_viewing_key(),
+                &birthday
+            ),
+            Err(SqliteClientError::IncompatibleViewingKey)
+        );
+
+        // A key that adds a component belonging to another account is rejected.
+        let orchard_only = UnifiedFullViewingKey::new(
+            #[cfg(feature = "transparent-inputs")]
+            None,
+            None,
+            ufvk.orchard().cloned(),
+        )
+        .unwrap();
+        let other_account = st
+            .wallet_mut()
+            .import_account_ufvk("", &orchard_only, &birthday, AccountPurpose::ViewOnly, None)
+            .unwrap();
+        assert_matches!(
+            st.wallet_mut()
+                .extend_account_viewing_key(account.id(), &ufvk, &birthday),
+            Err(SqliteClientError::AccountCollision(id)) if id == other_account.id()
+        );
+
+        // A key that retains the existing components and adds new ones is accepted.
+        let other_ufvk = other_usk.to_unified_full_viewing_key();
+        let extended_ufvk = UnifiedFullViewingKey::new(
+            #[cfg(feature = "transparent-inputs")]
+            other_ufvk.transparent().cloned(),
+            ufvk.sapling().cloned(),
+            other_ufvk.orchard().cloned(),
+        )
+        .unwrap();
+        let extended = st
+            .wallet_mut()
+            .extend_account_viewing_key(account.id(), &extended_ufvk, &birthday)
+            .unwrap();
+        assert_eq!(extended.id(), account.id());
+        assert_eq!(
+            extended.ufvk().unwrap().encode(st.network()),
+            extended_ufvk.encode(st.network())
+        );
+
+        // An account with ZIP 32 derivation metadata cannot be extended, even with a key that
+        // would otherwise be accepted.
+        let derived_seed = [2u8; 32];
+        let derived_ufvk =
+            UnifiedSpendingKey::from_seed(st.network(), &derived_seed, zip32::AccountId::ZERO)
+                .unwrap()
+                .to_unified_full_viewing_key();
+        let derived_sapling_only = UnifiedFullViewingKey::new(
+            #[cfg(feature = "transparent-inputs")]
+            None,
+            derived_ufvk.sapling().cloned(),
+            None,
+        )
+        .unwrap();
+        let derived_account = st
+            .wallet_mut()
+            .import_account_ufvk(
+                "",
+                &derived_sapling_only,
+                &birthday,
+                AccountPurpose::Spending {
+                    derivation: Some(Zip32Derivation::new(
+                        SeedFingerprint::from_seed(&derived_seed).unwrap(),
+                        zip32::AccountId::ZERO,
+                    )),
+                },
+                None,
+            )
+            .unwrap();
+        assert_matches!(
+            st.wallet_mut().extend_account_viewing_key(
+                derived_account.id(),
+                &derived_ufvk,
+                &birthday
+            ),
+            Err(SqliteClientError::IncompatibleViewingKey)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn extend_account_viewing_key_rescans() {
+        use zcash_client_backend::data_api::{
+            scanning::{ScanPriority, ScanRange},
+            testing::AddressType,
+        };
+        use zcash_protocol::value::Zatoshis;
+
+        use crate::testing::BlockCache;
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .build();
+
+        let birthday = AccountBirthday::from_parts(
+            ChainState::empty(st.network().sapling.unwrap() - 1, BlockHash([0; 32])),
+            None,
+        );
+        let usk = UnifiedSpendingKey::from_seed(st.network(), &[0u8; 32], zip32::AccountId::ZERO)
+            .unwrap();
+        let ufvk = usk.to_unified_full_viewing_key();
+        let sapling_only = UnifiedFullViewingKey::new(
+            #[cfg(feature = "transparent-inputs")]
+            None,
+            ufvk.sapling().cloned(),
+            None,
+        )
+        .unwrap();
+        let account = st
+            .wallet_mut()
+            .import_account_ufvk("", &sapling_only, &birthday, AccountPurpose::ViewOnly, None)
+            .unwrap();
+
+        // Receive a note to each of the account's Sapling and (not yet known) Orchard keys.
+        let sapling_value = Zatoshis::const_from_u64(50000);
+        let orchard_value = Zatoshis::const_from_u64(70000);
+        let (h1, _, _) = st.generate_next_block(
+            ufvk.sapling().unwrap(),
+            AddressType::DefaultExternal,
+            sapling_value,
+        );
+        let (h2, _, _) = st.generate_next_block(
+            ufvk.orchard().unwrap(),
+            AddressType::DefaultExternal,
+            orchard_value,
+        );
+        st.wallet_mut().update_chain_tip(h2).unwrap();
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(st.get_total_balance(account.id()), sapling_value);
+
+        let count_rows = |conn: &rusqlite::Connection, table: &str| {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get::<_, u32>(0)
+            })
+            .unwrap()
+        };
+        assert_eq!(count_rows(st.wallet().conn(), "sapling_received_notes"), 1);
+        assert_eq!(count_rows(st.wallet().conn(), "orchard_received_notes"), 0);
+
+        // Extending the key queues the range from its birthday to the chain tip for rescan.
+        st.wallet_mut()
+            .extend_account_viewing_key(account.id(), &ufvk, &birthday)
+            .unwrap();
+        assert_eq!(
+            st.wallet().suggest_scan_ranges().unwrap(),
+            vec![ScanRange::from_parts(
+                birthday.height()..(h2 + 1),
+                ScanPriority::Historic
+            )]
+        );
+
+        // Rescanning discovers the Orchard note, without duplicating any existing data.
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(
+            st.get_total_balance(account.id()),
+            (sapling_value + orchard_value).unwrap()
+        );
+        assert_eq!(count_rows(st.wallet().conn(), "sapling_received_notes"), 1);
+        assert_eq!(count_rows(st.wallet().conn(), "orchard_received_notes"), 1);
+        assert_eq!(count_rows(st.wallet().conn(), "transactions"), 2);
+
+        // Rescanning again leaves the wallet unchanged.
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(count_rows(st.wallet().conn(), "sapling_received_notes"), 1);
+        assert_eq!(count_rows(st.wallet().conn(), "orchard_received_notes"), 1);
+        assert_eq!(count_rows(st.wallet().conn(), "transactions"), 2);
+    }
+
     #[test]
     pub(crate) fn create_account_then_conflicts() {
         let mut st = TestBuilder::new()
@@ -2785,6 +3340,561 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_note_witnesses_within_transaction() {
+        use zcash_client_backend::data_api::testing::AddressType;
+        use zcash_protocol::value::Zatoshis;
+
+        use crate::testing::BlockCache;
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = st.test_account_sapling().unwrap().clone();
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h, 1);
+
+        let witnesses = st.wallet().get_note_witnesses(&[], h).unwrap();
+        assert!(witnesses.sapling_anchor().is_some());
+
+        // The trees can also be read when the connection is already within a transaction.
+        let witnesses_in_tx = st
+            .wallet_mut()
+            .db_mut()
+            .transactionally::<_, _, SqliteClientError>(|wdb| wdb.get_note_witnesses(&[], h))
+            .unwrap();
+        assert_eq!(witnesses_in_tx.sapling_anchor(), witnesses.sapling_anchor());
+    }
+
+    #[test]
+    fn wallet_events() {
+        use std::sync::{Arc, Mutex};
+
+        use ::sapling::zip32::ExtendedSpendingKey;
+        use zcash_client_backend::data_api::{events::WalletEvent, testing::AddressType};
+        use zcash_protocol::value::Zatoshis;
+
+        use crate::testing::BlockCache;
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account_id = st.test_account().unwrap().id();
+        let dfvk = st.test_account_sapling().unwrap().clone();
+
+        let events = Arc::new(Mutex::new(vec![]));
+        st.wallet_mut().db_mut().subscribe({
+            let events = events.clone();
+            move |event: &WalletEvent<AccountUuid>| events.lock().unwrap().push(event.clone())
+        });
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, nf) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 2);
+
+        let first_note = {
+            let events = events.lock().unwrap();
+            let mined = events
+                .iter()
+                .filter(|e| matches!(e, WalletEvent::TransactionMined { .. }))
+                .count();
+            let received = events
+                .iter()
+                .filter(|e| {
+                    matches!(
+                        e,
+                        WalletEvent::NoteReceived { account_id: a, value: v, is_change: false, .. }
+                            if *a == account_id && *v == value
+                    )
+                })
+                .count();
+            assert_eq!(mined, 2);
+            assert_eq!(received, 2);
+            assert_eq!(
+                events.last(),
+                Some(&WalletEvent::BalanceChanged { account_id })
+            );
+            match &events[1] {
+                WalletEvent::NoteReceived { note_id, .. } => *note_id,
+                e => panic!("unexpected event {:?}", e),
+            }
+        };
+
+        // Spending a note identifies the spent note.
+        events.lock().unwrap().clear();
+        let not_our_key =
+            ExtendedSpendingKey::master(&[0xf5; 32]).to_diversifiable_full_viewing_key();
+        let (h3, _) = st.generate_next_block_spending(
+            &dfvk,
+            (nf, value),
+            not_our_key.default_address().1,
+            Zatoshis::const_from_u64(20000),
+        );
+        st.scan_cached_blocks(h3, 1);
+        assert!(events.lock().unwrap().iter().any(|e| matches!(
+            e,
+            WalletEvent::NoteSpent { account_id: a, note_id, .. }
+                if *a == account_id && *note_id == first_note
+        )));
+
+        // Truncation reports the reorg, followed by the affected balances.
+        events.lock().unwrap().clear();
+        st.truncate_to_height(h2);
+        assert_eq!(
+            events.lock().unwrap().as_slice(),
+            &[
+                WalletEvent::Reorg { truncated_to: h2 },
+                WalletEvent::BalanceChanged { account_id }
+            ]
+        );
+
+        // Truncating again removes nothing, so no events are emitted.
+        events.lock().unwrap().clear();
+        st.wallet_mut().truncate_to_height(h2).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn wallet_events_not_repeated_on_enhancement() {
+        use std::{
+            convert::Infallible,
+            num::NonZeroU32,
+            sync::{Arc, Mutex},
+        };
+
+        use zcash_client_backend::{
+            data_api::{
+                events::WalletEvent, testing::AddressType, wallet::decrypt_and_store_transaction,
+                TransactionStatus,
+            },
+            fees::StandardFeeRule,
+            wallet::OvkPolicy,
+        };
+        use zcash_keys::address::Address;
+        use zcash_protocol::{value::Zatoshis, ShieldedProtocol};
+
+        use crate::testing::BlockCache;
+
+        // The sending and receiving wallets are both derived from the test seed, so the
+        // receiving wallet observes the payment as well as the sender's change.
+        let mut sender = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let sender_account = sender.test_account().cloned().unwrap();
+        let dfvk = sender.test_account_sapling().unwrap().clone();
+        let (h, _, _) = sender.generate_next_block(
+            &dfvk,
+            AddressType::DefaultExternal,
+            Zatoshis::const_from_u64(60000),
+        );
+        sender.scan_cached_blocks(h, 1);
+
+        let proposal = sender
+            .propose_standard_transfer::<Infallible>(
+                sender_account.id(),
+                StandardFeeRule::Zip317,
+                NonZeroU32::new(1).unwrap(),
+                &Address::Sapling(dfvk.default_address().1),
+                Zatoshis::const_from_u64(20000),
+                None,
+                None,
+                ShieldedProtocol::Sapling,
+            )
+            .unwrap();
+        let txid = sender
+            .create_proposed_transactions::<Infallible, _, Infallible>(
+                sender_account.usk(),
+                OvkPolicy::Sender,
+                &proposal,
+            )
+            .unwrap()[0];
+        let tx = sender.wallet().get_transaction(txid).unwrap().unwrap();
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let events = Arc::new(Mutex::new(vec![]));
+        st.wallet_mut().db_mut().subscribe({
+            let events = events.clone();
+            move |event: &WalletEvent<AccountUuid>| events.lock().unwrap().push(event.clone())
+        });
+
+        // Scan the block containing the transaction, then enhance it.
+        let (h, _) = st.generate_next_block_from_tx(1, &tx);
+        st.scan_cached_blocks(h, 1);
+        let network = *st.network();
+        decrypt_and_store_transaction(&network, st.wallet_mut(), &tx, Some(h)).unwrap();
+
+        let count = |pred: &dyn Fn(&WalletEvent<AccountUuid>) -> bool| {
+            events.lock().unwrap().iter().filter(|e| pred(e)).count()
+        };
+        let received = |value: u64| {
+            count(&|e| {
+                matches!(
+                    e,
+                    WalletEvent::NoteReceived { note_id, value: v, .. }
+                        if *note_id.txid() == txid && *v == Zatoshis::const_from_u64(value)
+                )
+            })
+        };
+        let mined = || {
+            count(&|e| {
+                matches!(
+                    e,
+                    WalletEvent::TransactionMined { txid: t, height } if *t == txid && *height == h
+                )
+            })
+        };
+        assert_eq!(received(20000), 1);
+        assert_eq!(received(30000), 1);
+        assert_eq!(mined(), 1);
+
+        // Reporting the already-known mined height again does not repeat the event.
+        st.wallet_mut()
+            .set_transaction_status(txid, TransactionStatus::Mined(h))
+            .unwrap();
+        assert_eq!(mined(), 1);
+    }
+
+    #[test]
+    fn verify_and_repair_commitment_trees() {
+        use rusqlite::named_params;
+        use zcash_client_backend::data_api::{scanning::ScanPriority, testing::AddressType};
+        use zcash_protocol::{value::Zatoshis, ShieldedProtocol};
+
+        use crate::{testing::BlockCache, wallet::commitment_tree::InconsistencyKind};
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = st.test_account_sapling().unwrap().clone();
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 2);
+        let chain_state = st.latest_cached_block().unwrap().chain_state().clone();
+
+        // A freshly scanned wallet is consistent with itself and with the server.
+        assert_eq!(
+            st.wallet_mut()
+                .db_mut()
+                .verify_commitment_trees(&[chain_state.clone()])
+                .unwrap(),
+            vec![]
+        );
+
+        // Corrupt the checkpoint for the last scanned block.
+        st.wallet()
+            .conn()
+            .execute(
+                "UPDATE sapling_tree_checkpoints SET position = position + 1
+                 WHERE checkpoint_id = :height",
+                named_params![":height": u32::from(h2)],
+            )
+            .unwrap();
+
+        let inconsistencies = st
+            .wallet_mut()
+            .db_mut()
+            .verify_commitment_trees(&[chain_state.clone()])
+            .unwrap();
+        let checkpoint_mismatch = inconsistencies
+            .iter()
+            .find(|i| matches!(i.kind(), InconsistencyKind::CheckpointMismatch { .. }))
+            .expect("the corrupted checkpoint is detected");
+        assert_eq!(checkpoint_mismatch.protocol(), ShieldedProtocol::Sapling);
+        assert_eq!(
+            checkpoint_mismatch.kind(),
+            &InconsistencyKind::CheckpointMismatch {
+                checkpoint_tree_size: 3,
+                block_tree_size: 2,
+            }
+        );
+        assert_eq!(checkpoint_mismatch.height(), Some(h2));
+        assert_eq!(checkpoint_mismatch.shard_index(), Some(0));
+        // The shard is not yet complete, so its range extends past the last scanned block.
+        assert_eq!(checkpoint_mismatch.block_range(), Some(&(h1..(h2 + 2))));
+
+        // Repairing the tree queues the affected blocks for rescan.
+        let repaired = st
+            .wallet_mut()
+            .db_mut()
+            .repair_commitment_trees(&inconsistencies)
+            .unwrap();
+        assert_eq!(repaired, vec![h1..(h2 + 2)]);
+        assert!(st
+            .wallet()
+            .suggest_scan_ranges()
+            .unwrap()
+            .iter()
+            .any(|r| r.priority() == ScanPriority::FoundNote
+                && r.block_range().start == h1
+                && r.block_range().end > h2));
+        assert_eq!(
+            st.wallet_mut()
+                .db_mut()
+                .verify_commitment_trees(&[chain_state.clone()])
+                .unwrap(),
+            vec![]
+        );
+
+        // Rescanning the affected range restores the tree.
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(
+            st.wallet_mut()
+                .db_mut()
+                .verify_commitment_trees(&[chain_state])
+                .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            st.get_total_balance(st.test_account().unwrap().id()),
+            (value + value).unwrap()
+        );
+    }
+
+    #[test]
+    fn verify_and_repair_corrupt_tree_data() {
+        use std::io::Cursor;
+
+        use ::sapling::{Node, NOTE_COMMITMENT_TREE_DEPTH};
+        use incrementalmerkletree::{Address, Hashable, Level, Position};
+        use rusqlite::named_params;
+        use shardtree::LocatedTree;
+        use zcash_client_backend::{
+            data_api::{
+                chain::{ChainState, CommitmentTreeRoot},
+                testing::AddressType,
+                WalletCommitmentTrees, SAPLING_SHARD_HEIGHT,
+            },
+            serialization::shardtree::{read_shard, write_shard},
+        };
+        use zcash_primitives::merkle_tree::HashSer;
+        use zcash_protocol::value::Zatoshis;
+
+        use crate::{
+            testing::{db::TestDb, BlockCache},
+            wallet::commitment_tree::InconsistencyKind,
+        };
+
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = st.test_account_sapling().unwrap().clone();
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let chain_state_1 = st.latest_cached_block().unwrap().chain_state().clone();
+        let (h2, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 2);
+        let chain_state = st.latest_cached_block().unwrap().chain_state().clone();
+
+        let verify = |st: &mut TestState<_, TestDb, _>, chain_state: &ChainState| {
+            st.wallet_mut()
+                .db_mut()
+                .verify_commitment_trees(&[chain_state.clone()])
+                .unwrap()
+        };
+        let repair = |st: &mut TestState<_, TestDb, _>, inconsistencies| {
+            st.wallet_mut()
+                .db_mut()
+                .repair_commitment_trees(inconsistencies)
+                .unwrap()
+        };
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        // The server reports a different tree size for the last scanned block.
+        let server_state = ChainState::new(
+            h2,
+            chain_state.block_hash(),
+            chain_state_1.final_sapling_tree().clone(),
+            #[cfg(feature = "orchard")]
+            chain_state_1.final_orchard_tree().clone(),
+        );
+        let inconsistencies = verify(&mut st, &server_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(
+            inconsistencies[0].kind(),
+            &InconsistencyKind::TreeSizeMismatch {
+                server_tree_size: 1,
+                block_tree_size: 2,
+            }
+        );
+        assert_eq!(inconsistencies[0].height(), Some(h2));
+        assert_eq!(inconsistencies[0].shard_index(), Some(0));
+
+        // The checkpoint for the block is removed along with the shard data, and the
+        // affected blocks are rescanned.
+        assert_eq!(repair(&mut st, &inconsistencies), vec![h1..(h2 + 2)]);
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        // Shard data that cannot be decoded is reported, rather than being treated as absent.
+        st.wallet()
+            .conn()
+            .execute(
+                "UPDATE sapling_tree_shards SET shard_data = x'ff' WHERE shard_index = 0",
+                [],
+            )
+            .unwrap();
+        let inconsistencies = verify(&mut st, &chain_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(
+            inconsistencies[0].kind(),
+            &InconsistencyKind::UndecodableShard
+        );
+        assert_eq!(inconsistencies[0].shard_index(), Some(0));
+        assert_eq!(repair(&mut st, &inconsistencies), vec![h1..(h2 + 2)]);
+        st.scan_cached_blocks(h1, 2);
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        // The same is true of the tree cap. It is rebuilt without the need for a rescan.
+        st.wallet()
+            .conn()
+            .execute(
+                "INSERT INTO sapling_tree_cap (cap_id, cap_data) VALUES (0, x'ff')
+                 ON CONFLICT (cap_id) DO UPDATE SET cap_data = x'ff'",
+                [],
+            )
+            .unwrap();
+        let inconsistencies = verify(&mut st, &chain_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(
+            inconsistencies[0].kind(),
+            &InconsistencyKind::UndecodableCap
+        );
+        assert_eq!(inconsistencies[0].shard_index(), None);
+        assert_eq!(inconsistencies[0].block_range(), None);
+        assert_eq!(repair(&mut st, &inconsistencies), vec![]);
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        // Shard and cap roots can only be checked for complete shards, so we add the root of
+        // the next shard as though it had been obtained from the server.
+        let shard_root = Node::empty_root(Level::from(SAPLING_SHARD_HEIGHT));
+        let other_root = Node::empty_root(Level::from(SAPLING_SHARD_HEIGHT - 1));
+        st.wallet_mut()
+            .put_sapling_subtree_roots(1, &[CommitmentTreeRoot::from_parts(h2 + 1000, shard_root)])
+            .unwrap();
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        let encode_hash = |node: &Node| {
+            let mut data = vec![];
+            node.write(&mut data).unwrap();
+            data
+        };
+        let encode_leaf = |node: &Node| {
+            let mut data = vec![];
+            write_shard(
+                &mut data,
+                &shardtree::PrunableTree::leaf((*node, shardtree::RetentionFlags::EPHEMERAL)),
+            )
+            .unwrap();
+            data
+        };
+        let set_shard = |st: &TestState<_, TestDb, _>, root_hash, shard_data| {
+            st.wallet()
+                .conn()
+                .execute(
+                    "UPDATE sapling_tree_shards
+                     SET root_hash = :root_hash, shard_data = :shard_data
+                     WHERE shard_index = 1",
+                    named_params![":root_hash": root_hash, ":shard_data": shard_data],
+                )
+                .unwrap();
+        };
+
+        // The stored root of the shard does not match its contents.
+        set_shard(&st, encode_hash(&other_root), encode_leaf(&shard_root));
+        let inconsistencies = verify(&mut st, &chain_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(
+            inconsistencies[0].kind(),
+            &InconsistencyKind::ShardRootMismatch
+        );
+        assert_eq!(inconsistencies[0].shard_index(), Some(1));
+
+        // The shard is self-consistent, but its root does not match the cap.
+        set_shard(&st, encode_hash(&other_root), encode_leaf(&other_root));
+        let inconsistencies = verify(&mut st, &chain_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(inconsistencies[0].kind(), &InconsistencyKind::CapMismatch);
+        assert_eq!(inconsistencies[0].shard_index(), Some(1));
+
+        // Repair resets the shard, discarding its untrusted root hash, and rebuilds the cap
+        // without it.
+        let cap_root = |st: &TestState<_, TestDb, _>| {
+            let cap_data = st
+                .wallet()
+                .conn()
+                .query_row(
+                    "SELECT cap_data FROM sapling_tree_cap WHERE cap_id = 0",
+                    [],
+                    |row| row.get::<_, Vec<u8>>(0),
+                )
+                .unwrap();
+            let cap = read_shard::<Node, _>(&mut Cursor::new(cap_data)).unwrap();
+            LocatedTree::from_parts(
+                Address::from_parts(
+                    (NOTE_COMMITMENT_TREE_DEPTH - SAPLING_SHARD_HEIGHT).into(),
+                    0,
+                ),
+                cap,
+            )
+            .unwrap()
+            .value_at_position(Position::from(1))
+            .map(|(root, _)| *root)
+        };
+        assert_eq!(repair(&mut st, &inconsistencies), vec![]);
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+        assert_eq!(cap_root(&st), None);
+        assert_eq!(
+            st.wallet()
+                .conn()
+                .query_row(
+                    "SELECT root_hash FROM sapling_tree_shards WHERE shard_index = 1",
+                    [],
+                    |row| row.get::<_, Option<Vec<u8>>>(0),
+                )
+                .unwrap(),
+            None
+        );
+
+        // Once the root of the shard is obtained from the server again, the cap contains the
+        // known-good root, and the corrupted shard contents are detected once more.
+        st.wallet_mut()
+            .put_sapling_subtree_roots(1, &[CommitmentTreeRoot::from_parts(h2 + 1000, shard_root)])
+            .unwrap();
+        assert_eq!(cap_root(&st), Some(shard_root));
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+        set_shard(&st, encode_hash(&other_root), encode_leaf(&other_root));
+        let inconsistencies = verify(&mut st, &chain_state);
+        assert_eq!(inconsistencies.len(), 1);
+        assert_eq!(inconsistencies[0].kind(), &InconsistencyKind::CapMismatch);
+        assert_eq!(repair(&mut st, &inconsistencies), vec![]);
+        assert_eq!(verify(&mut st, &chain_state), vec![]);
+
+        assert_eq!(
+            st.get_total_balance(st.test_account().unwrap().id()),
+            (value + value).unwrap()
+        );
+    }
+
     #[cfg(feature = "unstable")]
     #[test]
     pub(crate) fn fsblockdb_api() {
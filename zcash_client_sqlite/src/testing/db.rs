@@ -35,8 +35,10 @@ use zcash_protocol::{
 use zip32::{fingerprint::SeedFingerprint, DiversifierIndex};
 
 use crate::{
-    error::SqliteClientError, util::testing::FixedClock, wallet::init::WalletMigrator, AccountUuid,
-    WalletDb,
+    error::SqliteClientError,
+    util::testing::FixedClock,
+    wallet::{init::WalletMigrator, pruning::PruningPolicy},
+    AccountUuid, WalletDb,
 };
 
 #[cfg(feature = "transparent-inputs")]
@@ -170,6 +172,18 @@ unsafe fn run_sqlite3<S: AsRef<OsStr>>(db_path: S, command: &str) {
 #[derive(Default)]
 pub(crate) struct TestDbFactory {
     target_migrations: Option<Vec<Uuid>>,
+    pruning_policy: Option<PruningPolicy>,
+}
+
+impl TestDbFactory {
+    /// Configures the wallet databases produced by this factory with the given pruning
+    /// policy.
+    pub(crate) fn with_pruning_policy(self, pruning_policy: PruningPolicy) -> Self {
+        Self {
+            pruning_policy: Some(pruning_policy),
+            ..self
+        }
+    }
 }
 
 impl DataStoreFactory for TestDbFactory {
@@ -191,6 +205,9 @@ impl DataStoreFactory for TestDbFactory {
         {
             db_data = db_data.with_gap_limits(gap_limits.into());
         }
+        if let Some(pruning_policy) = self.pruning_policy {
+            db_data = db_data.with_pruning_policy(pruning_policy);
+        }
 
         let migrator = WalletMigrator::new();
         if let Some(migrations) = &self.target_migrations {
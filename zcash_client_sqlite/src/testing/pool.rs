@@ -36,6 +36,35 @@ pub(crate) fn send_single_step_proposed_transfer<T: ShieldedPoolTester>() {
     )
 }
 
+pub(crate) fn send_structured_memo<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::send_structured_memo::<T>(
+        TestDbFactory::default(),
+        BlockCache::new(),
+    )
+}
+
+#[cfg(feature = "orchard")]
+pub(crate) fn created_transaction_passes_consensus_checks<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::created_transaction_passes_consensus_checks::<T>(
+        TestDbFactory::default(),
+        BlockCache::new(),
+    )
+}
+
+pub(crate) fn max_sendable_deducts_fee<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::max_sendable_deducts_fee::<T>(
+        TestDbFactory::default(),
+        BlockCache::new(),
+    )
+}
+
+pub(crate) fn send_max_spends_all_notes<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::send_max_spends_all_notes::<T>(
+        TestDbFactory::default(),
+        BlockCache::new(),
+    )
+}
+
 pub(crate) fn send_with_multiple_change_outputs<T: ShieldedPoolTester>() {
     zcash_client_backend::data_api::testing::pool::send_with_multiple_change_outputs::<T>(
         TestDbFactory::default(),
@@ -255,6 +284,13 @@ pub(crate) fn scan_cached_blocks_detects_spends_out_of_order<T: ShieldedPoolTest
     >(TestDbFactory::default(), BlockCache::new())
 }
 
+pub(crate) fn scan_cached_block_ranges_detects_spends_across_ranges<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::scan_cached_block_ranges_detects_spends_across_ranges::<
+        T,
+        _,
+    >(TestDbFactory::default(), BlockCache::new())
+}
+
 pub(crate) fn metadata_queries_exclude_unwanted_notes<T: ShieldedPoolTester>() {
     zcash_client_backend::data_api::testing::pool::metadata_queries_exclude_unwanted_notes::<T, _, _>(
         TestDbFactory::default(),
@@ -269,3 +305,10 @@ pub(crate) fn pczt_single_step<P0: ShieldedPoolTester, P1: ShieldedPoolTester>()
         BlockCache::new(),
     )
 }
+
+pub(crate) fn note_witnesses<T: ShieldedPoolTester>() {
+    zcash_client_backend::data_api::testing::pool::note_witnesses::<T, _>(
+        TestDbFactory::default(),
+        BlockCache::new(),
+    )
+}
@@ -87,6 +87,7 @@ use uuid::Uuid;
 use zcash_address::ZcashAddress;
 use zcash_client_backend::{
     data_api::{
+        events::WalletEvent,
         scanning::{ScanPriority, ScanRange},
         Account as _, AccountBalance, AccountBirthday, AccountPurpose, AccountSource, AddressInfo,
         BlockMetadata, DecryptedTransaction, Progress, Ratio, SentTransaction,
@@ -148,8 +149,10 @@ pub(crate) mod common;
 mod db;
 pub(crate) mod encoding;
 pub mod init;
+pub mod invoices;
 #[cfg(feature = "orchard")]
 pub(crate) mod orchard;
+pub mod pruning;
 pub(crate) mod sapling;
 pub(crate) mod scanning;
 #[cfg(feature = "transparent-inputs")]
@@ -508,60 +511,7 @@ pub(crate) fn add_account<P: consensus::Parameters>(
         birthday: birthday.height(),
     };
 
-    // If a birthday frontier is available, insert it into the note commitment tree. If the
-    // birthday frontier is the empty frontier, we don't need to do anything.
-    if let Some(frontier) = birthday.sapling_frontier().value() {
-        debug!("Inserting Sapling frontier into ShardTree: {:?}", frontier);
-        let shard_store =
-            SqliteShardStore::<_, ::sapling::Node, SAPLING_SHARD_HEIGHT>::from_connection(
-                conn,
-                crate::SAPLING_TABLES_PREFIX,
-            )?;
-        let mut shard_tree: ShardTree<
-            _,
-            { ::sapling::NOTE_COMMITMENT_TREE_DEPTH },
-            SAPLING_SHARD_HEIGHT,
-        > = ShardTree::new(shard_store, PRUNING_DEPTH.try_into().unwrap());
-        shard_tree.insert_frontier_nodes(
-            frontier.clone(),
-            Retention::Checkpoint {
-                // This subtraction is safe, because all leaves in the tree appear in blocks, and
-                // the invariant that birthday.height() always corresponds to the block for which
-                // `frontier` is the tree state at the start of the block. Together, this means
-                // there exists a prior block for which frontier is the tree state at the end of
-                // the block.
-                id: birthday.height() - 1,
-                marking: Marking::Reference,
-            },
-        )?;
-    }
-
-    #[cfg(feature = "orchard")]
-    if let Some(frontier) = birthday.orchard_frontier().value() {
-        debug!("Inserting Orchard frontier into ShardTree: {:?}", frontier);
-        let shard_store = SqliteShardStore::<
-            _,
-            ::orchard::tree::MerkleHashOrchard,
-            ORCHARD_SHARD_HEIGHT,
-        >::from_connection(conn, crate::ORCHARD_TABLES_PREFIX)?;
-        let mut shard_tree: ShardTree<
-            _,
-            { ::orchard::NOTE_COMMITMENT_TREE_DEPTH as u8 },
-            ORCHARD_SHARD_HEIGHT,
-        > = ShardTree::new(shard_store, PRUNING_DEPTH.try_into().unwrap());
-        shard_tree.insert_frontier_nodes(
-            frontier.clone(),
-            Retention::Checkpoint {
-                // This subtraction is safe, because all leaves in the tree appear in blocks, and
-                // the invariant that birthday.height() always corresponds to the block for which
-                // `frontier` is the tree state at the start of the block. Together, this means
-                // there exists a prior block for which frontier is the tree state at the end of
-                // the block.
-                id: birthday.height() - 1,
-                marking: Marking::Reference,
-            },
-        )?;
-    }
+    insert_birthday_frontiers(conn, birthday)?;
 
     // The ignored range always starts at Sapling activation
     let sapling_activation_height = params
@@ -648,6 +598,244 @@ pub(crate) fn add_account<P: consensus::Parameters>(
     Ok(account)
 }
 
+/// Inserts the note commitment tree frontiers of the given birthday into the wallet's note
+/// commitment trees.
+fn insert_birthday_frontiers(
+    conn: &rusqlite::Transaction,
+    birthday: &AccountBirthday,
+) -> Result<(), SqliteClientError> {
+    // If a birthday frontier is available, insert it into the note commitment tree. If the
+    // birthday frontier is the empty frontier, we don't need to do anything.
+    if let Some(frontier) = birthday.sapling_frontier().value() {
+        debug!("Inserting Sapling frontier into ShardTree: {:?}", frontier);
+        let shard_store =
+            SqliteShardStore::<_, ::sapling::Node, SAPLING_SHARD_HEIGHT>::from_connection(
+                conn,
+                crate::SAPLING_TABLES_PREFIX,
+            )?;
+        let mut shard_tree: ShardTree<
+            _,
+            { ::sapling::NOTE_COMMITMENT_TREE_DEPTH },
+            SAPLING_SHARD_HEIGHT,
+        > = ShardTree::new(shard_store, PRUNING_DEPTH.try_into().unwrap());
+        shard_tree.insert_frontier_nodes(
+            frontier.clone(),
+            Retention::Checkpoint {
+                // This subtraction is safe, because all leaves in the tree appear in blocks, and
+                // the invariant that birthday.height() always corresponds to the block for which
+                // `frontier` is the tree state at the start of the block. Together, this means
+                // there exists a prior block for which frontier is the tree state at the end of
+                // the block.
+                id: birthday.height() - 1,
+                marking: Marking::Reference,
+            },
+        )?;
+    }
+
+    #[cfg(feature = "orchard")]
+    if let Some(frontier) = birthday.orchard_frontier().value() {
+        debug!("Inserting Orchard frontier into ShardTree: {:?}", frontier);
+        let shard_store = SqliteShardStore::<
+            _,
+            ::orchard::tree::MerkleHashOrchard,
+            ORCHARD_SHARD_HEIGHT,
+        >::from_connection(conn, crate::ORCHARD_TABLES_PREFIX)?;
+        let mut shard_tree: ShardTree<
+            _,
+            { ::orchard::NOTE_COMMITMENT_TREE_DEPTH as u8 },
+            ORCHARD_SHARD_HEIGHT,
+        > = ShardTree::new(shard_store, PRUNING_DEPTH.try_into().unwrap());
+        shard_tree.insert_frontier_nodes(
+            frontier.clone(),
+            Retention::Checkpoint {
+                // This subtraction is safe, because all leaves in the tree appear in blocks, and
+                // the invariant that birthday.height() always corresponds to the block for which
+                // `frontier` is the tree state at the start of the block. Together, this means
+                // there exists a prior block for which frontier is the tree state at the end of
+                // the block.
+                id: birthday.height() - 1,
+                marking: Marking::Reference,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Extends the viewing key of the given account with the components of `ufvk` that the
+/// account's existing full viewing key lacks, and queues a rescan of the chain from the
+/// birthday of the new components.
+///
+/// If `birthday` precedes the account's birthday, the account's birthday is moved back to it.
+///
+/// Accounts with ZIP 32 derivation metadata are rejected, because a component that was not
+/// derived from the recorded seed and account index would contradict that metadata.
+pub(crate) fn extend_account_viewing_key<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    account_uuid: AccountUuid,
+    ufvk: &UnifiedFullViewingKey,
+    birthday: &AccountBirthday,
+    #[cfg(feature = "transparent-inputs")] gap_limits: &GapLimits,
+) -> Result<Account, SqliteClientError> {
+    let account =
+        get_account(conn, params, account_uuid)?.ok_or(SqliteClientError::AccountUnknown)?;
+    if account.kind.key_derivation().is_some() {
+        return Err(SqliteClientError::IncompatibleViewingKey);
+    }
+    let existing_ufvk = match &account.viewing_key {
+        ViewingKey::Full(existing_ufvk) => existing_ufvk,
+        ViewingKey::Incoming(_) => return Err(SqliteClientError::IncompatibleViewingKey),
+    };
+
+    // Every component of the account's existing key must be retained unchanged, and at
+    // least one component must be added.
+    let existing_items = fvk_item_caches(existing_ufvk);
+    let new_items = fvk_item_caches(ufvk);
+    let pairs = [
+        (&existing_items.0, &new_items.0),
+        (&existing_items.1, &new_items.1),
+        (&existing_items.2, &new_items.2),
+    ];
+    if pairs
+        .iter()
+        .any(|(existing, new)| existing.is_some() && existing != new)
+        || !pairs
+            .iter()
+            .any(|(existing, new)| existing.is_none() && new.is_some())
+    {
+        return Err(SqliteClientError::IncompatibleViewingKey);
+    }
+
+    // None of the added components may belong to another account.
+    let (orchard_item, sapling_item, transparent_item) = new_items;
+    if let Some(colliding_uuid) = conn
+        .query_row(
+            "SELECT uuid FROM accounts
+             WHERE id != :account_id
+             AND (
+                orchard_fvk_item_cache = :orchard_fvk_item_cache
+                OR sapling_fvk_item_cache = :sapling_fvk_item_cache
+                OR p2pkh_fvk_item_cache = :p2pkh_fvk_item_cache
+             )",
+            named_params![
+                ":account_id": account.id.0,
+                ":orchard_fvk_item_cache": orchard_item,
+                ":sapling_fvk_item_cache": sapling_item,
+                ":p2pkh_fvk_item_cache": transparent_item,
+            ],
+            |row| Ok(AccountUuid(row.get(0)?)),
+        )
+        .optional()?
+    {
+        return Err(SqliteClientError::AccountCollision(colliding_uuid));
+    }
+
+    conn.execute(
+        "UPDATE accounts
+         SET ufvk = :ufvk,
+             uivk = :uivk,
+             orchard_fvk_item_cache = :orchard_fvk_item_cache,
+             sapling_fvk_item_cache = :sapling_fvk_item_cache,
+             p2pkh_fvk_item_cache = :p2pkh_fvk_item_cache
+         WHERE id = :account_id",
+        named_params![
+            ":ufvk": ufvk.encode(params),
+            ":uivk": ufvk.to_unified_incoming_viewing_key().encode(params),
+            ":orchard_fvk_item_cache": orchard_item,
+            ":sapling_fvk_item_cache": sapling_item,
+            ":p2pkh_fvk_item_cache": transparent_item,
+            ":account_id": account.id.0,
+        ],
+    )?;
+
+    // If the new components are older than the account, move the account's birthday back so
+    // that the note commitment trees can be constructed from the earlier birthday.
+    if birthday.height() < account.birthday {
+        #[cfg(feature = "orchard")]
+        let birthday_orchard_tree_size = Some(birthday.orchard_frontier().tree_size());
+        #[cfg(not(feature = "orchard"))]
+        let birthday_orchard_tree_size: Option<u64> = None;
+
+        conn.execute(
+            "UPDATE accounts
+             SET birthday_height = :birthday_height,
+                 birthday_sapling_tree_size = :birthday_sapling_tree_size,
+                 birthday_orchard_tree_size = :birthday_orchard_tree_size
+             WHERE id = :account_id",
+            named_params![
+                ":birthday_height": u32::from(birthday.height()),
+                ":birthday_sapling_tree_size": birthday.sapling_frontier().tree_size(),
+                ":birthday_orchard_tree_size": birthday_orchard_tree_size,
+                ":account_id": account.id.0,
+            ],
+        )?;
+
+        insert_birthday_frontiers(conn, birthday)?;
+    }
+
+    // Rescan from the birthday of the new components up to the chain tip. Data that the wallet
+    // has already stored for this range is updated in place as blocks are rescanned, so later
+    // history is retained and is reconciled with any newly discovered notes and spends.
+    if let Some(t) = chain_tip_height(conn)? {
+        if birthday.height() <= t {
+            let rescan_range = birthday.height()..(t + 1);
+
+            replace_queue_entries::<SqliteClientError>(
+                conn,
+                &rescan_range,
+                Some(ScanRange::from_parts(
+                    rescan_range.clone(),
+                    ScanPriority::Historic,
+                ))
+                .into_iter(),
+                true, // force rescan
+            )?;
+        }
+    }
+
+    // If a transparent component was added, pre-generate its addresses up to the gap limits
+    // so that funds received by them can be detected.
+    #[cfg(feature = "transparent-inputs")]
+    if existing_ufvk.transparent().is_none() && ufvk.transparent().is_some() {
+        for key_scope in [KeyScope::EXTERNAL, KeyScope::INTERNAL, KeyScope::Ephemeral] {
+            use ReceiverRequirement::*;
+            transparent::generate_gap_addresses(
+                conn,
+                params,
+                account.id,
+                key_scope,
+                gap_limits,
+                UnifiedAddressRequest::unsafe_custom(Allow, Allow, Require),
+                false,
+            )?;
+        }
+    }
+
+    get_account(conn, params, account_uuid)?.ok_or(SqliteClientError::AccountUnknown)
+}
+
+/// Returns the Orchard, Sapling, and transparent FVK item encodings of the given UFVK, as
+/// stored in the `accounts` table's item cache columns.
+#[allow(clippy::type_complexity)]
+fn fvk_item_caches(
+    ufvk: &UnifiedFullViewingKey,
+) -> (Option<Vec<u8>>, Option<Vec<u8>>, Option<Vec<u8>>) {
+    #[cfg(feature = "orchard")]
+    let orchard_item = ufvk.orchard().map(|k| k.to_bytes().to_vec());
+    #[cfg(not(feature = "orchard"))]
+    let orchard_item: Option<Vec<u8>> = None;
+
+    let sapling_item = ufvk.sapling().map(|k| k.to_bytes().to_vec());
+
+    #[cfg(feature = "transparent-inputs")]
+    let transparent_item = ufvk.transparent().map(|k| k.serialize());
+    #[cfg(not(feature = "transparent-inputs"))]
+    let transparent_item: Option<Vec<u8>> = None;
+
+    (orchard_item, sapling_item, transparent_item)
+}
+
 pub(crate) fn get_next_available_address<P: consensus::Parameters, C: Clock>(
     conn: &rusqlite::Transaction,
     params: &P,
@@ -2154,7 +2342,8 @@ pub(crate) fn get_transaction<P: Parameters>(
 ) -> Result<Option<(BlockHeight, Transaction)>, SqliteClientError> {
     conn.query_row(
         "SELECT raw, block, expiry_height FROM transactions
-        WHERE txid = ?",
+        WHERE txid = ?
+        AND raw IS NOT NULL",
         [txid.as_ref()],
         |row| {
             let h: Option<u32> = row.get(1)?;
@@ -2836,18 +3025,93 @@ pub(crate) fn set_transaction_status(
     Ok(())
 }
 
-/// Truncates the database to at most the given height.
-///
-/// If the requested height is greater than or equal to the height of the last scanned
-/// block, this function does nothing.
-///
-/// This should only be executed inside a transactional context.
+/// Returns whether the given transaction is known to the wallet, has not been mined, and can
+/// no longer be mined because the chain tip has reached its expiry height.
+pub(crate) fn is_expired_unmined(
+    conn: &rusqlite::Connection,
+    txid: TxId,
+) -> Result<bool, SqliteClientError> {
+    let expiry_height = conn
+        .query_row(
+            "SELECT expiry_height FROM transactions
+             WHERE txid = :txid AND mined_height IS NULL",
+            named_params![":txid": txid.as_ref()],
+            |row| row.get::<_, Option<u32>>(0),
+        )
+        .optional()?
+        .flatten()
+        .filter(|h| *h > 0)
+        .map(BlockHeight::from);
+
+    Ok(match (expiry_height, chain_tip_height(conn)?) {
+        (Some(expiry_height), Some(chain_tip)) => chain_tip >= expiry_height,
+        _ => false,
+    })
+}
+
+/// Removes from `events` the [`WalletEvent::NoteReceived`] events for notes that are already
+/// stored in the wallet, and the [`WalletEvent::TransactionMined`] events for transactions
+/// whose stored mined height already matches.
 ///
-/// Returns the block height to which the database was truncated.
-pub(crate) fn truncate_to_height<P: consensus::Parameters>(
-    conn: &rusqlite::Transaction,
-    params: &P,
-    #[cfg(feature = "transparent-inputs")] gap_limits: &GapLimits,
+/// This must be called within the database transaction that stores the changes described by
+/// `events`, before those changes are written, so that data the wallet has already seen (for
+/// example when enhancing a transaction that was previously found by scanning) is not
+/// reported a second time.
+pub(crate) fn retain_new_events(
+    conn: &rusqlite::Connection,
+    events: &mut Vec<WalletEvent<AccountUuid>>,
+) -> Result<(), SqliteClientError> {
+    let mut retained = Vec::with_capacity(events.len());
+    for event in events.drain(..) {
+        let is_new = match &event {
+            WalletEvent::NoteReceived { note_id, .. } => {
+                let TableConstants {
+                    table_prefix,
+                    output_index_col,
+                    ..
+                } = table_constants::<SqliteClientError>(note_id.protocol())?;
+                !conn.query_row(
+                    &format!(
+                        "SELECT EXISTS (
+                            SELECT 1
+                            FROM {table_prefix}_received_notes rn
+                            JOIN transactions t ON t.id_tx = rn.tx
+                            WHERE t.txid = :txid AND rn.{output_index_col} = :output_index
+                        )"
+                    ),
+                    named_params![
+                        ":txid": note_id.txid().as_ref(),
+                        ":output_index": note_id.output_index(),
+                    ],
+                    |row| row.get::<_, bool>(0),
+                )?
+            }
+            WalletEvent::TransactionMined { txid, height } => {
+                let mined_height = conn
+                    .query_row(
+                        "SELECT mined_height FROM transactions WHERE txid = :txid",
+                        named_params![":txid": txid.as_ref()],
+                        |row| row.get::<_, Option<u32>>(0),
+                    )
+                    .optional()?
+                    .flatten()
+                    .map(BlockHeight::from);
+                mined_height != Some(*height)
+            }
+            _ => true,
+        };
+        if is_new {
+            retained.push(event);
+        }
+    }
+    *events = retained;
+    Ok(())
+}
+
+/// Returns the height of the most recent checkpoint at or below `max_height`, to which the
+/// wallet can be truncated.
+fn truncation_height(
+    conn: &rusqlite::Connection,
     max_height: BlockHeight,
 ) -> Result<BlockHeight, SqliteClientError> {
     // Determine a checkpoint to which we can rewind, if any.
@@ -2866,43 +3130,106 @@ pub(crate) fn truncate_to_height<P: consensus::Parameters>(
         WHERE blocks.height <= :block_height
     "#;
 
-    let truncation_height = conn
-        .query_row(
-            truncation_height_query,
-            named_params! {":block_height": u32::from(max_height)},
-            |row| row.get::<_, Option<u32>>(0),
-        )
-        .optional()?
-        .flatten()
-        .map_or_else(
-            || {
-                // If we don't have a checkpoint at a height less than or equal to the requested
-                // truncation height, query for the minimum height to which it's possible for us to
-                // truncate so that we can report it to the caller.
-                #[cfg(not(feature = "orchard"))]
-                let min_checkpoint_height_query =
-                    "SELECT MIN(checkpoint_id) FROM sapling_tree_checkpoints";
-                #[cfg(feature = "orchard")]
-                let min_checkpoint_height_query = "SELECT MIN(sc.checkpoint_id)
+    conn.query_row(
+        truncation_height_query,
+        named_params! {":block_height": u32::from(max_height)},
+        |row| row.get::<_, Option<u32>>(0),
+    )
+    .optional()?
+    .flatten()
+    .map_or_else(
+        || {
+            // If we don't have a checkpoint at a height less than or equal to the requested
+            // truncation height, query for the minimum height to which it's possible for us to
+            // truncate so that we can report it to the caller.
+            #[cfg(not(feature = "orchard"))]
+            let min_checkpoint_height_query =
+                "SELECT MIN(checkpoint_id) FROM sapling_tree_checkpoints";
+            #[cfg(feature = "orchard")]
+            let min_checkpoint_height_query = "SELECT MIN(sc.checkpoint_id)
                      FROM sapling_tree_checkpoints sc
                      JOIN orchard_tree_checkpoints oc
                      ON oc.checkpoint_id = sc.checkpoint_id";
 
-                let min_truncation_height = conn
-                    .query_row(min_checkpoint_height_query, [], |row| {
-                        row.get::<_, Option<u32>>(0)
-                    })
-                    .optional()?
-                    .flatten()
-                    .map(BlockHeight::from);
-
-                Err(SqliteClientError::RequestedRewindInvalid {
-                    safe_rewind_height: min_truncation_height,
-                    requested_height: max_height,
+            let min_truncation_height = conn
+                .query_row(min_checkpoint_height_query, [], |row| {
+                    row.get::<_, Option<u32>>(0)
                 })
-            },
-            |h| Ok(BlockHeight::from(h)),
-        )?;
+                .optional()?
+                .flatten()
+                .map(BlockHeight::from);
+
+            Err(SqliteClientError::RequestedRewindInvalid {
+                safe_rewind_height: min_truncation_height,
+                requested_height: max_height,
+            })
+        },
+        |h| Ok(BlockHeight::from(h)),
+    )
+}
+
+/// Returns the accounts whose balances would be affected by truncating the database to at
+/// most the given height, or `None` if the truncation would not remove any blocks or un-mine
+/// any transactions.
+pub(crate) fn accounts_affected_by_truncation(
+    conn: &rusqlite::Connection,
+    max_height: BlockHeight,
+) -> Result<Option<Vec<AccountUuid>>, SqliteClientError> {
+    let truncation_height = truncation_height(conn, max_height)?;
+
+    let removes_rows = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM blocks WHERE height > :height)
+             OR EXISTS (SELECT 1 FROM transactions WHERE mined_height > :height)",
+        named_params![":height": u32::from(truncation_height)],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if !removes_rows {
+        return Ok(None);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT accounts.uuid
+         FROM accounts
+         JOIN (
+             SELECT ro.account_id
+             FROM v_received_outputs ro
+             JOIN transactions t ON t.id_tx = ro.transaction_id
+             WHERE t.mined_height > :height
+             UNION
+             SELECT ro.account_id
+             FROM v_received_output_spends ros
+             JOIN v_received_outputs ro
+                 ON ro.pool = ros.pool
+                 AND ro.id_within_pool_table = ros.received_output_id
+             JOIN transactions t ON t.id_tx = ros.transaction_id
+             WHERE t.mined_height > :height
+         ) affected ON affected.account_id = accounts.id",
+    )?;
+    let accounts = stmt
+        .query_and_then(
+            named_params![":height": u32::from(truncation_height)],
+            |row| Ok::<_, SqliteClientError>(AccountUuid(row.get(0)?)),
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(accounts))
+}
+
+/// Truncates the database to at most the given height.
+///
+/// If the requested height is greater than or equal to the height of the last scanned
+/// block, this function does nothing.
+///
+/// This should only be executed inside a transactional context.
+///
+/// Returns the block height to which the database was truncated.
+pub(crate) fn truncate_to_height<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    #[cfg(feature = "transparent-inputs")] gap_limits: &GapLimits,
+    max_height: BlockHeight,
+) -> Result<BlockHeight, SqliteClientError> {
+    let truncation_height = truncation_height(conn, max_height)?;
 
     let last_scanned_height = conn.query_row("SELECT MAX(height) FROM blocks", [], |row| {
         let h = row.get::<_, Option<u32>>(0)?;
@@ -2968,6 +3295,8 @@ pub(crate) fn truncate_to_height<P: consensus::Parameters>(
             rng: (),
             #[cfg(feature = "transparent-inputs")]
             gap_limits: *gap_limits,
+            pruning_policy: None,
+            subscribers: vec![],
         };
         wdb.with_sapling_tree_mut(|tree| {
             tree.truncate_to_checkpoint(&truncation_height)?;
@@ -3781,7 +4110,9 @@ pub(crate) fn queue_tx_retrieval(
     txids: impl Iterator<Item = TxId>,
     dependent_tx_ref: Option<TxRef>,
 ) -> Result<(), SqliteClientError> {
-    // Add an entry to the transaction retrieval queue if it would not be redundant.
+    // Add an entry to the transaction retrieval queue if it would not be redundant. The raw
+    // data of pruned transactions was discarded intentionally, and their mined heights are
+    // below the reorg window, so they are never queued.
     let mut stmt_insert_tx = conn.prepare_cached(
         "INSERT INTO tx_retrieval_queue (txid, query_type, dependent_transaction_id)
             SELECT
@@ -3792,6 +4123,12 @@ pub(crate) fn queue_tx_retrieval(
                 :enhancement_type
             ),
             :dependent_transaction_id
+            WHERE NOT EXISTS (
+                SELECT 1 FROM transactions t
+                JOIN pruned_transactions p ON p.transaction_id = t.id_tx
+                WHERE t.txid = :txid
+                AND t.raw IS NULL
+            )
         ON CONFLICT (txid) DO UPDATE
         SET query_type =
             IIF(
@@ -1,34 +1,47 @@
 use rusqlite::{self, named_params, OptionalExtension};
 use std::{
+    cmp::{max, min},
     collections::BTreeSet,
     error, fmt,
     io::{self, Cursor},
+    iter,
     marker::PhantomData,
     num::NonZeroU32,
     ops::Range,
     sync::Arc,
 };
 
-use incrementalmerkletree::{Address, Hashable, Level, Position, Retention};
+use incrementalmerkletree::{frontier::Frontier, Address, Hashable, Level, Position, Retention};
 use shardtree::{
     error::{QueryError, ShardTreeError},
     store::{Checkpoint, ShardStore, TreeState},
-    LocatedPrunableTree, LocatedTree, PrunableTree, RetentionFlags,
+    LocatedPrunableTree, LocatedTree, Node, PrunableTree, RetentionFlags, ShardTree,
 };
 
 use zcash_client_backend::{
-    data_api::chain::CommitmentTreeRoot,
+    data_api::{
+        chain::{ChainState, CommitmentTreeRoot},
+        scanning::{ScanPriority, ScanRange},
+        NoteWitnesses, SAPLING_SHARD_HEIGHT,
+    },
     serialization::shardtree::{read_shard, write_shard},
+    wallet::NoteId,
 };
 use zcash_primitives::merkle_tree::HashSer;
 use zcash_protocol::{consensus::BlockHeight, ShieldedProtocol};
 
-use crate::{error::SqliteClientError, sapling_tree};
+use crate::{error::SqliteClientError, sapling_tree, PRUNING_DEPTH, SAPLING_TABLES_PREFIX};
 
 #[cfg(feature = "orchard")]
-use crate::orchard_tree;
+use {
+    crate::{orchard_tree, ORCHARD_TABLES_PREFIX},
+    zcash_client_backend::data_api::ORCHARD_SHARD_HEIGHT,
+};
 
-use super::common::{table_constants, TableConstants};
+use super::{
+    common::{table_constants, TableConstants},
+    scanning::replace_queue_entries,
+};
 
 /// Errors that can appear in SQLite-back [`ShardStore`] implementation operations.
 #[derive(Debug)]
@@ -225,6 +238,135 @@ impl<'conn, 'a: 'conn, H: HashSer, const SHARD_HEIGHT: u8> ShardStore
     }
 }
 
+/// A shard store over a borrowed connection, which may already be within a transaction.
+///
+/// Reads are performed directly on the connection, so that they observe any uncommitted
+/// changes made by an enclosing transaction. Each write is performed within a transaction of
+/// its own, and so fails if the connection is already within a transaction; callers that
+/// need to write should use a [`rusqlite::Transaction`] instead.
+impl<'a, H: HashSer, const SHARD_HEIGHT: u8> ShardStore
+    for SqliteShardStore<&'a rusqlite::Connection, H, SHARD_HEIGHT>
+{
+    type H = H;
+    type CheckpointId = BlockHeight;
+    type Error = Error;
+
+    fn get_shard(
+        &self,
+        shard_root: Address,
+    ) -> Result<Option<LocatedPrunableTree<Self::H>>, Self::Error> {
+        get_shard(self.conn, self.table_prefix, shard_root)
+    }
+
+    fn last_shard(&self) -> Result<Option<LocatedPrunableTree<Self::H>>, Self::Error> {
+        last_shard(self.conn, self.table_prefix, Self::SHARD_ROOT_LEVEL)
+    }
+
+    fn put_shard(&mut self, subtree: LocatedPrunableTree<Self::H>) -> Result<(), Self::Error> {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        put_shard(&tx, self.table_prefix, subtree)?;
+        tx.commit().map_err(Error::Query)
+    }
+
+    fn get_shard_roots(&self) -> Result<Vec<Address>, Self::Error> {
+        get_shard_roots(self.conn, self.table_prefix, Self::SHARD_ROOT_LEVEL)
+    }
+
+    fn truncate_shards(&mut self, shard_index: u64) -> Result<(), Self::Error> {
+        truncate_shards(self.conn, self.table_prefix, shard_index)
+    }
+
+    fn get_cap(&self) -> Result<PrunableTree<Self::H>, Self::Error> {
+        get_cap(self.conn, self.table_prefix)
+    }
+
+    fn put_cap(&mut self, cap: PrunableTree<Self::H>) -> Result<(), Self::Error> {
+        put_cap(self.conn, self.table_prefix, cap)
+    }
+
+    fn min_checkpoint_id(&self) -> Result<Option<Self::CheckpointId>, Self::Error> {
+        min_checkpoint_id(self.conn, self.table_prefix)
+    }
+
+    fn max_checkpoint_id(&self) -> Result<Option<Self::CheckpointId>, Self::Error> {
+        max_checkpoint_id(self.conn, self.table_prefix)
+    }
+
+    fn add_checkpoint(
+        &mut self,
+        checkpoint_id: Self::CheckpointId,
+        checkpoint: Checkpoint,
+    ) -> Result<(), Self::Error> {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        add_checkpoint(&tx, self.table_prefix, checkpoint_id, checkpoint)?;
+        tx.commit().map_err(Error::Query)
+    }
+
+    fn checkpoint_count(&self) -> Result<usize, Self::Error> {
+        checkpoint_count(self.conn, self.table_prefix)
+    }
+
+    fn get_checkpoint_at_depth(
+        &self,
+        checkpoint_depth: usize,
+    ) -> Result<Option<(Self::CheckpointId, Checkpoint)>, Self::Error> {
+        get_checkpoint_at_depth(self.conn, self.table_prefix, checkpoint_depth)
+            .map_err(Error::Query)
+    }
+
+    fn get_checkpoint(
+        &self,
+        checkpoint_id: &Self::CheckpointId,
+    ) -> Result<Option<Checkpoint>, Self::Error> {
+        get_checkpoint(self.conn, self.table_prefix, *checkpoint_id)
+    }
+
+    fn with_checkpoints<F>(&mut self, limit: usize, callback: F) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::CheckpointId, &Checkpoint) -> Result<(), Self::Error>,
+    {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        with_checkpoints(&tx, self.table_prefix, limit, callback)?;
+        tx.commit().map_err(Error::Query)
+    }
+
+    fn for_each_checkpoint<F>(&self, limit: usize, callback: F) -> Result<(), Self::Error>
+    where
+        F: FnMut(&Self::CheckpointId, &Checkpoint) -> Result<(), Self::Error>,
+    {
+        with_checkpoints(self.conn, self.table_prefix, limit, callback)
+    }
+
+    fn update_checkpoint_with<F>(
+        &mut self,
+        checkpoint_id: &Self::CheckpointId,
+        update: F,
+    ) -> Result<bool, Self::Error>
+    where
+        F: Fn(&mut Checkpoint) -> Result<(), Self::Error>,
+    {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        let result = update_checkpoint_with(&tx, self.table_prefix, *checkpoint_id, update)?;
+        tx.commit().map_err(Error::Query)?;
+        Ok(result)
+    }
+
+    fn remove_checkpoint(&mut self, checkpoint_id: &Self::CheckpointId) -> Result<(), Self::Error> {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        remove_checkpoint(&tx, self.table_prefix, *checkpoint_id)?;
+        tx.commit().map_err(Error::Query)
+    }
+
+    fn truncate_checkpoints_retaining(
+        &mut self,
+        checkpoint_id: &Self::CheckpointId,
+    ) -> Result<(), Self::Error> {
+        let tx = self.conn.unchecked_transaction().map_err(Error::Query)?;
+        truncate_checkpoints_retaining(&tx, self.table_prefix, *checkpoint_id)?;
+        tx.commit().map_err(Error::Query)
+    }
+}
+
 impl<H: HashSer, const SHARD_HEIGHT: u8> ShardStore
     for SqliteShardStore<rusqlite::Connection, H, SHARD_HEIGHT>
 {
@@ -845,7 +987,7 @@ pub(crate) fn get_checkpoint_at_depth(
 }
 
 pub(crate) fn with_checkpoints<F>(
-    conn: &rusqlite::Transaction<'_>,
+    conn: &rusqlite::Connection,
     table_prefix: &'static str,
     limit: usize,
     mut callback: F,
@@ -964,6 +1106,39 @@ pub(crate) fn truncate_checkpoints_retaining(
     Ok(())
 }
 
+/// A wrapper that shifts the levels of a hash type by `SHARD_HEIGHT`, so that the cap of a
+/// note commitment tree can be treated as a tree whose leaves are the roots of its shards.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LevelShifter<H, const SHARD_HEIGHT: u8>(H);
+impl<H: Hashable, const SHARD_HEIGHT: u8> Hashable for LevelShifter<H, SHARD_HEIGHT> {
+    fn empty_leaf() -> Self {
+        Self(H::empty_root(SHARD_HEIGHT.into()))
+    }
+
+    fn combine(level: Level, a: &Self, b: &Self) -> Self {
+        Self(H::combine(level + SHARD_HEIGHT, &a.0, &b.0))
+    }
+
+    fn empty_root(level: Level) -> Self
+    where
+        Self: Sized,
+    {
+        Self(H::empty_root(level + SHARD_HEIGHT))
+    }
+}
+impl<H: HashSer, const SHARD_HEIGHT: u8> HashSer for LevelShifter<H, SHARD_HEIGHT> {
+    fn read<R: io::Read>(reader: R) -> io::Result<Self>
+    where
+        Self: Sized,
+    {
+        H::read(reader).map(Self)
+    }
+
+    fn write<W: io::Write>(&self, writer: W) -> io::Result<()> {
+        self.0.write(writer)
+    }
+}
+
 #[tracing::instrument(skip(conn, roots))]
 pub(crate) fn put_shard_roots<
     H: Hashable + HashSer + Clone + Eq,
@@ -983,37 +1158,6 @@ pub(crate) fn put_shard_roots<
     // We treat the cap as a tree with `DEPTH - SHARD_HEIGHT` levels, so that we can make a
     // batch insertion of root data using `Position::from(start_index)` as the starting position
     // and treating the roots as level-0 leaves.
-    #[derive(Clone, Debug, PartialEq, Eq)]
-    struct LevelShifter<H, const SHARD_HEIGHT: u8>(H);
-    impl<H: Hashable, const SHARD_HEIGHT: u8> Hashable for LevelShifter<H, SHARD_HEIGHT> {
-        fn empty_leaf() -> Self {
-            Self(H::empty_root(SHARD_HEIGHT.into()))
-        }
-
-        fn combine(level: Level, a: &Self, b: &Self) -> Self {
-            Self(H::combine(level + SHARD_HEIGHT, &a.0, &b.0))
-        }
-
-        fn empty_root(level: Level) -> Self
-        where
-            Self: Sized,
-        {
-            Self(H::empty_root(level + SHARD_HEIGHT))
-        }
-    }
-    impl<H: HashSer, const SHARD_HEIGHT: u8> HashSer for LevelShifter<H, SHARD_HEIGHT> {
-        fn read<R: io::Read>(reader: R) -> io::Result<Self>
-        where
-            Self: Sized,
-        {
-            H::read(reader).map(Self)
-        }
-
-        fn write<W: io::Write>(&self, writer: W) -> io::Result<()> {
-            self.0.write(writer)
-        }
-    }
-
     let cap = LocatedTree::from_parts(
         Address::from_parts((DEPTH - SHARD_HEIGHT).into(), 0),
         get_cap::<LevelShifter<H, SHARD_HEIGHT>>(conn, table_prefix)
@@ -1142,6 +1286,620 @@ pub(crate) fn check_witnesses(
     Ok(scan_ranges)
 }
 
+/// Returns the commitment tree positions of those of the specified notes that belong to the
+/// given shielded protocol and are known to the wallet as having been mined.
+fn get_note_positions(
+    conn: &rusqlite::Connection,
+    note_ids: &[NoteId],
+    protocol: ShieldedProtocol,
+) -> Result<Vec<(NoteId, Position)>, SqliteClientError> {
+    let TableConstants {
+        table_prefix,
+        output_index_col,
+        ..
+    } = table_constants::<SqliteClientError>(protocol)?;
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT rn.commitment_tree_position
+         FROM {table_prefix}_received_notes rn
+         JOIN transactions t ON t.id_tx = rn.tx
+         WHERE t.txid = :txid
+         AND rn.{output_index_col} = :output_index
+         AND rn.commitment_tree_position IS NOT NULL"
+    ))?;
+
+    let mut positions = vec![];
+    for note_id in note_ids.iter().filter(|n| n.protocol() == protocol) {
+        let position = stmt
+            .query_row(
+                named_params![
+                    ":txid": note_id.txid().as_ref(),
+                    ":output_index": note_id.output_index(),
+                ],
+                |row| row.get::<_, u64>(0).map(Position::from),
+            )
+            .optional()?;
+        positions.extend(position.map(|p| (*note_id, p)));
+    }
+
+    Ok(positions)
+}
+
+/// Computes Merkle witnesses for the specified notes as of the checkpoint at the given anchor
+/// height.
+pub(crate) fn get_note_witnesses(
+    conn: &rusqlite::Connection,
+    note_ids: &[NoteId],
+    anchor_height: BlockHeight,
+) -> Result<NoteWitnesses, SqliteClientError> {
+    let checkpoint_pruned =
+        || SqliteClientError::CommitmentTree(ShardTreeError::Query(QueryError::CheckpointPruned));
+
+    let sapling_tree = sapling_tree(conn)?;
+    let sapling_anchor = sapling_tree
+        .root_at_checkpoint_id(&anchor_height)?
+        .map(sapling::Anchor::from);
+    let sapling_witnesses = get_note_positions(conn, note_ids, ShieldedProtocol::Sapling)?
+        .into_iter()
+        .map(|(note_id, position)| {
+            let path = sapling_tree
+                .witness_at_checkpoint_id(position, &anchor_height)?
+                .ok_or_else(checkpoint_pruned)?;
+            Ok((note_id, path))
+        })
+        .collect::<Result<Vec<_>, SqliteClientError>>()?;
+
+    #[cfg(feature = "orchard")]
+    let (orchard_anchor, orchard_witnesses) = {
+        let orchard_tree = orchard_tree(conn)?;
+        let orchard_anchor = orchard_tree
+            .root_at_checkpoint_id(&anchor_height)?
+            .map(orchard::Anchor::from);
+        let orchard_witnesses = get_note_positions(conn, note_ids, ShieldedProtocol::Orchard)?
+            .into_iter()
+            .map(|(note_id, position)| {
+                let path = orchard_tree
+                    .witness_at_checkpoint_id(position, &anchor_height)?
+                    .ok_or_else(checkpoint_pruned)?;
+                Ok((note_id, path.into()))
+            })
+            .collect::<Result<Vec<_>, SqliteClientError>>()?;
+
+        (orchard_anchor, orchard_witnesses)
+    };
+
+    Ok(NoteWitnesses::from_parts(
+        anchor_height,
+        sapling_anchor,
+        sapling_witnesses,
+        #[cfg(feature = "orchard")]
+        orchard_anchor,
+        #[cfg(feature = "orchard")]
+        orchard_witnesses,
+    ))
+}
+
+/// A kind of inconsistency detected by [`WalletDb::verify_commitment_trees`] in the wallet's
+/// note commitment tree data.
+///
+/// [`WalletDb::verify_commitment_trees`]: crate::WalletDb::verify_commitment_trees
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InconsistencyKind {
+    /// The stored data for a shard, or its stored root hash, could not be decoded.
+    UndecodableShard,
+    /// The stored tree cap could not be decoded.
+    UndecodableCap,
+    /// The root hash recomputed from the leaves of a shard does not match either its stored
+    /// root hash or a hash cached at one of the shard's internal nodes.
+    ShardRootMismatch,
+    /// The root of a shard recorded in the tree cap does not match the root recomputed from
+    /// the leaves of the shard.
+    CapMismatch,
+    /// The tree position recorded for a checkpoint does not correspond to the note commitment
+    /// tree size recorded for the block at the checkpoint height.
+    CheckpointMismatch {
+        checkpoint_tree_size: u64,
+        block_tree_size: u64,
+    },
+    /// The note commitment tree size recorded for a block does not match the tree size
+    /// reported by the server for that block.
+    TreeSizeMismatch {
+        server_tree_size: u64,
+        block_tree_size: u64,
+    },
+    /// The root of the note commitment tree at a checkpoint does not match the root of the
+    /// frontier reported by the server for that block.
+    RootMismatch,
+}
+
+/// An inconsistency detected in the wallet's note commitment tree data for a single shielded
+/// protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeInconsistency {
+    protocol: ShieldedProtocol,
+    kind: InconsistencyKind,
+    height: Option<BlockHeight>,
+    shard_index: Option<u64>,
+    block_range: Option<Range<BlockHeight>>,
+}
+
+impl TreeInconsistency {
+    /// Returns the shielded protocol whose note commitment tree is affected.
+    pub fn protocol(&self) -> ShieldedProtocol {
+        self.protocol
+    }
+
+    /// Returns the kind of inconsistency that was detected.
+    pub fn kind(&self) -> &InconsistencyKind {
+        &self.kind
+    }
+
+    /// Returns the height of the checkpoint or block at which the inconsistency was detected,
+    /// if it was not detected in shard data.
+    pub fn height(&self) -> Option<BlockHeight> {
+        self.height
+    }
+
+    /// Returns the index of the shard that must be reset in order to repair the
+    /// inconsistency, if any.
+    pub fn shard_index(&self) -> Option<u64> {
+        self.shard_index
+    }
+
+    /// Returns the range of blocks that must be rescanned in order to repair the
+    /// inconsistency, if the wallet has enough block metadata to determine it.
+    pub fn block_range(&self) -> Option<&Range<BlockHeight>> {
+        self.block_range.as_ref()
+    }
+}
+
+/// Recomputes the root of the given subtree from its leaves, ignoring any cached annotations.
+///
+/// Returns `None` if the subtree contains any nodes that have not yet been observed by the
+/// wallet. The address of each annotated node whose cached hash conflicts with the recomputed
+/// hash is appended to `conflicts`.
+fn recompute_root<H: Hashable + Clone + PartialEq>(
+    addr: Address,
+    tree: &PrunableTree<H>,
+    conflicts: &mut Vec<Address>,
+) -> Option<H> {
+    match &**tree {
+        Node::Parent { ann, left, right } => {
+            let (l_addr, r_addr) = addr
+                .children()
+                .expect("parent nodes cannot appear at level 0");
+            let left = recompute_root(l_addr, left, conflicts);
+            let right = recompute_root(r_addr, right, conflicts);
+            let root = left
+                .zip(right)
+                .map(|(l, r)| H::combine(l_addr.level(), &l, &r));
+            if let (Some(ann), Some(root)) = (ann, &root) {
+                if ann.as_ref() != root {
+                    conflicts.push(addr);
+                }
+            }
+            root
+        }
+        Node::Leaf { value } => Some(value.0.clone()),
+        Node::Nil => None,
+    }
+}
+
+/// Returns the range of blocks containing the note commitments of the given shard, extended to
+/// include the block at `height` if one is provided.
+fn shard_block_range(
+    conn: &rusqlite::Connection,
+    protocol: ShieldedProtocol,
+    shard_addr: Option<Address>,
+    height: Option<BlockHeight>,
+) -> Result<Option<Range<BlockHeight>>, SqliteClientError> {
+    let shard_range = shard_addr
+        .map(|addr| super::get_block_range(conn, protocol, addr))
+        .transpose()?
+        .flatten();
+
+    Ok(match (shard_range, height) {
+        (Some(r), Some(h)) => Some(min(r.start, h)..max(r.end, h + 1)),
+        (Some(r), None) => Some(r),
+        (None, Some(h)) => Some(h..(h + 1)),
+        (None, None) => None,
+    })
+}
+
+/// Returns the address of the shard containing the last leaf of a tree of the given size.
+fn last_leaf_shard(tree_size: u64, shard_height: u8) -> Option<Address> {
+    tree_size
+        .checked_sub(1)
+        .map(|p| Address::above_position(shard_height.into(), Position::from(p)))
+}
+
+fn verify_tree<H, const DEPTH: u8, const SHARD_HEIGHT: u8>(
+    conn: &rusqlite::Transaction<'_>,
+    protocol: ShieldedProtocol,
+    frontiers: &[(BlockHeight, &Frontier<H, DEPTH>)],
+) -> Result<Vec<TreeInconsistency>, SqliteClientError>
+where
+    H: Hashable + HashSer + Clone + PartialEq,
+{
+    let TableConstants { table_prefix, .. } = table_constants::<SqliteClientError>(protocol)?;
+    let mut result = vec![];
+    let mut report = |kind, height: Option<BlockHeight>, shard_addr: Option<Address>| {
+        let block_range = shard_block_range(conn, protocol, shard_addr, height)?;
+        result.push(TreeInconsistency {
+            protocol,
+            kind,
+            height,
+            shard_index: shard_addr.map(|addr| addr.index()),
+            block_range,
+        });
+        Ok::<_, SqliteClientError>(())
+    };
+
+    let cap = match get_cap::<LevelShifter<H, SHARD_HEIGHT>>(conn, table_prefix) {
+        Ok(cap) => {
+            LocatedTree::from_parts(Address::from_parts((DEPTH - SHARD_HEIGHT).into(), 0), cap).ok()
+        }
+        Err(Error::Query(e)) => return Err(SqliteClientError::DbError(e)),
+        Err(_) => None,
+    };
+    if cap.is_none() {
+        report(InconsistencyKind::UndecodableCap, None, None)?;
+    }
+
+    // Recompute the root of each shard from its leaves, and compare it against the stored
+    // root hash, the hashes cached within the shard, and the shard root recorded in the cap.
+    let mut stmt_shards = conn.prepare(&format!(
+        "SELECT shard_index, root_hash, shard_data
+         FROM {table_prefix}_tree_shards
+         ORDER BY shard_index"
+    ))?;
+    let mut rows = stmt_shards.query([])?;
+    while let Some(row) = rows.next()? {
+        let shard_addr = Address::from_parts(SHARD_HEIGHT.into(), row.get(0)?);
+        let root_hash = row
+            .get::<_, Option<Vec<u8>>>(1)?
+            .map(|data| H::read(Cursor::new(data)))
+            .transpose();
+        let shard = read_shard(&mut Cursor::new(row.get::<_, Vec<u8>>(2)?))
+            .map(|tree: PrunableTree<H>| LocatedPrunableTree::from_parts(shard_addr, tree).ok());
+
+        // A shard that cannot be read is reported rather than being treated as absent, as it
+        // would otherwise be silently replaced when the wallet next writes to it.
+        let (root_hash, shard) = match (root_hash, shard) {
+            (Ok(root_hash), Ok(Some(shard))) => (root_hash, shard),
+            _ => {
+                report(InconsistencyKind::UndecodableShard, None, Some(shard_addr))?;
+                continue;
+            }
+        };
+
+        let mut conflicts = vec![];
+        let recomputed = recompute_root(shard_addr, shard.root(), &mut conflicts);
+        if !conflicts.is_empty()
+            || matches!((&recomputed, &root_hash), (Some(r), Some(s)) if r != s)
+        {
+            report(InconsistencyKind::ShardRootMismatch, None, Some(shard_addr))?;
+        } else if let Some((recomputed, (cap_root, _))) = recomputed.zip(
+            cap.as_ref()
+                .and_then(|cap| cap.value_at_position(Position::from(shard_addr.index()))),
+        ) {
+            if recomputed != cap_root.0 {
+                report(InconsistencyKind::CapMismatch, None, Some(shard_addr))?;
+            }
+        }
+    }
+
+    // Compare the tree position recorded for each checkpoint with the tree size recorded for
+    // the corresponding block.
+    let mut stmt_checkpoints = conn.prepare(&format!(
+        "SELECT c.checkpoint_id, c.position, b.{table_prefix}_commitment_tree_size
+         FROM {table_prefix}_tree_checkpoints c
+         JOIN blocks b ON b.height = c.checkpoint_id
+         WHERE b.{table_prefix}_commitment_tree_size IS NOT NULL
+         ORDER BY c.checkpoint_id"
+    ))?;
+    let mut rows = stmt_checkpoints.query([])?;
+    while let Some(row) = rows.next()? {
+        let checkpoint_height = BlockHeight::from(row.get::<_, u32>(0)?);
+        let checkpoint_tree_size = row.get::<_, Option<u64>>(1)?.map_or(0, |p| p + 1);
+        let block_tree_size = row.get::<_, u64>(2)?;
+        if checkpoint_tree_size != block_tree_size {
+            report(
+                InconsistencyKind::CheckpointMismatch {
+                    checkpoint_tree_size,
+                    block_tree_size,
+                },
+                Some(checkpoint_height),
+                last_leaf_shard(block_tree_size, SHARD_HEIGHT),
+            )?;
+        }
+    }
+
+    // Compare the tree sizes and roots of the wallet's tree with those reported by the server.
+    let tree = ShardTree::<_, DEPTH, SHARD_HEIGHT>::new(
+        SqliteShardStore::<_, H, SHARD_HEIGHT>::from_connection(conn, table_prefix)?,
+        PRUNING_DEPTH.try_into().unwrap(),
+    );
+    for (height, frontier) in frontiers {
+        let server_tree_size = frontier.tree_size();
+        let server_shard = last_leaf_shard(server_tree_size, SHARD_HEIGHT);
+        let block_tree_size = conn
+            .query_row(
+                &format!(
+                    "SELECT {table_prefix}_commitment_tree_size FROM blocks WHERE height = :height"
+                ),
+                named_params![":height": u32::from(*height)],
+                |row| row.get::<_, Option<u64>>(0),
+            )
+            .optional()?
+            .flatten();
+
+        if let Some(block_tree_size) = block_tree_size {
+            if block_tree_size != server_tree_size {
+                report(
+                    InconsistencyKind::TreeSizeMismatch {
+                        server_tree_size,
+                        block_tree_size,
+                    },
+                    Some(*height),
+                    server_shard,
+                )?;
+                continue;
+            }
+        }
+
+        match tree.root_at_checkpoint_id(height) {
+            Ok(Some(root)) if root != frontier.root() => {
+                report(InconsistencyKind::RootMismatch, Some(*height), server_shard)?;
+            }
+            Ok(_) | Err(ShardTreeError::Query(QueryError::TreeIncomplete(_))) => {}
+            // Undecodable shards and caps have already been reported above.
+            Err(ShardTreeError::Storage(Error::Serialization(_))) => {}
+            Err(e) => return Err(SqliteClientError::CommitmentTree(e)),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Walks the shards, cap and checkpoints of each of the wallet's note commitment trees and
+/// checks them for consistency with one another, with the tree sizes recorded in the `blocks`
+/// table, and with the provided chain states as reported by the server.
+///
+/// Unlike [`check_witnesses`], this detects inconsistencies regardless of whether they affect
+/// the wallet's currently spendable notes.
+pub(crate) fn verify_commitment_trees(
+    conn: &rusqlite::Transaction<'_>,
+    chain_states: &[ChainState],
+) -> Result<Vec<TreeInconsistency>, SqliteClientError> {
+    let sapling_frontiers = chain_states
+        .iter()
+        .map(|s| (s.block_height(), s.final_sapling_tree()))
+        .collect::<Vec<_>>();
+    #[allow(unused_mut)]
+    let mut result = verify_tree::<_, { sapling::NOTE_COMMITMENT_TREE_DEPTH }, SAPLING_SHARD_HEIGHT>(
+        conn,
+        ShieldedProtocol::Sapling,
+        &sapling_frontiers,
+    )?;
+
+    #[cfg(feature = "orchard")]
+    {
+        let orchard_frontiers = chain_states
+            .iter()
+            .map(|s| (s.block_height(), s.final_orchard_tree()))
+            .collect::<Vec<_>>();
+        result.extend(verify_tree::<
+            _,
+            { orchard::NOTE_COMMITMENT_TREE_DEPTH as u8 },
+            ORCHARD_SHARD_HEIGHT,
+        >(
+            conn, ShieldedProtocol::Orchard, &orchard_frontiers
+        )?);
+    }
+
+    Ok(result)
+}
+
+/// Discards the contents of the given shard, along with its stored root hash and subtree end
+/// height, as none of these can be trusted once the shard has been found to be inconsistent.
+/// They are restored when the shard is rescanned, or when its root is next obtained from the
+/// server.
+fn reset_shard<H: HashSer>(
+    conn: &rusqlite::Transaction<'_>,
+    table_prefix: &'static str,
+    shard_index: u64,
+) -> Result<(), Error> {
+    let mut shard_data = vec![];
+    write_shard(&mut shard_data, &PrunableTree::<H>::empty()).map_err(Error::Serialization)?;
+
+    conn.execute(
+        &format!(
+            "UPDATE {table_prefix}_tree_shards
+             SET shard_data = :shard_data,
+                 root_hash = NULL,
+                 subtree_end_height = NULL,
+                 contains_marked = NULL
+             WHERE shard_index = :shard_index"
+        ),
+        named_params![
+            ":shard_data": shard_data,
+            ":shard_index": shard_index,
+        ],
+    )
+    .map_err(Error::Query)?;
+
+    Ok(())
+}
+
+/// Replaces the tree cap with one containing the root of each shard whose root can be
+/// recomputed from the shard's data.
+///
+/// The stored root hashes of the shards are not used, as they may be the source of the
+/// inconsistency. Shards that have not been scanned contain only the root obtained from the
+/// server via [`put_shard_roots`], and so are retained. The roots of shards that are neither
+/// complete nor obtained from the server are restored by rescanning them, or when subtree
+/// roots are next obtained from the server.
+fn rebuild_cap<
+    H: Hashable + HashSer + Clone + PartialEq,
+    const DEPTH: u8,
+    const SHARD_HEIGHT: u8,
+>(
+    conn: &rusqlite::Transaction<'_>,
+    table_prefix: &'static str,
+) -> Result<(), ShardTreeError<Error>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT shard_index, shard_data
+             FROM {table_prefix}_tree_shards
+             ORDER BY shard_index"
+        ))
+        .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?;
+    let mut rows = stmt
+        .query([])
+        .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?;
+
+    let mut cap = LocatedTree::from_parts(
+        Address::from_parts((DEPTH - SHARD_HEIGHT).into(), 0),
+        PrunableTree::empty(),
+    )
+    .expect("an empty tree is valid at any address");
+    while let Some(row) = rows
+        .next()
+        .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?
+    {
+        let shard_index = row
+            .get::<_, u64>(0)
+            .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?;
+        let shard_data = row
+            .get::<_, Vec<u8>>(1)
+            .map_err(|e| ShardTreeError::Storage(Error::Query(e)))?;
+
+        // Undecodable shards are reported and reset separately.
+        let Ok(shard) = read_shard::<H, _>(&mut Cursor::new(shard_data)) else {
+            continue;
+        };
+        let mut conflicts = vec![];
+        let root = recompute_root(
+            Address::from_parts(SHARD_HEIGHT.into(), shard_index),
+            &shard,
+            &mut conflicts,
+        );
+        let Some(root) = root.filter(|_| conflicts.is_empty()) else {
+            continue;
+        };
+
+        cap = cap
+            .batch_insert::<(), _>(
+                Position::from(shard_index),
+                iter::once((LevelShifter::<H, SHARD_HEIGHT>(root), Retention::Reference)),
+            )
+            .map_err(ShardTreeError::Insert)?
+            .expect("a root was inserted")
+            .subtree;
+    }
+
+    put_cap(conn, table_prefix, cap.take_root()).map_err(ShardTreeError::Storage)
+}
+
+/// Repairs the given inconsistencies by resetting only the affected shards, removing any
+/// conflicting checkpoints, and queueing the affected block ranges for rescan. A tree cap
+/// that is undecodable or that conflicts with a shard is rebuilt from the roots of the
+/// shards that remain intact; see [`rebuild_cap`].
+///
+/// Returns the block ranges that were queued for rescan.
+pub(crate) fn repair_commitment_trees(
+    conn: &rusqlite::Transaction<'_>,
+    inconsistencies: &[TreeInconsistency],
+) -> Result<Vec<Range<BlockHeight>>, SqliteClientError> {
+    for inconsistency in inconsistencies {
+        let TableConstants { table_prefix, .. } =
+            table_constants::<SqliteClientError>(inconsistency.protocol)?;
+
+        if let Some(shard_index) = inconsistency.shard_index {
+            match inconsistency.protocol {
+                ShieldedProtocol::Sapling => {
+                    reset_shard::<sapling::Node>(conn, table_prefix, shard_index)
+                        .map_err(ShardTreeError::Storage)?
+                }
+                #[cfg(feature = "orchard")]
+                ShieldedProtocol::Orchard => {
+                    reset_shard::<orchard::tree::MerkleHashOrchard>(conn, table_prefix, shard_index)
+                        .map_err(ShardTreeError::Storage)?
+                }
+                #[cfg(not(feature = "orchard"))]
+                ShieldedProtocol::Orchard => unreachable!("rejected by table_constants"),
+            }
+        }
+
+        // A checkpoint that conflicts with the block being rescanned would prevent the
+        // checkpoint from being re-added, so we remove it.
+        if let (
+            InconsistencyKind::CheckpointMismatch { .. }
+            | InconsistencyKind::TreeSizeMismatch { .. },
+            Some(height),
+        ) = (&inconsistency.kind, inconsistency.height)
+        {
+            remove_checkpoint(conn, table_prefix, height).map_err(ShardTreeError::Storage)?;
+        }
+    }
+
+    // Caps are rebuilt only once all affected shards have been reset, so that the roots of
+    // corrupted shards are not carried over into the rebuilt cap.
+    let cap_inconsistent = |protocol| {
+        inconsistencies.iter().any(|i| {
+            i.protocol == protocol
+                && matches!(
+                    i.kind,
+                    InconsistencyKind::UndecodableCap | InconsistencyKind::CapMismatch
+                )
+        })
+    };
+    if cap_inconsistent(ShieldedProtocol::Sapling) {
+        rebuild_cap::<sapling::Node, { sapling::NOTE_COMMITMENT_TREE_DEPTH }, SAPLING_SHARD_HEIGHT>(
+            conn,
+            SAPLING_TABLES_PREFIX,
+        )?;
+    }
+    #[cfg(feature = "orchard")]
+    if cap_inconsistent(ShieldedProtocol::Orchard) {
+        rebuild_cap::<
+            orchard::tree::MerkleHashOrchard,
+            { orchard::NOTE_COMMITMENT_TREE_DEPTH as u8 },
+            ORCHARD_SHARD_HEIGHT,
+        >(conn, ORCHARD_TABLES_PREFIX)?;
+    }
+
+    // Merge overlapping block ranges so that each block is queued for rescan only once.
+    let mut ranges = inconsistencies
+        .iter()
+        .filter_map(|i| i.block_range.clone())
+        .collect::<Vec<_>>();
+    ranges.sort_by_key(|r| r.start);
+    let ranges = ranges
+        .into_iter()
+        .fold(vec![], |mut acc: Vec<Range<_>>, r| {
+            match acc.last_mut() {
+                Some(last) if r.start <= last.end => last.end = max(last.end, r.end),
+                _ => acc.push(r),
+            }
+            acc
+        });
+
+    if let (Some(first), Some(last)) = (ranges.first(), ranges.last()) {
+        replace_queue_entries::<SqliteClientError>(
+            conn,
+            &(first.start..last.end),
+            ranges
+                .iter()
+                .map(|r| ScanRange::from_parts(r.clone(), ScanPriority::FoundNote)),
+            true,
+        )?;
+    }
+
+    Ok(ranges)
+}
+
 #[cfg(test)]
 mod tests {
     use tempfile::NamedTempFile;
@@ -1,17 +1,17 @@
 //! Functions common to Sapling and Orchard support in the wallet.
 
 use incrementalmerkletree::Position;
-use rusqlite::{named_params, types::Value, Connection, Row};
+use rusqlite::{named_params, types::Value, Connection, OptionalExtension, Row};
 use std::{num::NonZeroU64, rc::Rc};
 
 use zcash_client_backend::{
-    data_api::{NoteFilter, PoolMeta, TargetValue, SAPLING_SHARD_HEIGHT},
-    wallet::ReceivedNote,
+    data_api::{MaxSpendMode, NoteFilter, PoolMeta, TargetValue, SAPLING_SHARD_HEIGHT},
+    wallet::{NoteId, ReceivedNote},
 };
-use zcash_primitives::transaction::TxId;
+use zcash_primitives::transaction::{fees::zip317::MARGINAL_FEE, TxId};
 use zcash_protocol::{
     consensus::{self, BlockHeight},
-    value::{BalanceError, Zatoshis},
+    value::{BalanceError, Zatoshis, MAX_MONEY},
     PoolType, ShieldedProtocol,
 };
 
@@ -87,6 +87,39 @@ fn unscanned_tip_exists(
     )
 }
 
+/// Returns the identifier of the received note of the given protocol having the given
+/// nullifier, if any.
+pub(crate) fn find_received_note_id(
+    conn: &Connection,
+    protocol: ShieldedProtocol,
+    nf: &[u8; 32],
+) -> Result<Option<NoteId>, SqliteClientError> {
+    let TableConstants {
+        table_prefix,
+        output_index_col,
+        ..
+    } = table_constants::<SqliteClientError>(protocol)?;
+
+    conn.query_row(
+        &format!(
+            "SELECT txid, {output_index_col}
+             FROM {table_prefix}_received_notes rn
+             INNER JOIN transactions ON transactions.id_tx = rn.tx
+             WHERE nf = :nf"
+        ),
+        named_params![":nf": &nf[..]],
+        |row| {
+            Ok(NoteId::new(
+                TxId::from_bytes(row.get(0)?),
+                protocol,
+                row.get(1)?,
+            ))
+        },
+    )
+    .optional()
+    .map_err(SqliteClientError::from)
+}
+
 // The `clippy::let_and_return` lint is explicitly allowed here because a bug in Clippy
 // (https://github.com/rust-lang/rust-clippy/issues/11308) means it fails to identify that the `result` temporary
 // is required in order to resolve the borrows involved in the `query_and_then` call.
@@ -162,18 +195,30 @@ pub(crate) fn select_spendable_notes<P: consensus::Parameters, F, Note>(
 where
     F: Fn(&P, &Row) -> Result<Option<ReceivedNote<ReceivedNoteId, Note>>, SqliteClientError>,
 {
-    match target_value {
-        TargetValue::AtLeast(zats) => select_minimum_spendable_notes(
-            conn,
-            params,
-            account,
-            zats,
-            anchor_height,
-            exclude,
-            protocol,
-            to_spendable_note,
-        ),
-    }
+    // Notes that cannot pay the standard marginal fee for their own inclusion are only
+    // selected when all notes are explicitly requested.
+    let (target_value, min_note_value) = match target_value {
+        TargetValue::AtLeast(zats) => (zats, MARGINAL_FEE),
+        // Selecting notes up to the maximum possible value selects all eligible notes.
+        TargetValue::AllFunds(MaxSpendMode::MaxSpendable) => {
+            (Zatoshis::const_from_u64(MAX_MONEY), MARGINAL_FEE)
+        }
+        TargetValue::AllFunds(MaxSpendMode::Everything) => {
+            (Zatoshis::const_from_u64(MAX_MONEY), Zatoshis::ZERO)
+        }
+    };
+
+    select_minimum_spendable_notes(
+        conn,
+        params,
+        account,
+        target_value,
+        min_note_value,
+        anchor_height,
+        exclude,
+        protocol,
+        to_spendable_note,
+    )
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -182,6 +227,7 @@ fn select_minimum_spendable_notes<P: consensus::Parameters, F, Note>(
     params: &P,
     account: AccountUuid,
     target_value: Zatoshis,
+    min_note_value: Zatoshis,
     anchor_height: BlockHeight,
     exclude: &[ReceivedNoteId],
     protocol: ShieldedProtocol,
@@ -238,7 +284,7 @@ where
                     ON transactions.id_tx = {table_prefix}_received_notes.tx
                  WHERE accounts.uuid = :account_uuid
                  AND {table_prefix}_received_notes.account_id = accounts.id
-                 AND value > 5000 -- FIXME #1316, allow selection of dust inputs
+                 AND value > :min_note_value
                  AND accounts.ufvk IS NOT NULL
                  AND recipient_key_scope IS NOT NULL
                  AND nf IS NOT NULL
@@ -293,6 +339,7 @@ where
             ":account_uuid": account.0,
             ":anchor_height": &u32::from(anchor_height),
             ":target_value": &u64::from(target_value),
+            ":min_note_value": &u64::from(min_note_value),
             ":exclude": &excluded_ptr,
             ":wallet_birthday": u32::from(birthday_height)
         ],
@@ -517,6 +517,51 @@ CREATE TABLE transparent_spend_search_queue (
     CONSTRAINT value_received_height UNIQUE (transaction_id, output_index)
 )"#;
 
+/// Stores the ZIP 321 payment requests (invoices) issued by the wallet.
+///
+/// Each invoice directs payment to a freshly generated diversified address that is used for
+/// no other purpose, so every output received at that address is treated as a payment of
+/// the invoice.
+///
+/// ### Columns:
+/// - `uuid`: The identifier for the invoice that is exposed to callers.
+/// - `account_id`: The account to which the invoice directs payment.
+/// - `address_id`: A foreign key to the address generated for the invoice.
+/// - `request`: The ZIP 321 URI encoding of the payment request.
+/// - `amount`: The requested amount, in zatoshis.
+/// - `created_height`: The chain tip height at the time that the invoice was created.
+/// - `expiry_height`: The last block height at which payment of the invoice is expected, or
+///   NULL if the invoice does not expire.
+/// - `min_confirmations`: The number of confirmations that every payment of the invoice must
+///   have before the invoice is reported as paid.
+pub(super) const TABLE_INVOICES: &str = r#"
+CREATE TABLE invoices (
+    id INTEGER NOT NULL PRIMARY KEY,
+    uuid BLOB NOT NULL UNIQUE,
+    account_id INTEGER NOT NULL,
+    address_id INTEGER NOT NULL UNIQUE,
+    request TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    created_height INTEGER NOT NULL,
+    expiry_height INTEGER,
+    min_confirmations INTEGER NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts(id),
+    FOREIGN KEY (address_id) REFERENCES addresses(id)
+)"#;
+
+/// Records the transactions whose raw bytes have been discarded by wallet pruning.
+///
+/// While a transaction listed here has a null `raw` column in [`TABLE_TRANSACTIONS`], it is
+/// not queued for retrieval from the chain, as its data was discarded intentionally.
+///
+/// ### Columns:
+/// - `transaction_id`: A foreign key to the pruned transaction.
+pub(super) const TABLE_PRUNED_TRANSACTIONS: &str = r#"
+CREATE TABLE pruned_transactions (
+    transaction_id INTEGER NOT NULL PRIMARY KEY,
+    FOREIGN KEY (transaction_id) REFERENCES transactions(id_tx) ON DELETE CASCADE
+)"#;
+
 //
 // State for shard trees
 //
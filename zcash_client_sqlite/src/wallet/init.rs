@@ -202,6 +202,7 @@ fn sqlite_client_error_to_wallet_migration_error(e: SqliteClientError) -> Wallet
         | SqliteClientError::KeyDerivationError(_)
         | SqliteClientError::Zip32AccountIndexOutOfRange
         | SqliteClientError::AccountCollision(_)
+        | SqliteClientError::IncompatibleViewingKey
         | SqliteClientError::CacheMiss(_) => {
             unreachable!("we only call WalletRead methods; mutations can't occur")
         }
@@ -758,6 +759,7 @@ mod tests {
             db::TABLE_ACCOUNTS,
             db::TABLE_ADDRESSES,
             db::TABLE_BLOCKS,
+            db::TABLE_INVOICES,
             db::TABLE_NULLIFIER_MAP,
             db::TABLE_ORCHARD_RECEIVED_NOTE_SPENDS,
             db::TABLE_ORCHARD_RECEIVED_NOTES,
@@ -765,6 +767,7 @@ mod tests {
             db::TABLE_ORCHARD_TREE_CHECKPOINT_MARKS_REMOVED,
             db::TABLE_ORCHARD_TREE_CHECKPOINTS,
             db::TABLE_ORCHARD_TREE_SHARDS,
+            db::TABLE_PRUNED_TRANSACTIONS,
             db::TABLE_SAPLING_RECEIVED_NOTE_SPENDS,
             db::TABLE_SAPLING_RECEIVED_NOTES,
             db::TABLE_SAPLING_TREE_CAP,
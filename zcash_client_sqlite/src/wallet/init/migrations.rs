@@ -20,9 +20,11 @@ mod fix_broken_commitment_trees;
 mod fix_transparent_received_outputs;
 mod full_account_ids;
 mod initial_setup;
+mod invoices;
 mod nullifier_map;
 mod orchard_received_notes;
 mod orchard_shardtree;
+mod pruned_transactions;
 mod received_notes_nullable_nf;
 mod receiving_key_scopes;
 mod sapling_memo_consistency;
@@ -112,6 +114,10 @@ pub(super) fn all_migrations<
     //                              \      ensure_default_transparent_address    /
     //                               \                     |                    /
     //                                `---- fix_transparent_received_outputs --'
+    //                                                     |
+    //                                                 invoices
+    //                                                     |
+    //                                            pruned_transactions
     let rng = Rc::new(Mutex::new(rng));
     vec![
         Box::new(initial_setup::Migration {}),
@@ -186,6 +192,8 @@ pub(super) fn all_migrations<
             _params: params.clone(),
         }),
         Box::new(fix_transparent_received_outputs::Migration),
+        Box::new(invoices::Migration),
+        Box::new(pruned_transactions::Migration),
     ]
 }
 
@@ -0,0 +1,71 @@
+//! Adds the `invoices` table, which records ZIP 321 payment requests issued by the wallet
+//! along with the diversified address to which each request directs payment.
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use crate::wallet::init::WalletMigrationError;
+
+use super::fix_transparent_received_outputs;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xe20024b8_6ac1_4d8b_9386_0b59a67b88da);
+
+const DEPENDENCIES: &[Uuid] = &[fix_transparent_received_outputs::MIGRATION_ID];
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        DEPENDENCIES.iter().copied().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table for tracking payment requests issued by the wallet."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction) -> Result<(), WalletMigrationError> {
+        transaction.execute_batch(
+            r#"
+            CREATE TABLE invoices (
+                id INTEGER NOT NULL PRIMARY KEY,
+                uuid BLOB NOT NULL UNIQUE,
+                account_id INTEGER NOT NULL,
+                address_id INTEGER NOT NULL UNIQUE,
+                request TEXT NOT NULL,
+                amount INTEGER NOT NULL,
+                created_height INTEGER NOT NULL,
+                expiry_height INTEGER,
+                min_confirmations INTEGER NOT NULL,
+                FOREIGN KEY (account_id) REFERENCES accounts(id),
+                FOREIGN KEY (address_id) REFERENCES addresses(id)
+            );
+            "#,
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction) -> Result<(), WalletMigrationError> {
+        transaction.execute_batch("DROP TABLE invoices;")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::init::migrations::tests::test_migrate;
+
+    #[test]
+    fn migrate() {
+        test_migrate(&[super::MIGRATION_ID]);
+    }
+}
@@ -0,0 +1,62 @@
+//! Adds the `pruned_transactions` table, which records the transactions whose raw bytes have
+//! been discarded by wallet pruning, so that they are not retrieved again.
+use std::collections::HashSet;
+
+use schemerz_rusqlite::RusqliteMigration;
+use uuid::Uuid;
+
+use crate::wallet::init::WalletMigrationError;
+
+use super::invoices;
+
+pub(super) const MIGRATION_ID: Uuid = Uuid::from_u128(0xdd9a0dbc_5311_4882_801d_13c5eaddb9ba);
+
+const DEPENDENCIES: &[Uuid] = &[invoices::MIGRATION_ID];
+
+pub(super) struct Migration;
+
+impl schemerz::Migration<Uuid> for Migration {
+    fn id(&self) -> Uuid {
+        MIGRATION_ID
+    }
+
+    fn dependencies(&self) -> HashSet<Uuid> {
+        DEPENDENCIES.iter().copied().collect()
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a table recording transactions whose raw data has been pruned."
+    }
+}
+
+impl RusqliteMigration for Migration {
+    type Error = WalletMigrationError;
+
+    fn up(&self, transaction: &rusqlite::Transaction) -> Result<(), WalletMigrationError> {
+        transaction.execute_batch(
+            r#"
+            CREATE TABLE pruned_transactions (
+                transaction_id INTEGER NOT NULL PRIMARY KEY,
+                FOREIGN KEY (transaction_id) REFERENCES transactions(id_tx) ON DELETE CASCADE
+            );
+            "#,
+        )?;
+
+        Ok(())
+    }
+
+    fn down(&self, transaction: &rusqlite::Transaction) -> Result<(), WalletMigrationError> {
+        transaction.execute_batch("DROP TABLE pruned_transactions;")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::init::migrations::tests::test_migrate;
+
+    #[test]
+    fn migrate() {
+        test_migrate(&[super::MIGRATION_ID]);
+    }
+}
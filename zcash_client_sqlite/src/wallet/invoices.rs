@@ -0,0 +1,612 @@
+//! Tracking of payment requests issued by the wallet.
+//!
+//! An invoice is a [ZIP 321] payment request that directs payment to a diversified address
+//! generated for that invoice alone. Because the address is never used for any other
+//! purpose, every output that the wallet receives at the address (in any pool) is treated
+//! as a payment of the invoice; the invoice's status is therefore derived from the wallet's
+//! received outputs each time it is read, and reflects every block that has been scanned.
+//!
+//! An invoice is only reported as paid once every payment of it has the minimum number of
+//! confirmations set by [`InvoiceRequest::with_min_confirmations`]; until then, an invoice
+//! for which the requested amount has been received is reported as
+//! [`InvoiceStatus::PendingConfirmation`].
+//!
+//! [ZIP 321]: https://zips.z.cash/zip-0321
+
+use std::num::NonZeroU32;
+
+use rusqlite::{named_params, Row};
+use uuid::Uuid;
+use zcash_client_backend::{
+    data_api::Account as _,
+    zip321::{Payment, TransactionRequest},
+};
+use zcash_keys::{
+    address::{Address, UnifiedAddress},
+    encoding::AddressCodec,
+    keys::{AddressGenerationError, ReceiverRequirement, UnifiedAddressRequest},
+};
+use zcash_protocol::{
+    consensus::{self, BlockHeight},
+    memo::MemoBytes,
+    value::Zatoshis,
+};
+
+use super::{
+    chain_tip_height,
+    encoding::{encode_diversifier_index_be, KeyScope},
+    get_account, get_account_ref, get_next_available_address,
+};
+use crate::{error::SqliteClientError, util::Clock, AccountUuid};
+
+#[cfg(feature = "transparent-inputs")]
+use crate::GapLimits;
+
+/// The identifier for an invoice issued by a [`WalletDb`].
+///
+/// [`WalletDb`]: crate::WalletDb
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InvoiceUuid(#[cfg_attr(feature = "serde", serde(with = "uuid::serde::compact"))] Uuid);
+
+impl InvoiceUuid {
+    /// Constructs an `InvoiceUuid` from a bare [`Uuid`] value.
+    ///
+    /// The resulting identifier is not guaranteed to correspond to any invoice stored in
+    /// a [`WalletDb`].
+    ///
+    /// [`WalletDb`]: crate::WalletDb
+    pub fn from_uuid(value: Uuid) -> Self {
+        InvoiceUuid(value)
+    }
+
+    /// Exposes the opaque invoice identifier from its typesafe wrapper.
+    pub fn expose_uuid(&self) -> Uuid {
+        self.0
+    }
+}
+
+/// The parameters of a payment request to be issued by the wallet.
+#[derive(Clone, Debug)]
+pub struct InvoiceRequest {
+    amount: Zatoshis,
+    memo: Option<MemoBytes>,
+    label: Option<String>,
+    message: Option<String>,
+    expiry_height: Option<BlockHeight>,
+    min_confirmations: NonZeroU32,
+}
+
+impl InvoiceRequest {
+    /// Constructs a request for payment of the given amount.
+    ///
+    /// By default, payments must be mined in order for the invoice to be reported as paid.
+    pub fn new(amount: Zatoshis) -> Self {
+        Self {
+            amount,
+            memo: None,
+            label: None,
+            message: None,
+            expiry_height: None,
+            min_confirmations: NonZeroU32::MIN,
+        }
+    }
+
+    /// Sets the memo that the payer is asked to include with the payment.
+    pub fn with_memo(mut self, memo: MemoBytes) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    /// Sets the human-readable label for the recipient to be included in the request.
+    pub fn with_label(mut self, label: String) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Sets the human-readable description of the purpose of the payment to be included in
+    /// the request.
+    pub fn with_message(mut self, message: String) -> Self {
+        self.message = Some(message);
+        self
+    }
+
+    /// Sets the last block height at which payment of the invoice is expected.
+    ///
+    /// If the invoice has not been paid in full once the chain tip reaches this height, its
+    /// status will be reported as [`InvoiceStatus::Expired`].
+    pub fn with_expiry_height(mut self, expiry_height: BlockHeight) -> Self {
+        self.expiry_height = Some(expiry_height);
+        self
+    }
+
+    /// Sets the number of confirmations that every payment of the invoice must have before
+    /// the invoice is reported as [`InvoiceStatus::Paid`] or [`InvoiceStatus::Overpaid`].
+    pub fn with_min_confirmations(mut self, min_confirmations: NonZeroU32) -> Self {
+        self.min_confirmations = min_confirmations;
+        self
+    }
+}
+
+/// The payment status of an invoice.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    /// No payment of the invoice has been received.
+    Unpaid,
+    /// Payments totalling less than the requested amount have been received.
+    PartiallyPaid,
+    /// Payments totalling at least the requested amount have been received, but not all of
+    /// them have the invoice's minimum number of confirmations.
+    PendingConfirmation,
+    /// Payments totalling exactly the requested amount have been received, each with at
+    /// least the invoice's minimum number of confirmations.
+    Paid,
+    /// Payments totalling more than the requested amount have been received, each with at
+    /// least the invoice's minimum number of confirmations.
+    Overpaid,
+    /// The chain tip has reached the invoice's expiry height without the invoice having been
+    /// paid in full.
+    Expired,
+}
+
+/// A payment request issued by the wallet, along with the payments received for it.
+#[derive(Clone, Debug)]
+pub struct Invoice {
+    id: InvoiceUuid,
+    account_id: AccountUuid,
+    address: UnifiedAddress,
+    request: TransactionRequest,
+    amount: Zatoshis,
+    created_height: BlockHeight,
+    expiry_height: Option<BlockHeight>,
+    min_confirmations: NonZeroU32,
+    received: Zatoshis,
+    confirmations: Option<u32>,
+    status: InvoiceStatus,
+}
+
+impl Invoice {
+    /// Returns the identifier for this invoice.
+    pub fn id(&self) -> InvoiceUuid {
+        self.id
+    }
+
+    /// Returns the account to which this invoice directs payment.
+    pub fn account_id(&self) -> AccountUuid {
+        self.account_id
+    }
+
+    /// Returns the diversified address that was generated for this invoice.
+    pub fn address(&self) -> &UnifiedAddress {
+        &self.address
+    }
+
+    /// Returns the ZIP 321 payment request for this invoice.
+    ///
+    /// Use [`TransactionRequest::to_uri`] to obtain the URI to be presented to the payer.
+    pub fn request(&self) -> &TransactionRequest {
+        &self.request
+    }
+
+    /// Returns the requested amount.
+    pub fn amount(&self) -> Zatoshis {
+        self.amount
+    }
+
+    /// Returns the chain tip height at the time that this invoice was created.
+    pub fn created_height(&self) -> BlockHeight {
+        self.created_height
+    }
+
+    /// Returns the last block height at which payment of this invoice is expected, if any.
+    pub fn expiry_height(&self) -> Option<BlockHeight> {
+        self.expiry_height
+    }
+
+    /// Returns the number of confirmations that every payment of this invoice must have
+    /// before the invoice is reported as paid.
+    pub fn min_confirmations(&self) -> NonZeroU32 {
+        self.min_confirmations
+    }
+
+    /// Returns the total value of the payments received for this invoice, including
+    /// payments in transactions that have not yet been mined.
+    ///
+    /// Payments in unmined transactions that have expired are not included.
+    pub fn received(&self) -> Zatoshis {
+        self.received
+    }
+
+    /// Returns the number of confirmations of the least-confirmed payment received for this
+    /// invoice, or `None` if no payment has been received.
+    ///
+    /// A payment in a transaction that has not yet been mined has zero confirmations; a
+    /// payment mined in the block at the chain tip has one confirmation.
+    pub fn confirmations(&self) -> Option<u32> {
+        self.confirmations
+    }
+
+    /// Returns the payment status of this invoice.
+    pub fn status(&self) -> InvoiceStatus {
+        self.status
+    }
+}
+
+/// Issues a new invoice for the given account, directing payment to a newly generated
+/// shielded diversified address.
+pub(crate) fn create_invoice<P: consensus::Parameters, C: Clock>(
+    conn: &rusqlite::Transaction,
+    params: &P,
+    clock: &C,
+    account_uuid: AccountUuid,
+    request: &InvoiceRequest,
+    #[cfg(feature = "transparent-inputs")] gap_limits: &GapLimits,
+) -> Result<Invoice, SqliteClientError> {
+    let account_id = get_account_ref(conn, account_uuid)?;
+    let uivk = get_account(conn, params, account_uuid)?
+        .ok_or(SqliteClientError::AccountUnknown)?
+        .uivk();
+
+    // Require a receiver for each shielded key item of the account, so that the address
+    // search skips diversifier indices at which any of them would be missing.
+    let receiver_for = |has_key: bool| {
+        if has_key {
+            ReceiverRequirement::Require
+        } else {
+            ReceiverRequirement::Omit
+        }
+    };
+    let address_request = UnifiedAddressRequest::custom(
+        receiver_for(uivk.has_orchard()),
+        receiver_for(uivk.has_sapling()),
+        ReceiverRequirement::Omit,
+    )
+    .map_err(|_| AddressGenerationError::ShieldedReceiverRequired)?;
+
+    let (address, diversifier_index) = get_next_available_address(
+        conn,
+        params,
+        clock,
+        account_uuid,
+        address_request,
+        #[cfg(feature = "transparent-inputs")]
+        gap_limits,
+    )?
+    .ok_or(SqliteClientError::AccountUnknown)?;
+
+    let payment = Payment::new(
+        Address::from(address.clone()).to_zcash_address(params),
+        request.amount,
+        request.memo.clone(),
+        request.label.clone(),
+        request.message.clone(),
+        vec![],
+    )
+    .expect("shielded addresses can receive memos");
+    let tx_request =
+        TransactionRequest::new(vec![payment]).expect("a single payment is a valid request");
+
+    let address_id = conn.query_row(
+        "SELECT id FROM addresses
+         WHERE account_id = :account_id
+         AND key_scope = :key_scope
+         AND diversifier_index_be = :diversifier_index_be",
+        named_params![
+            ":account_id": account_id.0,
+            ":key_scope": KeyScope::EXTERNAL.encode(),
+            ":diversifier_index_be": &encode_diversifier_index_be(diversifier_index),
+        ],
+        |row| row.get::<_, i64>(0),
+    )?;
+
+    let created_height = chain_tip_height(conn)?.ok_or(SqliteClientError::ChainHeightUnknown)?;
+    let invoice_id = InvoiceUuid(Uuid::new_v4());
+    conn.execute(
+        "INSERT INTO invoices (
+            uuid, account_id, address_id, request, amount, created_height, expiry_height,
+            min_confirmations
+        )
+        VALUES (
+            :uuid, :account_id, :address_id, :request, :amount, :created_height, :expiry_height,
+            :min_confirmations
+        )",
+        named_params![
+            ":uuid": invoice_id.0,
+            ":account_id": account_id.0,
+            ":address_id": address_id,
+            ":request": tx_request.to_uri(),
+            ":amount": u64::from(request.amount),
+            ":created_height": u32::from(created_height),
+            ":expiry_height": request.expiry_height.map(u32::from),
+            ":min_confirmations": request.min_confirmations.get(),
+        ],
+    )?;
+
+    get_invoice(conn, params, invoice_id)?
+        .ok_or_else(|| SqliteClientError::CorruptedData("Inserted invoice not found.".to_owned()))
+}
+
+const INVOICE_QUERY: &str = "
+    SELECT i.uuid, accounts.uuid AS account_uuid, addresses.address, i.address_id, i.request,
+           i.amount, i.created_height, i.expiry_height, i.min_confirmations
+    FROM invoices i
+    JOIN accounts ON accounts.id = i.account_id
+    JOIN addresses ON addresses.id = i.address_id";
+
+/// Returns the invoice with the given identifier, if it exists.
+pub(crate) fn get_invoice<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    invoice_id: InvoiceUuid,
+) -> Result<Option<Invoice>, SqliteClientError> {
+    let chain_tip = chain_tip_height(conn)?;
+    let mut stmt = conn.prepare(&format!("{INVOICE_QUERY} WHERE i.uuid = :uuid"))?;
+    let mut invoices = stmt.query_and_then(named_params![":uuid": invoice_id.0], |row| {
+        to_invoice(conn, params, chain_tip, row)
+    })?;
+
+    invoices.next().transpose()
+}
+
+/// Returns the invoices issued for the given account, in the order in which they were
+/// created.
+pub(crate) fn get_invoices<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    account_uuid: AccountUuid,
+) -> Result<Vec<Invoice>, SqliteClientError> {
+    let account_id = get_account_ref(conn, account_uuid)?;
+    let chain_tip = chain_tip_height(conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "{INVOICE_QUERY} WHERE i.account_id = :account_id ORDER BY i.id"
+    ))?;
+    let invoices = stmt
+        .query_and_then(named_params![":account_id": account_id.0], |row| {
+            to_invoice(conn, params, chain_tip, row)
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(invoices)
+}
+
+fn to_invoice<P: consensus::Parameters>(
+    conn: &rusqlite::Connection,
+    params: &P,
+    chain_tip: Option<BlockHeight>,
+    row: &Row,
+) -> Result<Invoice, SqliteClientError> {
+    let address = UnifiedAddress::decode(params, &row.get::<_, String>("address")?)
+        .map_err(SqliteClientError::CorruptedData)?;
+    let request = TransactionRequest::from_uri(&row.get::<_, String>("request")?)
+        .map_err(|e| SqliteClientError::CorruptedData(format!("Invalid invoice request: {e}")))?;
+    let amount = Zatoshis::from_nonnegative_i64(row.get("amount")?)
+        .map_err(|_| SqliteClientError::CorruptedData("Invalid invoice amount.".to_owned()))?;
+    let expiry_height = row
+        .get::<_, Option<u32>>("expiry_height")?
+        .map(BlockHeight::from);
+    let min_confirmations = NonZeroU32::new(row.get("min_confirmations")?).ok_or_else(|| {
+        SqliteClientError::CorruptedData("Invalid invoice confirmation depth.".to_owned())
+    })?;
+
+    let (received, confirmations) = get_payments(conn, row.get("address_id")?, chain_tip)?;
+
+    let expired = matches!(
+        (expiry_height, chain_tip),
+        (Some(expiry_height), Some(chain_tip)) if chain_tip >= expiry_height
+    );
+    let confirmed = confirmations.is_some_and(|c| c >= min_confirmations.get());
+    let status = if received >= amount && !confirmed {
+        InvoiceStatus::PendingConfirmation
+    } else if received > amount {
+        InvoiceStatus::Overpaid
+    } else if received == amount {
+        InvoiceStatus::Paid
+    } else if expired {
+        InvoiceStatus::Expired
+    } else if received.is_zero() {
+        InvoiceStatus::Unpaid
+    } else {
+        InvoiceStatus::PartiallyPaid
+    };
+
+    Ok(Invoice {
+        id: InvoiceUuid(row.get("uuid")?),
+        account_id: AccountUuid(row.get("account_uuid")?),
+        address,
+        request,
+        amount,
+        created_height: BlockHeight::from(row.get::<_, u32>("created_height")?),
+        expiry_height,
+        min_confirmations,
+        received,
+        confirmations,
+        status,
+    })
+}
+
+/// Returns the total value of the outputs received at the given address, along with the
+/// number of confirmations of the least-confirmed such output.
+fn get_payments(
+    conn: &rusqlite::Connection,
+    address_id: i64,
+    chain_tip: Option<BlockHeight>,
+) -> Result<(Zatoshis, Option<u32>), SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT ro.value, t.mined_height
+         FROM v_received_outputs ro
+         JOIN transactions t ON t.id_tx = ro.transaction_id
+         WHERE ro.address_id = :address_id
+         AND (
+            t.mined_height IS NOT NULL -- the payment is mined
+            OR t.expiry_height IS NULL -- the payment will not expire
+            OR t.expiry_height = 0 -- the payment will not expire
+            OR t.expiry_height > :chain_tip_height -- the payment is unexpired
+         )",
+    )?;
+    let mut rows = stmt.query(named_params![
+        ":address_id": address_id,
+        ":chain_tip_height": chain_tip.map(u32::from),
+    ])?;
+
+    let mut received = Zatoshis::ZERO;
+    let mut confirmations: Option<u32> = None;
+    while let Some(row) = rows.next()? {
+        let value = Zatoshis::from_nonnegative_i64(row.get(0)?).map_err(|_| {
+            SqliteClientError::CorruptedData("Invalid received output value.".to_owned())
+        })?;
+        received = (received + value).ok_or(SqliteClientError::BalanceError(
+            zcash_protocol::value::BalanceError::Overflow,
+        ))?;
+
+        let depth = match (row.get::<_, Option<u32>>(1)?, chain_tip) {
+            (Some(mined_height), Some(chain_tip)) if mined_height <= u32::from(chain_tip) => {
+                u32::from(chain_tip) - mined_height + 1
+            }
+            // A payment mined above the known chain tip has one confirmation.
+            (Some(_), _) => 1,
+            (None, _) => 0,
+        };
+        confirmations = Some(confirmations.map_or(depth, |c| c.min(depth)));
+    }
+
+    Ok((received, confirmations))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use zcash_client_backend::data_api::{
+        testing::{AddressType, TestBuilder},
+        Account as _, WalletWrite,
+    };
+    use zcash_primitives::block::BlockHash;
+    use zcash_protocol::value::Zatoshis;
+
+    use super::{InvoiceRequest, InvoiceStatus};
+    use crate::testing::{db::TestDbFactory, BlockCache};
+
+    #[test]
+    fn invoice_payment_tracking() {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = st.test_account_sapling().unwrap().clone();
+        st.wallet_mut()
+            .update_chain_tip(account.birthday().height())
+            .unwrap();
+
+        let amount = Zatoshis::const_from_u64(100000);
+        let expiry_height = account.birthday().height() + 10;
+        let invoice = st
+            .wallet_mut()
+            .db_mut()
+            .create_invoice(
+                account.id(),
+                &InvoiceRequest::new(amount)
+                    .with_expiry_height(expiry_height)
+                    .with_min_confirmations(NonZeroU32::new(2).unwrap()),
+            )
+            .unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::Unpaid);
+        assert_eq!(invoice.min_confirmations().get(), 2);
+        assert_eq!(invoice.confirmations(), None);
+        assert_eq!(invoice.request().payments()[&0].amount(), amount);
+
+        // Each invoice is bound to its own address.
+        let other = st
+            .wallet_mut()
+            .db_mut()
+            .create_invoice(account.id(), &InvoiceRequest::new(amount))
+            .unwrap();
+        assert_ne!(other.address(), invoice.address());
+
+        let (diversifier_index, _) = dfvk
+            .decrypt_diversifier(invoice.address().sapling().unwrap())
+            .unwrap();
+        let invoice_address = AddressType::DiversifiedExternal(diversifier_index);
+
+        // A partial payment to the invoice address is recognized.
+        let (h, _, _) =
+            st.generate_next_block(&dfvk, invoice_address, Zatoshis::const_from_u64(60000));
+        st.scan_cached_blocks(h, 1);
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::PartiallyPaid);
+        assert_eq!(invoice.received(), Zatoshis::const_from_u64(60000));
+
+        // Payments to other addresses do not affect the invoice.
+        st.generate_next_block(
+            &dfvk,
+            AddressType::DefaultExternal,
+            Zatoshis::const_from_u64(40000),
+        );
+        st.scan_cached_blocks(h + 1, 1);
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::PartiallyPaid);
+
+        // Completing the payment marks the invoice as paid once the payments have the
+        // minimum number of confirmations.
+        st.generate_next_block(&dfvk, invoice_address, Zatoshis::const_from_u64(40000));
+        st.scan_cached_blocks(h + 2, 1);
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::PendingConfirmation);
+        assert_eq!(invoice.confirmations(), Some(1));
+
+        st.wallet_mut().update_chain_tip(h + 3).unwrap();
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::Paid);
+        assert_eq!(invoice.confirmations(), Some(2));
+
+        // Any further payment results in the invoice being overpaid, once that payment is
+        // also confirmed.
+        st.generate_next_block(&dfvk, invoice_address, Zatoshis::const_from_u64(1));
+        st.scan_cached_blocks(h + 3, 1);
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::PendingConfirmation);
+
+        st.wallet_mut().update_chain_tip(h + 4).unwrap();
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::Overpaid);
+
+        // The unpaid invoice without an expiry height remains unpaid.
+        let invoices = st.wallet().db().get_invoices(account.id()).unwrap();
+        assert_eq!(invoices.len(), 2);
+        assert_eq!(invoices[1].id(), other.id());
+        assert_eq!(invoices[1].status(), InvoiceStatus::Unpaid);
+    }
+
+    #[test]
+    fn invoice_expiry() {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let account = st.test_account().cloned().unwrap();
+        let birthday = account.birthday().height();
+        st.wallet_mut().update_chain_tip(birthday).unwrap();
+
+        let invoice = st
+            .wallet_mut()
+            .db_mut()
+            .create_invoice(
+                account.id(),
+                &InvoiceRequest::new(Zatoshis::const_from_u64(100000))
+                    .with_expiry_height(birthday + 5),
+            )
+            .unwrap();
+        assert_eq!(invoice.expiry_height(), Some(birthday + 5));
+        assert_eq!(invoice.created_height(), birthday);
+
+        st.wallet_mut().update_chain_tip(birthday + 4).unwrap();
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::Unpaid);
+
+        st.wallet_mut().update_chain_tip(birthday + 5).unwrap();
+        let invoice = st.wallet().db().get_invoice(invoice.id()).unwrap().unwrap();
+        assert_eq!(invoice.status(), InvoiceStatus::Expired);
+    }
+}
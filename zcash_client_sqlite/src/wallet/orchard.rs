@@ -476,6 +476,27 @@ pub(crate) mod tests {
         testing::pool::send_single_step_proposed_transfer::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn send_structured_memo() {
+        testing::pool::send_structured_memo::<OrchardPoolTester>()
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn created_transaction_passes_consensus_checks() {
+        testing::pool::created_transaction_passes_consensus_checks::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn max_sendable_deducts_fee() {
+        testing::pool::max_sendable_deducts_fee::<OrchardPoolTester>()
+    }
+
+    #[test]
+    fn send_max_spends_all_notes() {
+        testing::pool::send_max_spends_all_notes::<OrchardPoolTester>()
+    }
+
     #[test]
     fn send_with_multiple_change_outputs() {
         testing::pool::send_with_multiple_change_outputs::<OrchardPoolTester>()
@@ -558,6 +579,11 @@ pub(crate) mod tests {
         testing::pool::checkpoint_gaps::<OrchardPoolTester>()
     }
 
+    #[test]
+    fn note_witnesses() {
+        testing::pool::note_witnesses::<OrchardPoolTester>()
+    }
+
     #[test]
     fn scan_cached_blocks_detects_spends_out_of_order() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<OrchardPoolTester>()
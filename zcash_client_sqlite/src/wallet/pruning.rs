@@ -0,0 +1,449 @@
+//! Functions for discarding wallet data that is no longer needed, so that long-lived wallets
+//! can be kept within a storage budget.
+//!
+//! Pruning is governed by a [`PruningPolicy`] configured on the [`WalletDb`] via
+//! [`WalletDb::with_pruning_policy`], and is performed by calling [`WalletDb::prune`]. Data is
+//! only ever discarded for blocks that are more than the policy's retention depth below the
+//! highest block that the wallet has scanned, and never for blocks within the wallet's reorg
+//! window. The data that the wallet needs in order to display transaction history (values,
+//! memos, fees, and mined heights) is always retained.
+//!
+//! Nullifier map entries and note commitment tree checkpoints are not governed by the policy:
+//! the wallet already discards both as it scans, retaining only those within its 100-block
+//! reorg window, which is never deeper than the policy's retention depth.
+//!
+//! [`WalletDb`]: crate::WalletDb
+//! [`WalletDb::with_pruning_policy`]: crate::WalletDb::with_pruning_policy
+//! [`WalletDb::prune`]: crate::WalletDb::prune
+
+use std::cmp::max;
+
+use incrementalmerkletree::Position;
+use rusqlite::named_params;
+use zcash_protocol::{
+    consensus::{self, BlockHeight},
+    ShieldedProtocol,
+};
+
+use crate::{error::SqliteClientError, sapling_tree, PRUNING_DEPTH};
+
+#[cfg(feature = "orchard")]
+use crate::orchard_tree;
+
+use super::common::{table_constants, TableConstants};
+
+/// A policy describing the data that is discarded by [`WalletDb::prune`].
+///
+/// [`WalletDb::prune`]: crate::WalletDb::prune
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PruningPolicy {
+    retention_depth: u32,
+    prune_raw_transactions: bool,
+    compact: bool,
+}
+
+impl PruningPolicy {
+    /// Constructs a policy that discards data relevant only to notes that were spent, and
+    /// transactions that were mined, more than `retention_depth` blocks below the highest
+    /// block scanned by the wallet.
+    ///
+    /// Data within the wallet's 100-block reorg window is always retained, so smaller values
+    /// of `retention_depth` are treated as 100. By default, the raw bytes of pruned
+    /// transactions are discarded and the database is compacted after pruning.
+    pub fn new(retention_depth: u32) -> Self {
+        Self {
+            retention_depth: max(retention_depth, PRUNING_DEPTH),
+            prune_raw_transactions: true,
+            compact: true,
+        }
+    }
+
+    /// Sets whether the raw bytes of transactions mined below the retention depth are
+    /// discarded.
+    ///
+    /// Once a transaction's raw bytes have been discarded, [`WalletRead::get_transaction`]
+    /// returns `None` for that transaction, and the wallet does not request its data from
+    /// the chain again; the wallet's record of the transaction's effects is unaffected.
+    ///
+    /// [`WalletRead::get_transaction`]: zcash_client_backend::data_api::WalletRead::get_transaction
+    pub fn with_raw_transaction_pruning(mut self, prune_raw_transactions: bool) -> Self {
+        self.prune_raw_transactions = prune_raw_transactions;
+        self
+    }
+
+    /// Sets whether the database is compacted, returning the space freed by pruning to the
+    /// filesystem, after any data has been pruned.
+    pub fn with_compaction(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Returns the number of blocks below the highest scanned block for which all data is
+    /// retained.
+    pub fn retention_depth(&self) -> u32 {
+        self.retention_depth
+    }
+
+    /// Returns whether the raw bytes of old transactions are discarded.
+    pub fn prunes_raw_transactions(&self) -> bool {
+        self.prune_raw_transactions
+    }
+
+    /// Returns whether the database is compacted after pruning.
+    pub fn compacts(&self) -> bool {
+        self.compact
+    }
+}
+
+/// A summary of the data discarded by a call to [`WalletDb::prune`].
+///
+/// [`WalletDb::prune`]: crate::WalletDb::prune
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PruningSummary {
+    spent_notes: usize,
+    raw_transactions: usize,
+}
+
+impl PruningSummary {
+    /// Returns the number of fully spent notes for which nullifiers and note commitment tree
+    /// witness data were discarded.
+    pub fn spent_notes(&self) -> usize {
+        self.spent_notes
+    }
+
+    /// Returns the number of transactions for which raw transaction bytes were discarded.
+    pub fn raw_transactions(&self) -> usize {
+        self.raw_transactions
+    }
+
+    /// Returns `true` if no data was discarded.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Removes the note commitment tree marks at the given positions, allowing the witness data
+/// for those positions to be pruned from the tree.
+fn remove_marks(
+    conn: &rusqlite::Transaction<'_>,
+    protocol: ShieldedProtocol,
+    positions: &[Position],
+) -> Result<(), SqliteClientError> {
+    match protocol {
+        ShieldedProtocol::Sapling => {
+            let mut tree = sapling_tree(conn)?;
+            for position in positions {
+                tree.remove_mark(*position, None)?;
+            }
+        }
+        #[cfg(feature = "orchard")]
+        ShieldedProtocol::Orchard => {
+            let mut tree = orchard_tree(conn)?;
+            for position in positions {
+                tree.remove_mark(*position, None)?;
+            }
+        }
+        #[cfg(not(feature = "orchard"))]
+        ShieldedProtocol::Orchard => {}
+    }
+
+    Ok(())
+}
+
+/// Discards the nullifiers and note commitment tree marks for notes of the given protocol that
+/// were spent by transactions mined below the given height.
+///
+/// The values and memos of such notes are retained, as they are required for the display of
+/// transaction history.
+fn prune_spent_notes(
+    conn: &rusqlite::Transaction<'_>,
+    protocol: ShieldedProtocol,
+    prune_height: BlockHeight,
+) -> Result<usize, SqliteClientError> {
+    let TableConstants { table_prefix, .. } = table_constants::<SqliteClientError>(protocol)?;
+
+    let mut stmt_spent_notes = conn.prepare(&format!(
+        "SELECT DISTINCT rn.id, rn.commitment_tree_position
+         FROM {table_prefix}_received_notes rn
+         JOIN {table_prefix}_received_note_spends rns
+            ON rns.{table_prefix}_received_note_id = rn.id
+         JOIN transactions stx ON stx.id_tx = rns.transaction_id
+         WHERE rn.nf IS NOT NULL
+         AND stx.mined_height < :prune_height"
+    ))?;
+    let spent_notes = stmt_spent_notes
+        .query_and_then(
+            named_params![":prune_height": u32::from(prune_height)],
+            |row| {
+                Ok::<_, rusqlite::Error>((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<u64>>(1)?.map(Position::from),
+                ))
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let positions = spent_notes
+        .iter()
+        .filter_map(|(_, position)| *position)
+        .collect::<Vec<_>>();
+    remove_marks(conn, protocol, &positions)?;
+
+    let mut stmt_clear_nf = conn.prepare_cached(&format!(
+        "UPDATE {table_prefix}_received_notes SET nf = NULL WHERE id = :id"
+    ))?;
+    for (id, _) in &spent_notes {
+        stmt_clear_nf.execute(named_params![":id": id])?;
+    }
+
+    Ok(spent_notes.len())
+}
+
+/// Discards the raw bytes of transactions mined below the given height, other than those that
+/// the wallet may still need to inspect in order to complete its view of transaction history.
+///
+/// Each pruned transaction is recorded in the `pruned_transactions` table, so that its absent
+/// raw bytes are not mistaken for data that has yet to be retrieved from the chain.
+fn prune_raw_transactions(
+    conn: &rusqlite::Transaction<'_>,
+    prune_height: BlockHeight,
+) -> Result<usize, SqliteClientError> {
+    let prunable = "raw IS NOT NULL
+         AND mined_height < :prune_height
+         AND txid NOT IN (SELECT txid FROM tx_retrieval_queue)
+         AND id_tx NOT IN (
+            SELECT dependent_transaction_id FROM tx_retrieval_queue
+            WHERE dependent_transaction_id IS NOT NULL
+         )
+         AND id_tx NOT IN (SELECT transaction_id FROM transparent_spend_search_queue)";
+
+    conn.execute(
+        &format!(
+            "INSERT INTO pruned_transactions (transaction_id)
+             SELECT id_tx FROM transactions WHERE {prunable}
+             ON CONFLICT (transaction_id) DO NOTHING"
+        ),
+        named_params![":prune_height": u32::from(prune_height)],
+    )?;
+
+    Ok(conn.execute(
+        &format!("UPDATE transactions SET raw = NULL WHERE {prunable}"),
+        named_params![":prune_height": u32::from(prune_height)],
+    )?)
+}
+
+/// Discards the data permitted by the given policy, relative to the highest block scanned by
+/// the wallet.
+pub(crate) fn prune<P: consensus::Parameters>(
+    conn: &rusqlite::Transaction<'_>,
+    params: &P,
+    policy: &PruningPolicy,
+) -> Result<PruningSummary, SqliteClientError> {
+    let mut summary = PruningSummary::default();
+    let max_scanned_height = match super::block_max_scanned(conn, params)? {
+        Some(meta) => meta.block_height(),
+        None => return Ok(summary),
+    };
+    let prune_height = max_scanned_height.saturating_sub(policy.retention_depth);
+
+    summary.spent_notes += prune_spent_notes(conn, ShieldedProtocol::Sapling, prune_height)?;
+    #[cfg(feature = "orchard")]
+    {
+        summary.spent_notes += prune_spent_notes(conn, ShieldedProtocol::Orchard, prune_height)?;
+    }
+
+    if policy.prune_raw_transactions {
+        summary.raw_transactions = prune_raw_transactions(conn, prune_height)?;
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use rusqlite::named_params;
+    use zcash_client_backend::data_api::{
+        testing::{
+            pool::ShieldedPoolTester, sapling::SaplingPoolTester, AddressType, TestBuilder,
+            TestState,
+        },
+        Account as _, NullifierQuery, TransactionDataRequest, TransactionStatus, WalletRead,
+        WalletTest, WalletWrite,
+    };
+    use zcash_primitives::block::BlockHash;
+    use zcash_protocol::{local_consensus::LocalNetwork, value::Zatoshis};
+
+    use super::PruningPolicy;
+    use crate::testing::{
+        db::{TestDb, TestDbFactory},
+        BlockCache,
+    };
+
+    fn test_state(policy: PruningPolicy) -> TestState<BlockCache, TestDb, LocalNetwork> {
+        TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default().with_pruning_policy(policy))
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build()
+    }
+
+    /// Generates and scans enough empty blocks to move the given height below the retention
+    /// depth of the default policy.
+    fn advance_past_retention_depth(st: &mut TestState<BlockCache, TestDb, LocalNetwork>) {
+        let (start, _) = st.generate_empty_block();
+        for _ in 0..100 {
+            st.generate_empty_block();
+        }
+        st.scan_cached_blocks(start, 101);
+    }
+
+    fn count(st: &TestState<BlockCache, TestDb, LocalNetwork>, query: &str) -> usize {
+        st.wallet()
+            .conn()
+            .query_row(query, [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn policy_retains_reorg_window() {
+        assert_eq!(PruningPolicy::new(10).retention_depth(), 100);
+        assert_eq!(PruningPolicy::new(1000).retention_depth(), 1000);
+    }
+
+    #[test]
+    fn prune_without_policy_does_nothing() {
+        let mut st = TestBuilder::new()
+            .with_data_store_factory(TestDbFactory::default())
+            .with_block_cache(BlockCache::new())
+            .with_account_from_sapling_activation(BlockHash([0; 32]))
+            .build();
+        let dfvk = SaplingPoolTester::test_account_fvk(&st);
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, nf) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let to = SaplingPoolTester::random_address(st.rng_mut());
+        st.generate_next_block_spending(&dfvk, (nf, value), to, Zatoshis::const_from_u64(10000));
+        st.scan_cached_blocks(h1, 2);
+        advance_past_retention_depth(&mut st);
+
+        assert!(st.wallet_mut().db_mut().prune().unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_spent_notes() {
+        let mut st = test_state(PruningPolicy::new(100));
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = SaplingPoolTester::test_account_fvk(&st);
+
+        // Receive a note, and then spend it in a later block.
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, nf) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        let to = SaplingPoolTester::random_address(st.rng_mut());
+        st.generate_next_block_spending(&dfvk, (nf, value), to, Zatoshis::const_from_u64(10000));
+        st.scan_cached_blocks(h1, 2);
+
+        // Nothing is pruned while the spend is within the retention depth.
+        assert!(st.wallet_mut().db_mut().prune().unwrap().is_empty());
+
+        advance_past_retention_depth(&mut st);
+
+        let balance = st.get_total_balance(account.id());
+        let history_len = st.wallet().get_tx_history().unwrap().len();
+        let nullifier_count = st
+            .wallet()
+            .get_sapling_nullifiers(NullifierQuery::All)
+            .unwrap()
+            .len();
+
+        let summary = st.wallet_mut().db_mut().prune().unwrap();
+        assert_eq!(summary.spent_notes(), 1);
+
+        // The spent note's nullifier is discarded, but balance and history are unaffected.
+        assert_eq!(
+            st.wallet()
+                .get_sapling_nullifiers(NullifierQuery::All)
+                .unwrap()
+                .len(),
+            nullifier_count - 1
+        );
+        assert_eq!(st.get_total_balance(account.id()), balance);
+        assert_eq!(st.wallet().get_tx_history().unwrap().len(), history_len);
+
+        // The database was compacted after pruning.
+        assert_eq!(count(&st, "PRAGMA freelist_count"), 0);
+
+        // Pruning is idempotent.
+        assert!(st.wallet_mut().db_mut().prune().unwrap().is_empty());
+    }
+
+    #[test]
+    fn prune_raw_transactions() {
+        let mut st = test_state(PruningPolicy::new(100));
+        let account = st.test_account().cloned().unwrap();
+        let dfvk = SaplingPoolTester::test_account_fvk(&st);
+
+        let value = Zatoshis::const_from_u64(50000);
+        let (h1, _, _) = st.generate_next_block(&dfvk, AddressType::DefaultExternal, value);
+        st.scan_cached_blocks(h1, 1);
+
+        // Create a transaction, for which the wallet stores the raw bytes, and mine it.
+        let to = SaplingPoolTester::random_address(st.rng_mut());
+        let txid = *st
+            .create_standard_transaction(
+                &account,
+                to.to_zcash_address(st.network()),
+                Zatoshis::const_from_u64(10000),
+            )
+            .unwrap()
+            .first();
+        let (h2, _) = st.generate_next_block_including(txid);
+        st.scan_cached_blocks(h2, 1);
+        st.wallet_mut()
+            .set_transaction_status(txid, TransactionStatus::Mined(h2))
+            .unwrap();
+
+        // The raw bytes are retained while the transaction is within the retention depth.
+        assert!(st.wallet_mut().db_mut().prune().unwrap().is_empty());
+        assert!(st.wallet().get_transaction(txid).unwrap().is_some());
+
+        advance_past_retention_depth(&mut st);
+        let balance = st.get_total_balance(account.id());
+        let summary = st.wallet_mut().db_mut().prune().unwrap();
+        assert_eq!(summary.raw_transactions(), 1);
+        assert!(st.wallet().get_transaction(txid).unwrap().is_none());
+        assert_eq!(st.get_total_balance(account.id()), balance);
+
+        // A pruned transaction is not queued for retrieval from the chain.
+        st.wallet_mut()
+            .db_mut()
+            .transactionally(|wdb| {
+                super::super::queue_tx_retrieval(wdb.conn.0, iter::once(txid), None)
+            })
+            .unwrap();
+        let queued: usize = st
+            .wallet()
+            .conn()
+            .query_row(
+                "SELECT COUNT(*) FROM tx_retrieval_queue WHERE txid = :txid",
+                named_params![":txid": txid.as_ref()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(queued, 0);
+        assert!(!st
+            .wallet()
+            .transaction_data_requests()
+            .unwrap()
+            .iter()
+            .any(|req| matches!(
+                req,
+                TransactionDataRequest::Enhancement(id) | TransactionDataRequest::GetStatus(id)
+                    if *id == txid
+            )));
+
+        // Pruning is idempotent.
+        assert!(st.wallet_mut().db_mut().prune().unwrap().is_empty());
+    }
+}
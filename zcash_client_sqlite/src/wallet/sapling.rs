@@ -493,6 +493,27 @@ pub(crate) mod tests {
         testing::pool::send_single_step_proposed_transfer::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn send_structured_memo() {
+        testing::pool::send_structured_memo::<SaplingPoolTester>()
+    }
+
+    #[test]
+    #[cfg(feature = "orchard")]
+    fn created_transaction_passes_consensus_checks() {
+        testing::pool::created_transaction_passes_consensus_checks::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn max_sendable_deducts_fee() {
+        testing::pool::max_sendable_deducts_fee::<SaplingPoolTester>()
+    }
+
+    #[test]
+    fn send_max_spends_all_notes() {
+        testing::pool::send_max_spends_all_notes::<SaplingPoolTester>()
+    }
+
     #[test]
     fn send_with_multiple_change_outputs() {
         testing::pool::send_with_multiple_change_outputs::<SaplingPoolTester>()
@@ -575,6 +596,11 @@ pub(crate) mod tests {
         testing::pool::checkpoint_gaps::<SaplingPoolTester>()
     }
 
+    #[test]
+    fn note_witnesses() {
+        testing::pool::note_witnesses::<SaplingPoolTester>()
+    }
+
     #[test]
     fn scan_cached_blocks_detects_spends_out_of_order() {
         testing::pool::scan_cached_blocks_detects_spends_out_of_order::<SaplingPoolTester>()
@@ -751,6 +751,40 @@ pub(crate) fn get_wallet_transparent_output(
     result
 }
 
+/// Returns the outpoints of the transparent outputs received by this wallet that have not been
+/// spent by a mined transaction, along with the accounts that received them.
+pub(crate) fn get_unspent_transparent_outpoints(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<(AccountUuid, OutPoint)>, SqliteClientError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT accounts.uuid, t.txid, u.output_index
+         FROM transparent_received_outputs u
+         JOIN accounts ON accounts.id = u.account_id
+         JOIN transactions t ON t.id_tx = u.transaction_id
+         WHERE u.id NOT IN (
+            SELECT txo_spends.transparent_received_output_id
+            FROM transparent_received_output_spends txo_spends
+            JOIN transactions tx ON tx.id_tx = txo_spends.transaction_id
+            WHERE tx.mined_height IS NOT NULL -- the spending transaction is mined
+         )",
+    )?;
+
+    let result = stmt
+        .query_and_then([], |row| {
+            let txid: Vec<u8> = row.get("txid")?;
+            let txid = txid.try_into().map_err(|_| {
+                SqliteClientError::CorruptedData("Invalid transaction ID length".to_owned())
+            })?;
+            Ok::<_, SqliteClientError>((
+                AccountUuid(row.get("uuid")?),
+                OutPoint::new(txid, row.get("output_index")?),
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(result)
+}
+
 /// Returns the list of spendable transparent outputs received by this wallet at `address`
 /// such that, at height `target_height`:
 /// * the transaction that produced the output had or will have at least `min_confirmations`
@@ -1540,6 +1574,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn transparent_detected_in_compact_blocks() {
+        zcash_client_backend::data_api::testing::transparent::transparent_detected_in_compact_blocks(
+            TestDbFactory::default(),
+            BlockCache::new(),
+        );
+    }
+
     #[test]
     fn gap_limits() {
         zcash_client_backend::data_api::testing::transparent::gap_limits(
@@ -8,6 +8,7 @@
 
 mod entry;
 mod node_data;
+pub mod store;
 mod tree;
 mod version;
 
@@ -0,0 +1,464 @@
+//! Persistent storage for chain history trees.
+//!
+//! [`Tree`] only ever holds a partial view of the array representation of the MMR, and
+//! it is up to the caller to decide which nodes need to be loaded for the operations it
+//! is about to perform. The [`HistoryStore`] trait abstracts over a backend that
+//! persists the full array representation, one tree per consensus branch, and the
+//! functions in this module take care of loading exactly the nodes required to append
+//! or truncate leaves.
+//!
+//! A new chain history tree is started at each network upgrade, so entries are keyed by
+//! the consensus branch ID of the epoch they belong to. When a reorg crosses a network
+//! upgrade boundary, the caller should truncate the tree for the newer branch to zero
+//! leaves before truncating the tree for the older branch.
+
+use std::fmt;
+
+use crate::{Entry, Error, Tree, Version};
+
+pub mod memory;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// A backend that persists the array representation of chain history trees.
+///
+/// Entries for each consensus branch are stored contiguously from index zero. The
+/// functions in this module only ever append entries at the end of the array, or
+/// truncate it; they never overwrite existing entries.
+pub trait HistoryStore<V: Version> {
+    /// The type of errors produced by this store.
+    type Error;
+
+    /// Returns the number of entries stored for the given consensus branch.
+    fn len(&self, consensus_branch_id: u32) -> Result<u32, Self::Error>;
+
+    /// Returns the entry at the given index of the array representation for the given
+    /// consensus branch, or `None` if no such entry is stored.
+    fn get(&self, consensus_branch_id: u32, index: u32) -> Result<Option<Entry<V>>, Self::Error>;
+
+    /// Appends the given entries to the end of the array representation for the given
+    /// consensus branch.
+    fn append(
+        &mut self,
+        consensus_branch_id: u32,
+        entries: &[&Entry<V>],
+    ) -> Result<(), Self::Error>;
+
+    /// Removes all entries for the given consensus branch at indices greater than or
+    /// equal to `len`.
+    ///
+    /// Implementations must remove the entries atomically: if an error is returned, the
+    /// stored entries must be unchanged.
+    fn truncate(&mut self, consensus_branch_id: u32, len: u32) -> Result<(), Self::Error>;
+}
+
+/// Errors that can occur while operating on a tree persisted in a [`HistoryStore`].
+#[derive(Debug)]
+pub enum StoreError<E> {
+    /// An error occurred in the underlying tree operation.
+    Tree(Error),
+    /// An entry required to reconstruct the tree was not present in the store.
+    MissingEntry {
+        /// The consensus branch of the tree.
+        consensus_branch_id: u32,
+        /// The index of the missing entry in the array representation.
+        index: u32,
+    },
+    /// The store contains a number of entries that is not a valid MMR length.
+    InvalidLength(u32),
+    /// The caller attempted to truncate more leaves than the tree contains.
+    InsufficientLeaves {
+        /// The number of leaves in the tree.
+        available: u64,
+        /// The number of leaves the caller requested to remove.
+        requested: u64,
+    },
+    /// An error occurred in the storage backend.
+    Storage(E),
+}
+
+impl<E: fmt::Display> fmt::Display for StoreError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Tree(e) => write!(f, "History tree error: {e}"),
+            StoreError::MissingEntry {
+                consensus_branch_id,
+                index,
+            } => write!(
+                f,
+                "History tree entry {index} missing for branch {consensus_branch_id:08x}"
+            ),
+            StoreError::InvalidLength(len) => {
+                write!(f, "{len} is not a valid history tree length")
+            }
+            StoreError::InsufficientLeaves {
+                available,
+                requested,
+            } => write!(
+                f,
+                "Cannot remove {requested} leaves from a tree with {available} leaves"
+            ),
+            StoreError::Storage(e) => write!(f, "History store error: {e}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for StoreError<E> {}
+
+impl<E> From<Error> for StoreError<E> {
+    fn from(e: Error) -> Self {
+        StoreError::Tree(e)
+    }
+}
+
+/// Returns the positions and heights of the peaks of an MMR with the given array length,
+/// ordered from left to right.
+///
+/// Returns `None` if `len` is not a valid array length for an MMR.
+pub fn peaks(len: u32) -> Option<Vec<(u32, u32)>> {
+    let mut peaks = vec![];
+    let mut offset = 0u32;
+    let mut remaining = u64::from(len);
+    while remaining > 0 {
+        // Find the largest complete subtree that fits in the remaining entries.
+        let mut height = 0u32;
+        while (1u64 << (height + 2)) - 1 <= remaining {
+            height += 1;
+        }
+        let size = (1u64 << (height + 1)) - 1;
+        offset += size as u32;
+        peaks.push((offset - 1, height));
+        remaining -= size;
+
+        // Peaks of an MMR strictly decrease in height.
+        if remaining >= size {
+            return None;
+        }
+    }
+    Some(peaks)
+}
+
+/// Returns the number of leaves in an MMR with the given array length.
+///
+/// Returns `None` if `len` is not a valid array length for an MMR.
+pub fn leaf_count(len: u32) -> Option<u64> {
+    peaks(len).map(|peaks| peaks.iter().map(|(_, height)| 1u64 << height).sum())
+}
+
+/// Returns the array length of an MMR with the given number of leaves.
+///
+/// Returns `None` if the length does not fit in a `u32`.
+fn array_len(leaves: u64) -> Option<u32> {
+    // Each leaf is accompanied by the parents it completes: an MMR with `n` leaves has
+    // `n - popcount(n)` internal nodes.
+    u32::try_from(2 * leaves - u64::from(leaves.count_ones())).ok()
+}
+
+/// Returns the positions of the nodes that must be loaded in addition to the peaks in
+/// order to truncate the last leaf of an MMR with the given peaks.
+///
+/// These are the children of every node along the right-most path of the last peak.
+fn truncation_extra(peaks: &[(u32, u32)]) -> Vec<u32> {
+    let mut extra = vec![];
+    if let Some(&(mut pos, mut height)) = peaks.last() {
+        while height > 0 {
+            // In the post-order array representation, the right child immediately
+            // precedes its parent, and the left child precedes the right subtree.
+            let right = pos - 1;
+            let left = pos - (1 << height);
+            extra.push(left);
+            extra.push(right);
+            pos = right;
+            height -= 1;
+        }
+    }
+    extra
+}
+
+fn load_entry<V: Version, S: HistoryStore<V>>(
+    store: &S,
+    consensus_branch_id: u32,
+    index: u32,
+) -> Result<Entry<V>, StoreError<S::Error>> {
+    store
+        .get(consensus_branch_id, index)
+        .map_err(StoreError::Storage)?
+        .ok_or(StoreError::MissingEntry {
+            consensus_branch_id,
+            index,
+        })
+}
+
+/// Loads the tree for the given consensus branch from the store, with the nodes
+/// required to either append a leaf or truncate the last leaf.
+///
+/// Returns `None` if the store contains no entries for the branch.
+pub fn load_tree<V: Version, S: HistoryStore<V>>(
+    store: &S,
+    consensus_branch_id: u32,
+) -> Result<Option<Tree<V>>, StoreError<S::Error>> {
+    let len = store
+        .len(consensus_branch_id)
+        .map_err(StoreError::Storage)?;
+    if len == 0 {
+        return Ok(None);
+    }
+
+    let peaks = peaks(len).ok_or(StoreError::InvalidLength(len))?;
+    let extra = truncation_extra(&peaks);
+
+    let peaks = peaks
+        .into_iter()
+        .map(|(idx, _)| load_entry(store, consensus_branch_id, idx).map(|entry| (idx, entry)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let extra = extra
+        .into_iter()
+        .map(|idx| load_entry(store, consensus_branch_id, idx).map(|entry| (idx, entry)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(Tree::new(len, peaks, extra)))
+}
+
+/// Returns the root hash of the tree for the given consensus branch, or `None` if the
+/// tree is empty.
+pub fn root_hash<V: Version, S: HistoryStore<V>>(
+    store: &S,
+    consensus_branch_id: u32,
+) -> Result<Option<[u8; 32]>, StoreError<S::Error>> {
+    load_tree(store, consensus_branch_id)?
+        .map(|tree| Ok(V::hash(tree.root_node()?.data())))
+        .transpose()
+}
+
+/// Appends a leaf to the tree for the consensus branch of the leaf, persisting all new
+/// nodes in the store.
+///
+/// Returns the root hash of the updated tree.
+pub fn append_leaf<V: Version, S: HistoryStore<V>>(
+    store: &mut S,
+    leaf: V::NodeData,
+) -> Result<[u8; 32], StoreError<S::Error>> {
+    let consensus_branch_id = V::consensus_branch_id(&leaf);
+    match load_tree(store, consensus_branch_id)? {
+        None => {
+            let root = V::hash(&leaf);
+            store
+                .append(consensus_branch_id, &[&Entry::new_leaf(leaf)])
+                .map_err(StoreError::Storage)?;
+            Ok(root)
+        }
+        Some(mut tree) => {
+            let appended = tree.append_leaf(leaf)?;
+            let entries = appended
+                .into_iter()
+                .map(|link| tree.resolve_link(link).map(|node| node.node()))
+                .collect::<Result<Vec<_>, _>>()?;
+            store
+                .append(consensus_branch_id, &entries)
+                .map_err(StoreError::Storage)?;
+            Ok(V::hash(tree.root_node()?.data()))
+        }
+    }
+}
+
+/// Removes the last `count` leaves from the tree for the given consensus branch, along
+/// with any nodes that depend on them.
+///
+/// Returns the root hash of the updated tree, or `None` if the tree is now empty.
+pub fn truncate_leaves<V: Version, S: HistoryStore<V>>(
+    store: &mut S,
+    consensus_branch_id: u32,
+    count: u64,
+) -> Result<Option<[u8; 32]>, StoreError<S::Error>> {
+    let len = store
+        .len(consensus_branch_id)
+        .map_err(StoreError::Storage)?;
+    let available = leaf_count(len).ok_or(StoreError::InvalidLength(len))?;
+    if count > available {
+        return Err(StoreError::InsufficientLeaves {
+            available,
+            requested: count,
+        });
+    }
+
+    // The new length is determined by the number of remaining leaves alone, so the
+    // entries can be removed with a single write; a failure part-way through cannot leave
+    // the store with a partially-truncated tree.
+    let new_len = array_len(available - count).expect("smaller than the current length");
+    if new_len < len {
+        store
+            .truncate(consensus_branch_id, new_len)
+            .map_err(StoreError::Storage)?;
+    }
+
+    root_hash(store, consensus_branch_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use assert_matches::assert_matches;
+    use proptest::prelude::*;
+
+    use super::{
+        append_leaf, array_len, leaf_count, peaks, root_hash, truncate_leaves, HistoryStore,
+        StoreError,
+    };
+    use crate::{node_data, store::memory::MemoryStore, Entry, NodeData, V2};
+
+    const BRANCH_A: u32 = 0xc2d6_d0b4;
+    const BRANCH_B: u32 = 0xc8e7_1055;
+
+    fn leaf(consensus_branch_id: u32, height: u64) -> node_data::V2 {
+        node_data::V2 {
+            v1: NodeData {
+                consensus_branch_id,
+                subtree_commitment: [height as u8; 32],
+                start_time: height as u32 * 10,
+                end_time: height as u32 * 10 + 5,
+                start_target: 0,
+                end_target: 0,
+                start_sapling_root: [0u8; 32],
+                end_sapling_root: [0u8; 32],
+                subtree_total_work: height.into(),
+                start_height: height,
+                end_height: height,
+                sapling_tx: 7,
+            },
+            start_orchard_root: [0u8; 32],
+            end_orchard_root: [0u8; 32],
+            orchard_tx: 42,
+        }
+    }
+
+    #[test]
+    fn peak_positions() {
+        assert_eq!(peaks(0), Some(vec![]));
+        assert_eq!(peaks(1), Some(vec![(0, 0)]));
+        assert_eq!(peaks(2), None);
+        assert_eq!(peaks(3), Some(vec![(2, 1)]));
+        assert_eq!(peaks(4), Some(vec![(2, 1), (3, 0)]));
+        assert_eq!(peaks(5), None);
+        assert_eq!(peaks(7), Some(vec![(6, 2)]));
+        assert_eq!(peaks(8), Some(vec![(6, 2), (7, 0)]));
+        assert_eq!(peaks(10), Some(vec![(6, 2), (9, 1)]));
+        assert_eq!(peaks(11), Some(vec![(6, 2), (9, 1), (10, 0)]));
+        assert_eq!(leaf_count(11), Some(7));
+    }
+
+    #[test]
+    fn branches_are_independent() {
+        let mut store = MemoryStore::<V2>::new();
+        for height in 1..=5 {
+            append_leaf(&mut store, leaf(BRANCH_A, height)).unwrap();
+        }
+        let root_a = root_hash(&store, BRANCH_A).unwrap();
+        assert!(root_a.is_some());
+        assert_eq!(root_hash(&store, BRANCH_B).unwrap(), None);
+
+        append_leaf(&mut store, leaf(BRANCH_B, 6)).unwrap();
+        assert_eq!(root_hash(&store, BRANCH_A).unwrap(), root_a);
+        assert_eq!(HistoryStore::<V2>::len(&store, BRANCH_B).unwrap(), 1);
+
+        truncate_leaves(&mut store, BRANCH_B, 1).unwrap();
+        assert_eq!(HistoryStore::<V2>::len(&store, BRANCH_B).unwrap(), 0);
+        assert_eq!(root_hash(&store, BRANCH_A).unwrap(), root_a);
+    }
+
+    #[test]
+    fn array_lengths() {
+        for len in 0..1000 {
+            if let Some(leaves) = leaf_count(len) {
+                assert_eq!(array_len(leaves), Some(len));
+            }
+        }
+    }
+
+    /// A store whose truncation always fails.
+    struct FailingTruncation(MemoryStore<V2>);
+
+    impl HistoryStore<V2> for FailingTruncation {
+        type Error = io::Error;
+
+        fn len(&self, consensus_branch_id: u32) -> Result<u32, Self::Error> {
+            self.0.len(consensus_branch_id)
+        }
+
+        fn get(
+            &self,
+            consensus_branch_id: u32,
+            index: u32,
+        ) -> Result<Option<Entry<V2>>, Self::Error> {
+            self.0.get(consensus_branch_id, index)
+        }
+
+        fn append(
+            &mut self,
+            consensus_branch_id: u32,
+            entries: &[&Entry<V2>],
+        ) -> Result<(), Self::Error> {
+            self.0.append(consensus_branch_id, entries)
+        }
+
+        fn truncate(&mut self, _: u32, _: u32) -> Result<(), Self::Error> {
+            Err(io::Error::other("truncation failed"))
+        }
+    }
+
+    #[test]
+    fn failed_truncation_leaves_tree_intact() {
+        let mut store = FailingTruncation(MemoryStore::new());
+        for height in 1..=11 {
+            append_leaf(&mut store, leaf(BRANCH_A, height)).unwrap();
+        }
+        let len = store.len(BRANCH_A).unwrap();
+        let root = root_hash(&store, BRANCH_A).unwrap();
+
+        assert_matches!(
+            truncate_leaves(&mut store, BRANCH_A, 5),
+            Err(StoreError::Storage(_))
+        );
+        assert_eq!(store.len(BRANCH_A).unwrap(), len);
+        assert_eq!(root_hash(&store, BRANCH_A).unwrap(), root);
+
+        // Truncating no leaves does not write to the store.
+        assert_eq!(truncate_leaves(&mut store, BRANCH_A, 0).unwrap(), root);
+    }
+
+    #[test]
+    fn truncate_too_many() {
+        let mut store = MemoryStore::<V2>::new();
+        append_leaf(&mut store, leaf(BRANCH_A, 1)).unwrap();
+        assert!(truncate_leaves(&mut store, BRANCH_A, 2).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_truncate_matches_append(total in 1u64..=100, removed in 0u64..=100) {
+            let removed = removed.min(total);
+
+            let mut store = MemoryStore::<V2>::new();
+            for height in 1..=total {
+                append_leaf(&mut store, leaf(BRANCH_A, height)).unwrap();
+            }
+            let truncated_root = truncate_leaves(&mut store, BRANCH_A, removed).unwrap();
+
+            let mut expected = MemoryStore::<V2>::new();
+            let mut expected_root = None;
+            for height in 1..=(total - removed) {
+                expected_root = Some(append_leaf(&mut expected, leaf(BRANCH_A, height)).unwrap());
+            }
+
+            prop_assert_eq!(truncated_root, expected_root);
+            prop_assert_eq!(
+                HistoryStore::<V2>::len(&store, BRANCH_A).unwrap(),
+                HistoryStore::<V2>::len(&expected, BRANCH_A).unwrap()
+            );
+            prop_assert_eq!(
+                leaf_count(HistoryStore::<V2>::len(&store, BRANCH_A).unwrap()),
+                Some(total - removed)
+            );
+        }
+    }
+}
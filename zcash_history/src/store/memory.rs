@@ -0,0 +1,89 @@
+//! An in-memory implementation of [`HistoryStore`].
+
+use std::collections::HashMap;
+use std::io;
+use std::marker::PhantomData;
+
+use super::HistoryStore;
+use crate::{Entry, Version};
+
+/// A [`HistoryStore`] that keeps serialized entries in memory.
+///
+/// This is primarily useful for testing, and for short-lived processes that reconstruct
+/// the history tree from chain data at startup.
+#[derive(Debug)]
+pub struct MemoryStore<V: Version> {
+    branches: HashMap<u32, Vec<Vec<u8>>>,
+    _version: PhantomData<V>,
+}
+
+impl<V: Version> MemoryStore<V> {
+    /// Constructs a new empty store.
+    pub fn new() -> Self {
+        MemoryStore {
+            branches: HashMap::new(),
+            _version: PhantomData,
+        }
+    }
+
+    /// Returns the consensus branch IDs for which this store holds at least one entry.
+    pub fn branches(&self) -> impl Iterator<Item = u32> + '_ {
+        self.branches
+            .iter()
+            .filter(|(_, entries)| !entries.is_empty())
+            .map(|(branch_id, _)| *branch_id)
+    }
+}
+
+impl<V: Version> Default for MemoryStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Version> HistoryStore<V> for MemoryStore<V> {
+    type Error = io::Error;
+
+    fn len(&self, consensus_branch_id: u32) -> Result<u32, Self::Error> {
+        Ok(self
+            .branches
+            .get(&consensus_branch_id)
+            .map_or(0, |entries| entries.len() as u32))
+    }
+
+    fn get(&self, consensus_branch_id: u32, index: u32) -> Result<Option<Entry<V>>, Self::Error> {
+        self.branches
+            .get(&consensus_branch_id)
+            .and_then(|entries| entries.get(index as usize))
+            .map(|bytes| Entry::from_bytes(consensus_branch_id, bytes))
+            .transpose()
+    }
+
+    fn append(
+        &mut self,
+        consensus_branch_id: u32,
+        entries: &[&Entry<V>],
+    ) -> Result<(), Self::Error> {
+        let encoded = entries
+            .iter()
+            .map(|entry| {
+                let mut buf = vec![];
+                entry.write(&mut buf)?;
+                Ok(buf)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        self.branches
+            .entry(consensus_branch_id)
+            .or_default()
+            .extend(encoded);
+        Ok(())
+    }
+
+    fn truncate(&mut self, consensus_branch_id: u32, len: u32) -> Result<(), Self::Error> {
+        if let Some(entries) = self.branches.get_mut(&consensus_branch_id) {
+            entries.truncate(len as usize);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+//! A SQLite-backed implementation of [`HistoryStore`].
+
+use std::marker::PhantomData;
+use std::path::Path;
+
+use rusqlite::{named_params, types::Type, Connection, OptionalExtension};
+
+use super::HistoryStore;
+use crate::{Entry, Version};
+
+/// A [`HistoryStore`] that persists entries in a SQLite database.
+///
+/// Entries are stored in a `history_tree_entries` table, which is created by
+/// [`SqliteStore::for_connection`] if it does not already exist. The table may live
+/// alongside other application data in the same database.
+pub struct SqliteStore<V: Version> {
+    conn: Connection,
+    _version: PhantomData<V>,
+}
+
+impl<V: Version> SqliteStore<V> {
+    /// Opens the database at the given path, creating it if necessary.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
+        Self::for_connection(Connection::open(path)?)
+    }
+
+    /// Constructs a store using the given connection, initializing the schema if it does
+    /// not already exist.
+    pub fn for_connection(conn: Connection) -> Result<Self, rusqlite::Error> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS history_tree_entries (
+                consensus_branch_id INTEGER NOT NULL,
+                entry_index INTEGER NOT NULL,
+                entry_data BLOB NOT NULL,
+                PRIMARY KEY (consensus_branch_id, entry_index)
+            )",
+        )?;
+
+        Ok(SqliteStore {
+            conn,
+            _version: PhantomData,
+        })
+    }
+
+    /// Returns the underlying database connection.
+    pub fn into_connection(self) -> Connection {
+        self.conn
+    }
+}
+
+impl<V: Version> HistoryStore<V> for SqliteStore<V> {
+    type Error = rusqlite::Error;
+
+    fn len(&self, consensus_branch_id: u32) -> Result<u32, Self::Error> {
+        self.conn.query_row(
+            "SELECT COUNT(*) FROM history_tree_entries
+            WHERE consensus_branch_id = :consensus_branch_id",
+            named_params![":consensus_branch_id": consensus_branch_id],
+            |row| row.get(0),
+        )
+    }
+
+    fn get(&self, consensus_branch_id: u32, index: u32) -> Result<Option<Entry<V>>, Self::Error> {
+        self.conn
+            .query_row(
+                "SELECT entry_data FROM history_tree_entries
+                WHERE consensus_branch_id = :consensus_branch_id
+                AND entry_index = :entry_index",
+                named_params![
+                    ":consensus_branch_id": consensus_branch_id,
+                    ":entry_index": index,
+                ],
+                |row| row.get::<_, Vec<u8>>(0),
+            )
+            .optional()?
+            .map(|bytes| {
+                Entry::from_bytes(consensus_branch_id, bytes).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(0, Type::Blob, Box::new(e))
+                })
+            })
+            .transpose()
+    }
+
+    fn append(
+        &mut self,
+        consensus_branch_id: u32,
+        entries: &[&Entry<V>],
+    ) -> Result<(), Self::Error> {
+        let start = HistoryStore::<V>::len(self, consensus_branch_id)?;
+
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt_insert = tx.prepare_cached(
+                "INSERT INTO history_tree_entries (consensus_branch_id, entry_index, entry_data)
+                VALUES (:consensus_branch_id, :entry_index, :entry_data)",
+            )?;
+            for (i, entry) in entries.iter().enumerate() {
+                let mut entry_data = vec![];
+                entry
+                    .write(&mut entry_data)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                stmt_insert.execute(named_params![
+                    ":consensus_branch_id": consensus_branch_id,
+                    ":entry_index": start + i as u32,
+                    ":entry_data": entry_data,
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    fn truncate(&mut self, consensus_branch_id: u32, len: u32) -> Result<(), Self::Error> {
+        self.conn.execute(
+            "DELETE FROM history_tree_entries
+            WHERE consensus_branch_id = :consensus_branch_id
+            AND entry_index >= :len",
+            named_params![
+                ":consensus_branch_id": consensus_branch_id,
+                ":len": len,
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::Connection;
+
+    use super::SqliteStore;
+    use crate::{
+        store::{append_leaf, memory::MemoryStore, root_hash, truncate_leaves},
+        NodeData, V1,
+    };
+
+    fn leaf(height: u64) -> NodeData {
+        NodeData {
+            consensus_branch_id: 0xe9ff_75a6,
+            subtree_commitment: [height as u8; 32],
+            start_time: 0,
+            end_time: 0,
+            start_target: 0,
+            end_target: 0,
+            start_sapling_root: [0u8; 32],
+            end_sapling_root: [0u8; 32],
+            subtree_total_work: 0.into(),
+            start_height: height,
+            end_height: height,
+            sapling_tx: 0,
+        }
+    }
+
+    #[test]
+    fn matches_memory_store() {
+        let mut db =
+            SqliteStore::<V1>::for_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let mut mem = MemoryStore::<V1>::new();
+
+        for height in 1..=40 {
+            assert_eq!(
+                append_leaf(&mut db, leaf(height)).unwrap(),
+                append_leaf(&mut mem, leaf(height)).unwrap(),
+            );
+        }
+
+        assert_eq!(
+            truncate_leaves(&mut db, 0xe9ff_75a6, 13).unwrap(),
+            truncate_leaves(&mut mem, 0xe9ff_75a6, 13).unwrap(),
+        );
+        assert_eq!(
+            root_hash(&db, 0xe9ff_75a6).unwrap(),
+            root_hash(&mem, 0xe9ff_75a6).unwrap(),
+        );
+    }
+}
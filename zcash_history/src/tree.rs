@@ -291,7 +291,7 @@ pub struct IndexedNode<'a, V: Version> {
     link: EntryLink,
 }
 
-impl<V: Version> IndexedNode<'_, V> {
+impl<'a, V: Version> IndexedNode<'a, V> {
     fn left(&self) -> Result<EntryLink, Error> {
         self.node.left().map_err(|e| e.augment(self.link))
     }
@@ -301,7 +301,7 @@ impl<V: Version> IndexedNode<'_, V> {
     }
 
     /// Reference to the entry struct.
-    pub fn node(&self) -> &Entry<V> {
+    pub fn node(&self) -> &'a Entry<V> {
         self.node
     }
 
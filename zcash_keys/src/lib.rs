@@ -24,6 +24,9 @@ extern crate std;
 pub mod address;
 pub mod encoding;
 
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+
 #[cfg(any(
     feature = "orchard",
     feature = "sapling",
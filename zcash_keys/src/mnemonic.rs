@@ -0,0 +1,423 @@
+//! Support for [BIP 39] mnemonic phrases.
+//!
+//! Zcash wallets conventionally back up the seed from which their [ZIP 32] accounts are
+//! derived as a BIP 39 mnemonic phrase. This module provides generation and validation of
+//! such phrases in each of the standard BIP 39 wordlists, along with derivation of the
+//! corresponding seed bytes and [`SeedFingerprint`]. The seed returned by
+//! [`Mnemonic::to_seed`] can be passed directly to
+//! [`UnifiedSpendingKey::from_seed`](crate::keys::UnifiedSpendingKey::from_seed), and the
+//! fingerprint returned by [`Mnemonic::seed_fingerprint`] identifies the seed in the same
+//! way as the [ZIP 32] seed fingerprint that wallets record for derived accounts.
+//!
+//! [BIP 39]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+//! [ZIP 32]: https://zips.z.cash/zip-0032
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use rand_core::{CryptoRng, RngCore};
+use secrecy::{zeroize::Zeroize, ExposeSecret, SecretString, SecretVec};
+use zip32::fingerprint::SeedFingerprint;
+
+/// The length of a seed derived from a BIP 39 mnemonic phrase, in bytes.
+pub const SEED_LENGTH: usize = 64;
+
+/// The wordlists defined by BIP 39.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    ChineseSimplified,
+    ChineseTraditional,
+    Czech,
+    French,
+    Italian,
+    Japanese,
+    Korean,
+    Portuguese,
+    Spanish,
+}
+
+impl Language {
+    /// All supported wordlists, in the order in which they are tried by
+    /// [`Mnemonic::from_phrase_in_any_language`].
+    pub const ALL: [Language; 10] = [
+        Language::English,
+        Language::ChineseSimplified,
+        Language::ChineseTraditional,
+        Language::Czech,
+        Language::French,
+        Language::Italian,
+        Language::Japanese,
+        Language::Korean,
+        Language::Portuguese,
+        Language::Spanish,
+    ];
+}
+
+/// Dispatches to the `bip0039` wordlist type corresponding to a [`Language`].
+macro_rules! with_language {
+    ($language:expr, $lang:ident => $body:expr) => {
+        match $language {
+            Language::English => {
+                type $lang = bip0039::English;
+                $body
+            }
+            Language::ChineseSimplified => {
+                type $lang = bip0039::ChineseSimplified;
+                $body
+            }
+            Language::ChineseTraditional => {
+                type $lang = bip0039::ChineseTraditional;
+                $body
+            }
+            Language::Czech => {
+                type $lang = bip0039::Czech;
+                $body
+            }
+            Language::French => {
+                type $lang = bip0039::French;
+                $body
+            }
+            Language::Italian => {
+                type $lang = bip0039::Italian;
+                $body
+            }
+            Language::Japanese => {
+                type $lang = bip0039::Japanese;
+                $body
+            }
+            Language::Korean => {
+                type $lang = bip0039::Korean;
+                $body
+            }
+            Language::Portuguese => {
+                type $lang = bip0039::Portuguese;
+                $body
+            }
+            Language::Spanish => {
+                type $lang = bip0039::Spanish;
+                $body
+            }
+        }
+    };
+}
+
+/// The number of words in a mnemonic phrase.
+///
+/// Each word encodes 11 bits; the phrase as a whole encodes `32 * n` bits of entropy
+/// along with an `n`-bit checksum, where `n = word_count / 3`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WordCount {
+    Words12,
+    Words15,
+    Words18,
+    Words21,
+    /// 24 words, encoding 256 bits of entropy. This is the length used by Zcash wallets.
+    #[default]
+    Words24,
+}
+
+impl WordCount {
+    /// Returns the number of words in a phrase of this length.
+    pub fn words(&self) -> usize {
+        match self {
+            WordCount::Words12 => 12,
+            WordCount::Words15 => 15,
+            WordCount::Words18 => 18,
+            WordCount::Words21 => 21,
+            WordCount::Words24 => 24,
+        }
+    }
+
+    /// Returns the number of bytes of entropy encoded by a phrase of this length.
+    pub fn entropy_len(&self) -> usize {
+        self.words() * 4 / 3
+    }
+
+    /// Returns the [`WordCount`] corresponding to the given number of words, if it is a
+    /// valid BIP 39 phrase length.
+    pub fn from_words(words: usize) -> Option<Self> {
+        match words {
+            12 => Some(WordCount::Words12),
+            15 => Some(WordCount::Words15),
+            18 => Some(WordCount::Words18),
+            21 => Some(WordCount::Words21),
+            24 => Some(WordCount::Words24),
+            _ => None,
+        }
+    }
+}
+
+/// Errors that can occur in the construction of a [`Mnemonic`].
+#[derive(Debug)]
+pub enum Error {
+    /// The phrase or entropy is not valid for the requested wordlist.
+    Invalid(bip0039::Error),
+    /// The phrase is not a valid mnemonic in any supported wordlist.
+    UnrecognizedLanguage,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Invalid(e) => write!(f, "Invalid mnemonic: {}", e),
+            Error::UnrecognizedLanguage => write!(
+                f,
+                "Phrase is not a valid mnemonic in any supported BIP 39 wordlist"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+impl From<bip0039::Error> for Error {
+    fn from(e: bip0039::Error) -> Self {
+        Error::Invalid(e)
+    }
+}
+
+/// A validated [BIP 39] mnemonic phrase.
+///
+/// The phrase is held in secret memory and is zeroized when the [`Mnemonic`] is dropped.
+///
+/// [BIP 39]: https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki
+pub struct Mnemonic {
+    language: Language,
+    phrase: SecretString,
+}
+
+impl fmt::Debug for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mnemonic")
+            .field("language", &self.language)
+            .field("word_count", &self.word_count())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Mnemonic {
+    /// Generates a new mnemonic phrase of the given length in the given language, using
+    /// entropy drawn from the provided RNG.
+    pub fn generate<R: RngCore + CryptoRng>(
+        mut rng: R,
+        language: Language,
+        word_count: WordCount,
+    ) -> Self {
+        let mut entropy = vec![0u8; word_count.entropy_len()];
+        rng.fill_bytes(&mut entropy);
+        let entropy = SecretVec::new(entropy);
+        Self::from_entropy(language, entropy.expose_secret())
+            .expect("entropy length is valid for the requested word count")
+    }
+
+    /// Constructs the mnemonic phrase in the given language that encodes the given entropy.
+    ///
+    /// Returns an error if the length of `entropy` is not one of 16, 20, 24, 28 or 32
+    /// bytes.
+    pub fn from_entropy(language: Language, entropy: &[u8]) -> Result<Self, Error> {
+        let phrase = with_language!(language, L => {
+            let mnemonic = bip0039::Mnemonic::<L>::from_entropy(entropy.to_vec())?;
+            String::from(mnemonic.phrase())
+        });
+
+        Ok(Mnemonic {
+            language,
+            phrase: SecretString::new(phrase),
+        })
+    }
+
+    /// Parses and validates a mnemonic phrase in the given language.
+    ///
+    /// Returns an error if any word of the phrase is not in the language's wordlist, if
+    /// the phrase has an invalid number of words, or if its checksum is invalid.
+    pub fn from_phrase(language: Language, phrase: &str) -> Result<Self, Error> {
+        let phrase = with_language!(language, L => {
+            let mnemonic = bip0039::Mnemonic::<L>::from_phrase(phrase)?;
+            String::from(mnemonic.phrase())
+        });
+
+        Ok(Mnemonic {
+            language,
+            phrase: SecretString::new(phrase),
+        })
+    }
+
+    /// Parses and validates a mnemonic phrase, detecting its language.
+    ///
+    /// Each wordlist in [`Language::ALL`] is tried in turn, and the first one in which
+    /// the phrase is valid is used.
+    pub fn from_phrase_in_any_language(phrase: &str) -> Result<Self, Error> {
+        Language::ALL
+            .iter()
+            .find_map(|language| Self::from_phrase(*language, phrase).ok())
+            .ok_or(Error::UnrecognizedLanguage)
+    }
+
+    /// Checks whether the given phrase is a valid mnemonic in the given language.
+    pub fn validate(language: Language, phrase: &str) -> Result<(), Error> {
+        Self::from_phrase(language, phrase).map(|_| ())
+    }
+
+    /// Returns the language of this mnemonic phrase.
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// Returns the number of words in this mnemonic phrase.
+    pub fn word_count(&self) -> WordCount {
+        WordCount::from_words(self.phrase.expose_secret().split_whitespace().count())
+            .expect("phrase was validated on construction")
+    }
+
+    /// Returns the mnemonic phrase, with words separated by single spaces (or, for
+    /// Japanese, by ideographic spaces).
+    pub fn phrase(&self) -> &SecretString {
+        &self.phrase
+    }
+
+    /// Returns the entropy encoded by this mnemonic phrase.
+    pub fn entropy(&self) -> SecretVec<u8> {
+        let phrase = self.phrase.expose_secret().as_str();
+        let entropy: Vec<u8> = with_language!(self.language, L => {
+            bip0039::Mnemonic::<L>::from_phrase(phrase)
+                .expect("phrase was validated on construction")
+                .entropy()
+                .to_vec()
+        });
+        SecretVec::new(entropy)
+    }
+
+    /// Derives the [`SEED_LENGTH`]-byte seed corresponding to this mnemonic phrase and
+    /// the given passphrase.
+    ///
+    /// Use the empty string as the passphrase if the user has not set one.
+    pub fn to_seed(&self, passphrase: &str) -> SecretVec<u8> {
+        let phrase = self.phrase.expose_secret().as_str();
+        let mut seed: [u8; SEED_LENGTH] = with_language!(self.language, L => {
+            bip0039::Mnemonic::<L>::from_phrase(phrase)
+                .expect("phrase was validated on construction")
+                .to_seed(passphrase)
+        });
+        let result = SecretVec::new(seed.to_vec());
+        seed.zeroize();
+        result
+    }
+
+    /// Returns the [ZIP 32] fingerprint of the seed corresponding to this mnemonic phrase
+    /// and the given passphrase.
+    ///
+    /// [ZIP 32]: https://zips.z.cash/zip-0032
+    pub fn seed_fingerprint(&self, passphrase: &str) -> SeedFingerprint {
+        SeedFingerprint::from_seed(self.to_seed(passphrase).expose_secret())
+            .expect("BIP 39 seeds have a valid length for ZIP 32")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::ExposeSecret;
+    use zip32::fingerprint::SeedFingerprint;
+
+    use super::{Error, Language, Mnemonic, WordCount};
+
+    // Test vector from https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+    const PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon \
+                          abandon abandon abandon about";
+    const SEED_TREZOR: &str = "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+                               1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04";
+
+    #[test]
+    fn from_entropy_matches_test_vector() {
+        let mnemonic = Mnemonic::from_entropy(Language::English, &[0u8; 16]).unwrap();
+        assert_eq!(mnemonic.phrase().expose_secret(), PHRASE);
+        assert_eq!(mnemonic.word_count(), WordCount::Words12);
+        assert_eq!(mnemonic.entropy().expose_secret(), &[0u8; 16]);
+    }
+
+    #[test]
+    fn to_seed_matches_test_vector() {
+        let mnemonic = Mnemonic::from_phrase(Language::English, PHRASE).unwrap();
+        let seed = mnemonic.to_seed("TREZOR");
+        assert_eq!(hex::encode(seed.expose_secret()), SEED_TREZOR);
+
+        assert_eq!(
+            mnemonic.seed_fingerprint("TREZOR"),
+            SeedFingerprint::from_seed(seed.expose_secret()).unwrap()
+        );
+        assert_ne!(
+            mnemonic.seed_fingerprint("TREZOR"),
+            mnemonic.seed_fingerprint("")
+        );
+    }
+
+    #[test]
+    fn invalid_phrases_are_rejected() {
+        // Invalid checksum.
+        let bad_checksum = PHRASE.replace("about", "abandon");
+        assert!(matches!(
+            Mnemonic::from_phrase(Language::English, &bad_checksum),
+            Err(Error::Invalid(_))
+        ));
+
+        // Invalid word count.
+        assert!(Mnemonic::validate(Language::English, "abandon about").is_err());
+
+        // Valid phrase, wrong language.
+        assert!(Mnemonic::validate(Language::Spanish, PHRASE).is_err());
+        assert!(matches!(
+            Mnemonic::from_phrase_in_any_language("not a mnemonic"),
+            Err(Error::UnrecognizedLanguage)
+        ));
+    }
+
+    #[test]
+    fn language_detection() {
+        for language in Language::ALL {
+            let mnemonic = Mnemonic::from_entropy(language, &[0x7f; 32]).unwrap();
+            let phrase = mnemonic.phrase().expose_secret();
+            let detected = Mnemonic::from_phrase_in_any_language(phrase).unwrap();
+
+            // The simplified and traditional Chinese wordlists share many characters, so a
+            // phrase in one of them may also be valid in the other; in that case the first
+            // matching language in `Language::ALL` is detected.
+            if language == Language::ChineseTraditional {
+                assert!(matches!(
+                    detected.language(),
+                    Language::ChineseSimplified | Language::ChineseTraditional
+                ));
+            } else {
+                assert_eq!(detected.language(), language);
+            }
+            assert!(Mnemonic::validate(detected.language(), phrase).is_ok());
+            assert_eq!(detected.word_count(), WordCount::Words24);
+            assert_eq!(detected.seed_fingerprint(""), mnemonic.seed_fingerprint(""));
+        }
+    }
+
+    #[test]
+    fn chinese_wordlists_are_ambiguous() {
+        // The all-zero entropy encodes to characters that appear in both Chinese
+        // wordlists, so the same phrase is valid in each of them.
+        let simplified = Mnemonic::from_entropy(Language::ChineseSimplified, &[0; 16]).unwrap();
+        let traditional = Mnemonic::from_entropy(Language::ChineseTraditional, &[0; 16]).unwrap();
+        let phrase = simplified.phrase().expose_secret();
+        assert_eq!(phrase, traditional.phrase().expose_secret());
+
+        assert!(Mnemonic::validate(Language::ChineseSimplified, phrase).is_ok());
+        assert!(Mnemonic::validate(Language::ChineseTraditional, phrase).is_ok());
+        assert_eq!(
+            Mnemonic::from_phrase_in_any_language(phrase)
+                .unwrap()
+                .language(),
+            Language::ChineseSimplified
+        );
+        // Both interpretations encode the same entropy, and so derive the same seed.
+        assert_eq!(
+            simplified.seed_fingerprint(""),
+            traditional.seed_fingerprint("")
+        );
+    }
+}
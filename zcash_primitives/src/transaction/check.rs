@@ -0,0 +1,485 @@
+//! Transaction-level consensus checks.
+//!
+//! This module provides checks that can be performed on a single [`Transaction`] without
+//! access to the rest of the chain state, for use by services that want to reject
+//! malformed transactions before relaying them. The checks are split into:
+//!
+//! - [`check_context_free`], covering the rules that depend only on the transaction.
+//! - [`check_contextual`], covering the rules that depend on the height of the block in
+//!   which the transaction would be mined.
+//! - [`check_zip317_fee`], which checks that the transaction pays at least the
+//!   [ZIP 317] conventional fee.
+//! - [`BatchVerifier`], which verifies the Sapling and Orchard proofs, spend
+//!   authorization signatures and binding signatures of one or more transactions.
+//!
+//! Notably, these checks do not include verification of transparent input scripts, nor
+//! of Sprout JoinSplit proofs and signatures; [`BatchVerifier::queue`] rejects any
+//! transaction containing JoinSplits. They also cannot detect double-spends, nor check
+//! that Sapling and Orchard anchors refer to valid note commitment tree states.
+//!
+//! [ZIP 317]: https://zips.z.cash/zip-0317
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+use core::cmp::max;
+use core::fmt;
+
+use rand_core::{CryptoRng, RngCore};
+
+use ::transparent::bundle::{OutPoint, TxOut};
+use zcash_protocol::{
+    consensus::{BlockHeight, BranchId, NetworkUpgrade, Parameters},
+    value::{BalanceError, ZatBalance, Zatoshis},
+};
+
+use super::{
+    fees::zip317::{FeeRule, MARGINAL_FEE},
+    sighash::shielded_sighash_with_prevouts,
+    Transaction, TxId, TxVersion,
+};
+
+/// The maximum permitted value of a transaction's `nExpiryHeight` field.
+pub const MAX_EXPIRY_HEIGHT: u32 = 499_999_999;
+
+/// Errors that can be produced by the checks in this module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    /// Only v4 and v5 transactions can be checked.
+    UnsupportedVersion(TxVersion),
+    /// The transaction has no inputs in any pool.
+    NoInputs,
+    /// The transaction has no outputs in any pool.
+    NoOutputs,
+    /// The transaction's expiry height exceeds [`MAX_EXPIRY_HEIGHT`].
+    ExpiryHeightTooLarge(BlockHeight),
+    /// The transaction reveals the same nullifier more than once.
+    DuplicateNullifier,
+    /// A coinbase transaction contains shielded spends or JoinSplits.
+    CoinbaseHasShieldedInputs,
+    /// A value or sum of values in the transaction is outside the valid range.
+    ValueOutOfRange(BalanceError),
+    /// The transaction's version is not valid at the given height.
+    VersionNotActive {
+        /// The version of the transaction.
+        version: TxVersion,
+        /// The height at which the transaction was checked.
+        height: BlockHeight,
+    },
+    /// The transaction's consensus branch ID does not match the one active at the given
+    /// height.
+    BranchIdMismatch {
+        /// The branch ID active at the given height.
+        expected: BranchId,
+        /// The branch ID of the transaction.
+        actual: BranchId,
+    },
+    /// The transaction has expired as of the given height.
+    Expired {
+        /// The expiry height of the transaction.
+        expiry_height: BlockHeight,
+        /// The height at which the transaction was checked.
+        height: BlockHeight,
+    },
+    /// A coinbase transaction created after NU5 activation has an expiry height that does
+    /// not match the height of its block.
+    CoinbaseExpiryMismatch {
+        /// The expiry height of the transaction.
+        expiry_height: BlockHeight,
+        /// The height at which the transaction was checked.
+        height: BlockHeight,
+    },
+    /// The number of previous outputs provided does not match the number of transparent
+    /// inputs to the transaction.
+    PrevoutCountMismatch {
+        /// The number of transparent inputs to the transaction.
+        expected: usize,
+        /// The number of previous outputs provided by the caller.
+        actual: usize,
+    },
+    /// The transaction pays a fee less than the [ZIP 317] conventional fee.
+    ///
+    /// [ZIP 317]: https://zips.z.cash/zip-0317
+    FeeTooLow {
+        /// The fee paid by the transaction.
+        paid: ZatBalance,
+        /// The conventional fee for the transaction.
+        required: Zatoshis,
+    },
+    /// The transaction contains Sprout JoinSplits, which cannot be verified.
+    SproutUnsupported,
+    /// The Sapling bundle violates a consensus rule that can be checked prior to proof
+    /// and signature verification.
+    SaplingConsensusRule,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckError::UnsupportedVersion(v) => {
+                write!(f, "Transaction version {v:?} is not supported")
+            }
+            CheckError::NoInputs => write!(f, "Transaction has no inputs"),
+            CheckError::NoOutputs => write!(f, "Transaction has no outputs"),
+            CheckError::ExpiryHeightTooLarge(h) => {
+                write!(f, "Expiry height {h} exceeds {MAX_EXPIRY_HEIGHT}")
+            }
+            CheckError::DuplicateNullifier => {
+                write!(f, "Transaction reveals the same nullifier more than once")
+            }
+            CheckError::CoinbaseHasShieldedInputs => {
+                write!(f, "Coinbase transaction has shielded inputs")
+            }
+            CheckError::ValueOutOfRange(e) => write!(f, "Value out of range: {e}"),
+            CheckError::VersionNotActive { version, height } => {
+                write!(f, "Transaction version {version:?} is not valid at height {height}")
+            }
+            CheckError::BranchIdMismatch { expected, actual } => write!(
+                f,
+                "Transaction has branch ID {actual:?}, but {expected:?} is active"
+            ),
+            CheckError::Expired {
+                expiry_height,
+                height,
+            } => write!(
+                f,
+                "Transaction expired at height {expiry_height}, cannot be mined at height {height}"
+            ),
+            CheckError::CoinbaseExpiryMismatch {
+                expiry_height,
+                height,
+            } => write!(
+                f,
+                "Coinbase transaction at height {height} has expiry height {expiry_height}"
+            ),
+            CheckError::PrevoutCountMismatch { expected, actual } => write!(
+                f,
+                "Transaction has {expected} transparent inputs, but {actual} previous outputs were provided"
+            ),
+            CheckError::FeeTooLow { paid, required } => write!(
+                f,
+                "Transaction pays a fee of {} zatoshis, but at least {} zatoshis are required",
+                i64::from(*paid),
+                u64::from(*required)
+            ),
+            CheckError::SproutUnsupported => {
+                write!(f, "Transactions containing JoinSplits cannot be verified")
+            }
+            CheckError::SaplingConsensusRule => {
+                write!(f, "Sapling bundle violates a consensus rule")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckError {}
+
+impl From<BalanceError> for CheckError {
+    fn from(e: BalanceError) -> Self {
+        CheckError::ValueOutOfRange(e)
+    }
+}
+
+fn is_coinbase(tx: &Transaction) -> bool {
+    tx.transparent_bundle()
+        .is_some_and(|bundle| bundle.is_coinbase())
+}
+
+/// Checks the consensus rules that depend only on the transaction itself.
+///
+/// Value bounds that are enforced by the types of this crate (such as the range of each
+/// individual output value, and of each value balance) are checked when the transaction
+/// is parsed; this function additionally checks that sums of values are in range.
+pub fn check_context_free(tx: &Transaction) -> Result<(), CheckError> {
+    let version = tx.version();
+    if !matches!(version, TxVersion::V4 | TxVersion::V5) {
+        return Err(CheckError::UnsupportedVersion(version));
+    }
+
+    let (n_vin, n_vout) = tx
+        .transparent_bundle()
+        .map_or((0, 0), |b| (b.vin.len(), b.vout.len()));
+    let n_joinsplits = tx.sprout_bundle().map_or(0, |b| b.joinsplits.len());
+    let (n_sapling_spends, n_sapling_outputs) = tx.sapling_bundle().map_or((0, 0), |b| {
+        (b.shielded_spends().len(), b.shielded_outputs().len())
+    });
+    let (orchard_spends, orchard_outputs) = tx.orchard_bundle().map_or((false, false), |b| {
+        (b.flags().spends_enabled(), b.flags().outputs_enabled())
+    });
+
+    if n_vin == 0 && n_joinsplits == 0 && n_sapling_spends == 0 && !orchard_spends {
+        return Err(CheckError::NoInputs);
+    }
+    if n_vout == 0 && n_joinsplits == 0 && n_sapling_outputs == 0 && !orchard_outputs {
+        return Err(CheckError::NoOutputs);
+    }
+
+    if u32::from(tx.expiry_height()) > MAX_EXPIRY_HEIGHT {
+        return Err(CheckError::ExpiryHeightTooLarge(tx.expiry_height()));
+    }
+
+    if is_coinbase(tx) && (n_joinsplits > 0 || n_sapling_spends > 0 || orchard_spends) {
+        return Err(CheckError::CoinbaseHasShieldedInputs);
+    }
+
+    // The sum of the transparent outputs must be a valid amount.
+    if let Some(bundle) = tx.transparent_bundle() {
+        bundle
+            .vout
+            .iter()
+            .map(|txout| txout.value)
+            .sum::<Option<Zatoshis>>()
+            .ok_or(BalanceError::Overflow)?;
+    }
+
+    // The net value of the JoinSplits must be a valid amount.
+    if let Some(bundle) = tx.sprout_bundle() {
+        bundle.value_balance().ok_or(BalanceError::Overflow)?;
+    }
+
+    // Nullifiers must be unique within each pool.
+    if let Some(bundle) = tx.sprout_bundle() {
+        let mut seen = BTreeSet::new();
+        for nf in bundle.joinsplits.iter().flat_map(|js| js.nullifiers.iter()) {
+            if !seen.insert(*nf) {
+                return Err(CheckError::DuplicateNullifier);
+            }
+        }
+    }
+    if let Some(bundle) = tx.sapling_bundle() {
+        let mut seen = BTreeSet::new();
+        for spend in bundle.shielded_spends() {
+            if !seen.insert(spend.nullifier().0) {
+                return Err(CheckError::DuplicateNullifier);
+            }
+        }
+    }
+    if let Some(bundle) = tx.orchard_bundle() {
+        let mut seen = BTreeSet::new();
+        for action in bundle.actions() {
+            if !seen.insert(action.nullifier().to_bytes()) {
+                return Err(CheckError::DuplicateNullifier);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the consensus rules that depend on the height of the block in which the
+/// transaction would be mined.
+pub fn check_contextual<P: Parameters>(
+    params: &P,
+    tx: &Transaction,
+    height: BlockHeight,
+) -> Result<(), CheckError> {
+    let version = tx.version();
+    let version_active = match version {
+        TxVersion::V4 => params.is_nu_active(NetworkUpgrade::Sapling, height),
+        TxVersion::V5 => params.is_nu_active(NetworkUpgrade::Nu5, height),
+        _ => return Err(CheckError::UnsupportedVersion(version)),
+    };
+    if !version_active {
+        return Err(CheckError::VersionNotActive { version, height });
+    }
+
+    let expected = BranchId::for_height(params, height);
+    if tx.consensus_branch_id() != expected {
+        return Err(CheckError::BranchIdMismatch {
+            expected,
+            actual: tx.consensus_branch_id(),
+        });
+    }
+
+    let expiry_height = tx.expiry_height();
+    if is_coinbase(tx) {
+        if params.is_nu_active(NetworkUpgrade::Nu5, height) && expiry_height != height {
+            return Err(CheckError::CoinbaseExpiryMismatch {
+                expiry_height,
+                height,
+            });
+        }
+    } else if u32::from(expiry_height) != 0 && height > expiry_height {
+        return Err(CheckError::Expired {
+            expiry_height,
+            height,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns the [ZIP 317] conventional fee for the given transaction.
+///
+/// Unlike [`FeeRule`], which is used during transaction construction and must estimate
+/// the size of transparent inputs, this uses the actual serialized size of each input,
+/// and includes the logical actions contributed by JoinSplits.
+///
+/// [ZIP 317]: https://zips.z.cash/zip-0317
+pub fn zip317_conventional_fee(tx: &Transaction) -> Result<Zatoshis, BalanceError> {
+    let rule = FeeRule::standard();
+
+    let (tx_in_total_size, tx_out_total_size) = tx.transparent_bundle().map_or((0, 0), |bundle| {
+        (
+            bundle
+                .vin
+                .iter()
+                // outpoint + script_sig + sequence
+                .map(|txin| 36 + txin.script_sig.serialized_size() + 4)
+                .sum::<usize>(),
+            bundle
+                .vout
+                .iter()
+                .map(|txout| 8 + txout.script_pubkey.serialized_size())
+                .sum::<usize>(),
+        )
+    });
+    let n_joinsplits = tx.sprout_bundle().map_or(0, |b| b.joinsplits.len());
+    let sapling_actions = tx.sapling_bundle().map_or(0, |b| {
+        max(b.shielded_spends().len(), b.shielded_outputs().len())
+    });
+    let orchard_actions = tx.orchard_bundle().map_or(0, |b| b.actions().len());
+
+    let logical_actions = max(
+        tx_in_total_size.div_ceil(rule.p2pkh_standard_input_size()),
+        tx_out_total_size.div_ceil(rule.p2pkh_standard_output_size()),
+    ) + 2 * n_joinsplits
+        + sapling_actions
+        + orchard_actions;
+
+    (MARGINAL_FEE * max(rule.grace_actions(), logical_actions)).ok_or(BalanceError::Overflow)
+}
+
+/// Checks that the transaction pays at least the [ZIP 317] conventional fee, given the
+/// previous outputs being spent by its transparent inputs (in the same order as the
+/// inputs).
+///
+/// Returns the fee paid by the transaction. Coinbase transactions do not pay fees, and
+/// are always accepted with a fee of zero.
+///
+/// [ZIP 317]: https://zips.z.cash/zip-0317
+pub fn check_zip317_fee(tx: &Transaction, prevouts: &[TxOut]) -> Result<ZatBalance, CheckError> {
+    if is_coinbase(tx) {
+        return Ok(ZatBalance::zero());
+    }
+
+    let vin = tx
+        .transparent_bundle()
+        .map_or(&[][..], |bundle| &bundle.vin[..]);
+    if prevouts.len() != vin.len() {
+        return Err(CheckError::PrevoutCountMismatch {
+            expected: vin.len(),
+            actual: prevouts.len(),
+        });
+    }
+
+    let paid = tx.fee_paid(|outpoint: &OutPoint| {
+        vin.iter()
+            .position(|txin| &txin.prevout == outpoint)
+            .map(|i| ZatBalance::from(prevouts[i].value))
+            .ok_or(CheckError::ValueOutOfRange(BalanceError::Underflow))
+    })?;
+    let required = zip317_conventional_fee(tx)?;
+
+    if paid < ZatBalance::from(required) {
+        Err(CheckError::FeeTooLow { paid, required })
+    } else {
+        Ok(paid)
+    }
+}
+
+/// A batch verifier for the proofs and signatures in the shielded components of
+/// transactions.
+///
+/// Verifying many transactions at once is considerably cheaper than verifying each of
+/// them individually. If the batch fails to verify, the caller must verify each
+/// transaction in a separate batch to determine which of them are invalid.
+///
+/// The Sapling verifying keys can be obtained from the Sapling parameters loaded by
+/// `zcash_proofs`, via `SpendParameters::verifying_key` and
+/// `OutputParameters::verifying_key`.
+pub struct BatchVerifier {
+    sapling: sapling::BatchValidator,
+    orchard: orchard::bundle::BatchValidator,
+    queued: Vec<TxId>,
+}
+
+impl Default for BatchVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BatchVerifier {
+    /// Constructs a new empty batch verifier.
+    pub fn new() -> Self {
+        BatchVerifier {
+            sapling: sapling::BatchValidator::new(),
+            orchard: orchard::bundle::BatchValidator::new(),
+            queued: vec![],
+        }
+    }
+
+    /// Returns the IDs of the transactions that have been queued in this batch.
+    pub fn queued_txids(&self) -> &[TxId] {
+        &self.queued
+    }
+
+    /// Adds the shielded components of the given transaction to the batch.
+    ///
+    /// `prevouts` must contain the previous outputs being spent by the transaction's
+    /// transparent inputs, in the same order as the inputs. They are only used for v5
+    /// transactions that have both transparent inputs and shielded components, and may be
+    /// empty otherwise.
+    pub fn queue(&mut self, tx: &Transaction, prevouts: &[TxOut]) -> Result<(), CheckError> {
+        let version = tx.version();
+        if !matches!(version, TxVersion::V4 | TxVersion::V5) {
+            return Err(CheckError::UnsupportedVersion(version));
+        }
+        if tx
+            .sprout_bundle()
+            .is_some_and(|bundle| !bundle.joinsplits.is_empty())
+        {
+            return Err(CheckError::SproutUnsupported);
+        }
+        if tx.sapling_bundle().is_none() && tx.orchard_bundle().is_none() {
+            return Ok(());
+        }
+
+        let n_vin = tx.transparent_bundle().map_or(0, |bundle| bundle.vin.len());
+        if version == TxVersion::V5 && !is_coinbase(tx) && prevouts.len() != n_vin {
+            return Err(CheckError::PrevoutCountMismatch {
+                expected: n_vin,
+                actual: prevouts.len(),
+            });
+        }
+        let sighash = shielded_sighash_with_prevouts(tx, prevouts);
+
+        if let Some(bundle) = tx.sapling_bundle() {
+            if !self.sapling.check_bundle(bundle.clone(), sighash) {
+                return Err(CheckError::SaplingConsensusRule);
+            }
+        }
+        if let Some(bundle) = tx.orchard_bundle() {
+            self.orchard.add_bundle(bundle, sighash);
+        }
+
+        self.queued.push(tx.txid());
+        Ok(())
+    }
+
+    /// Verifies all of the proofs and signatures in the batch.
+    ///
+    /// Returns `true` if and only if every queued transaction is valid.
+    pub fn validate<R: RngCore + CryptoRng>(
+        self,
+        sapling_spend_vk: &sapling::circuit::SpendVerifyingKey,
+        sapling_output_vk: &sapling::circuit::OutputVerifyingKey,
+        orchard_vk: &orchard::circuit::VerifyingKey,
+        mut rng: R,
+    ) -> bool {
+        self.sapling
+            .validate(sapling_spend_vk, sapling_output_vk, &mut rng)
+            && self.orchard.validate(orchard_vk, &mut rng)
+    }
+}
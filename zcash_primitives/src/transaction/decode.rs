@@ -0,0 +1,486 @@
+//! Decoding of transactions into a structured, JSON-serializable form.
+//!
+//! The output of [`decode_transaction`] is intended for support tooling and debugging,
+//! similar in spirit to the `decoderawtransaction` RPC method provided by `zcashd`. Its
+//! JSON serialization is stable: fields will not be renamed or removed without a
+//! breaking release of this crate, although new fields may be added.
+//!
+//! # Encoding conventions
+//!
+//! - Transaction IDs (including those of transparent outpoints) are hex-encoded in the
+//!   conventional byte-reversed display order.
+//! - All other byte strings (commitments, nullifiers, keys, signatures, proofs, scripts
+//!   and digests) are hex-encoded in the byte order in which they are serialized in the
+//!   transaction encoding.
+//! - Values are expressed as integers in zatoshis, in fields with a `Zat` suffix.
+//! - Value balances have the sign convention of the transaction encoding: a positive
+//!   value balance moves value out of the shielded pool into the transparent pool.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use ff::PrimeField;
+use serde::Serialize;
+
+use ::transparent::{
+    address::TransparentAddress,
+    bundle::{OutPoint, TxOut},
+};
+use zcash_address::{ToAddress, ZcashAddress};
+use zcash_protocol::{
+    consensus::Parameters,
+    value::{BalanceError, ZatBalance},
+};
+
+use super::{components::sprout, sighash::shielded_sighash_with_prevouts, Transaction, TxVersion};
+
+/// Errors that can occur when decoding a transaction.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Only v4 and v5 transactions can be decoded.
+    UnsupportedVersion(TxVersion),
+    /// The number of previous outputs provided does not match the number of transparent
+    /// inputs to the transaction.
+    PrevoutCountMismatch {
+        /// The number of transparent inputs to the transaction.
+        expected: usize,
+        /// The number of previous outputs provided by the caller.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "Cannot decode transactions with version {version:?}")
+            }
+            DecodeError::PrevoutCountMismatch { expected, actual } => write!(
+                f,
+                "Transaction has {expected} transparent inputs, but {actual} previous outputs were provided"
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
+
+/// A decoded transaction.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedTransaction {
+    /// The transaction ID.
+    pub txid: String,
+    /// The [ZIP 244] authorizing data commitment, for v5 transactions.
+    ///
+    /// [ZIP 244]: https://zips.z.cash/zip-0244
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_digest: Option<String>,
+    /// The transaction version number.
+    pub version: u32,
+    /// The version group ID, as a hex-encoded big-endian integer.
+    pub version_group_id: String,
+    /// The consensus branch ID, as a hex-encoded big-endian integer.
+    pub consensus_branch_id: String,
+    /// The lock time of the transaction.
+    pub lock_time: u32,
+    /// The height after which the transaction will expire if it is not mined.
+    pub expiry_height: u32,
+    /// The transparent part of the transaction.
+    pub transparent: DecodedTransparent,
+    /// The Sprout JoinSplits of the transaction (v4 only).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sprout: Vec<DecodedJoinSplit>,
+    /// The Sapling bundle of the transaction, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sapling: Option<DecodedSaplingBundle>,
+    /// The Orchard bundle of the transaction, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orchard: Option<DecodedOrchardBundle>,
+    /// The fee paid by the transaction.
+    ///
+    /// This is only present if it can be computed, which requires the previous outputs
+    /// being spent by any transparent inputs, and requires the transaction's values to
+    /// balance within the valid monetary range. It is never present for coinbase
+    /// transactions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_zat: Option<i64>,
+    /// The signature hash used by shielded spends and binding signatures.
+    ///
+    /// For v5 transactions with transparent inputs, this is only present if the previous
+    /// outputs being spent were provided.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sighash_shielded: Option<String>,
+}
+
+/// The transparent part of a decoded transaction.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedTransparent {
+    /// Whether this is a coinbase transaction.
+    pub coinbase: bool,
+    /// The transparent inputs.
+    pub vin: Vec<DecodedTxIn>,
+    /// The transparent outputs.
+    pub vout: Vec<DecodedTxOut>,
+}
+
+/// A decoded transparent input.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedTxIn {
+    /// The ID of the transaction containing the output being spent.
+    pub txid: String,
+    /// The index of the output being spent.
+    pub vout: u32,
+    /// The hex-encoded signature script.
+    pub script_sig: String,
+    /// The sequence number.
+    pub sequence: u32,
+    /// The output being spent, if it was provided to the decoder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prevout: Option<DecodedTxOut>,
+}
+
+/// A decoded transparent output.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedTxOut {
+    /// The index of this output within its transaction.
+    ///
+    /// This is omitted for previous outputs supplied to the decoder.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+    /// The value of the output.
+    pub value_zat: u64,
+    /// The hex-encoded output script.
+    pub script_pub_key: String,
+    /// The type of the output script: one of `"pubkeyhash"`, `"scripthash"` or
+    /// `"nonstandard"`.
+    pub script_type: &'static str,
+    /// The address that the output script pays to, for standard scripts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+/// A decoded Sprout JoinSplit description.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedJoinSplit {
+    /// The value that the JoinSplit removes from the transparent value pool.
+    pub vpub_old_zat: i64,
+    /// The value that the JoinSplit inserts into the transparent value pool.
+    pub vpub_new_zat: i64,
+    /// The Sprout note commitment tree anchor.
+    pub anchor: String,
+    /// The nullifiers of the spent notes.
+    pub nullifiers: Vec<String>,
+    /// The note commitments of the created notes.
+    pub commitments: Vec<String>,
+}
+
+/// A decoded Sapling bundle.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedSaplingBundle {
+    /// The net value moved out of the Sapling pool.
+    pub value_balance_zat: i64,
+    /// The Sapling spends.
+    pub spends: Vec<DecodedSaplingSpend>,
+    /// The Sapling outputs.
+    pub outputs: Vec<DecodedSaplingOutput>,
+    /// The binding signature.
+    pub binding_sig: String,
+}
+
+/// A decoded Sapling spend description.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedSaplingSpend {
+    /// The value commitment.
+    pub cv: String,
+    /// The Sapling note commitment tree anchor.
+    pub anchor: String,
+    /// The nullifier of the spent note.
+    pub nullifier: String,
+    /// The randomized validating key.
+    pub rk: String,
+    /// The Groth16 proof.
+    pub proof: String,
+    /// The spend authorization signature.
+    pub spend_auth_sig: String,
+}
+
+/// A decoded Sapling output description.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedSaplingOutput {
+    /// The value commitment.
+    pub cv: String,
+    /// The note commitment.
+    pub cmu: String,
+    /// The ephemeral public key.
+    pub ephemeral_key: String,
+    /// The encrypted note plaintext.
+    pub enc_ciphertext: String,
+    /// The encrypted outgoing plaintext.
+    pub out_ciphertext: String,
+    /// The Groth16 proof.
+    pub proof: String,
+}
+
+/// A decoded Orchard bundle.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedOrchardBundle {
+    /// The net value moved out of the Orchard pool.
+    pub value_balance_zat: i64,
+    /// The bundle flags.
+    pub flags: DecodedOrchardFlags,
+    /// The Orchard note commitment tree anchor.
+    pub anchor: String,
+    /// The Orchard actions.
+    pub actions: Vec<DecodedOrchardAction>,
+    /// The aggregated Halo 2 proof.
+    pub proof: String,
+    /// The binding signature.
+    pub binding_sig: String,
+}
+
+/// The decoded flags of an Orchard bundle.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedOrchardFlags {
+    /// Whether spends are enabled for the bundle.
+    pub enable_spends: bool,
+    /// Whether outputs are enabled for the bundle.
+    pub enable_outputs: bool,
+}
+
+/// A decoded Orchard action.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedOrchardAction {
+    /// The net value commitment.
+    pub cv: String,
+    /// The nullifier of the spent note.
+    pub nullifier: String,
+    /// The randomized validating key.
+    pub rk: String,
+    /// The extracted note commitment of the created note.
+    pub cmx: String,
+    /// The ephemeral public key.
+    pub ephemeral_key: String,
+    /// The encrypted note plaintext.
+    pub enc_ciphertext: String,
+    /// The encrypted outgoing plaintext.
+    pub out_ciphertext: String,
+    /// The spend authorization signature.
+    pub spend_auth_sig: String,
+}
+
+impl DecodedTransaction {
+    /// Renders this decoded transaction as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("decoded transactions are always serializable")
+    }
+}
+
+/// Decodes the given transaction.
+///
+/// `prevouts`, if provided, must contain the outputs being spent by each of the
+/// transaction's transparent inputs, in the same order as the inputs. They are used to
+/// compute the fee and (for v5 transactions with transparent inputs) the shielded
+/// signature hash, and are included alongside the inputs in the decoded output.
+pub fn decode_transaction<P: Parameters>(
+    params: &P,
+    tx: &Transaction,
+    prevouts: Option<&[TxOut]>,
+) -> Result<DecodedTransaction, DecodeError> {
+    let version = tx.version();
+    if !matches!(version, TxVersion::V4 | TxVersion::V5) {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let vin = tx
+        .transparent_bundle()
+        .map_or(&[][..], |bundle| &bundle.vin[..]);
+    if let Some(prevouts) = prevouts {
+        if prevouts.len() != vin.len() {
+            return Err(DecodeError::PrevoutCountMismatch {
+                expected: vin.len(),
+                actual: prevouts.len(),
+            });
+        }
+    }
+
+    let is_coinbase = tx
+        .transparent_bundle()
+        .is_some_and(|bundle| bundle.is_coinbase());
+
+    let transparent = tx
+        .transparent_bundle()
+        .map(|bundle| DecodedTransparent {
+            coinbase: is_coinbase,
+            vin: bundle
+                .vin
+                .iter()
+                .enumerate()
+                .map(|(i, txin)| DecodedTxIn {
+                    txid: txin.prevout.txid().to_string(),
+                    vout: txin.prevout.n(),
+                    script_sig: hex::encode(&txin.script_sig.0),
+                    sequence: txin.sequence,
+                    prevout: prevouts.map(|p| decode_txout(params, None, &p[i])),
+                })
+                .collect(),
+            vout: bundle
+                .vout
+                .iter()
+                .enumerate()
+                .map(|(n, txout)| decode_txout(params, Some(n as u32), txout))
+                .collect(),
+        })
+        .unwrap_or_default();
+
+    let sprout = tx.sprout_bundle().map_or_else(Vec::new, |bundle| {
+        bundle.joinsplits.iter().map(decode_joinsplit).collect()
+    });
+
+    let sapling = tx.sapling_bundle().map(|bundle| DecodedSaplingBundle {
+        value_balance_zat: i64::from(*bundle.value_balance()),
+        spends: bundle
+            .shielded_spends()
+            .iter()
+            .map(|spend| DecodedSaplingSpend {
+                cv: hex::encode(spend.cv().to_bytes()),
+                anchor: hex::encode(spend.anchor().to_repr()),
+                nullifier: hex::encode(spend.nullifier().0),
+                rk: hex::encode(<[u8; 32]>::from(*spend.rk())),
+                proof: hex::encode(spend.zkproof()),
+                spend_auth_sig: hex::encode(<[u8; 64]>::from(*spend.spend_auth_sig())),
+            })
+            .collect(),
+        outputs: bundle
+            .shielded_outputs()
+            .iter()
+            .map(|output| DecodedSaplingOutput {
+                cv: hex::encode(output.cv().to_bytes()),
+                cmu: hex::encode(output.cmu().to_bytes()),
+                ephemeral_key: hex::encode(output.ephemeral_key().0),
+                enc_ciphertext: hex::encode(output.enc_ciphertext()),
+                out_ciphertext: hex::encode(output.out_ciphertext()),
+                proof: hex::encode(output.zkproof()),
+            })
+            .collect(),
+        binding_sig: hex::encode(<[u8; 64]>::from(bundle.authorization().binding_sig)),
+    });
+
+    let orchard = tx.orchard_bundle().map(|bundle| DecodedOrchardBundle {
+        value_balance_zat: i64::from(*bundle.value_balance()),
+        flags: DecodedOrchardFlags {
+            enable_spends: bundle.flags().spends_enabled(),
+            enable_outputs: bundle.flags().outputs_enabled(),
+        },
+        anchor: hex::encode(bundle.anchor().to_bytes()),
+        actions: bundle
+            .actions()
+            .iter()
+            .map(|action| DecodedOrchardAction {
+                cv: hex::encode(action.cv_net().to_bytes()),
+                nullifier: hex::encode(action.nullifier().to_bytes()),
+                rk: hex::encode(<[u8; 32]>::from(action.rk())),
+                cmx: hex::encode(action.cmx().to_bytes()),
+                ephemeral_key: hex::encode(action.encrypted_note().epk_bytes),
+                enc_ciphertext: hex::encode(action.encrypted_note().enc_ciphertext),
+                out_ciphertext: hex::encode(action.encrypted_note().out_ciphertext),
+                spend_auth_sig: hex::encode(<[u8; 64]>::from(action.authorization())),
+            })
+            .collect(),
+        proof: hex::encode(bundle.authorization().proof()),
+        binding_sig: hex::encode(<[u8; 64]>::from(bundle.authorization().binding_signature())),
+    });
+
+    // The fee can be computed without previous outputs only if there are no transparent
+    // inputs. A transaction whose values do not balance within the valid monetary range
+    // is still decoded, but without a fee.
+    let fee_zat = if is_coinbase {
+        None
+    } else if vin.is_empty() || prevouts.is_some() {
+        let prevouts = prevouts.unwrap_or(&[]);
+        tx.fee_paid(|outpoint: &OutPoint| {
+            vin.iter()
+                .position(|txin| &txin.prevout == outpoint)
+                .map(|i| ZatBalance::from(prevouts[i].value))
+                .ok_or(BalanceError::Underflow)
+        })
+        .ok()
+        .map(i64::from)
+    } else {
+        None
+    };
+
+    // The v5 shielded sighash commits to the amounts and scripts of the outputs being
+    // spent by transparent inputs, unless there are no such inputs.
+    let sighash_shielded =
+        if version == TxVersion::V4 || vin.is_empty() || is_coinbase || prevouts.is_some() {
+            Some(hex::encode(shielded_sighash_with_prevouts(
+                tx,
+                prevouts.unwrap_or(&[]),
+            )))
+        } else {
+            None
+        };
+
+    Ok(DecodedTransaction {
+        txid: tx.txid().to_string(),
+        auth_digest: (version == TxVersion::V5)
+            .then(|| hex::encode(tx.auth_commitment().as_bytes())),
+        version: version.header() & 0x7FFF_FFFF,
+        version_group_id: format!("{:08x}", version.version_group_id()),
+        consensus_branch_id: format!("{:08x}", u32::from(tx.consensus_branch_id())),
+        lock_time: tx.lock_time(),
+        expiry_height: u32::from(tx.expiry_height()),
+        transparent,
+        sprout,
+        sapling,
+        orchard,
+        fee_zat,
+        sighash_shielded,
+    })
+}
+
+fn decode_txout<P: Parameters>(params: &P, n: Option<u32>, txout: &TxOut) -> DecodedTxOut {
+    let network = params.network_type();
+    let (script_type, address) = match txout.recipient_address() {
+        Some(TransparentAddress::PublicKeyHash(data)) => (
+            "pubkeyhash",
+            Some(ZcashAddress::from_transparent_p2pkh(network, data).encode()),
+        ),
+        Some(TransparentAddress::ScriptHash(data)) => (
+            "scripthash",
+            Some(ZcashAddress::from_transparent_p2sh(network, data).encode()),
+        ),
+        None => ("nonstandard", None),
+    };
+
+    DecodedTxOut {
+        n,
+        value_zat: u64::from(txout.value),
+        script_pub_key: hex::encode(&txout.script_pubkey.0),
+        script_type,
+        address,
+    }
+}
+
+fn decode_joinsplit(js: &sprout::JsDescription) -> DecodedJoinSplit {
+    DecodedJoinSplit {
+        vpub_old_zat: i64::from(js.vpub_old),
+        vpub_new_zat: i64::from(js.vpub_new),
+        anchor: hex::encode(js.anchor),
+        nullifiers: js.nullifiers.iter().map(hex::encode).collect(),
+        commitments: js.commitments.iter().map(hex::encode).collect(),
+    }
+}
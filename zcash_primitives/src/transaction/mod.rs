@@ -1,6 +1,10 @@
 //! Structs and methods for handling Zcash transactions.
 pub mod builder;
+#[cfg(feature = "circuits")]
+pub mod check;
 pub mod components;
+#[cfg(feature = "json")]
+pub mod decode;
 pub mod fees;
 pub mod sighash;
 pub mod sighash_v4;
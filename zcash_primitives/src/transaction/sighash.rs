@@ -7,7 +7,28 @@ use super::{
 use ::sapling::bundle::GrothProofBytes;
 
 #[cfg(zcash_unstable = "zfuture")]
-use {crate::extensions::transparent::Precondition, zcash_protocol::value::Zatoshis};
+use crate::extensions::transparent::Precondition;
+
+#[cfg(any(zcash_unstable = "zfuture", feature = "circuits", feature = "json"))]
+use zcash_protocol::value::Zatoshis;
+
+#[cfg(any(feature = "circuits", feature = "json"))]
+use {
+    super::{txid::TxIdDigester, Transaction},
+    ::transparent::{
+        address::Script,
+        bundle::{self as transparent_bundle, TxIn, TxOut},
+        sighash::TransparentAuthorizingContext,
+    },
+    alloc::vec::Vec,
+    core::ops::Deref,
+};
+
+#[cfg(all(
+    zcash_unstable = "zfuture",
+    any(feature = "circuits", feature = "json")
+))]
+use super::components::tze;
 
 #[deprecated(note = "use `::zcash_transparent::sighash::SIGHASH_ALL` instead.")]
 pub const SIGHASH_ALL: u8 = ::transparent::sighash::SIGHASH_ALL;
@@ -79,3 +100,101 @@ pub fn signature_hash<
         TxVersion::ZFuture => v5_signature_hash(tx, signable_input, txid_parts),
     })
 }
+
+/// Transparent authorization that carries the previous outputs being spent, for use in
+/// computing signature hashes of fully-authorized transactions.
+#[cfg(any(feature = "circuits", feature = "json"))]
+#[derive(Debug)]
+struct PrevoutsAuth {
+    prevouts: Vec<TxOut>,
+}
+
+#[cfg(any(feature = "circuits", feature = "json"))]
+impl transparent_bundle::Authorization for PrevoutsAuth {
+    type ScriptSig = Script;
+}
+
+#[cfg(any(feature = "circuits", feature = "json"))]
+impl TransparentAuthorizingContext for PrevoutsAuth {
+    fn input_amounts(&self) -> Vec<Zatoshis> {
+        self.prevouts.iter().map(|prevout| prevout.value).collect()
+    }
+
+    fn input_scriptpubkeys(&self) -> Vec<Script> {
+        self.prevouts
+            .iter()
+            .map(|prevout| prevout.script_pubkey.clone())
+            .collect()
+    }
+}
+
+#[cfg(any(feature = "circuits", feature = "json"))]
+struct WithPrevouts;
+
+#[cfg(any(feature = "circuits", feature = "json"))]
+impl Authorization for WithPrevouts {
+    type TransparentAuth = PrevoutsAuth;
+    type SaplingAuth = sapling::bundle::Authorized;
+    type OrchardAuth = orchard::bundle::Authorized;
+
+    #[cfg(zcash_unstable = "zfuture")]
+    type TzeAuth = tze::Authorized;
+}
+
+/// Computes the signature hash for the shielded components of a fully-authorized
+/// transaction, given the previous outputs being spent by its transparent inputs (in the
+/// same order as the inputs).
+///
+/// `prevouts` is only required to be non-empty for v5 transactions that have
+/// non-coinbase transparent inputs.
+#[cfg(any(feature = "circuits", feature = "json"))]
+pub(crate) fn shielded_sighash_with_prevouts(tx: &Transaction, prevouts: &[TxOut]) -> [u8; 32] {
+    let txdata = tx.deref();
+    let txid_parts = txdata.digest(TxIdDigester);
+
+    // We rebuild the transparent bundle so that its authorization carries the previous
+    // outputs, as required by `TransparentAuthorizingContext`.
+    let transparent_bundle = txdata
+        .transparent_bundle()
+        .map(|b| transparent_bundle::Bundle {
+            vin: b
+                .vin
+                .iter()
+                .map(|txin| TxIn {
+                    prevout: txin.prevout.clone(),
+                    script_sig: txin.script_sig.clone(),
+                    sequence: txin.sequence,
+                })
+                .collect(),
+            vout: b.vout.clone(),
+            authorization: PrevoutsAuth {
+                prevouts: prevouts.to_vec(),
+            },
+        });
+
+    #[cfg(not(zcash_unstable = "zfuture"))]
+    let tdata = TransactionData::<WithPrevouts>::from_parts(
+        txdata.version(),
+        txdata.consensus_branch_id(),
+        txdata.lock_time(),
+        txdata.expiry_height(),
+        transparent_bundle,
+        txdata.sprout_bundle().cloned(),
+        txdata.sapling_bundle().cloned(),
+        txdata.orchard_bundle().cloned(),
+    );
+    #[cfg(zcash_unstable = "zfuture")]
+    let tdata = TransactionData::<WithPrevouts>::from_parts_zfuture(
+        txdata.version(),
+        txdata.consensus_branch_id(),
+        txdata.lock_time(),
+        txdata.expiry_height(),
+        transparent_bundle,
+        txdata.sprout_bundle().cloned(),
+        txdata.sapling_bundle().cloned(),
+        txdata.orchard_bundle().cloned(),
+        txdata.tze_bundle().cloned(),
+    );
+
+    *signature_hash(&tdata, &SignableInput::Shielded, &txid_parts).as_ref()
+}
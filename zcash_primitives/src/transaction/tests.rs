@@ -361,3 +361,197 @@ fn zip_0244() {
         );
     }
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn decode_zip_0244() {
+    use ::transparent::bundle::TxOut;
+    use zcash_protocol::{
+        consensus::MainNetwork,
+        value::{BalanceError, ZatBalance},
+    };
+
+    use super::decode::{decode_transaction, DecodeError};
+
+    for tv in self::data::zip_0244::make_test_vectors() {
+        let tx = Transaction::read(&tv.tx[..], BranchId::Nu5).unwrap();
+        let prevouts = tv
+            .amounts
+            .iter()
+            .zip(tv.script_pubkeys.iter())
+            .map(|(amount, script)| TxOut {
+                value: Zatoshis::from_nonnegative_i64(*amount).unwrap(),
+                script_pubkey: Script(script.clone()),
+            })
+            .collect::<Vec<_>>();
+        let n_vin = tx.transparent_bundle().map_or(0, |b| b.vin.len());
+        let is_coinbase = tx.transparent_bundle().is_some_and(|b| b.is_coinbase());
+
+        let decoded = if prevouts.len() == n_vin {
+            let decoded = decode_transaction(&MainNetwork, &tx, Some(&prevouts)).unwrap();
+            // The test vectors use random values, so the fee is only present if those
+            // happen to balance within the valid monetary range.
+            let fee = tx
+                .fee_paid(|outpoint| {
+                    tx.transparent_bundle()
+                        .and_then(|b| b.vin.iter().position(|txin| &txin.prevout == outpoint))
+                        .map(|i| ZatBalance::from(prevouts[i].value))
+                        .ok_or(BalanceError::Underflow)
+                })
+                .ok()
+                .map(i64::from);
+            assert_eq!(decoded.fee_zat, fee.filter(|_| !is_coinbase));
+            assert_eq!(
+                decoded.sighash_shielded,
+                Some(hex::encode(tv.sighash_shielded))
+            );
+            decoded
+        } else {
+            decode_transaction(&MainNetwork, &tx, None).unwrap()
+        };
+
+        assert_eq!(decoded.txid, format!("{}", tx.txid()));
+        assert_eq!(decoded.auth_digest, Some(hex::encode(tv.auth_digest)));
+        assert_eq!(decoded.version, 5);
+        assert_eq!(decoded.transparent.coinbase, is_coinbase);
+        assert_eq!(decoded.transparent.vin.len(), n_vin);
+        assert_eq!(
+            decoded.sapling.as_ref().map(|b| b.spends.len()),
+            tx.sapling_bundle().map(|b| b.shielded_spends().len()),
+        );
+        assert_eq!(
+            decoded.orchard.as_ref().map(|b| b.actions.len()),
+            tx.orchard_bundle().map(|b| b.actions().len()),
+        );
+
+        // The rendered JSON must be parseable and carry the same transaction ID.
+        let json: serde_json::Value = serde_json::from_str(&decoded.to_json()).unwrap();
+        assert_eq!(json["txid"], decoded.txid);
+
+        if n_vin > 0 {
+            assert!(matches!(
+                decode_transaction(&MainNetwork, &tx, Some(&[])),
+                Err(DecodeError::PrevoutCountMismatch { .. })
+            ));
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn decode_zip_0243() {
+    use zcash_protocol::consensus::MainNetwork;
+
+    use super::decode::decode_transaction;
+
+    // Only the test vectors for the shielded signature hash are relevant here.
+    for tv in self::data::zip_0243::make_test_vectors()
+        .into_iter()
+        .filter(|tv| tv.transparent_input.is_none())
+    {
+        let tx = Transaction::read(&tv.tx[..], tv.consensus_branch_id).unwrap();
+
+        // v4 signature hashes do not commit to the previous outputs, so they are not
+        // needed in order to compute the shielded signature hash.
+        let decoded = decode_transaction(&MainNetwork, &tx, None).unwrap();
+        assert_eq!(decoded.txid, format!("{}", tx.txid()));
+        assert_eq!(decoded.version, 4);
+        assert_eq!(decoded.auth_digest, None);
+        assert_eq!(decoded.sighash_shielded, Some(hex::encode(tv.sighash)));
+        assert_eq!(
+            decoded.sprout.len(),
+            tx.sprout_bundle().map_or(0, |b| b.joinsplits.len()),
+        );
+        assert_eq!(
+            decoded.sapling.as_ref().map(|b| b.spends.len()),
+            tx.sapling_bundle().map(|b| b.shielded_spends().len()),
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "circuits")]
+fn check_tx_read_write() {
+    use ::transparent::bundle::TxOut;
+    use zcash_protocol::{
+        consensus::{BlockHeight, TEST_NETWORK},
+        value::ZatBalance,
+    };
+
+    use super::check::{
+        check_context_free, check_contextual, check_zip317_fee, zip317_conventional_fee,
+        BatchVerifier, CheckError,
+    };
+
+    // This transaction was mined in testnet block 280003, while Sapling was active.
+    let data = &self::data::tx_read_write::TX_READ_WRITE;
+    let tx = Transaction::read(&data[..], BranchId::Sapling).unwrap();
+    let height = BlockHeight::from_u32(280003);
+
+    assert_eq!(check_context_free(&tx), Ok(()));
+    assert_eq!(check_contextual(&TEST_NETWORK, &tx, height), Ok(()));
+    assert_eq!(
+        check_contextual(&TEST_NETWORK, &tx, tx.expiry_height() + 1),
+        Err(CheckError::Expired {
+            expiry_height: tx.expiry_height(),
+            height: tx.expiry_height() + 1,
+        })
+    );
+
+    let canopy = Transaction::read(&data[..], BranchId::Canopy).unwrap();
+    assert_eq!(
+        check_contextual(&TEST_NETWORK, &canopy, height),
+        Err(CheckError::BranchIdMismatch {
+            expected: BranchId::Sapling,
+            actual: BranchId::Canopy,
+        })
+    );
+
+    // Construct a previous output that makes the transaction pay exactly the given fee.
+    let bundle = tx.transparent_bundle().unwrap();
+    let prevout_for_fee = |fee: Zatoshis| {
+        let out_total = bundle
+            .vout
+            .iter()
+            .map(|txout| txout.value)
+            .sum::<Option<Zatoshis>>()
+            .unwrap();
+        let sprout_balance = tx.sprout_bundle().unwrap().value_balance().unwrap();
+        let value = (ZatBalance::from(out_total) - sprout_balance)
+            .and_then(|v| v + ZatBalance::from(fee))
+            .and_then(|v| Zatoshis::try_from(v).ok())
+            .unwrap();
+        TxOut {
+            value,
+            script_pubkey: Script(vec![]),
+        }
+    };
+
+    let required = zip317_conventional_fee(&tx).unwrap();
+    assert_eq!(
+        check_zip317_fee(&tx, &[prevout_for_fee(Zatoshis::ZERO)]),
+        Err(CheckError::FeeTooLow {
+            paid: ZatBalance::zero(),
+            required,
+        })
+    );
+    assert_eq!(
+        check_zip317_fee(&tx, &[prevout_for_fee(required)]),
+        Ok(ZatBalance::from(required))
+    );
+    assert_eq!(
+        check_zip317_fee(&tx, &[]),
+        Err(CheckError::PrevoutCountMismatch {
+            expected: 1,
+            actual: 0,
+        })
+    );
+
+    // JoinSplits cannot be batch-verified.
+    let mut verifier = BatchVerifier::new();
+    assert_eq!(
+        verifier.queue(&tx, &[prevout_for_fee(required)]),
+        Err(CheckError::SproutUnsupported)
+    );
+    assert!(verifier.queued_txids().is_empty());
+}
@@ -43,6 +43,45 @@ impl super::Input {
 
         Ok(())
     }
+
+    /// Appends a signature for the transparent spend that was created elsewhere (for
+    /// example, by a hardware wallet), after checking that it is valid.
+    ///
+    /// `signature` must have been created over the sighash for this input, using the
+    /// input's sighash type, by the secret key corresponding to `pubkey`.
+    pub fn append_signature<C: secp256k1::Verification, F>(
+        &mut self,
+        index: usize,
+        calculate_sighash: F,
+        pubkey: &secp256k1::PublicKey,
+        signature: &secp256k1::ecdsa::Signature,
+        secp: &secp256k1::Secp256k1<C>,
+    ) -> Result<(), SignerError>
+    where
+        F: FnOnce(SignableInput) -> [u8; 32],
+    {
+        let sighash = calculate_sighash(SignableInput {
+            hash_type: self.sighash_type,
+            index,
+            // for p2pkh, always the same as script_pubkey
+            script_code: self.redeem_script.as_ref().unwrap_or(&self.script_pubkey),
+            script_pubkey: &self.script_pubkey,
+            value: self.value,
+        });
+
+        let msg = secp256k1::Message::from_digest_slice(&sighash).expect("32 bytes");
+        secp.verify_ecdsa(&msg, signature, pubkey)
+            .map_err(|_| SignerError::InvalidSignature)?;
+
+        // Signature has to have the SighashType appended to it.
+        let mut sig_bytes: Vec<u8> = signature.serialize_der()[..].to_vec();
+        sig_bytes.extend([self.sighash_type.encode()]);
+
+        self.partial_signatures
+            .insert(pubkey.serialize(), sig_bytes);
+
+        Ok(())
+    }
 }
 
 /// Errors that can occur while signing a transparent input in a PCZT.
@@ -51,4 +90,6 @@ pub enum SignerError {
     /// The provided `sk` does not match any pubkey involved with spend control of the
     /// input's spent coin.
     WrongSpendingKey,
+    /// The provided signature is not valid for the input under the given pubkey.
+    InvalidSignature,
 }